@@ -0,0 +1,247 @@
+#[cfg(windows)]
+#[macro_use] extern crate log;
+#[macro_use] extern crate serde_json;
+use clap::{App, Arg, ArgMatches};
+use std::process::exit;
+use std::collections::BTreeMap;
+use serde_json::Value;
+use rswinthings::errors::WinThingError;
+use rswinthings::channels::graph::ChannelGraph;
+use rswinthings::channels::policy::read_channel_dump_path;
+use rswinthings::publishers::read_publisher_dump_path;
+use rswinthings::utils::debug::set_debug_level;
+#[cfg(windows)]
+use rswinthings::utils::cli::{
+    add_session_options_to_app,
+    get_session_from_matches
+};
+#[cfg(windows)]
+use rswinthings::winetl::publisher::PublisherEnumerator;
+#[cfg(windows)]
+use rswinthings::winevt::EvtHandle;
+#[cfg(windows)]
+use rswinthings::winevt::channels::ChannelConfig;
+#[cfg(windows)]
+use rswinthings::winevt::channels::get_channel_name_list;
+
+static VERSION: &'static str = "0.1.0";
+static DESCRIPTION: &'static str = r"
+Cross-reference publishers and the channels they write to.
+
+The index is built from every publisher's channel references and every
+channel's publisher list, either on the computer or from saved
+print_publishers and print_channels JSONL output. Publishers that write
+to a disabled channel are flagged.
+";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let publishers = Arg::with_name("publishers")
+        .long("publishers")
+        .value_name("PUBLISHERS")
+        .takes_value(true)
+        .requires("channels")
+        .help("A saved JSONL dump (print_publishers -f jsonl) to use instead of the publishers on the computer.");
+
+    let channels = Arg::with_name("channels")
+        .long("channels")
+        .value_name("CHANNELS")
+        .takes_value(true)
+        .requires("publishers")
+        .help("A saved JSONL dump (print_channels -f jsonl) to use instead of the channels on the computer.");
+
+    let channel = Arg::with_name("channel")
+        .short("-c")
+        .long("channel")
+        .value_name("CHANNEL")
+        .takes_value(true)
+        .conflicts_with_all(&["provider", "disabled"])
+        .help("List the providers that write to this channel.");
+
+    let provider = Arg::with_name("provider")
+        .short("-p")
+        .long("provider")
+        .value_name("PROVIDER")
+        .takes_value(true)
+        .conflicts_with("disabled")
+        .help("List the channels this provider can write to.");
+
+    let disabled = Arg::with_name("disabled")
+        .long("disabled")
+        .help("List the providers that write to a disabled channel.");
+
+    let format = Arg::with_name("format")
+        .short("-f")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["json", "dot"])
+        .help("Output format of the whole index. (defaults to json)");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    let app = App::new("channel_graph")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about(DESCRIPTION)
+        .arg(publishers)
+        .arg(channels)
+        .arg(channel)
+        .arg(provider)
+        .arg(disabled)
+        .arg(format)
+        .arg(debug);
+
+    add_live_options(app)
+}
+
+
+/// Add session arguments to app
+#[cfg(windows)]
+fn add_live_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    add_session_options_to_app(app)
+}
+
+
+#[cfg(not(windows))]
+fn add_live_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app
+}
+
+
+/// Get the channel configs of the session's computer keyed by name.
+#[cfg(windows)]
+fn get_channel_configs(
+    session: &Option<EvtHandle>
+) -> Result<BTreeMap<String, Value>, WinThingError> {
+    let mut configs = BTreeMap::new();
+
+    for channel in get_channel_name_list(session)? {
+        let channel_config = match ChannelConfig::from_session(session, channel.clone()) {
+            Ok(c) => c,
+            Err(e) => {
+                debug!("Error opening ChannelConfig for {}: {}", channel, e.display_chain());
+                continue;
+            }
+        };
+
+        match channel_config.to_json_value() {
+            Ok(v) => { configs.insert(channel, v); },
+            Err(e) => eprintln!("Error getting channel config for {}: {}", channel, e.display_chain())
+        }
+    }
+
+    Ok(configs)
+}
+
+
+/// Get the publisher records of the session's computer.
+#[cfg(windows)]
+fn get_publisher_values(
+    session: Option<EvtHandle>
+) -> Result<Vec<Value>, WinThingError> {
+    let mut publishers = Vec::new();
+
+    for publisher_meta in PublisherEnumerator::new(session)? {
+        match publisher_meta.to_json_value() {
+            Ok(v) => publishers.push(v),
+            Err(e) => eprintln!("Error getting publisher {}: {}", publisher_meta.name, e.display_chain())
+        }
+    }
+
+    Ok(publishers)
+}
+
+
+/// Get the publisher records and channel configs of the computer.
+#[cfg(windows)]
+fn get_live_values(
+    options: &ArgMatches
+) -> Result<(Vec<Value>, BTreeMap<String, Value>), WinThingError> {
+    let session = get_session_from_matches(options)?
+        .map(|s| s.0);
+
+    // The publisher enumerator takes the session
+    let channels = get_channel_configs(&session)?;
+    Ok((get_publisher_values(session)?, channels))
+}
+
+
+#[cfg(not(windows))]
+fn get_live_values(
+    _options: &ArgMatches
+) -> Result<(Vec<Value>, BTreeMap<String, Value>), WinThingError> {
+    Err(
+        WinThingError::cli_error(
+            "Reading the computer's publishers and channels is only supported on Windows, use --publishers and --channels.".to_owned()
+        )
+    )
+}
+
+
+fn get_graph(options: &ArgMatches) -> Result<ChannelGraph, WinThingError> {
+    let (publishers, channels) = match (options.value_of("publishers"), options.value_of("channels")) {
+        (Some(publisher_path), Some(channel_path)) => (
+            read_publisher_dump_path(publisher_path)?.publishers,
+            read_channel_dump_path(channel_path)?
+        ),
+        _ => get_live_values(options)?
+    };
+
+    ChannelGraph::from_values(
+        &publishers,
+        &channels
+    )
+}
+
+
+fn run(options: &ArgMatches) -> Result<(), WinThingError> {
+    let graph = get_graph(options)?;
+
+    if let Some(channel) = options.value_of("channel") {
+        for publisher in graph.get_channel_publishers(channel) {
+            println!("{}", publisher);
+        }
+    } else if let Some(provider) = options.value_of("provider") {
+        for channel in graph.get_publisher_channels(provider) {
+            println!("{}", channel);
+        }
+    } else if options.is_present("disabled") {
+        for disabled in graph.get_disabled_publishers() {
+            println!("{}", json!(disabled));
+        }
+    } else {
+        match options.value_of("format") {
+            Some("dot") => print!("{}", graph.to_dot()),
+            _ => println!("{}", graph.to_json_value())
+        }
+    }
+
+    Ok(())
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => set_debug_level("Error").expect(
+            "Error setting debug level"
+        )
+    }
+
+    if let Err(e) = run(&options) {
+        eprintln!("Error building channel graph: {}", e.display_chain());
+        exit(-1);
+    }
+}
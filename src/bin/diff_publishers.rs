@@ -0,0 +1,237 @@
+extern crate serde_json;
+use clap::{App, Arg, ArgMatches};
+use std::process::exit;
+use rswinthings::errors::WinThingError;
+use rswinthings::publishers::{read_publisher_dump_path, PublisherDump};
+use rswinthings::publishers::diff::PublisherDiff;
+use rswinthings::utils::debug::set_debug_level;
+#[cfg(windows)]
+use std::sync::Arc;
+#[cfg(windows)]
+use rswinthings::utils::pool::{
+    run_tasks,
+    PoolOptions
+};
+#[cfg(windows)]
+use rswinthings::utils::cli::{
+    add_session_options_to_app,
+    get_session_from_matches
+};
+#[cfg(windows)]
+use rswinthings::winetl::publisher::PublisherMeta;
+#[cfg(windows)]
+use rswinthings::winetl::publisher::get_publisher_name_list;
+#[cfg(windows)]
+use rswinthings::winevt::session::SharedSession;
+
+static VERSION: &'static str = "0.1.0";
+static DESCRIPTION: &'static str = r"
+Compare the publishers of two print_publishers JSONL snapshots, or of the
+computer against a snapshot.
+
+Reports the providers that were added or removed, and the properties
+(channels, keywords, resource paths, ...) of the providers that changed.
+Providers that fail to open on the computer are reported as unavailable,
+not removed. Exits with 1 if there are any differences.
+";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let before = Arg::with_name("before")
+        .short("-b")
+        .long("before")
+        .value_name("BEFORE")
+        .takes_value(true)
+        .required(true)
+        .help("The earlier snapshot (print_publishers -f jsonl).");
+
+    let after = Arg::with_name("after")
+        .short("-a")
+        .long("after")
+        .value_name("AFTER")
+        .takes_value(true)
+        .help("The later snapshot. The publishers on the computer are used if not given (Windows only).");
+
+    let property = Arg::with_name("property")
+        .long("property")
+        .value_name("PROPERTY")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Only compare this property, e.g. EvtPublisherMetadataChannelReferences. Can be given more than once.");
+
+    let format = Arg::with_name("format")
+        .short("-f")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["text", "jsonl"])
+        .help("Output format. (defaults to text)");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    let app = App::new("diff_publishers")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about(DESCRIPTION)
+        .arg(before)
+        .arg(after)
+        .arg(property)
+        .arg(format)
+        .arg(debug);
+
+    add_live_options(app)
+}
+
+
+/// Add session arguments to app
+#[cfg(windows)]
+fn add_live_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    add_session_options_to_app(app)
+}
+
+
+#[cfg(not(windows))]
+fn add_live_options<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app
+}
+
+
+/// Get the publisher records of the session's computer. The publishers
+/// that failed are reported and kept as unavailable.
+#[cfg(windows)]
+fn get_live_publishers(options: &ArgMatches) -> Result<PublisherDump, WinThingError> {
+    let session = get_session_from_matches(options)?
+        .map(|s| s.0);
+    let names = get_publisher_name_list(&session)?;
+
+    let session = Arc::new(SharedSession::new(session));
+    let outcomes = run_tasks(names, PoolOptions::new(), move |name| {
+        PublisherMeta::new(
+            session.get(),
+            name.to_string()
+        )?.to_json_value()
+    });
+
+    let mut dump = PublisherDump::default();
+    for outcome in outcomes {
+        match outcome.result {
+            Ok(v) => dump.publishers.push(v),
+            Err(e) => {
+                eprintln!("Error getting publisher {}: {}", outcome.name, e.display_chain());
+                dump.unavailable.push(outcome.name);
+            }
+        }
+    }
+
+    Ok(dump)
+}
+
+
+#[cfg(not(windows))]
+fn get_live_publishers(_options: &ArgMatches) -> Result<PublisherDump, WinThingError> {
+    Err(
+        WinThingError::cli_error(
+            "Reading the computer's publishers is only supported on Windows, use --after.".to_owned()
+        )
+    )
+}
+
+
+fn print_diff(diff: &PublisherDiff, out_format: &str) {
+    match out_format {
+        "jsonl" => {
+            for record in diff.to_records() {
+                println!("{}", record);
+            }
+        },
+        _ => {
+            for name in &diff.added {
+                println!("+ {}", name);
+            }
+            for name in &diff.removed {
+                println!("- {}", name);
+            }
+            for change in &diff.changed {
+                println!("~ {}: {}", change.name, change.get_changed_properties().join(", "));
+                if let Some(differences) = change.differences.as_object() {
+                    for (path, difference) in differences {
+                        println!("    {}: {}", path, difference);
+                    }
+                }
+            }
+            for name in &diff.unavailable {
+                println!("? {}", name);
+            }
+        }
+    }
+}
+
+
+/// Diff the publishers. Returns true if there are no differences.
+fn run(options: &ArgMatches) -> Result<bool, WinThingError> {
+    let before = read_publisher_dump_path(
+        options.value_of("before").unwrap_or("")
+    )?;
+
+    let after = match options.value_of("after") {
+        Some(path) => read_publisher_dump_path(path)?,
+        None => get_live_publishers(options)?
+    };
+
+    // Publishers with error records in either dump are unavailable
+    let mut unavailable = before.unavailable;
+    for name in after.unavailable {
+        if !unavailable.contains(&name) {
+            unavailable.push(name);
+        }
+    }
+
+    let properties: Vec<String> = match options.values_of("property") {
+        Some(p) => p.map(|p| p.to_string()).collect(),
+        None => Vec::new()
+    };
+
+    let diff = PublisherDiff::from_values(
+        &before.publishers,
+        &after.publishers,
+        &properties
+    )?.with_unavailable(&unavailable);
+
+    print_diff(
+        &diff,
+        options.value_of("format").unwrap_or("text")
+    );
+
+    Ok(diff.is_empty())
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => set_debug_level("Error").expect(
+            "Error setting debug level"
+        )
+    }
+
+    match run(&options) {
+        Ok(true) => {},
+        Ok(false) => exit(1),
+        Err(e) => {
+            eprintln!("Error diffing publishers: {}", e.display_chain());
+            exit(-1);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+#[macro_use] extern crate log;
+use clap::{App, Arg, ArgMatches};
+use std::process::exit;
+use rswinthings::errors::WinThingError;
+use rswinthings::utils::debug::set_debug_level;
+use rswinthings::timeline::record::normalize_path;
+use rswinthings::volume::image::NtfsImage;
+#[cfg(windows)]
+use rswinthings::volume::liventfs::WindowsLiveNtfs;
+use rswinthings::mft::offline::OfflineMft;
+use rswinthings::mft::enumerate::{
+    get_entry_csv_line,
+    EntryFilter,
+    MftEnumerator,
+    MftSource,
+    ENTRY_CSV_HEADER
+};
+
+static VERSION: &'static str = "0.1.0";
+static DESCRIPTION: &'static str = r"
+Enumerate every record of an MFT with its resolved path.
+
+Records are read from a live volume (FSCTL_GET_NTFS_FILE_RECORD up to the
+MftValidDataLength, with records that are not in use read from the $MFT's
+clusters), an NTFS image or a $MFT file. Paths of entries whose parent was
+deleted and reused are under \$OrphanFiles.
+";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let live = Arg::with_name("live")
+        .short("-l")
+        .long("live")
+        .value_name("VOLUME_PATH")
+        .takes_value(true)
+        .conflicts_with_all(&["image", "mft"])
+        .help(r"The live volume to enumerate (e.g. \\.\C:).");
+
+    let image = Arg::with_name("image")
+        .long("image")
+        .value_name("IMAGE")
+        .takes_value(true)
+        .conflicts_with("mft")
+        .help("A raw NTFS image to enumerate.");
+
+    let offset = Arg::with_name("offset")
+        .long("offset")
+        .value_name("OFFSET")
+        .takes_value(true)
+        .requires("image")
+        .help("The byte offset of the volume in the image. [default: 0]");
+
+    let mft = Arg::with_name("mft")
+        .short("-m")
+        .long("mft")
+        .value_name("MFT")
+        .takes_value(true)
+        .help("A $MFT file to enumerate.");
+
+    let in_use = Arg::with_name("in_use")
+        .long("in-use")
+        .help("Include records that are in use. (Without --in-use or --deleted both are included.)");
+
+    let deleted = Arg::with_name("deleted")
+        .long("deleted")
+        .help("Include records that are not in use.");
+
+    let directories = Arg::with_name("directories")
+        .long("directories")
+        .help("Only include directories.");
+
+    let volume = Arg::with_name("volume")
+        .long("volume")
+        .value_name("VOLUME")
+        .takes_value(true)
+        .help("The volume prefixed to paths (e.g. C:). Defaults to the live volume.");
+
+    let format = Arg::with_name("format")
+        .short("-f")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["jsonl", "csv"])
+        .help("Output format. (defaults to jsonl)");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    App::new("dump_mft")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about(DESCRIPTION)
+        .arg(live)
+        .arg(image)
+        .arg(offset)
+        .arg(mft)
+        .arg(in_use)
+        .arg(deleted)
+        .arg(directories)
+        .arg(volume)
+        .arg(format)
+        .arg(debug)
+}
+
+
+fn get_filter(options: &ArgMatches) -> EntryFilter {
+    EntryFilter::new()
+        .with_in_use(options.is_present("in_use"))
+        .with_deleted(options.is_present("deleted"))
+        .with_directories(options.is_present("directories"))
+}
+
+
+/// Print every entry of a source that matches the filter. Entries that fail
+/// to read are logged and skipped.
+fn dump_entries<S: MftSource>(
+    source: &mut S,
+    options: &ArgMatches,
+    volume: Option<&str>
+) -> Result<(), WinThingError> {
+    let is_csv = options.value_of("format") == Some("csv");
+    if is_csv {
+        println!("{}", ENTRY_CSV_HEADER);
+    }
+
+    let mut enumerator = MftEnumerator::new(source)
+        .with_filter(get_filter(options));
+
+    while let Some(result) = enumerator.next() {
+        let mut entry = match result {
+            Ok(e) => e,
+            Err(e) => {
+                error!("Error reading entry {}: {}", enumerator.get_last_entry(), e.display_chain());
+                continue;
+            }
+        };
+
+        entry.path = entry.path.map(|p| normalize_path(&p, volume));
+        let entry_number = entry.entry;
+        let value = match entry.into_json_value() {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Error serializing entry {}: {}", entry_number, e.display_chain());
+                continue;
+            }
+        };
+
+        match is_csv {
+            true => println!("{}", get_entry_csv_line(&value)),
+            false => println!("{}", value)
+        }
+    }
+
+    Ok(())
+}
+
+
+#[cfg(windows)]
+fn dump_live(options: &ArgMatches, volume_path: &str) -> Result<(), WinThingError> {
+    let live_volume_name = normalize_path(volume_path, None);
+    let mut live_volume = WindowsLiveNtfs::from_volume_path(volume_path)?;
+    dump_entries(
+        &mut live_volume,
+        options,
+        options.value_of("volume").or_else(|| Some(live_volume_name.as_str()))
+    )
+}
+
+
+#[cfg(not(windows))]
+fn dump_live(_options: &ArgMatches, _volume_path: &str) -> Result<(), WinThingError> {
+    Err(
+        WinThingError::cli_error(
+            "--live is only supported on Windows, use --image or --mft.".to_owned()
+        )
+    )
+}
+
+
+fn run(options: &ArgMatches) -> Result<(), WinThingError> {
+    let volume = options.value_of("volume");
+
+    if let Some(volume_path) = options.value_of("live") {
+        return dump_live(options, volume_path);
+    }
+
+    if let Some(image_path) = options.value_of("image") {
+        let offset = match options.value_of("offset") {
+            Some(offset) => offset.parse::<u64>().map_err(|e|
+                WinThingError::cli_error(
+                    format!("Invalid offset '{}': {}", offset, e)
+                )
+            )?,
+            None => 0
+        };
+
+        let mut image = NtfsImage::from_path(image_path, offset)?;
+        return dump_entries(&mut image, options, volume);
+    }
+
+    if let Some(mft_path) = options.value_of("mft") {
+        let mut mft = OfflineMft::from_path(mft_path)?;
+        return dump_entries(&mut mft, options, volume);
+    }
+
+    Err(
+        WinThingError::cli_error(
+            "One of --live, --image or --mft is required.".to_owned()
+        )
+    )
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => set_debug_level("Error").expect(
+            "Error setting debug level"
+        )
+    }
+
+    if let Err(e) = run(&options) {
+        eprintln!("Error enumerating MFT: {}", e.display_chain());
+        exit(-1);
+    }
+}
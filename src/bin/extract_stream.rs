@@ -0,0 +1,231 @@
+extern crate serde_json;
+use std::fs::File;
+use std::io::{sink, Write};
+use clap::{App, Arg, ArgMatches};
+use std::process::exit;
+use rswinthings::errors::WinThingError;
+use rswinthings::utils::debug::set_debug_level;
+use rswinthings::mft::stream::HashWriter;
+use rswinthings::volume::image::NtfsImage;
+#[cfg(windows)]
+use rswinthings::volume::liventfs::WindowsLiveNtfs;
+#[cfg(windows)]
+use rswinthings::file::helper::{
+    get_entry_from_path,
+    get_volume_path_from_path
+};
+
+static VERSION: &'static str = "0.1.0";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let file = Arg::with_name("file")
+        .short("-f")
+        .long("file")
+        .value_name("FILE")
+        .takes_value(true)
+        .conflicts_with("image")
+        .help("The file on a live volume to extract from.");
+
+    let image = Arg::with_name("image")
+        .long("image")
+        .value_name("IMAGE")
+        .takes_value(true)
+        .requires("entry")
+        .help("A raw NTFS image to extract from.");
+
+    let offset = Arg::with_name("offset")
+        .long("offset")
+        .value_name("OFFSET")
+        .takes_value(true)
+        .help("The byte offset of the volume in the image. [default: 0]");
+
+    let entry = Arg::with_name("entry")
+        .short("-e")
+        .long("entry")
+        .value_name("ENTRY")
+        .takes_value(true)
+        .help("The entry to extract from when using an image.");
+
+    let stream = Arg::with_name("stream")
+        .short("-s")
+        .long("stream")
+        .value_name("STREAM")
+        .takes_value(true)
+        .help("The name of the alternate data stream to extract. The unnamed stream is used if not given.");
+
+    let output = Arg::with_name("output")
+        .short("-o")
+        .long("output")
+        .value_name("OUTPUT")
+        .takes_value(true)
+        .help("The file to write the stream to.");
+
+    let hash = Arg::with_name("hash")
+        .long("hash")
+        .help("Print the MD5, SHA1 and SHA256 of the stream.");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    App::new("extract_stream")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about("Extract a file's data or alternate data stream from a live volume or image.")
+        .arg(file)
+        .arg(image)
+        .arg(offset)
+        .arg(entry)
+        .arg(stream)
+        .arg(output)
+        .arg(hash)
+        .arg(debug)
+}
+
+
+fn get_u64_option(options: &ArgMatches, name: &str) -> Option<u64> {
+    match options.value_of(name) {
+        Some(v) => match v.parse::<u64>() {
+            Ok(i) => Some(i),
+            Err(e) => {
+                eprintln!("Error parsing {} '{}': {}", name, v, e);
+                exit(-1);
+            }
+        },
+        None => None
+    }
+}
+
+
+fn extract<W: Write>(
+    options: &ArgMatches,
+    stream_name: &str,
+    writer: &mut W
+) -> Result<(u64, u64), WinThingError> {
+    if let Some(image_path) = options.value_of("image") {
+        let entry = get_u64_option(options, "entry").unwrap_or(0);
+        let offset = get_u64_option(options, "offset").unwrap_or(0);
+
+        let mut image = NtfsImage::from_path(
+            image_path,
+            offset
+        )?;
+
+        let size = image.write_stream(
+            entry,
+            stream_name,
+            writer
+        )?;
+
+        return Ok((entry, size));
+    }
+
+    let file_path = match options.value_of("file") {
+        Some(p) => p,
+        None => {
+            return Err(
+                WinThingError::cli_error(
+                    "file or image parameter was expected.".to_owned()
+                )
+            );
+        }
+    };
+
+    extract_live(
+        file_path,
+        stream_name,
+        writer
+    )
+}
+
+
+#[cfg(windows)]
+fn extract_live<W: Write>(
+    file_path: &str,
+    stream_name: &str,
+    writer: &mut W
+) -> Result<(u64, u64), WinThingError> {
+    let entry = get_entry_from_path(file_path)?;
+    let volume = get_volume_path_from_path(file_path)?;
+    let mut live_volume = WindowsLiveNtfs::from_volume_path(
+        &volume
+    )?;
+
+    let size = live_volume.write_stream(
+        entry as i64,
+        stream_name,
+        writer
+    )?;
+
+    Ok((entry, size))
+}
+
+
+#[cfg(not(windows))]
+fn extract_live<W: Write>(
+    _file_path: &str,
+    _stream_name: &str,
+    _writer: &mut W
+) -> Result<(u64, u64), WinThingError> {
+    Err(
+        WinThingError::cli_error(
+            "Extracting from a live volume is only supported on Windows, use --image.".to_owned()
+        )
+    )
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    // Set debug
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => {}
+    }
+
+    let stream_name = options.value_of("stream").unwrap_or("");
+
+    let output: Box<dyn Write> = match options.value_of("output") {
+        Some(p) => Box::new(
+            File::create(p).expect("Error creating output file")
+        ),
+        None => {
+            if !options.is_present("hash") {
+                eprintln!("output or hash parameter was expected.");
+                exit(-1);
+            }
+            Box::new(sink())
+        }
+    };
+
+    let mut writer = HashWriter::new(output);
+    let (entry, size) = match extract(&options, stream_name, &mut writer) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error extracting stream: {}", e.display_chain());
+            exit(-1);
+        }
+    };
+
+    let (_, hashes) = writer.finalize();
+
+    let mut result = serde_json::json!({
+        "entry": entry,
+        "stream": stream_name,
+        "size": size
+    });
+    if options.is_present("hash") {
+        result["hashes"] = serde_json::to_value(&hashes).expect("Unable to format hashes");
+    }
+
+    println!("{}", result.to_string());
+}
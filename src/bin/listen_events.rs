@@ -2,11 +2,15 @@ extern crate log;
 extern crate clap;
 extern crate chrono;
 extern crate serde_json;
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use std::process::exit;
 use std::thread::sleep;
+use std::sync::Arc;
 use std::time::Duration;
 use rswinthings::utils::debug::set_debug_level;
+use rswinthings::errors::WinThingError;
+use rswinthings::detection::engine::RuleEngine;
+use rswinthings::detection::sigma::FieldMapping;
 use rswinthings::winevt::channels::get_channel_name_list;
 use rswinthings::winevt::channels::ChannelConfig;
 use rswinthings::winevt::callback::OutputFormat;
@@ -21,6 +25,8 @@ use rswinthings::utils::cli::{
     get_session_from_matches
 };
 use rswinthings::winevt::EvtHandle;
+use rswinthings::winevt::collector::HostCollector;
+use rswinthings::utils::inventory::HostInventory;
 
 
 static VERSION: &'static str = "0.3.0";
@@ -56,6 +62,32 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .long("historical")
         .help("List historical records along with listening to new changes.");
 
+    let inventory = Arg::with_name("inventory")
+        .short("-i")
+        .long("inventory")
+        .value_name("INVENTORY")
+        .takes_value(true)
+        .conflicts_with_all(&["channel", "server"])
+        .help("JSON host inventory with per-host channels, queries and credentials.");
+
+    let rules = Arg::with_name("rules")
+        .long("rules")
+        .value_name("RULES")
+        .takes_value(true)
+        .help("A Sigma rule file, or a directory of them, to evaluate events against.");
+
+    let field_mapping = Arg::with_name("field_mapping")
+        .long("field-mapping")
+        .value_name("FIELD_MAPPING")
+        .takes_value(true)
+        .requires("rules")
+        .help("YAML mapping of Sigma fields to event JSON Pointers.");
+
+    let alerts_only = Arg::with_name("alerts_only")
+        .long("alerts-only")
+        .requires("rules")
+        .help("Only output alerts, not the events.");
+
     let debug = Arg::with_name("debug")
         .short("-d")
         .long("debug")
@@ -71,6 +103,10 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .arg(channel)
         .arg(format)
         .arg(historical)
+        .arg(inventory)
+        .arg(rules)
+        .arg(field_mapping)
+        .arg(alerts_only)
         .arg(debug);
 
     // Add session arguments to app
@@ -80,7 +116,7 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
 
 fn get_query_list_from_system(
     session: &Option<EvtHandle>,
-    context: &CallbackContext, 
+    context: &Arc<CallbackContext>, 
     flags: Option<u32>
 ) -> Vec<ChannelSubscription> {
     let mut subscriptions: Vec<ChannelSubscription> = Vec::new();
@@ -93,7 +129,7 @@ fn get_query_list_from_system(
         let channel_config = match ChannelConfig::new(channel.clone()) {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("Error getting ChannelConfig for {}: {:?}", channel, e);
+                eprintln!("Error getting ChannelConfig for {}: {}", channel, e.display_chain());
                 continue;
             }
         };
@@ -117,11 +153,11 @@ fn get_query_list_from_system(
             channel.to_string(),
             None,
             flags,
-            &context
+            context.clone()
         ){
             Ok(s) => s,
             Err(e) => {
-                eprintln!("Error creating subscription for {}: {:?}", channel, e);
+                eprintln!("Error creating subscription for {}: {}", channel, e.display_chain());
                 continue;
             }
         };
@@ -135,7 +171,7 @@ fn get_query_list_from_system(
 
 fn get_query_list_from_str_list<'a>(
     session: &Option<EvtHandle>,
-    context: &CallbackContext, 
+    context: &Arc<CallbackContext>, 
     flags: Option<u32>,
     channel_list: Vec<&'a str>
 ) -> Vec<ChannelSubscription> {
@@ -148,11 +184,11 @@ fn get_query_list_from_str_list<'a>(
             channel.to_string(),
             None,
             flags,
-            &context
+            context.clone()
         ){
             Ok(s) => s,
             Err(e) => {
-                eprintln!("Error creating subscription for {}: {:?}", channel, e);
+                eprintln!("Error creating subscription for {}: {}", channel, e.display_chain());
                 continue;
             }
         };
@@ -166,6 +202,63 @@ fn get_query_list_from_str_list<'a>(
 }
 
 
+/// Load the Sigma rules given in the options, if any.
+fn get_rules_from_matches(options: &ArgMatches) -> Result<Option<Arc<RuleEngine>>, WinThingError> {
+    let rules_path = match options.value_of("rules") {
+        Some(p) => p,
+        None => return Ok(None)
+    };
+
+    let mut engine = RuleEngine::new();
+    if let Some(mapping_path) = options.value_of("field_mapping") {
+        engine = engine.with_field_mapping(
+            FieldMapping::from_path(mapping_path)?
+        );
+    }
+
+    let count = engine.add_rules_from_path(rules_path)?;
+    eprintln!("Loaded {} rules from {}", count, rules_path);
+
+    Ok(Some(Arc::new(engine)))
+}
+
+
+fn listen_to_inventory(
+    inventory_path: &str,
+    format: OutputFormat,
+    flags: Option<u32>,
+    rules: Option<Arc<RuleEngine>>,
+    alerts_only: bool
+) {
+    let inventory = match HostInventory::from_path(inventory_path) {
+        Ok(i) => i,
+        Err(e) => {
+            eprintln!("Error reading inventory {}: {}", inventory_path, e.display_chain());
+            exit(-1);
+        }
+    };
+
+    let mut collectors: Vec<HostCollector> = inventory.hosts
+        .into_iter()
+        .map(|host| {
+            let collector = HostCollector::new(host, format, flags);
+            match &rules {
+                Some(r) => collector.with_rules(Arc::clone(r), alerts_only),
+                None => collector
+            }
+        })
+        .collect();
+
+    eprintln!("Listening to events from {} hosts...", collectors.len());
+    loop {
+        for collector in collectors.iter_mut() {
+            collector.maintain();
+        }
+        sleep(Duration::from_millis(200));
+    }
+}
+
+
 fn main() {
     let app = make_app();
     let options = app.get_matches();
@@ -179,12 +272,10 @@ fn main() {
         )
     }
 
-    // Get Session
-    let session: Option<EvtHandle> = match get_session_from_matches(
-        &options
-    ).expect("Error getting session from options") {
-        Some(s) => Some(s.0),
-        None => None
+    // Historical flag
+    let flags = match options.is_present("historical") {
+        true => Some(EvtSubscribeStartAtOldestRecord),
+        false => Some(EvtSubscribeToFutureEvents)
     };
 
     let format_enum = match options.value_of("format") {
@@ -201,17 +292,44 @@ fn main() {
         None => OutputFormat::JsonlFormat
     };
 
-    // Historical flag
-    let flags = match options.is_present("historical") {
-        true => Some(EvtSubscribeStartAtOldestRecord),
-        false => Some(EvtSubscribeToFutureEvents)
+    let rules = match get_rules_from_matches(&options) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Error loading rules: {}", e.display_chain());
+            exit(-1);
+        }
+    };
+    let alerts_only = options.is_present("alerts_only");
+
+    if let Some(inventory_path) = options.value_of("inventory") {
+        listen_to_inventory(
+            inventory_path,
+            format_enum,
+            flags,
+            rules,
+            alerts_only
+        );
+        return;
+    }
+
+    // Get Session
+    let session: Option<EvtHandle> = match get_session_from_matches(
+        &options
+    ).expect("Error getting session from options") {
+        Some(s) => Some(s.0),
+        None => None
     };
 
     // Create context
-    let context = CallbackContext::new()
-        .with_format(format_enum);
+    let mut context = CallbackContext::new()
+        .with_format(format_enum)
+        .with_alerts_only(alerts_only);
+    if let Some(rules) = rules {
+        context = context.with_rules(rules);
+    }
+    let context = Arc::new(context);
 
-    let _subscritions = match options.values_of("channel") {
+    let mut subscriptions = match options.values_of("channel") {
         Some(v_list) => {
             get_query_list_from_str_list(
                 &session,
@@ -229,6 +347,10 @@ fn main() {
 
     eprintln!("Listening to events...");
     loop {
+        // Re-establish subscriptions whose callbacks reported errors
+        for subscription in subscriptions.iter_mut() {
+            subscription.maintain();
+        }
         sleep(Duration::from_millis(200));
     }
 }
@@ -1,4 +1,5 @@
 extern crate serde_json;
+use std::fs::File;
 use std::io::stdin;
 use std::io::BufRead;
 use clap::{App, Arg};
@@ -7,10 +8,17 @@ use std::thread;
 use std::sync::mpsc;
 use std::sync::mpsc::{Sender, Receiver};
 use rusty_usn::record::UsnEntry;
-use rswinthings::utils::json::get_difference_value;
+use rswinthings::utils::json::{
+    get_difference_value,
+    get_json_patch,
+    DiffOptions
+};
+use rswinthings::errors::WinThingError;
 use rswinthings::utils::debug::set_debug_level;
 use rswinthings::mft::EntryListener;
 use rswinthings::usn::listener::UsnVolumeListener;
+use rswinthings::usn::record::get_usn;
+use rswinthings::mft::history::HistoryJournal;
 
 static VERSION: &'static str = "0.2.0";
 
@@ -23,6 +31,13 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .takes_value(true)
         .help("The file to difference.");
 
+    let journal = Arg::with_name("journal")
+        .short("-j")
+        .long("journal")
+        .value_name("JOURNAL")
+        .takes_value(true)
+        .help("JSONL file to append every snapshot and diff to.");
+
     let debug = Arg::with_name("debug")
         .short("-d")
         .long("debug")
@@ -36,16 +51,41 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
         .about("See the differences in MFT attirbues.")
         .arg(format)
+        .arg(journal)
         .arg(debug)
 }
 
 
-fn run(mut listener: EntryListener) {
+/// Get the sequence of an entry value. The journal keys records by entry
+/// and sequence, so a value without one can not be recorded.
+fn get_sequence(value: &serde_json::Value) -> Result<u16, WinThingError> {
+    match value["header"]["sequence"].as_u64() {
+        Some(sequence) => Ok(sequence as u16),
+        None => Err(
+            WinThingError::invalid_mft_record(
+                "Entry value has no header sequence.".to_owned()
+            )
+        )
+    }
+}
+
+
+fn run(mut listener: EntryListener, mut journal: Option<HistoryJournal<File>>) -> Result<(), WinThingError> {
     let (tx, rx): (Sender<UsnEntry>, Receiver<UsnEntry>) = mpsc::channel();
 
     let mut previous_value = listener.get_current_value().expect("Unable to get current mft entry value");
     println!("{}", previous_value.to_string());
 
+    if let Some(journal) = journal.as_mut() {
+        let next_usn = listener.get_next_usn().expect("Unable to query usn journal");
+        journal.append_snapshot(
+            listener.entry_to_monitor as u64,
+            get_sequence(&previous_value)?,
+            next_usn,
+            &previous_value
+        ).expect("Unable to write snapshot to journal");
+    }
+
     let volume_str = listener.get_volume_string().expect("Error getting volume path.");
     let usn_volume_listener = UsnVolumeListener::new(
         volume_str,
@@ -69,6 +109,20 @@ fn run(mut listener: EntryListener) {
             continue;
         }
 
+        // A diff recorded at the wrong usn would be replayed out of order,
+        // so a record without one is skipped. Its change is included in the
+        // next record's diff.
+        let usn = match journal {
+            Some(_) => match get_usn(&usn_entry) {
+                Ok(usn) => usn,
+                Err(e) => {
+                    eprintln!("Skipping USN record of entry {}: {}", file_ref.entry, e.display_chain());
+                    continue;
+                }
+            },
+            None => 0
+        };
+
         let current_value = listener.get_current_value().expect("Unable to get current mft entry value");
 
         let difference_value = get_difference_value(
@@ -88,7 +142,35 @@ fn run(mut listener: EntryListener) {
                 ).expect("Unable to format Value");
         
                 println!("{}", value_str);
-        
+
+                if let Some(journal) = journal.as_mut() {
+                    let sequence = get_sequence(&current_value)?;
+
+                    // A new sequence is a new file in a reused entry, which
+                    // needs its own snapshot to be rebuilt from the journal
+                    if sequence != get_sequence(&previous_value)? {
+                        journal.append_snapshot(
+                            listener.entry_to_monitor as u64,
+                            sequence,
+                            usn,
+                            &current_value
+                        ).expect("Unable to write snapshot to journal");
+                    } else {
+                        let patch = get_json_patch(
+                            &previous_value,
+                            &current_value,
+                            &DiffOptions::default()
+                        );
+
+                        journal.append_diff(
+                            listener.entry_to_monitor as u64,
+                            sequence,
+                            usn,
+                            &patch
+                        ).expect("Unable to write diff to journal");
+                    }
+                }
+
                 previous_value = current_value.to_owned();
             }
         }
@@ -120,5 +202,15 @@ fn main() {
         file_path
     ).expect("Error creating EntryListener");
 
-    run(listener);
+    let journal = match options.value_of("journal") {
+        Some(p) => Some(
+            HistoryJournal::open(p).expect("Error opening journal")
+        ),
+        None => None
+    };
+
+    if let Err(e) = run(listener, journal) {
+        eprintln!("Error listening to entry: {}", e.display_chain());
+        exit(-1);
+    }
 }
\ No newline at end of file
@@ -1,6 +1,17 @@
+#[macro_use] extern crate log;
 #[macro_use] extern crate serde_json;
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 use std::process::exit;
+use std::collections::BTreeMap;
+use serde_json::Value;
+use rswinthings::errors::WinThingError;
+use rswinthings::channels::policy::{
+    read_channel_dump_path,
+    AuditReport,
+    ChannelPolicy
+};
+use rswinthings::channels::info::ChannelConfigInfo;
+use rswinthings::winevt::EvtHandle;
 use rswinthings::utils::cli::{
     add_session_options_to_app,
     get_session_from_matches
@@ -18,8 +29,21 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .long("format")
         .value_name("FORMAT")
         .takes_value(true)
-        .possible_values(&["text", "jsonl"])
-        .help("Output format. (defaults to text)");
+        .possible_values(&["text", "jsonl", "info"])
+        .help("Output format. jsonl is the raw properties, info the decoded properties as JSONL. (defaults to text)");
+
+    let policy = Arg::with_name("policy")
+        .long("policy")
+        .value_name("POLICY")
+        .takes_value(true)
+        .help("Audit the channels against a JSON baseline policy. Exits with 1 if any channel deviates.");
+
+    let dump = Arg::with_name("dump")
+        .long("dump")
+        .value_name("DUMP")
+        .takes_value(true)
+        .requires("policy")
+        .help("Audit a saved JSONL dump (print_channels -f jsonl) instead of the channels on the computer.");
 
     let debug = Arg::with_name("debug")
         .short("-d")
@@ -34,6 +58,8 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
         .about("Print Channel Propperties.")
         .arg(format)
+        .arg(policy)
+        .arg(dump)
         .arg(debug);
     
     // Add session arguments to app
@@ -42,15 +68,24 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
 
 
 fn print_text_value(name: &str, config_value: serde_json::Value) {
-    let config_map = config_value.as_object().expect(
-        "config_value should be a mapping."
-    );
-
     println!("========================================================");
     println!("Channel: {}", name);
     println!("========================================================");
-    for (key, value) in config_map {
-        println!("{}: {}", key, value);
+    match ChannelConfigInfo::from_json_value(name, &config_value) {
+        Ok(info) => {
+            for line in info.to_text_lines() {
+                println!("{}", line);
+            }
+        },
+        Err(e) => {
+            eprintln!("Error decoding channel config for {}: {}", name, e.display_chain());
+            let config_map = config_value.as_object().expect(
+                "config_value should be a mapping."
+            );
+            for (key, value) in config_map {
+                println!("{}: {}", key, value);
+            }
+        }
     }
     println!("");
 }
@@ -60,6 +95,84 @@ fn print_jsonl_value(config_value: serde_json::Value) {
     println!("{}", config_value.to_string());
 }
 
+/// Get the configs of the policy's channels from the session's computer.
+/// Channels that can't be opened are left out and reported as missing.
+fn get_policy_channel_configs(
+    session: &Option<EvtHandle>,
+    policy: &ChannelPolicy
+) -> BTreeMap<String, Value> {
+    let mut configs = BTreeMap::new();
+
+    for channel in policy.get_channel_names() {
+        let channel_config = match ChannelConfig::from_session(session, channel.to_owned()) {
+            Ok(c) => c,
+            Err(e) => {
+                debug!("Error opening ChannelConfig for {}: {}", channel, e.display_chain());
+                continue;
+            }
+        };
+
+        match channel_config.to_json_value() {
+            Ok(v) => { configs.insert(channel.to_owned(), v); },
+            Err(e) => eprintln!("Error getting channel config for {}: {}", channel, e.display_chain())
+        }
+    }
+
+    configs
+}
+
+
+fn print_audit_report(report: &AuditReport, out_format: &str) {
+    for audit in &report.channels {
+        match out_format {
+            "jsonl" => println!("{}", json!(audit)),
+            _ => {
+                if !audit.present {
+                    println!("{}: MISSING", audit.channel);
+                    continue;
+                }
+
+                match audit.is_compliant() {
+                    true => println!("{}: OK", audit.channel),
+                    false => {
+                        println!("{}: {} deviations", audit.channel, audit.get_deviation_count());
+                        if let Some(deviations) = audit.deviations.as_object() {
+                            for (path, deviation) in deviations {
+                                println!("    {}: {}", path, deviation);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/// Audit the channels against the policy. Returns true if they comply.
+fn audit_channels(
+    options: &ArgMatches,
+    policy_path: &str,
+    out_format: &str
+) -> Result<bool, WinThingError> {
+    let policy = ChannelPolicy::from_path(policy_path)?;
+
+    let configs = match options.value_of("dump") {
+        Some(dump_path) => read_channel_dump_path(dump_path)?,
+        None => {
+            let session = get_session_from_matches(options)?
+                .map(|s| s.0);
+            get_policy_channel_configs(&session, &policy)
+        }
+    };
+
+    let report = policy.audit(&configs);
+    print_audit_report(&report, out_format);
+
+    Ok(report.is_compliant())
+}
+
+
 fn main() {
     let app = make_app();
     let options = app.get_matches();
@@ -77,6 +190,17 @@ fn main() {
         None => "text"
     };
 
+    if let Some(policy_path) = options.value_of("policy") {
+        match audit_channels(&options, policy_path, out_format) {
+            Ok(true) => return,
+            Ok(false) => exit(1),
+            Err(e) => {
+                eprintln!("Error auditing channels: {}", e.display_chain());
+                exit(-1);
+            }
+        }
+    }
+
     let session = match get_session_from_matches(
         &options
     ).expect("Error getting session from options") {
@@ -93,7 +217,7 @@ fn main() {
         let channel_config = match ChannelConfig::new(channel.clone()) {
             Ok(c) => c,
             Err(e) => {
-                eprintln!("Error creating ChannelConfig for: {:?}", e);
+                eprintln!("Error creating ChannelConfig for: {}", e.display_chain());
                 continue;
             }
         };
@@ -101,7 +225,7 @@ fn main() {
         let mut channel_config_value = match channel_config.to_json_value() {
             Ok(p) => p,
             Err(e) => {
-                eprintln!("Error getting channel config. {:?}", e);
+                eprintln!("Error getting channel config. {}", e.display_chain());
                 continue;
             }
         };
@@ -112,6 +236,12 @@ fn main() {
                 channel_config_value["ChannelName"] = json!(channel.to_owned());
                 print_jsonl_value(channel_config_value);
             },
+            "info" => {
+                match ChannelConfigInfo::from_json_value(&channel, &channel_config_value) {
+                    Ok(info) => println!("{}", json!(info)),
+                    Err(e) => eprintln!("Error decoding channel config for {}: {}", channel, e.display_chain())
+                }
+            },
             other => {
                 eprintln!("Unhandled output format: {}", other);
                 exit(-1);
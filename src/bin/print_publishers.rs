@@ -1,15 +1,27 @@
 #[macro_use] extern crate log;
-use clap::{App, Arg};
+#[macro_use] extern crate serde_json;
+use clap::{App, Arg, ArgMatches};
+use std::sync::Arc;
 use std::process::exit;
+use std::time::Duration;
+use rswinthings::errors::WinThingError;
 use winapi::um::winevt::*;
 use rswinthings::utils::debug::set_debug_level;
 use rswinthings::winetl::publisher::PublisherMeta;
+use rswinthings::winetl::publisher::get_publisher_name_list;
+use rswinthings::utils::pool::{
+    run_shared_tasks,
+    PoolOptions,
+    DEFAULT_TASK_TIMEOUT,
+    DEFAULT_WORKERS
+};
 use rswinthings::winetl::publisher::PublisherEnumerator;
 use rswinthings::utils::cli::{
     add_session_options_to_app,
     get_session_from_matches
 };
 use rswinthings::winevt::EvtHandle;
+use rswinthings::winevt::session::SharedSession;
 
 static VERSION: &'static str = "0.1.0";
 
@@ -31,6 +43,24 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .possible_values(&["text", "jsonl"])
         .help("Output format. (defaults to text)");
 
+    let events = Arg::with_name("events")
+        .short("-e")
+        .long("events")
+        .help("Include the event definitions (EventID, version, level, task, keywords, message and template fields).");
+
+    let jobs = Arg::with_name("jobs")
+        .short("-j")
+        .long("jobs")
+        .value_name("JOBS")
+        .takes_value(true)
+        .help("Open publishers concurrently with this many workers. Output is in the order publishers finish. [default: 8 with --timeout]");
+
+    let timeout = Arg::with_name("timeout")
+        .long("timeout")
+        .value_name("SECONDS")
+        .takes_value(true)
+        .help("With --jobs, give up on a publisher after this many seconds and output an error record for it. 0 waits forever. [default: 30]");
+
     let debug = Arg::with_name("debug")
         .short("-d")
         .long("debug")
@@ -45,6 +75,9 @@ fn make_app<'a, 'b>() -> App<'a, 'b> {
         .about("Print Publisher Propperties.")
         .arg(provider)
         .arg(format)
+        .arg(events)
+        .arg(jobs)
+        .arg(timeout)
         .arg(debug);
 
     // Add session arguments to app
@@ -58,7 +91,14 @@ fn get_message_desc(message: Option<String>) -> String {
     }
 }
 
-fn get_text_block(meta: &PublisherMeta) -> String {
+fn get_first_line(message: &Option<String>) -> &str {
+    match message {
+        Some(m) => m.lines().next().unwrap_or("").trim(),
+        None => ""
+    }
+}
+
+fn get_text_block(meta: &PublisherMeta, with_events: bool) -> String {
     let mut message: String;
     let mut temp: String;
     message = "----------------------------------------------\n".to_string();
@@ -204,10 +244,151 @@ fn get_text_block(meta: &PublisherMeta) -> String {
         }
     }
 
+    if with_events {
+        message.push_str(
+            &format!("--- Events ---\n")
+        );
+        match meta.get_metadata_events() {
+            Ok(metadata) => {
+                for event in metadata.0 {
+                    message.push_str(
+                        &format!(
+                            "{} v{} [Level {}, Task {}, Opcode {}, Keywords 0x{:016X}]: {}\n",
+                            event.get_event_id(),
+                            event.version,
+                            event.level,
+                            event.task,
+                            event.opcode,
+                            event.keywords,
+                            get_first_line(&event.message)
+                        )
+                    );
+                    if !event.fields.is_empty() {
+                        let names: Vec<&str> = event.fields.iter()
+                            .map(|f| f.name.as_str())
+                            .collect();
+                        message.push_str(
+                            &format!("    Fields: {}\n", names.join(", "))
+                        );
+                    }
+                }
+            },
+            Err(e) => {
+                error!("[{}] Error getting events: {}", meta.name, e.message);
+            }
+        }
+    }
+
     message
 }
 
 
+fn get_publisher_output(
+    publisher_meta: &PublisherMeta,
+    out_format: &str,
+    with_events: bool
+) -> Result<String, WinThingError> {
+    match out_format {
+        "text" => Ok(
+            get_text_block(&publisher_meta, with_events)
+        ),
+        _ => Ok(
+            publisher_meta.get_json_value(with_events)?.to_string()
+        )
+    }
+}
+
+
+fn print_publisher(publisher_meta: &PublisherMeta, out_format: &str, with_events: bool) {
+    match get_publisher_output(publisher_meta, out_format, with_events) {
+        Ok(out) => println!("{}", out),
+        Err(e) => error!("Error serializing value: {:?}", e)
+    }
+}
+
+
+/// Open and output the publishers on a pool of workers, giving up on any
+/// that take longer than the timeout. Failed publishers are output as error
+/// records. Returns the number of failed publishers.
+fn print_publishers_concurrently(
+    options: &ArgMatches,
+    session: Option<EvtHandle>,
+    out_format: &str,
+    with_events: bool
+) -> Result<usize, WinThingError> {
+    let names: Vec<String> = match options.values_of("provider") {
+        Some(p_list) => p_list.map(|p| p.to_string()).collect(),
+        None => get_publisher_name_list(&session)?
+    };
+
+    let workers = match options.value_of("jobs") {
+        Some(j) => j.parse::<usize>().map_err(|e|
+            WinThingError::cli_error(
+                format!("Invalid number of jobs '{}': {}", j, e)
+            )
+        )?,
+        None => DEFAULT_WORKERS
+    };
+
+    let timeout = match options.value_of("timeout") {
+        Some("0") => None,
+        Some(t) => Some(Duration::from_secs(
+            t.parse::<u64>().map_err(|e|
+                WinThingError::cli_error(
+                    format!("Invalid timeout '{}': {}", t, e)
+                )
+            )?
+        )),
+        None => Some(DEFAULT_TASK_TIMEOUT)
+    };
+
+    let pool_options = PoolOptions::new()
+        .with_workers(workers)
+        .with_timeout(timeout);
+
+    let session = Arc::new(SharedSession::new(session));
+    let cancel_session = Arc::clone(&session);
+    let task_format = out_format.to_owned();
+    let outcomes = run_shared_tasks(names, pool_options, move |name| {
+        let publisher_meta = PublisherMeta::new(
+            session.get(),
+            name.to_string()
+        )?;
+
+        get_publisher_output(&publisher_meta, &task_format, with_events)
+    }, move |name| {
+        // A timeout means the remote computer stopped responding, so the
+        // other pending calls are cancelled with it. run_shared_tasks runs
+        // the publishers cancelled this way again.
+        if let Err(e) = cancel_session.cancel() {
+            warn!("Error cancelling {}: {}", name, e.display_chain());
+        }
+    });
+
+    let mut error_count = 0;
+    for outcome in outcomes {
+        let status = match outcome.is_cancelled() {
+            true => "cancelled",
+            false => "publisher_error"
+        };
+        if let Some(record) = outcome.get_error_record(status) {
+            error_count += 1;
+            match out_format {
+                "jsonl" => println!("{}", json!(record)),
+                _ => eprintln!("{}", json!(record))
+            }
+            continue;
+        }
+
+        if let Ok(out) = outcome.result {
+            println!("{}", out);
+        }
+    }
+
+    Ok(error_count)
+}
+
+
 fn main() {
     let app = make_app();
     let options = app.get_matches();
@@ -234,6 +415,22 @@ fn main() {
         None => "text"
     };
 
+    let with_events = options.is_present("events");
+
+    if options.is_present("jobs") || options.is_present("timeout") {
+        match print_publishers_concurrently(&options, session, out_format, with_events) {
+            Ok(0) => return,
+            Ok(count) => {
+                eprintln!("{} publishers failed.", count);
+                exit(1);
+            },
+            Err(e) => {
+                eprintln!("Error enumerating publishers: {}", e.display_chain());
+                exit(-1);
+            }
+        }
+    }
+
     match options.values_of("provider") {
         Some(p_list) => {
             for value in p_list {
@@ -241,27 +438,8 @@ fn main() {
                     &session,
                     value.to_string()
                 ).expect("Error creating PublisherMeta");
-                
-                match out_format {
-                    "text" => {
-                        let out = get_text_block(&publisher_meta);
-                        println!("{}", out);
-                    },
-                    "jsonl" => {
-                        let meta_value = match publisher_meta.to_json_value() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                error!("Error serializing value: {:?}", e);
-                                continue;
-                            }
-                        };
-                        println!("{}", meta_value.to_string());
-                    },
-                    other => {
-                        eprintln!("Unhandled output format: {}", other);
-                        exit(-1);
-                    }
-                }
+
+                print_publisher(&publisher_meta, out_format, with_events);
             }
         },
         None => {
@@ -269,27 +447,8 @@ fn main() {
                 .expect("Error creating PublisherEnumerator");
 
             for publisher_meta in enumerator {
-                match out_format {
-                    "text" => {
-                        let out = get_text_block(&publisher_meta);
-                        println!("{}", out);
-                    },
-                    "jsonl" => {
-                        let meta_value = match publisher_meta.to_json_value() {
-                            Ok(v) => v,
-                            Err(e) => {
-                                error!("Error serializing value: {:?}", e);
-                                continue;
-                            }
-                        };
-                        println!("{}", meta_value.to_string());
-                    },
-                    other => {
-                        eprintln!("Unhandled output format: {}", other);
-                        exit(-1);
-                    }
-                }
+                print_publisher(&publisher_meta, out_format, with_events);
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,257 @@
+#[macro_use] extern crate log;
+extern crate serde_json;
+use clap::{App, Arg, ArgMatches};
+use std::process::exit;
+use rswinthings::errors::WinThingError;
+use rswinthings::events::query::{
+    parse_query_time,
+    QueryFilter,
+    QueryOptions
+};
+use rswinthings::utils::debug::set_debug_level;
+use rswinthings::winevt::EvtHandle;
+use rswinthings::winevt::callback::OutputFormat;
+use rswinthings::winevt::callback::CallbackContext;
+use rswinthings::winevt::query::EventQuery;
+use rswinthings::utils::cli::{
+    add_session_options_to_app,
+    get_session_from_matches
+};
+
+static VERSION: &'static str = "0.1.0";
+static DESCRIPTION: &'static str = r"
+Query the events of a channel or an EVTX file and exit.
+
+Filters on time range, EventID and provider are combined into a single
+XPath query, along with any extra XPath predicate given. Times are RFC 3339
+(2020-01-20T10:15:00Z) or dates (2020-01-20).
+";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let channel = Arg::with_name("channel")
+        .short("-c")
+        .long("channel")
+        .value_name("CHANNEL")
+        .takes_value(true)
+        .conflicts_with("evtx")
+        .required_unless("evtx")
+        .help("The channel to query.");
+
+    let evtx = Arg::with_name("evtx")
+        .short("-e")
+        .long("evtx")
+        .value_name("EVTX")
+        .takes_value(true)
+        .help("The EVTX file to query.");
+
+    let start = Arg::with_name("start")
+        .long("start")
+        .value_name("START")
+        .takes_value(true)
+        .help("Only events created at or after this time.");
+
+    let end = Arg::with_name("end")
+        .long("end")
+        .value_name("END")
+        .takes_value(true)
+        .help("Only events created at or before this time.");
+
+    let event_id = Arg::with_name("event_id")
+        .long("event-id")
+        .value_name("EVENT_ID")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Only events with this EventID. Can be given more than once.");
+
+    let provider = Arg::with_name("provider")
+        .long("provider")
+        .value_name("PROVIDER")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Only events from this provider. Can be given more than once.");
+
+    let xpath = Arg::with_name("xpath")
+        .long("xpath")
+        .value_name("XPATH")
+        .takes_value(true)
+        .help("An extra XPath predicate, e.g. \"EventData[Data[@Name='LogonType']='3']\".");
+
+    let reverse = Arg::with_name("reverse")
+        .short("-r")
+        .long("reverse")
+        .help("Return the newest events first.");
+
+    let max_count = Arg::with_name("max_count")
+        .short("-m")
+        .long("max-count")
+        .value_name("MAX_COUNT")
+        .takes_value(true)
+        .help("Stop after this many events.");
+
+    let format = Arg::with_name("format")
+        .short("-f")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["xml", "jsonl"])
+        .help("Output format to use. [defaults to jsonl]");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    let app = App::new("query_events")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about(DESCRIPTION)
+        .arg(channel)
+        .arg(evtx)
+        .arg(start)
+        .arg(end)
+        .arg(event_id)
+        .arg(provider)
+        .arg(xpath)
+        .arg(reverse)
+        .arg(max_count)
+        .arg(format)
+        .arg(debug);
+
+    // Add session arguments to app
+    add_session_options_to_app(app)
+}
+
+
+fn get_filter(options: &ArgMatches) -> Result<QueryFilter, WinThingError> {
+    let mut filter = QueryFilter::new();
+
+    if let Some(start) = options.value_of("start") {
+        filter = filter.with_start_time(
+            parse_query_time(start)?
+        );
+    }
+
+    if let Some(end) = options.value_of("end") {
+        filter = filter.with_end_time(
+            parse_query_time(end)?
+        );
+    }
+
+    if let Some(event_ids) = options.values_of("event_id") {
+        for event_id in event_ids {
+            let event_id = event_id.parse::<u32>().map_err(|e|
+                WinThingError::cli_error(
+                    format!("Invalid EventID '{}': {}", event_id, e)
+                )
+            )?;
+            filter = filter.with_event_id(event_id);
+        }
+    }
+
+    if let Some(providers) = options.values_of("provider") {
+        for provider in providers {
+            filter = filter.with_provider(provider);
+        }
+    }
+
+    if let Some(xpath) = options.value_of("xpath") {
+        filter = filter.with_xpath(xpath);
+    }
+
+    Ok(filter)
+}
+
+
+fn get_query_options(options: &ArgMatches) -> Result<QueryOptions, WinThingError> {
+    let mut query_options = QueryOptions::new()
+        .with_reverse(options.is_present("reverse"));
+
+    if let Some(max_count) = options.value_of("max_count") {
+        let max_count = max_count.parse::<usize>().map_err(|e|
+            WinThingError::cli_error(
+                format!("Invalid max count '{}': {}", max_count, e)
+            )
+        )?;
+        query_options = query_options.with_max_count(max_count);
+    }
+
+    Ok(query_options)
+}
+
+
+fn run(options: &ArgMatches, context: &CallbackContext) -> Result<usize, WinThingError> {
+    let filter = get_filter(options)?;
+    let query_options = get_query_options(options)?;
+    let xpath = filter.to_xpath();
+    debug!("query: {}", xpath);
+
+    // The session must outlive the query
+    let session: Option<EvtHandle> = match options.value_of("evtx") {
+        Some(_) => None,
+        None => get_session_from_matches(options)?.map(|s| s.0)
+    };
+
+    let query = match options.value_of("evtx") {
+        Some(path) => EventQuery::from_file(
+            path,
+            Some(&xpath),
+            query_options
+        )?,
+        None => EventQuery::from_channel(
+            &session,
+            options.value_of("channel").unwrap_or(""),
+            Some(&xpath),
+            query_options
+        )?
+    };
+
+    let mut count = 0;
+    for result in query {
+        match result {
+            Ok(xml_event) => {
+                context.handle_record(xml_event);
+                count += 1;
+            },
+            Err(e) => eprintln!("Error reading event: {}", e.display_chain())
+        }
+    }
+
+    Ok(count)
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => set_debug_level("Error").expect(
+            "Error setting debug level"
+        )
+    }
+
+    let format_enum = match options.value_of("format") {
+        Some("xml") => OutputFormat::XmlFormat,
+        _ => OutputFormat::JsonlFormat
+    };
+
+    let context = CallbackContext::new()
+        .with_format(format_enum);
+
+    match run(&options, &context) {
+        Ok(count) => eprintln!("{} events returned.", count),
+        Err(e) => {
+            eprintln!("Error querying events: {}", e.display_chain());
+            exit(-1);
+        }
+    }
+}
@@ -0,0 +1,325 @@
+use clap::{App, Arg, ArgMatches};
+use std::io;
+use std::process::exit;
+use rswinthings::errors::WinThingError;
+use rswinthings::utils::debug::set_debug_level;
+use rswinthings::events::query::parse_query_time;
+use rswinthings::mft::history::HistoryTimeline;
+use rswinthings::volume::image::NtfsImage;
+use rswinthings::timeline::merge::merge_timelines;
+use rswinthings::timeline::export::{
+    export_items,
+    get_history_export_items,
+    get_image_export_items,
+    ExportFormat,
+    ExportItem,
+    TimeRange
+};
+use rswinthings::timeline::record::{
+    read_event_timeline_path,
+    read_history_timeline_path,
+    read_usn_timeline_path,
+    TimelineRecord
+};
+
+static VERSION: &'static str = "0.1.0";
+static DESCRIPTION: &'static str = r"
+Merge event log records, USN records and MFT entry changes into one
+timeline ordered by time.
+
+Events are listen_events or query_events JSONL output (or XML), USN records
+are JSONL and MFT changes are listen_mft --journal files. Every record gets
+a UTC timestamp, host, source and a normalised path, so that, for example,
+a PowerShell 4104 event lines up with the creation of its script in the
+USN journal.
+
+Output is JSONL, or a super-timeline format: a Sleuthkit bodyfile,
+log2timeline CSV or TLN. These use the SI and FN MACB times of MFT
+entries, from the last recorded state of each entry in a journal or
+from entries read from an NTFS image (the --entry entries, or every
+entry of its MFT).
+";
+
+
+fn make_app<'a, 'b>() -> App<'a, 'b> {
+    let events = Arg::with_name("events")
+        .short("-e")
+        .long("events")
+        .value_name("EVENTS")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("An event JSONL or XML file. Can be given more than once.");
+
+    let usn = Arg::with_name("usn")
+        .short("-u")
+        .long("usn")
+        .value_name("USN")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("A USN record JSONL file. Can be given more than once.");
+
+    let mft_journal = Arg::with_name("mft_journal")
+        .short("-m")
+        .long("mft-journal")
+        .value_name("MFT_JOURNAL")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("A listen_mft history journal. Can be given more than once.");
+
+    let host = Arg::with_name("host")
+        .long("host")
+        .value_name("HOST")
+        .takes_value(true)
+        .help("The host of the USN and MFT records. (Events use their source host or computer.)");
+
+    let volume = Arg::with_name("volume")
+        .long("volume")
+        .value_name("VOLUME")
+        .takes_value(true)
+        .help("The volume of the USN and MFT records (e.g. C:), prefixed to their paths.");
+
+    let image = Arg::with_name("image")
+        .long("image")
+        .value_name("IMAGE")
+        .takes_value(true)
+        .help("An NTFS image to read the entries given with --entry, or every entry, from.");
+
+    let offset = Arg::with_name("offset")
+        .long("offset")
+        .value_name("OFFSET")
+        .takes_value(true)
+        .requires("image")
+        .help("The offset of the volume in the image in bytes. (defaults to 0)");
+
+    let entry = Arg::with_name("entry")
+        .long("entry")
+        .value_name("ENTRY")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .requires("image")
+        .help("An entry to read from the image. Can be given more than once.");
+
+    let format = Arg::with_name("format")
+        .short("-f")
+        .long("format")
+        .value_name("FORMAT")
+        .takes_value(true)
+        .possible_values(&["jsonl", "bodyfile", "l2tcsv", "tln"])
+        .help("Output format. (defaults to jsonl)");
+
+    let start = Arg::with_name("start")
+        .long("start")
+        .value_name("START")
+        .takes_value(true)
+        .help("Only times at or after this time.");
+
+    let end = Arg::with_name("end")
+        .long("end")
+        .value_name("END")
+        .takes_value(true)
+        .help("Only times at or before this time.");
+
+    let debug = Arg::with_name("debug")
+        .short("-d")
+        .long("debug")
+        .value_name("DEBUG")
+        .takes_value(true)
+        .possible_values(&["Off", "Error", "Warn", "Info", "Debug", "Trace"])
+        .help("Debug level to use.");
+
+    App::new("timeline")
+        .version(VERSION)
+        .author("Matthew Seyer <https://github.com/forensicmatt/RsWindowsThingies>")
+        .about(DESCRIPTION)
+        .arg(events)
+        .arg(usn)
+        .arg(mft_journal)
+        .arg(host)
+        .arg(volume)
+        .arg(image)
+        .arg(offset)
+        .arg(entry)
+        .arg(format)
+        .arg(start)
+        .arg(end)
+        .arg(debug)
+}
+
+
+fn get_paths<'a>(options: &'a ArgMatches, name: &str) -> Vec<&'a str> {
+    match options.values_of(name) {
+        Some(v) => v.collect(),
+        None => Vec::new()
+    }
+}
+
+
+fn get_time_range(options: &ArgMatches) -> Result<TimeRange, WinThingError> {
+    let start = match options.value_of("start") {
+        Some(start) => Some(parse_query_time(start)?),
+        None => None
+    };
+    let end = match options.value_of("end") {
+        Some(end) => Some(parse_query_time(end)?),
+        None => None
+    };
+
+    Ok(TimeRange::new(start, end))
+}
+
+
+fn get_image_entries(options: &ArgMatches) -> Result<Vec<u64>, WinThingError> {
+    get_paths(options, "entry").iter()
+        .map(|entry| entry.parse::<u64>().map_err(|e|
+            WinThingError::cli_error(
+                format!("Invalid entry '{}': {}", entry, e)
+            )
+        ))
+        .collect()
+}
+
+
+/// Read the event and USN files given in the options, one timeline per file.
+fn read_record_timelines(options: &ArgMatches) -> Result<Vec<Vec<TimelineRecord>>, WinThingError> {
+    let host = options.value_of("host");
+    let volume = options.value_of("volume");
+    let mut timelines = Vec::new();
+
+    for path in get_paths(options, "events") {
+        timelines.push(read_event_timeline_path(path)?);
+    }
+    for path in get_paths(options, "usn") {
+        timelines.push(read_usn_timeline_path(path, host, volume)?);
+    }
+
+    Ok(timelines)
+}
+
+
+fn check_inputs(options: &ArgMatches) -> Result<(), WinThingError> {
+    let has_input = ["events", "usn", "mft_journal", "image"].iter()
+        .any(|name| options.is_present(name));
+
+    if !has_input {
+        return Err(
+            WinThingError::cli_error(
+                "At least one of --events, --usn, --mft-journal or --image is required.".to_owned()
+            )
+        );
+    }
+
+    Ok(())
+}
+
+
+/// Write the merged records as JSONL. MFT journals add a record per change.
+fn write_jsonl(options: &ArgMatches, range: &TimeRange) -> Result<(), WinThingError> {
+    if options.is_present("image") {
+        return Err(
+            WinThingError::cli_error(
+                "--image requires a bodyfile, l2tcsv or tln --format.".to_owned()
+            )
+        );
+    }
+
+    let host = options.value_of("host");
+    let mut timelines = read_record_timelines(options)?;
+    for path in get_paths(options, "mft_journal") {
+        timelines.push(read_history_timeline_path(path, host)?);
+    }
+
+    for record in merge_timelines(timelines) {
+        if range.contains(record.timestamp) {
+            println!("{}", serde_json::to_string(&record)?);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Write a super-timeline format. MFT journals add the items of the last
+/// state of each entry.
+fn write_export(
+    options: &ArgMatches,
+    format: ExportFormat,
+    range: &TimeRange
+) -> Result<(), WinThingError> {
+    let host = options.value_of("host");
+    let volume = options.value_of("volume");
+
+    let mut items: Vec<ExportItem> = read_record_timelines(options)?
+        .iter()
+        .flatten()
+        .map(ExportItem::from_timeline_record)
+        .collect();
+
+    for path in get_paths(options, "mft_journal") {
+        let history = HistoryTimeline::from_path(path)?;
+        items.extend(
+            get_history_export_items(&history, host, volume)?
+        );
+    }
+
+    if let Some(image_path) = options.value_of("image") {
+        let offset = match options.value_of("offset") {
+            Some(offset) => offset.parse::<u64>().map_err(|e|
+                WinThingError::cli_error(
+                    format!("Invalid offset '{}': {}", offset, e)
+                )
+            )?,
+            None => 0
+        };
+
+        let entries = match options.is_present("entry") {
+            true => Some(get_image_entries(options)?),
+            false => None
+        };
+
+        let mut image = NtfsImage::from_path(image_path, offset)?;
+        items.extend(
+            get_image_export_items(&mut image, entries.as_deref(), host, volume)?
+        );
+    }
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+    export_items(&mut handle, &items, format, range)?;
+
+    Ok(())
+}
+
+
+fn run(options: &ArgMatches) -> Result<(), WinThingError> {
+    check_inputs(options)?;
+    let range = get_time_range(options)?;
+
+    match options.value_of("format") {
+        None | Some("jsonl") => write_jsonl(options, &range),
+        Some(format) => write_export(options, format.parse()?, &range)
+    }
+}
+
+
+fn main() {
+    let app = make_app();
+    let options = app.get_matches();
+
+    match options.value_of("debug") {
+        Some(d) => set_debug_level(d).expect(
+            "Error setting debug level"
+        ),
+        None => set_debug_level("Error").expect(
+            "Error setting debug level"
+        )
+    }
+
+    if let Err(e) = run(&options) {
+        eprintln!("Error creating timeline: {}", e.display_chain());
+        exit(-1);
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde::Serialize;
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::publishers::PUBLISHER_NAME_KEY;
+
+/// The key of the channels a publisher references.
+pub const CHANNEL_REFERENCES_KEY: &str = "EvtPublisherMetadataChannelReferences";
+/// The key of the publishers a channel lists.
+pub const CHANNEL_PUBLISHERS_KEY: &str = "EvtChannelPublisherList";
+/// The key of a channel's enabled flag.
+pub const CHANNEL_ENABLED_KEY: &str = "EvtChannelConfigEnabled";
+/// The key of a channel's owning publisher.
+pub const CHANNEL_OWNING_PUBLISHER_KEY: &str = "EvtChannelConfigOwningPublisher";
+
+
+/// A channel in the index. `enabled` and `owning_publisher` are `None` when
+/// the channel is only known from a publisher's channel references.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChannelNode {
+    pub name: String,
+    pub enabled: Option<bool>,
+    pub owning_publisher: Option<String>,
+    pub publishers: BTreeSet<String>
+}
+
+impl ChannelNode {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            enabled: None,
+            owning_publisher: None,
+            publishers: BTreeSet::new()
+        }
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.enabled == Some(false)
+    }
+}
+
+
+/// A publisher in the index and the channels it can write to.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PublisherNode {
+    pub name: String,
+    pub channels: BTreeSet<String>
+}
+
+impl PublisherNode {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            channels: BTreeSet::new()
+        }
+    }
+}
+
+
+/// A publisher with at least one disabled channel. `all_disabled` means
+/// none of its known channels are enabled, so its events are not logged.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DisabledPublisher {
+    pub publisher: String,
+    pub disabled_channels: Vec<String>,
+    pub all_disabled: bool
+}
+
+
+/// A cross-reference of publishers and the channels they write to, built
+/// from both the publishers' channel references and the channels' publisher
+/// lists.
+///
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ChannelGraph {
+    pub channels: BTreeMap<String, ChannelNode>,
+    pub publishers: BTreeMap<String, PublisherNode>
+}
+
+impl ChannelGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build the index from publisher records and channel configs keyed by
+    /// channel name. A channel config that can not be read is skipped with a
+    /// warning.
+    pub fn from_values(
+        publishers: &[Value],
+        channels: &BTreeMap<String, Value>
+    ) -> Result<Self, WinThingError> {
+        let mut graph = Self::new();
+
+        for (name, config) in channels {
+            if let Err(e) = graph.add_channel_config(name, config) {
+                warn!("Skipping channel {}: {}", name, e.display_chain());
+            }
+        }
+        for publisher in publishers {
+            graph.add_publisher_value(publisher)?;
+        }
+
+        Ok(graph)
+    }
+
+    /// Add an edge from a publisher to a channel.
+    pub fn add_edge(&mut self, publisher: &str, channel: &str) {
+        self.publishers.entry(publisher.to_owned())
+            .or_insert_with(|| PublisherNode::new(publisher))
+            .channels.insert(channel.to_owned());
+        self.channels.entry(channel.to_owned())
+            .or_insert_with(|| ChannelNode::new(channel))
+            .publishers.insert(publisher.to_owned());
+    }
+
+    /// Add a channel config as output by `ChannelConfig::to_json_value`. Only
+    /// the publisher list, enabled flag and owning publisher are read.
+    pub fn add_channel_config(&mut self, name: &str, config: &Value) -> Result<(), WinThingError> {
+        let unexpected = |key: &str, value: &Value| {
+            WinThingError::invalid_input(
+                format!("Not expecting {} for {} of {}", value, key, name)
+            )
+        };
+
+        let publishers: Vec<&str> = match config.get(CHANNEL_PUBLISHERS_KEY) {
+            Some(Value::Array(values)) => values.iter()
+                .map(|v| v.as_str().ok_or_else(|| unexpected(CHANNEL_PUBLISHERS_KEY, v)))
+                .collect::<Result<_, _>>()?,
+            Some(Value::String(publisher)) => vec![publisher],
+            Some(Value::Null) | None => Vec::new(),
+            Some(other) => return Err(unexpected(CHANNEL_PUBLISHERS_KEY, other))
+        };
+        let enabled = match config.get(CHANNEL_ENABLED_KEY) {
+            Some(Value::Bool(enabled)) => Some(*enabled),
+            Some(Value::Null) | None => None,
+            Some(other) => return Err(unexpected(CHANNEL_ENABLED_KEY, other))
+        };
+        let owning_publisher = match config.get(CHANNEL_OWNING_PUBLISHER_KEY) {
+            Some(Value::String(publisher)) => Some(publisher.to_owned()),
+            Some(Value::Null) | None => None,
+            Some(other) => return Err(unexpected(CHANNEL_OWNING_PUBLISHER_KEY, other))
+        };
+
+        for publisher in publishers {
+            self.add_edge(publisher, name);
+        }
+
+        let node = self.channels.entry(name.to_owned())
+            .or_insert_with(|| ChannelNode::new(name));
+        node.enabled = enabled;
+        node.owning_publisher = owning_publisher;
+
+        Ok(())
+    }
+
+    /// Add a publisher record as output by `PublisherMeta::to_json_value`.
+    pub fn add_publisher_value(&mut self, publisher: &Value) -> Result<(), WinThingError> {
+        let name = match publisher.get(PUBLISHER_NAME_KEY) {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(
+                    WinThingError::invalid_input(
+                        format!("Publisher record has no {}.", PUBLISHER_NAME_KEY)
+                    )
+                );
+            }
+        };
+
+        self.publishers.entry(name.to_owned())
+            .or_insert_with(|| PublisherNode::new(name));
+
+        let references = match publisher.get(CHANNEL_REFERENCES_KEY) {
+            Some(Value::Array(references)) => references,
+            Some(Value::Null) | None => return Ok(()),
+            Some(other) => {
+                return Err(
+                    WinThingError::invalid_input(
+                        format!("Not expecting {} for {} of {}", other, CHANNEL_REFERENCES_KEY, name)
+                    )
+                );
+            }
+        };
+
+        for reference in references {
+            match reference.get("path") {
+                Some(Value::String(path)) if !path.is_empty() => self.add_edge(name, path),
+                _ => debug!("[{}] Skipping channel reference without a path: {}", name, reference)
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the publishers that write to a channel.
+    pub fn get_channel_publishers(&self, channel: &str) -> Vec<&str> {
+        match self.channels.get(channel) {
+            Some(node) => node.publishers.iter()
+                .map(|p| p.as_str())
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// Get the channels a publisher can write to.
+    pub fn get_publisher_channels(&self, publisher: &str) -> Vec<&str> {
+        match self.publishers.get(publisher) {
+            Some(node) => node.channels.iter()
+                .map(|c| c.as_str())
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    /// Get the publishers that write to at least one disabled channel.
+    pub fn get_disabled_publishers(&self) -> Vec<DisabledPublisher> {
+        let mut disabled_publishers = Vec::new();
+
+        for publisher in self.publishers.values() {
+            let disabled_channels: Vec<String> = publisher.channels.iter()
+                .filter(|c| self.is_channel_disabled(c))
+                .cloned()
+                .collect();
+
+            if disabled_channels.is_empty() {
+                continue;
+            }
+
+            disabled_publishers.push(
+                DisabledPublisher {
+                    publisher: publisher.name.to_owned(),
+                    all_disabled: disabled_channels.len() == publisher.channels.len(),
+                    disabled_channels
+                }
+            );
+        }
+
+        disabled_publishers
+    }
+
+    fn is_channel_disabled(&self, channel: &str) -> bool {
+        match self.channels.get(channel) {
+            Some(node) => node.is_disabled(),
+            None => false
+        }
+    }
+
+    /// The index as JSON, with the disabled publishers flagged.
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "channels": self.channels,
+            "publishers": self.publishers,
+            "disabled_publishers": self.get_disabled_publishers()
+        })
+    }
+
+    /// The index as a GraphViz DOT digraph of publisher to channel edges.
+    /// Disabled channels are dashed and publishers with a disabled channel
+    /// are red.
+    pub fn to_dot(&self) -> String {
+        let disabled: BTreeMap<String, bool> = self.get_disabled_publishers()
+            .into_iter()
+            .map(|d| (d.publisher, d.all_disabled))
+            .collect();
+
+        let mut dot = String::from("digraph channels {\n    rankdir=LR;\n");
+
+        for publisher in self.publishers.keys() {
+            let style = match disabled.get(publisher) {
+                Some(true) => ", color=red, style=filled, fillcolor=mistyrose",
+                Some(false) => ", color=red",
+                None => ""
+            };
+            dot.push_str(
+                &format!("    \"p:{}\" [label=\"{}\", shape=box{}];\n", escape_dot(publisher), escape_dot(publisher), style)
+            );
+        }
+
+        for channel in self.channels.values() {
+            let style = match channel.enabled {
+                Some(true) => "",
+                Some(false) => ", style=dashed, color=gray, fontcolor=gray",
+                None => ", style=dotted"
+            };
+            dot.push_str(
+                &format!("    \"c:{}\" [label=\"{}\", shape=ellipse{}];\n", escape_dot(&channel.name), escape_dot(&channel.name), style)
+            );
+        }
+
+        for publisher in self.publishers.values() {
+            for channel in &publisher.channels {
+                dot.push_str(
+                    &format!("    \"p:{}\" -> \"c:{}\";\n", escape_dot(&publisher.name), escape_dot(channel))
+                );
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+
+/// Escape a string for a quoted DOT ID.
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
@@ -0,0 +1,404 @@
+use std::fmt;
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::channels::sddl::SecurityDescriptor;
+
+const BYTE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+
+/// Format a size in bytes with binary units, e.g. `20 MiB` or `1.5 GiB`.
+///
+pub fn format_bytes(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < BYTE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit == 0 || size.fract() == 0.0 {
+        true => format!("{} {}", size, BYTE_UNITS[unit]),
+        false => format!("{:.1} {}", size, BYTE_UNITS[unit])
+    }
+}
+
+
+/// A size in bytes. Serializes as its bytes and human-readable form.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub fn from_kilobytes(kilobytes: u64) -> Self {
+        ByteSize(kilobytes * 1024)
+    }
+
+    pub fn get_bytes(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format_bytes(self.0))
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("ByteSize", 2)?;
+        state.serialize_field("bytes", &self.0)?;
+        state.serialize_field("human", &format_bytes(self.0))?;
+        state.end()
+    }
+}
+
+
+/// EVT_CHANNEL_TYPE
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelType {
+    Admin,
+    Operational,
+    Analytic,
+    Debug,
+    Unknown(u64)
+}
+
+impl ChannelType {
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            0 => ChannelType::Admin,
+            1 => ChannelType::Operational,
+            2 => ChannelType::Analytic,
+            3 => ChannelType::Debug,
+            other => ChannelType::Unknown(other)
+        }
+    }
+
+    /// Only Admin and Operational channels can be subscribed to.
+    pub fn can_subscribe(&self) -> bool {
+        matches!(self, ChannelType::Admin | ChannelType::Operational)
+    }
+}
+
+
+/// EVT_CHANNEL_ISOLATION_TYPE
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelIsolation {
+    Application,
+    System,
+    Custom,
+    Unknown(u64)
+}
+
+impl ChannelIsolation {
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            0 => ChannelIsolation::Application,
+            1 => ChannelIsolation::System,
+            2 => ChannelIsolation::Custom,
+            other => ChannelIsolation::Unknown(other)
+        }
+    }
+}
+
+
+/// EVT_CHANNEL_CLOCK_TYPE
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelClockType {
+    SystemTime,
+    QueryPerformanceCounter,
+    Unknown(u64)
+}
+
+impl ChannelClockType {
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            0 => ChannelClockType::SystemTime,
+            1 => ChannelClockType::QueryPerformanceCounter,
+            other => ChannelClockType::Unknown(other)
+        }
+    }
+}
+
+
+/// EVT_CHANNEL_SID_TYPE
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelSidType {
+    None,
+    Publishing,
+    Unknown(u64)
+}
+
+impl ChannelSidType {
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            0 => ChannelSidType::None,
+            1 => ChannelSidType::Publishing,
+            other => ChannelSidType::Unknown(other)
+        }
+    }
+}
+
+
+// The decoded enums display and serialize as their names
+impl fmt::Display for ChannelType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelType::Unknown(v) => write!(f, "Unknown({})", v),
+            other => write!(f, "{:?}", other)
+        }
+    }
+}
+
+impl fmt::Display for ChannelIsolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelIsolation::Unknown(v) => write!(f, "Unknown({})", v),
+            other => write!(f, "{:?}", other)
+        }
+    }
+}
+
+impl fmt::Display for ChannelClockType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelClockType::Unknown(v) => write!(f, "Unknown({})", v),
+            other => write!(f, "{:?}", other)
+        }
+    }
+}
+
+impl fmt::Display for ChannelSidType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChannelSidType::Unknown(v) => write!(f, "Unknown({})", v),
+            other => write!(f, "{:?}", other)
+        }
+    }
+}
+
+fn serialize_display<T: fmt::Display, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+impl Serialize for ChannelType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl Serialize for ChannelIsolation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl Serialize for ChannelClockType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl Serialize for ChannelSidType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+
+/// The configuration of a channel with typed properties. Built from the
+/// property mapping of `ChannelConfig::to_json_value`, so it can also be
+/// read from a `print_channels -f jsonl` dump.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ChannelConfigInfo {
+    pub name: String,
+    pub enabled: Option<bool>,
+    pub channel_type: Option<ChannelType>,
+    pub isolation: Option<ChannelIsolation>,
+    pub owning_publisher: Option<String>,
+    pub classic_eventlog: Option<bool>,
+    pub access: Option<SecurityDescriptor>,
+    pub retention: Option<bool>,
+    pub auto_backup: Option<bool>,
+    pub max_size: Option<ByteSize>,
+    pub log_file_path: Option<String>,
+    pub level: Option<u64>,
+    pub keywords: Option<u64>,
+    pub control_guid: Option<String>,
+    pub buffer_size: Option<ByteSize>,
+    pub min_buffers: Option<u64>,
+    pub max_buffers: Option<u64>,
+    pub latency: Option<u64>,
+    pub clock_type: Option<ChannelClockType>,
+    pub sid_type: Option<ChannelSidType>,
+    pub publishers: Vec<String>,
+    pub file_max: Option<u64>
+}
+
+impl ChannelConfigInfo {
+    /// Decode a channel's property mapping. Missing properties are left
+    /// empty, properties of an unexpected type are an error.
+    pub fn from_json_value(name: &str, config: &Value) -> Result<Self, WinThingError> {
+        let properties = PropertyReader(config);
+
+        let access = match properties.get_str("EvtChannelConfigAccess")? {
+            Some(sddl) if !sddl.is_empty() => Some(
+                SecurityDescriptor::from_sddl(&sddl)?
+            ),
+            _ => None
+        };
+
+        Ok(
+            Self {
+                name: name.to_owned(),
+                enabled: properties.get_bool("EvtChannelConfigEnabled")?,
+                channel_type: properties.get_u64("EvtChannelConfigType")?
+                    .map(ChannelType::from_u64),
+                isolation: properties.get_u64("EvtChannelConfigIsolation")?
+                    .map(ChannelIsolation::from_u64),
+                owning_publisher: properties.get_str("EvtChannelConfigOwningPublisher")?,
+                classic_eventlog: properties.get_bool("EvtChannelConfigClassicEventlog")?,
+                access,
+                retention: properties.get_bool("EvtChannelLoggingConfigRetention")?,
+                auto_backup: properties.get_bool("EvtChannelLoggingConfigAutoBackup")?,
+                max_size: properties.get_u64("EvtChannelLoggingConfigMaxSize")?
+                    .map(ByteSize),
+                log_file_path: properties.get_str("EvtChannelLoggingConfigLogFilePath")?,
+                level: properties.get_u64("EvtChannelPublishingConfigLevel")?,
+                keywords: properties.get_u64("EvtChannelPublishingConfigKeywords")?,
+                control_guid: properties.get_str("EvtChannelPublishingConfigControlGuid")?,
+                // The buffer size is in kilobytes
+                buffer_size: properties.get_u64("EvtChannelPublishingConfigBufferSize")?
+                    .map(ByteSize::from_kilobytes),
+                min_buffers: properties.get_u64("EvtChannelPublishingConfigMinBuffers")?,
+                max_buffers: properties.get_u64("EvtChannelPublishingConfigMaxBuffers")?,
+                latency: properties.get_u64("EvtChannelPublishingConfigLatency")?,
+                clock_type: properties.get_u64("EvtChannelPublishingConfigClockType")?
+                    .map(ChannelClockType::from_u64),
+                sid_type: properties.get_u64("EvtChannelPublishingConfigSidType")?
+                    .map(ChannelSidType::from_u64),
+                publishers: properties.get_str_list("EvtChannelPublisherList")?,
+                file_max: properties.get_u64("EvtChannelPublishingConfigFileMax")?
+            }
+        )
+    }
+
+    /// Check if this channel can be subscribed to.
+    pub fn can_subscribe(&self) -> bool {
+        match self.channel_type {
+            Some(channel_type) => channel_type.can_subscribe(),
+            None => false
+        }
+    }
+
+    /// Get readable `name: value` lines for text output.
+    pub fn to_text_lines(&self) -> Vec<String> {
+        fn opt<T: fmt::Display>(value: &Option<T>) -> String {
+            match value {
+                Some(v) => v.to_string(),
+                None => "".to_owned()
+            }
+        }
+
+        let mut lines = vec![
+            format!("Enabled: {}", opt(&self.enabled)),
+            format!("Type: {}", opt(&self.channel_type)),
+            format!("Isolation: {}", opt(&self.isolation)),
+            format!("OwningPublisher: {}", opt(&self.owning_publisher)),
+            format!("ClassicEventlog: {}", opt(&self.classic_eventlog)),
+            format!("Retention: {}", opt(&self.retention)),
+            format!("AutoBackup: {}", opt(&self.auto_backup)),
+            format!("MaxSize: {}", self.max_size.map_or("".to_owned(), |s| format!("{} ({} bytes)", s, s.0))),
+            format!("LogFilePath: {}", opt(&self.log_file_path)),
+            format!("Level: {}", opt(&self.level)),
+            format!("Keywords: {}", self.keywords.map_or("".to_owned(), |k| format!("0x{:016x}", k))),
+            format!("ControlGuid: {}", opt(&self.control_guid)),
+            format!("BufferSize: {}", opt(&self.buffer_size)),
+            format!("MinBuffers: {}", opt(&self.min_buffers)),
+            format!("MaxBuffers: {}", opt(&self.max_buffers)),
+            format!("Latency: {}", opt(&self.latency)),
+            format!("ClockType: {}", opt(&self.clock_type)),
+            format!("SidType: {}", opt(&self.sid_type)),
+            format!("Publishers: {}", self.publishers.join(", ")),
+            format!("FileMax: {}", opt(&self.file_max))
+        ];
+
+        if let Some(access) = &self.access {
+            lines.push(
+                format!("Access: owner {}, group {}", opt(&access.owner), opt(&access.group))
+            );
+            for entry in &access.dacl {
+                lines.push(format!("    {}", entry));
+            }
+        }
+
+        lines
+    }
+}
+
+
+/// Typed access to a channel's property mapping.
+struct PropertyReader<'a>(&'a Value);
+
+impl<'a> PropertyReader<'a> {
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        match self.0.get(key) {
+            Some(Value::Null) | None => None,
+            Some(v) => Some(v)
+        }
+    }
+
+    fn unexpected(key: &str, value: &Value) -> WinThingError {
+        WinThingError::invalid_input(
+            format!("Not expecting {} for {}", value, key)
+        )
+    }
+
+    fn get_bool(&self, key: &str) -> Result<Option<bool>, WinThingError> {
+        match self.get(key) {
+            Some(Value::Bool(b)) => Ok(Some(*b)),
+            Some(other) => Err(Self::unexpected(key, other)),
+            None => Ok(None)
+        }
+    }
+
+    fn get_u64(&self, key: &str) -> Result<Option<u64>, WinThingError> {
+        match self.get(key) {
+            Some(value) => value.as_u64()
+                .map(Some)
+                .ok_or_else(|| Self::unexpected(key, value)),
+            None => Ok(None)
+        }
+    }
+
+    fn get_str(&self, key: &str) -> Result<Option<String>, WinThingError> {
+        match self.get(key) {
+            Some(Value::String(s)) => Ok(Some(s.to_owned())),
+            Some(other) => Err(Self::unexpected(key, other)),
+            None => Ok(None)
+        }
+    }
+
+    fn get_str_list(&self, key: &str) -> Result<Vec<String>, WinThingError> {
+        match self.get(key) {
+            Some(Value::Array(values)) => values.iter()
+                .map(|v| match v {
+                    Value::String(s) => Ok(s.to_owned()),
+                    other => Err(Self::unexpected(key, other))
+                })
+                .collect(),
+            Some(Value::String(s)) => Ok(vec![s.to_owned()]),
+            Some(other) => Err(Self::unexpected(key, other)),
+            None => Ok(Vec::new())
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod graph;
+pub mod info;
+pub mod policy;
+pub mod sddl;
\ No newline at end of file
@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use crate::errors::WinThingError;
+use crate::utils::json::{escape_pointer_token, get_difference_value};
+
+/// The key `print_channels` adds to each JSONL channel record.
+pub const CHANNEL_NAME_KEY: &str = "ChannelName";
+
+
+/// A bound on a numeric property, such as a minimum log size.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RangeRule {
+    #[serde(default)]
+    pub min: Option<u64>,
+    #[serde(default)]
+    pub max: Option<u64>
+}
+
+impl RangeRule {
+    fn is_satisfied(&self, actual: Option<&Value>) -> bool {
+        let actual = match actual.and_then(|v| v.as_u64()) {
+            Some(a) => a,
+            None => return false
+        };
+
+        let above_min = match self.min {
+            Some(min) => actual >= min,
+            None => true
+        };
+        let below_max = match self.max {
+            Some(max) => actual <= max,
+            None => true
+        };
+
+        above_min && below_max
+    }
+}
+
+
+/// The expected value of a channel property. An object with only `min`
+/// and/or `max` is a range, anything else must match exactly.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum PropertyRule {
+    Range(RangeRule),
+    Equals(Value)
+}
+
+
+/// A baseline of expected channel properties, keyed by channel name and
+/// then by property name as output by `ChannelConfig::to_json_value`.
+///
+/// ```json
+/// {
+///     "channels": {
+///         "Security": {
+///             "EvtChannelConfigEnabled": true,
+///             "EvtChannelLoggingConfigRetention": false,
+///             "EvtChannelLoggingConfigMaxSize": {"min": 1073741824}
+///         }
+///     }
+/// }
+/// ```
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ChannelPolicy {
+    pub channels: BTreeMap<String, BTreeMap<String, PropertyRule>>
+}
+
+impl ChannelPolicy {
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let file = File::open(path)?;
+        let policy: Self = serde_json::from_reader(
+            BufReader::new(file)
+        )?;
+
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn from_json_str(json_str: &str) -> Result<Self, WinThingError> {
+        let policy: Self = serde_json::from_str(json_str)?;
+
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    fn validate(&self) -> Result<(), WinThingError> {
+        for (channel, rules) in &self.channels {
+            for (property, rule) in rules {
+                if let PropertyRule::Range(range) = rule {
+                    if range.min.is_none() && range.max.is_none() {
+                        return Err(
+                            WinThingError::policy_error(
+                                format!("{} {} has a range without a min or max.", channel, property)
+                            )
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Get the names of the channels in the policy.
+    pub fn get_channel_names(&self) -> Vec<&str> {
+        self.channels.keys()
+            .map(|c| c.as_str())
+            .collect()
+    }
+
+    /// Audit channel configs, keyed by channel name, against the policy.
+    /// Channels that are not in the policy are ignored.
+    pub fn audit(&self, configs: &BTreeMap<String, Value>) -> AuditReport {
+        AuditReport {
+            channels: self.channels.keys()
+                .map(|channel| self.audit_channel(channel, configs.get(channel)))
+                .collect()
+        }
+    }
+
+    /// Audit a single channel's config. `None` means the channel does not
+    /// exist.
+    pub fn audit_channel(&self, channel: &str, config: Option<&Value>) -> ChannelAudit {
+        let mut deviations = Map::new();
+        let rules = match (self.channels.get(channel), config) {
+            (Some(rules), Some(_)) => rules,
+            _ => {
+                return ChannelAudit {
+                    channel: channel.to_owned(),
+                    present: config.is_some(),
+                    deviations: Value::Object(deviations)
+                };
+            }
+        };
+
+        let mut expected = Map::new();
+        let mut actual = Map::new();
+        for (property, rule) in rules {
+            let actual_value = config.and_then(|c| c.get(property));
+
+            match rule {
+                PropertyRule::Equals(value) => {
+                    expected.insert(property.to_owned(), value.clone());
+                    if let Some(v) = actual_value {
+                        actual.insert(property.to_owned(), v.clone());
+                    }
+                },
+                PropertyRule::Range(range) => {
+                    if !range.is_satisfied(actual_value) {
+                        deviations.insert(
+                            format!("/{}", escape_pointer_token(property)),
+                            json!({
+                                "min": range.min,
+                                "max": range.max,
+                                "actual": actual_value
+                            })
+                        );
+                    }
+                }
+            }
+        }
+
+        // The baseline is the original and the channel the change
+        let difference = get_difference_value(
+            &Value::Object(expected),
+            &Value::Object(actual)
+        );
+        if let Value::Object(difference) = difference {
+            for (path, change) in difference {
+                deviations.insert(
+                    path,
+                    change_to_deviation(change)
+                );
+            }
+        }
+
+        ChannelAudit {
+            channel: channel.to_owned(),
+            present: true,
+            deviations: Value::Object(deviations)
+        }
+    }
+}
+
+
+/// Rename a `get_difference_value` change from before/after to
+/// expected/actual.
+fn change_to_deviation(change: Value) -> Value {
+    let change = match change {
+        Value::Object(c) => c,
+        other => return other
+    };
+
+    let mut deviation = Map::new();
+    for (key, value) in change {
+        match key.as_str() {
+            "before" => { deviation.insert("expected".to_owned(), value); },
+            "after" => { deviation.insert("actual".to_owned(), value); },
+            "removed" => {
+                deviation.insert("expected".to_owned(), value);
+                deviation.insert("actual".to_owned(), Value::Null);
+            },
+            _ => { deviation.insert(key, value); }
+        }
+    }
+
+    Value::Object(deviation)
+}
+
+
+/// The result of auditing a channel. `deviations` maps the JSON Pointer of
+/// each property that does not match the policy to its expected and actual
+/// values.
+///
+#[derive(Serialize, Debug, Clone)]
+pub struct ChannelAudit {
+    pub channel: String,
+    pub present: bool,
+    pub deviations: Value
+}
+
+impl ChannelAudit {
+    /// The number of deviations. A missing channel is one deviation.
+    pub fn get_deviation_count(&self) -> usize {
+        match self.present {
+            true => self.deviations.as_object().map_or(0, |d| d.len()),
+            false => 1
+        }
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.get_deviation_count() == 0
+    }
+}
+
+
+/// The result of auditing every channel in a policy.
+///
+#[derive(Serialize, Debug, Clone)]
+pub struct AuditReport {
+    pub channels: Vec<ChannelAudit>
+}
+
+impl AuditReport {
+    pub fn get_deviation_count(&self) -> usize {
+        self.channels.iter()
+            .map(|c| c.get_deviation_count())
+            .sum()
+    }
+
+    pub fn is_compliant(&self) -> bool {
+        self.get_deviation_count() == 0
+    }
+}
+
+
+/// Read channel configs from a `print_channels -f jsonl` dump, keyed by
+/// channel name.
+///
+pub fn read_channel_dump<R: BufRead>(reader: R) -> Result<BTreeMap<String, Value>, WinThingError> {
+    let mut configs = BTreeMap::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut config: Value = serde_json::from_str(&line)?;
+        let name = match config.as_object_mut().and_then(|m| m.remove(CHANNEL_NAME_KEY)) {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(
+                    WinThingError::policy_error(
+                        format!("Line {} of the channel dump has no {}.", index + 1, CHANNEL_NAME_KEY)
+                    )
+                );
+            }
+        };
+
+        configs.insert(name, config);
+    }
+
+    Ok(configs)
+}
+
+
+/// Read channel configs from a `print_channels -f jsonl` dump file.
+///
+pub fn read_channel_dump_path(path: &str) -> Result<BTreeMap<String, Value>, WinThingError> {
+    let file = File::open(path)?;
+    read_channel_dump(
+        BufReader::new(file)
+    )
+}
@@ -0,0 +1,348 @@
+use std::fmt;
+use serde::Serialize;
+use crate::errors::WinThingError;
+
+/// Names of the SDDL SID aliases and well known SIDs found in channel access
+/// descriptors.
+const TRUSTEE_NAMES: [(&str, &str); 24] = [
+    ("SY", "Local System"),
+    ("BA", "Administrators"),
+    ("BU", "Users"),
+    ("BG", "Guests"),
+    ("BO", "Backup Operators"),
+    ("SO", "Server Operators"),
+    ("IU", "Interactive Users"),
+    ("SU", "Service Users"),
+    ("NU", "Network Users"),
+    ("AU", "Authenticated Users"),
+    ("WD", "Everyone"),
+    ("AN", "Anonymous Logon"),
+    ("LS", "Local Service"),
+    ("NS", "Network Service"),
+    ("ER", "Event Log Readers"),
+    ("CO", "Creator Owner"),
+    ("S-1-5-3", "Batch Users"),
+    ("S-1-5-18", "Local System"),
+    ("S-1-5-19", "Local Service"),
+    ("S-1-5-20", "Network Service"),
+    ("S-1-5-32-544", "Administrators"),
+    ("S-1-5-33", "Write Restricted"),
+    ("S-1-5-32-573", "Event Log Readers"),
+    ("S-1-15-2-1", "All Application Packages")
+];
+
+/// Event log access rights and the standard and generic rights.
+const ACCESS_RIGHTS: [(u32, &str); 12] = [
+    (0x0000_0001, "Read"),
+    (0x0000_0002, "Write"),
+    (0x0000_0004, "Clear"),
+    (0x0001_0000, "Delete"),
+    (0x0002_0000, "ReadControl"),
+    (0x0004_0000, "WriteDac"),
+    (0x0008_0000, "WriteOwner"),
+    (0x0010_0000, "Synchronize"),
+    (0x1000_0000, "GenericAll"),
+    (0x2000_0000, "GenericExecute"),
+    (0x4000_0000, "GenericWrite"),
+    (0x8000_0000, "GenericRead")
+];
+
+/// Two letter SDDL rights and their masks.
+const RIGHT_CODES: [(&str, u32); 25] = [
+    ("GA", 0x1000_0000),
+    ("GX", 0x2000_0000),
+    ("GW", 0x4000_0000),
+    ("GR", 0x8000_0000),
+    ("SD", 0x0001_0000),
+    ("RC", 0x0002_0000),
+    ("WD", 0x0004_0000),
+    ("WO", 0x0008_0000),
+    ("CC", 0x0000_0001),
+    ("DC", 0x0000_0002),
+    ("LC", 0x0000_0004),
+    ("SW", 0x0000_0008),
+    ("RP", 0x0000_0010),
+    ("WP", 0x0000_0020),
+    ("DT", 0x0000_0040),
+    ("LO", 0x0000_0080),
+    ("CR", 0x0000_0100),
+    ("FA", 0x001f_01ff),
+    ("FR", 0x0012_0089),
+    ("FW", 0x0012_0116),
+    ("FX", 0x0012_00a0),
+    ("KA", 0x000f_003f),
+    ("KR", 0x0002_0019),
+    ("KW", 0x0002_0006),
+    ("KX", 0x0002_0019)
+];
+
+const ACE_TYPES: [(&str, &str); 6] = [
+    ("A", "Allow"),
+    ("D", "Deny"),
+    ("AU", "Audit"),
+    ("AL", "Alarm"),
+    ("OA", "ObjectAllow"),
+    ("OD", "ObjectDeny")
+];
+
+const ACE_FLAGS: [(&str, &str); 7] = [
+    ("CI", "ContainerInherit"),
+    ("OI", "ObjectInherit"),
+    ("NP", "NoPropagateInherit"),
+    ("IO", "InheritOnly"),
+    ("ID", "Inherited"),
+    ("SA", "SuccessfulAccess"),
+    ("FA", "FailedAccess")
+];
+
+
+fn lookup<'a>(table: &[(&str, &'a str)], key: &str) -> Option<&'a str> {
+    table.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| *v)
+}
+
+
+/// Get a readable name for an SDDL SID alias or SID string. Unknown SIDs
+/// are returned as is.
+///
+pub fn get_trustee_name(sid: &str) -> String {
+    lookup(&TRUSTEE_NAMES, sid)
+        .map(|n| n.to_owned())
+        .unwrap_or_else(|| sid.to_owned())
+}
+
+
+/// Get the names of the rights in an access mask. Bits without a name are
+/// kept as a hex value.
+///
+pub fn get_access_right_names(mask: u32) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut remaining = mask;
+
+    for (bit, name) in ACCESS_RIGHTS.iter() {
+        if mask & bit != 0 {
+            names.push((*name).to_owned());
+            remaining &= !bit;
+        }
+    }
+
+    if remaining != 0 {
+        names.push(format!("0x{:x}", remaining));
+    }
+
+    names
+}
+
+
+/// Parse the rights field of an ACE, which is a hex mask or two letter
+/// codes. Codes that aren't known are returned as unknown rights rather
+/// than failing the ACE.
+fn parse_access_mask(rights: &str) -> Result<(u32, Vec<String>), WinThingError> {
+    if !rights.is_ascii() {
+        return Err(
+            WinThingError::sddl_error(
+                format!("Invalid access mask '{}'", rights)
+            )
+        );
+    }
+
+    if rights.starts_with("0x") || rights.starts_with("0X") {
+        let mask = u32::from_str_radix(&rights[2..], 16).map_err(|e|
+            WinThingError::sddl_error(
+                format!("Invalid access mask '{}': {}", rights, e)
+            )
+        )?;
+        return Ok((mask, Vec::new()));
+    }
+
+    let mut mask = 0;
+    let mut unknown: Vec<String> = Vec::new();
+    let mut index = 0;
+    while index < rights.len() {
+        let code = rights.get(index..index + 2).unwrap_or(&rights[index..]);
+        match RIGHT_CODES.iter().find(|(c, _)| *c == code) {
+            Some((_, bit)) => mask |= bit,
+            None => unknown.push(format!("Unknown({})", code))
+        }
+        index += 2;
+    }
+
+    Ok((mask, unknown))
+}
+
+
+/// An access control entry.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AccessEntry {
+    pub ace_type: String,
+    pub flags: Vec<String>,
+    pub access_mask: u32,
+    pub rights: Vec<String>,
+    pub sid: String,
+    pub trustee: String
+}
+
+impl AccessEntry {
+    /// Parse an ACE string without its parentheses, e.g. `A;;0x5;;;BA`.
+    pub fn from_ace_str(ace: &str) -> Result<Self, WinThingError> {
+        // Codes are sliced two bytes at a time
+        if !ace.is_ascii() {
+            return Err(
+                WinThingError::sddl_error(
+                    format!("ACE '{}' is not ASCII.", ace)
+                )
+            );
+        }
+
+        let fields: Vec<&str> = ace.split(';').collect();
+        if fields.len() < 6 {
+            return Err(
+                WinThingError::sddl_error(
+                    format!("ACE '{}' has {} fields, expected 6.", ace, fields.len())
+                )
+            );
+        }
+
+        let ace_type = lookup(&ACE_TYPES, fields[0])
+            .unwrap_or(fields[0])
+            .to_owned();
+
+        let mut flags: Vec<String> = Vec::new();
+        let mut index = 0;
+        while index < fields[1].len() {
+            let code = fields[1].get(index..index + 2).unwrap_or(&fields[1][index..]);
+            flags.push(
+                lookup(&ACE_FLAGS, code).unwrap_or(code).to_owned()
+            );
+            index += 2;
+        }
+
+        let (access_mask, unknown_rights) = parse_access_mask(fields[2])?;
+        let mut rights = get_access_right_names(access_mask);
+        rights.extend(unknown_rights);
+        let sid = fields[5].to_owned();
+
+        Ok(
+            Self {
+                ace_type,
+                flags,
+                access_mask,
+                rights,
+                trustee: get_trustee_name(&sid),
+                sid
+            }
+        )
+    }
+}
+
+impl fmt::Display for AccessEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.ace_type, self.trustee, self.rights.join(", "))
+    }
+}
+
+
+/// A security descriptor parsed from SDDL, such as a channel's access.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SecurityDescriptor {
+    pub sddl: String,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub dacl: Vec<AccessEntry>,
+    pub sacl: Vec<AccessEntry>
+}
+
+impl SecurityDescriptor {
+    pub fn from_sddl(sddl: &str) -> Result<Self, WinThingError> {
+        let mut descriptor = Self {
+            sddl: sddl.to_owned(),
+            owner: None,
+            group: None,
+            dacl: Vec::new(),
+            sacl: Vec::new()
+        };
+
+        for (section, value) in split_sections(sddl)? {
+            match section {
+                'O' => descriptor.owner = Some(get_trustee_name(value)),
+                'G' => descriptor.group = Some(get_trustee_name(value)),
+                'D' => descriptor.dacl = parse_acl(value)?,
+                _ => descriptor.sacl = parse_acl(value)?
+            }
+        }
+
+        Ok(descriptor)
+    }
+}
+
+
+/// Split SDDL into its O:, G:, D: and S: sections.
+fn split_sections(sddl: &str) -> Result<Vec<(char, &str)>, WinThingError> {
+    let bytes = sddl.as_bytes();
+    let mut starts: Vec<(char, usize)> = Vec::new();
+    let mut depth = 0;
+
+    for (index, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            b'O' | b'G' | b'D' | b'S' if depth == 0 && bytes.get(index + 1) == Some(&b':') => {
+                starts.push((*byte as char, index));
+            },
+            _ => {}
+        }
+    }
+
+    match starts.first() {
+        Some((_, 0)) => {},
+        _ => {
+            return Err(
+                WinThingError::sddl_error(
+                    format!("Invalid SDDL '{}'", sddl)
+                )
+            );
+        }
+    }
+
+    let mut sections: Vec<(char, &str)> = Vec::new();
+    for (i, (section, start)) in starts.iter().enumerate() {
+        let end = starts.get(i + 1)
+            .map(|(_, e)| *e)
+            .unwrap_or_else(|| sddl.len());
+        sections.push(
+            (*section, &sddl[start + 2..end])
+        );
+    }
+
+    Ok(sections)
+}
+
+
+/// Parse the ACEs of an ACL, skipping the ACL flags before the first ACE.
+fn parse_acl(acl: &str) -> Result<Vec<AccessEntry>, WinThingError> {
+    let mut entries: Vec<AccessEntry> = Vec::new();
+    let mut remaining = acl;
+
+    while let Some(start) = remaining.find('(') {
+        let end = match remaining[start..].find(')') {
+            Some(e) => start + e,
+            None => {
+                return Err(
+                    WinThingError::sddl_error(
+                        format!("Unterminated ACE in '{}'", acl)
+                    )
+                );
+            }
+        };
+
+        entries.push(
+            AccessEntry::from_ace_str(&remaining[start + 1..end])?
+        );
+        remaining = &remaining[end + 1..];
+    }
+
+    Ok(entries)
+}
@@ -0,0 +1,520 @@
+use std::str::FromStr;
+use std::collections::{BTreeMap, BTreeSet};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use crate::errors::WinThingError;
+use crate::detection::engine::Alert;
+use crate::detection::sigma::{
+    get_scalar_string,
+    string_or_list,
+    CompareOp,
+    FieldMapping,
+    LogSource,
+    RuleMeta
+};
+pub use crate::utils::time::get_event_timestamp;
+
+/// Where the host an event came from is found in its JSON, the collecting
+/// host first.
+const HOST_POINTERS: [&str; 2] = ["/source_host", "/Event/System/Computer"];
+/// Correlation conditions that can only be decided once a window closes.
+const UPPER_BOUND_CONDITIONS: [&str; 4] = ["lt", "lte", "eq", "range"];
+
+
+/// Parse a Sigma timespan, e.g. `30s`, `5m`, `1h`, `1d` or `1w`.
+///
+pub fn parse_timespan(timespan: &str) -> Result<Duration, WinThingError> {
+    let timespan = timespan.trim();
+    let error = || WinThingError::rule_error(
+        format!("Invalid timespan '{}'", timespan)
+    );
+
+    let unit = timespan.chars().last().ok_or_else(error)?;
+    let number: i64 = timespan[..timespan.len() - unit.len_utf8()]
+        .parse()
+        .map_err(|_| error())?;
+
+    match unit {
+        's' => Ok(Duration::seconds(number)),
+        'm' => Ok(Duration::minutes(number)),
+        'h' => Ok(Duration::hours(number)),
+        'd' => Ok(Duration::days(number)),
+        'w' => Ok(Duration::weeks(number)),
+        _ => Err(error())
+    }
+}
+
+
+/// The kinds of correlation.
+///
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrelationType {
+    /// The number of matching events in a group reaches the threshold.
+    EventCount,
+    /// The number of distinct values of a field in a group reaches the
+    /// threshold.
+    ValueCount,
+    /// The rules match in order, within the timespan.
+    TemporalOrdered
+}
+
+impl FromStr for CorrelationType {
+    type Err = WinThingError;
+
+    fn from_str(correlation_type: &str) -> Result<Self, Self::Err> {
+        match correlation_type {
+            "event_count" => Ok(CorrelationType::EventCount),
+            "value_count" => Ok(CorrelationType::ValueCount),
+            "temporal_ordered" => Ok(CorrelationType::TemporalOrdered),
+            other => Err(
+                WinThingError::rule_error(
+                    format!("Unsupported correlation type '{}'", other)
+                )
+            )
+        }
+    }
+}
+
+
+/// The `correlation` section of a Sigma correlation rule.
+///
+/// ```yaml
+/// correlation:
+///     type: event_count
+///     rules:
+///         - failed_logon
+///     group-by:
+///         - IpAddress
+///     timespan: 5m
+///     condition:
+///         gte: 5
+/// ```
+///
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CorrelationSpec {
+    #[serde(rename = "type")]
+    pub correlation_type: String,
+    #[serde(deserialize_with = "string_or_list")]
+    pub rules: Vec<String>,
+    #[serde(default, rename = "group-by", deserialize_with = "string_or_list")]
+    pub group_by: Vec<String>,
+    pub timespan: String,
+    #[serde(default)]
+    pub condition: Map<String, Value>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    pub generate: bool
+}
+
+
+/// A Sigma correlation rule as read from YAML. Its `rules` are the `id` or
+/// `name` of the rules whose alerts it correlates.
+///
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CorrelationRule {
+    pub title: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub references: Vec<String>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+    pub correlation: CorrelationSpec,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub falsepositives: Vec<String>
+}
+
+impl CorrelationRule {
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, WinThingError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn get_meta(&self) -> RuleMeta {
+        RuleMeta {
+            title: self.title.to_owned(),
+            id: self.id.to_owned(),
+            name: self.name.to_owned(),
+            status: self.status.to_owned(),
+            level: self.level.to_owned(),
+            description: self.description.to_owned(),
+            author: self.author.to_owned(),
+            tags: self.tags.to_owned(),
+            references: self.references.to_owned(),
+            falsepositives: self.falsepositives.to_owned(),
+            logsource: LogSource::default()
+        }
+    }
+}
+
+
+/// What a correlation found.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CorrelationResult {
+    #[serde(rename = "type")]
+    pub correlation_type: CorrelationType,
+    pub rules: Vec<String>,
+    pub group: Map<String, Value>,
+    pub count: usize,
+    pub first_time: DateTime<Utc>,
+    pub last_time: DateTime<Utc>
+}
+
+
+/// A synthetic alert for a correlation, with the events that caused it.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CorrelationAlert {
+    #[serde(rename = "alert")]
+    pub rule: RuleMeta,
+    pub correlation: CorrelationResult,
+    pub events: Vec<Value>
+}
+
+impl CorrelationAlert {
+    pub fn to_json_value(&self) -> Result<Value, WinThingError> {
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+
+/// An alert held in a correlation's window.
+#[derive(Debug, Clone)]
+struct WindowEntry {
+    timestamp: DateTime<Utc>,
+    value: Option<String>,
+    event: Value
+}
+
+
+/// A correlation rule and the windows of its groups. Alerts must be given
+/// in time order for the windows to be correct. Groups are kept per host,
+/// so events from different computers are never correlated.
+///
+#[derive(Debug, Clone)]
+pub struct Correlation {
+    meta: RuleMeta,
+    correlation_type: CorrelationType,
+    rules: Vec<String>,
+    group_by: Vec<String>,
+    aliases: BTreeMap<String, BTreeMap<String, String>>,
+    timespan: Duration,
+    threshold: Option<(CompareOp, f64)>,
+    field: Option<String>,
+    generate: bool,
+    groups: BTreeMap<String, Vec<WindowEntry>>,
+    /// The candidate sequences of each group of a temporal ordered
+    /// correlation, at most one per length
+    sequences: BTreeMap<String, Vec<Vec<WindowEntry>>>
+}
+
+impl Correlation {
+    pub fn compile(rule: &CorrelationRule) -> Result<Self, WinThingError> {
+        let spec = &rule.correlation;
+        let correlation_type = CorrelationType::from_str(&spec.correlation_type)?;
+
+        if spec.rules.is_empty() {
+            return Err(
+                WinThingError::rule_error(
+                    format!("{} does not reference any rules.", rule.title)
+                )
+            );
+        }
+
+        let mut threshold = None;
+        let mut field = None;
+        for (key, value) in &spec.condition {
+            let op = match key.as_str() {
+                "gt" => CompareOp::Greater,
+                "gte" => CompareOp::GreaterEqual,
+                "field" => {
+                    field = value.as_str().map(|f| f.to_owned());
+                    continue;
+                },
+                // Alerts are raised as alerts arrive, so a count can't be
+                // known to stay below a bound
+                other if UPPER_BOUND_CONDITIONS.contains(&other) => {
+                    return Err(
+                        WinThingError::rule_error(
+                            format!("Correlation condition '{}' of {} is not supported, only gt and gte are.", other, rule.title)
+                        )
+                    );
+                },
+                other => {
+                    return Err(
+                        WinThingError::rule_error(
+                            format!("Unsupported correlation condition '{}'", other)
+                        )
+                    );
+                }
+            };
+
+            let expected = value.as_f64().ok_or_else(||
+                WinThingError::rule_error(
+                    format!("Expected a number for {}, not {}", key, value)
+                )
+            )?;
+
+            if threshold.is_some() {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("{} has more than one gt or gte condition.", rule.title)
+                    )
+                );
+            }
+            threshold = Some((op, expected));
+        }
+
+        match correlation_type {
+            CorrelationType::EventCount | CorrelationType::ValueCount if threshold.is_none() => {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("{} has no gt or gte condition.", rule.title)
+                    )
+                );
+            },
+            CorrelationType::ValueCount if field.is_none() => {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("{} has no condition field to count.", rule.title)
+                    )
+                );
+            },
+            _ => {}
+        }
+
+        Ok(
+            Self {
+                meta: rule.get_meta(),
+                correlation_type,
+                rules: spec.rules.to_owned(),
+                group_by: spec.group_by.to_owned(),
+                aliases: spec.aliases.to_owned(),
+                timespan: parse_timespan(&spec.timespan)?,
+                threshold,
+                field,
+                generate: spec.generate,
+                groups: BTreeMap::new(),
+                sequences: BTreeMap::new()
+            }
+        )
+    }
+
+    pub fn get_meta(&self) -> &RuleMeta {
+        &self.meta
+    }
+
+    /// Are the alerts of the correlation's rules output on their own as well.
+    pub fn get_generate(&self) -> bool {
+        self.generate
+    }
+
+    /// Get the position of the rule in the correlation's rules.
+    pub fn get_rule_index(&self, rule: &RuleMeta) -> Option<usize> {
+        self.rules.iter().position(|r| {
+            Some(r) == rule.id.as_ref() || Some(r) == rule.name.as_ref()
+        })
+    }
+
+    /// Get the number of groups with alerts in their window.
+    pub fn get_group_count(&self) -> usize {
+        self.groups.len() + self.sequences.len()
+    }
+
+    /// Get the field of the rule for a group-by name or the condition field,
+    /// using the correlation's aliases.
+    fn get_rule_field<'a>(&'a self, field: &'a str, rule: &RuleMeta) -> &'a str {
+        let alias = self.aliases.get(field).and_then(|rules| {
+            [&rule.id, &rule.name].iter()
+                .filter_map(|r| r.as_ref())
+                .find_map(|r| rules.get(r))
+        });
+
+        match alias {
+            Some(alias) => alias,
+            None => field
+        }
+    }
+
+    fn get_group(&self, alert: &Alert, mapping: &FieldMapping) -> Map<String, Value> {
+        self.group_by.iter()
+            .map(|name| {
+                let field = self.get_rule_field(name, &alert.rule);
+                let value = mapping.get_field_values(field, &alert.event)
+                    .into_iter()
+                    .next()
+                    .cloned()
+                    .unwrap_or(Value::Null);
+                (name.to_owned(), value)
+            })
+            .collect()
+    }
+
+    /// Get the key of an alert's group, which includes the host of the event.
+    fn get_group_key(&self, group: &Map<String, Value>, event: &Value) -> String {
+        let host = HOST_POINTERS.iter()
+            .find_map(|pointer| event.pointer(pointer))
+            .cloned()
+            .unwrap_or(Value::Null);
+
+        json!([host, group]).to_string()
+    }
+
+    /// Drop the alerts that are outside of the window ending at `timestamp`.
+    /// A sequence is dropped once its first alert is outside the window.
+    fn prune(&mut self, timestamp: DateTime<Utc>) {
+        let start = timestamp - self.timespan;
+
+        for entries in self.groups.values_mut() {
+            entries.retain(|e| e.timestamp >= start);
+        }
+        for sequences in self.sequences.values_mut() {
+            sequences.retain(|sequence| {
+                sequence.first().map(|e| e.timestamp >= start).unwrap_or(false)
+            });
+        }
+
+        self.groups.retain(|_, entries| !entries.is_empty());
+        self.sequences.retain(|_, sequences| !sequences.is_empty());
+    }
+
+    /// Add the alert of the rule at `index` to the group's candidate
+    /// sequences. A match of the first rule starts a new candidate, and
+    /// any other match extends the candidate it follows on from. Of two
+    /// candidates of the same length only the later started is kept, as it
+    /// has more time left. Returns the sequence once it is complete, and
+    /// the group starts over.
+    fn add_to_sequence(
+        &mut self,
+        key: &str,
+        index: usize,
+        entry: WindowEntry
+    ) -> Option<Vec<WindowEntry>> {
+        let sequence = match index {
+            0 => vec![entry],
+            _ => {
+                let sequences = self.sequences.get_mut(key)?;
+                let position = sequences.iter()
+                    .position(|sequence| sequence.len() == index)?;
+                let mut sequence = sequences.remove(position);
+                sequence.push(entry);
+                sequence
+            }
+        };
+
+        if sequence.len() == self.rules.len() {
+            self.sequences.remove(key);
+            return Some(sequence);
+        }
+
+        let sequences = self.sequences.entry(key.to_owned()).or_default();
+        let start = sequence[0].timestamp;
+        sequences.retain(|s| s.len() != sequence.len() || s[0].timestamp > start);
+        if sequences.iter().all(|s| s.len() != sequence.len()) {
+            sequences.push(sequence);
+        }
+
+        None
+    }
+
+    /// Add an alert created at `timestamp` to its group's window. Returns a
+    /// correlation alert if the group's window now meets the condition, and
+    /// the group's window starts over.
+    pub fn process(
+        &mut self,
+        alert: &Alert,
+        timestamp: DateTime<Utc>,
+        mapping: &FieldMapping
+    ) -> Option<CorrelationAlert> {
+        let index = self.get_rule_index(&alert.rule)?;
+        self.prune(timestamp);
+
+        let group = self.get_group(alert, mapping);
+        let key = self.get_group_key(&group, &alert.event);
+
+        let value = match &self.field {
+            Some(field) => {
+                let field = self.get_rule_field(field, &alert.rule);
+                let value = mapping.get_field_values(field, &alert.event)
+                    .into_iter()
+                    .find_map(get_scalar_string);
+
+                // Alerts without the field don't count towards its values
+                if value.is_none() && self.correlation_type == CorrelationType::ValueCount {
+                    return None;
+                }
+                value
+            },
+            None => None
+        };
+
+        let entry = WindowEntry {
+            timestamp,
+            value,
+            event: alert.event.to_owned()
+        };
+
+        let (entries, count) = match self.correlation_type {
+            CorrelationType::TemporalOrdered => {
+                let sequence = self.add_to_sequence(&key, index, entry)?;
+                let count = sequence.len();
+                (sequence, count)
+            },
+            correlation_type => {
+                let entries = self.groups.entry(key.clone()).or_default();
+                entries.push(entry);
+
+                let count = match correlation_type {
+                    CorrelationType::ValueCount => entries.iter()
+                        .filter_map(|e| e.value.as_ref())
+                        .collect::<BTreeSet<&String>>()
+                        .len(),
+                    _ => entries.len()
+                };
+
+                let is_match = match self.threshold {
+                    Some((op, expected)) => op.compare(count as f64, expected),
+                    None => false
+                };
+                if !is_match {
+                    return None;
+                }
+
+                (self.groups.remove(&key).unwrap_or_default(), count)
+            }
+        };
+
+        let first_time = entries.iter().map(|e| e.timestamp).min().unwrap_or(timestamp);
+        let last_time = entries.iter().map(|e| e.timestamp).max().unwrap_or(timestamp);
+
+        Some(
+            CorrelationAlert {
+                rule: self.meta.to_owned(),
+                correlation: CorrelationResult {
+                    correlation_type: self.correlation_type,
+                    rules: self.rules.to_owned(),
+                    group,
+                    count,
+                    first_time,
+                    last_time
+                },
+                events: entries.into_iter()
+                    .map(|e| e.event)
+                    .collect()
+            }
+        )
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::utils::time::get_event_timestamp;
+use crate::detection::correlation::{
+    Correlation,
+    CorrelationAlert,
+    CorrelationRule
+};
+use crate::detection::sigma::{
+    CompiledRule,
+    FieldMapping,
+    RuleMeta,
+    SigmaRule
+};
+
+/// Files with these extensions are loaded from a rules directory.
+const RULE_EXTENSIONS: [&str; 2] = ["yml", "yaml"];
+
+
+/// An event that matched a rule.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Alert {
+    #[serde(rename = "alert")]
+    pub rule: RuleMeta,
+    pub event: Value
+}
+
+impl Alert {
+    pub fn to_json_value(&self) -> Result<Value, WinThingError> {
+        Ok(serde_json::to_value(self)?)
+    }
+}
+
+
+/// The alerts for an event: the rule alerts that are not hidden by a
+/// correlation, and the correlations the event completed.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Detections {
+    pub alerts: Vec<Alert>,
+    pub correlations: Vec<CorrelationAlert>
+}
+
+impl Detections {
+    pub fn is_empty(&self) -> bool {
+        self.alerts.is_empty() && self.correlations.is_empty()
+    }
+
+    pub fn to_json_values(&self) -> Result<Vec<Value>, WinThingError> {
+        let mut values = Vec::new();
+        for alert in &self.alerts {
+            values.push(alert.to_json_value()?);
+        }
+        for correlation in &self.correlations {
+            values.push(correlation.to_json_value()?);
+        }
+        Ok(values)
+    }
+}
+
+
+/// Evaluates events against a set of Sigma rules and correlation rules.
+/// Rules are compiled with the engine's field mapping as they are added, so
+/// the mapping must be set first. Correlations keep their windows behind a
+/// lock so a shared engine can process events from many threads.
+///
+#[derive(Debug, Default)]
+pub struct RuleEngine {
+    field_mapping: FieldMapping,
+    rules: Vec<CompiledRule>,
+    correlations: Mutex<Vec<Correlation>>
+}
+
+impl RuleEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_field_mapping(mut self, field_mapping: FieldMapping) -> Self {
+        self.field_mapping = field_mapping;
+        self
+    }
+
+    pub fn add_rule(&mut self, rule: &SigmaRule) -> Result<(), WinThingError> {
+        let compiled = CompiledRule::compile(
+            rule,
+            &self.field_mapping
+        )?;
+
+        self.rules.push(compiled);
+        Ok(())
+    }
+
+    pub fn add_correlation(&mut self, rule: &CorrelationRule) -> Result<(), WinThingError> {
+        let correlation = Correlation::compile(rule)?;
+        self.get_correlations_mut()?.push(correlation);
+        Ok(())
+    }
+
+    fn get_correlations_mut(&mut self) -> Result<&mut Vec<Correlation>, WinThingError> {
+        self.correlations.get_mut().map_err(|_|
+            WinThingError::unhandled(
+                "Correlation lock is poisoned.".to_owned()
+            )
+        )
+    }
+
+    /// Add the rules and correlation rules of a YAML string, which can hold
+    /// several documents separated by `---`. Nothing is added if any of them
+    /// fail. Returns the number of rules added.
+    pub fn add_rule_str(&mut self, yaml: &str) -> Result<usize, WinThingError> {
+        let mut rules = Vec::new();
+        let mut correlations = Vec::new();
+
+        for document in serde_yaml::Deserializer::from_str(yaml) {
+            let value = Value::deserialize(document)?;
+            if value.get("correlation").is_some() {
+                let rule: CorrelationRule = serde_json::from_value(value)?;
+                correlations.push(Correlation::compile(&rule)?);
+            } else {
+                let rule: SigmaRule = serde_json::from_value(value)?;
+                rules.push(CompiledRule::compile(&rule, &self.field_mapping)?);
+            }
+        }
+
+        let count = rules.len() + correlations.len();
+        self.get_correlations_mut()?.extend(correlations);
+        self.rules.extend(rules);
+
+        Ok(count)
+    }
+
+    /// Add a rule file, or every rule file in a directory and its
+    /// subdirectories. Rules in a directory that fail to load are logged and
+    /// skipped. Returns the number of rules added.
+    pub fn add_rules_from_path(&mut self, path: &str) -> Result<usize, WinThingError> {
+        if !Path::new(path).is_dir() {
+            let yaml = fs::read_to_string(path)?;
+            return self.add_rule_str(&yaml);
+        }
+
+        let mut entries: Vec<_> = fs::read_dir(path)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        entries.sort();
+
+        let mut count = 0;
+        for entry in entries {
+            let entry_path = entry.to_string_lossy().to_string();
+
+            if entry.is_dir() {
+                count += self.add_rules_from_path(&entry_path)?;
+                continue;
+            }
+
+            let is_rule = match entry.extension().and_then(|e| e.to_str()) {
+                Some(extension) => RULE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()),
+                None => false
+            };
+            if !is_rule {
+                continue;
+            }
+
+            let result = fs::read_to_string(&entry_path)
+                .map_err(WinThingError::from)
+                .and_then(|yaml| self.add_rule_str(&yaml));
+            match result {
+                Ok(added) => count += added,
+                Err(e) => warn!("Skipping rule {}: {}", entry_path, e.display_chain())
+            }
+        }
+
+        Ok(count)
+    }
+
+    pub fn get_rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn get_correlation_count(&self) -> usize {
+        match self.correlations.lock() {
+            Ok(correlations) => correlations.len(),
+            Err(_) => 0
+        }
+    }
+
+    /// Get an alert for each rule the event's JSON matches.
+    pub fn evaluate(&self, event: &Value) -> Vec<Alert> {
+        self.rules.iter()
+            .filter(|rule| rule.is_match(event))
+            .map(|rule| Alert {
+                rule: rule.get_meta().to_owned(),
+                event: event.to_owned()
+            })
+            .collect()
+    }
+
+    /// Evaluate an event and add its alerts to the correlations, using the
+    /// time the event was created, or now if it has none.
+    pub fn process(&self, event: &Value) -> Detections {
+        let timestamp = get_event_timestamp(event)
+            .unwrap_or_else(Utc::now);
+
+        self.process_at(event, timestamp)
+    }
+
+    /// Evaluate an event and add its alerts to the correlations as of
+    /// `timestamp`. Alerts of rules used by correlations are only returned
+    /// on their own if one of the correlations sets `generate`.
+    pub fn process_at(&self, event: &Value, timestamp: DateTime<Utc>) -> Detections {
+        let alerts = self.evaluate(event);
+        if alerts.is_empty() {
+            return Detections::default();
+        }
+
+        let mut correlations = match self.correlations.lock() {
+            Ok(c) => c,
+            Err(e) => {
+                error!("Correlation lock is poisoned: {}", e);
+                return Detections {
+                    alerts,
+                    correlations: Vec::new()
+                };
+            }
+        };
+
+        let mut detections = Detections::default();
+        for correlation in correlations.iter_mut() {
+            for alert in &alerts {
+                if let Some(c) = correlation.process(alert, timestamp, &self.field_mapping) {
+                    detections.correlations.push(c);
+                }
+            }
+        }
+
+        detections.alerts = alerts.into_iter()
+            .filter(|alert| {
+                let mut using = correlations.iter()
+                    .filter(|c| c.get_rule_index(&alert.rule).is_some())
+                    .peekable();
+                using.peek().is_none() || using.any(|c| c.get_generate())
+            })
+            .collect();
+
+        detections
+    }
+}
@@ -0,0 +1,3 @@
+pub mod correlation;
+pub mod engine;
+pub mod sigma;
@@ -0,0 +1,976 @@
+use std::fs;
+use std::collections::BTreeMap;
+use regex::Regex;
+use serde::{Serialize, Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use crate::errors::WinThingError;
+use crate::utils::json::escape_pointer_token;
+
+/// Sigma fields and the JSON Pointers they are found at in the JSON of an
+/// event (see `xml_string_to_json`).
+const DEFAULT_FIELD_POINTERS: [(&str, &str); 14] = [
+    ("EventID", "/Event/System/EventID"),
+    ("Channel", "/Event/System/Channel"),
+    ("Computer", "/Event/System/Computer"),
+    ("Provider_Name", "/Event/System/Provider_attributes/Name"),
+    ("Provider", "/Event/System/Provider_attributes/Name"),
+    ("Level", "/Event/System/Level"),
+    ("Task", "/Event/System/Task"),
+    ("Opcode", "/Event/System/Opcode"),
+    ("Keywords", "/Event/System/Keywords"),
+    ("Version", "/Event/System/Version"),
+    ("EventRecordID", "/Event/System/EventRecordID"),
+    ("SystemTime", "/Event/System/TimeCreated_attributes/SystemTime"),
+    ("UserID", "/Event/System/Security_attributes/UserID"),
+    ("SourceHost", "/source_host")
+];
+
+/// Fields without a mapping are looked up here, and in each child of
+/// `USER_DATA_POINTER`.
+const EVENT_DATA_POINTER: &str = "/Event/EventData";
+const USER_DATA_POINTER: &str = "/Event/UserData";
+
+/// The channels of Sigma `windows` logsource services.
+const SERVICE_CHANNELS: [(&str, &str); 12] = [
+    ("security", "Security"),
+    ("system", "System"),
+    ("application", "Application"),
+    ("sysmon", "Microsoft-Windows-Sysmon/Operational"),
+    ("powershell", "Microsoft-Windows-PowerShell/Operational"),
+    ("powershell-classic", "Windows PowerShell"),
+    ("taskscheduler", "Microsoft-Windows-TaskScheduler/Operational"),
+    ("wmi", "Microsoft-Windows-WMI-Activity/Operational"),
+    ("windefend", "Microsoft-Windows-Windows Defender/Operational"),
+    ("bits-client", "Microsoft-Windows-Bits-Client/Operational"),
+    ("dns-server", "DNS Server"),
+    ("driver-framework", "Microsoft-Windows-DriverFrameworks-UserMode/Operational")
+];
+
+const SYSMON_CHANNEL: &str = "Microsoft-Windows-Sysmon/Operational";
+const POWERSHELL_CHANNEL: &str = "Microsoft-Windows-PowerShell/Operational";
+
+/// The channels and EventIDs of Sigma `windows` logsource categories.
+const CATEGORY_SOURCES: [(&str, &str, &[u64]); 20] = [
+    ("process_creation", SYSMON_CHANNEL, &[1]),
+    ("process_creation", "Security", &[4688]),
+    ("network_connection", SYSMON_CHANNEL, &[3]),
+    ("process_termination", SYSMON_CHANNEL, &[5]),
+    ("driver_load", SYSMON_CHANNEL, &[6]),
+    ("image_load", SYSMON_CHANNEL, &[7]),
+    ("create_remote_thread", SYSMON_CHANNEL, &[8]),
+    ("raw_access_thread", SYSMON_CHANNEL, &[9]),
+    ("process_access", SYSMON_CHANNEL, &[10]),
+    ("file_event", SYSMON_CHANNEL, &[11]),
+    ("registry_event", SYSMON_CHANNEL, &[12, 13, 14]),
+    ("registry_add", SYSMON_CHANNEL, &[12]),
+    ("registry_set", SYSMON_CHANNEL, &[13]),
+    ("create_stream_hash", SYSMON_CHANNEL, &[15]),
+    ("pipe_created", SYSMON_CHANNEL, &[17, 18]),
+    ("wmi_event", SYSMON_CHANNEL, &[19, 20, 21]),
+    ("dns_query", SYSMON_CHANNEL, &[22]),
+    ("file_delete", SYSMON_CHANNEL, &[23, 26]),
+    ("ps_script", POWERSHELL_CHANNEL, &[4104]),
+    ("ps_module", POWERSHELL_CHANNEL, &[4103])
+];
+
+
+/// Accept a single string or a list of strings.
+pub(crate) fn string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+    where D: Deserializer<'de>
+{
+    let value = Value::deserialize(deserializer)?;
+    Ok(
+        match value {
+            Value::String(s) => vec![s],
+            Value::Array(values) => values.into_iter()
+                .filter_map(|v| match v {
+                    Value::String(s) => Some(s),
+                    Value::Null => None,
+                    other => Some(other.to_string())
+                })
+                .collect(),
+            _ => Vec::new()
+        }
+    )
+}
+
+
+/// The log source of a Sigma rule.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct LogSource {
+    #[serde(default)]
+    pub product: Option<String>,
+    #[serde(default)]
+    pub service: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>
+}
+
+
+/// A Sigma rule as read from YAML.
+///
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct SigmaRule {
+    pub title: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub references: Vec<String>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub logsource: LogSource,
+    pub detection: Map<String, Value>,
+    #[serde(default, deserialize_with = "string_or_list")]
+    pub falsepositives: Vec<String>
+}
+
+impl SigmaRule {
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, WinThingError> {
+        Ok(serde_yaml::from_str(yaml)?)
+    }
+
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let yaml = fs::read_to_string(path)?;
+        Self::from_yaml_str(&yaml)
+    }
+
+    /// Get the rule's metadata, as carried by its alerts.
+    pub fn get_meta(&self) -> RuleMeta {
+        RuleMeta {
+            title: self.title.to_owned(),
+            id: self.id.to_owned(),
+            name: self.name.to_owned(),
+            status: self.status.to_owned(),
+            level: self.level.to_owned(),
+            description: self.description.to_owned(),
+            author: self.author.to_owned(),
+            tags: self.tags.to_owned(),
+            references: self.references.to_owned(),
+            falsepositives: self.falsepositives.to_owned(),
+            logsource: self.logsource.to_owned()
+        }
+    }
+}
+
+
+/// The metadata of a rule.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RuleMeta {
+    pub title: String,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub status: Option<String>,
+    pub level: Option<String>,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub tags: Vec<String>,
+    pub references: Vec<String>,
+    pub falsepositives: Vec<String>,
+    pub logsource: LogSource
+}
+
+
+/// Maps Sigma field names to the JSON Pointers of an event's JSON. Fields
+/// that are not mapped are looked up by name in `EventData` and in each
+/// element of `UserData`.
+///
+/// Mappings can be read from YAML:
+/// ```yaml
+/// fieldmappings:
+///     ParentProcessName: /Event/EventData/ParentImage
+///     User: [/Event/EventData/User, /Event/EventData/SubjectUserName]
+/// ```
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMapping {
+    fields: BTreeMap<String, Vec<String>>
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            fields: DEFAULT_FIELD_POINTERS.iter()
+                .map(|(field, pointer)| ((*field).to_owned(), vec![(*pointer).to_owned()]))
+                .collect()
+        }
+    }
+}
+
+impl FieldMapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map a field to a JSON Pointer, replacing any existing mapping.
+    pub fn with_field(mut self, field: &str, pointers: &[&str]) -> Self {
+        self.fields.insert(
+            field.to_owned(),
+            pointers.iter().map(|p| (*p).to_owned()).collect()
+        );
+        self
+    }
+
+    pub fn from_yaml_str(yaml: &str) -> Result<Self, WinThingError> {
+        let value: Value = serde_yaml::from_str(yaml)?;
+        let mut mapping = Self::new();
+
+        let fields = match value.get("fieldmappings") {
+            Some(Value::Object(fields)) => fields,
+            _ => {
+                return Err(
+                    WinThingError::rule_error(
+                        "Field mapping has no fieldmappings mapping.".to_owned()
+                    )
+                );
+            }
+        };
+
+        for (field, pointers) in fields {
+            let pointers: Vec<&str> = match pointers {
+                Value::String(p) => vec![p.as_str()],
+                Value::Array(p) => p.iter()
+                    .filter_map(|p| p.as_str())
+                    .collect(),
+                other => {
+                    return Err(
+                        WinThingError::rule_error(
+                            format!("Not expecting {} for the mapping of {}", other, field)
+                        )
+                    );
+                }
+            };
+
+            if let Some(pointer) = pointers.iter().find(|p| !p.starts_with('/')) {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("Mapping of {} is not a JSON Pointer: {}", field, pointer)
+                    )
+                );
+            }
+
+            mapping = mapping.with_field(field, &pointers);
+        }
+
+        Ok(mapping)
+    }
+
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let yaml = fs::read_to_string(path)?;
+        Self::from_yaml_str(&yaml)
+    }
+
+    /// Get the values of a field in an event's JSON.
+    pub fn get_field_values<'a>(&self, field: &str, event: &'a Value) -> Vec<&'a Value> {
+        self.get_field_path(field)
+            .get_values(event)
+    }
+
+    fn get_field_path(&self, field: &str) -> FieldPath {
+        match self.fields.get(field) {
+            Some(pointers) => FieldPath {
+                name: field.to_owned(),
+                pointers: pointers.to_owned(),
+                search_user_data: false
+            },
+            None => FieldPath {
+                name: field.to_owned(),
+                pointers: vec![
+                    format!("{}/{}", EVENT_DATA_POINTER, escape_pointer_token(field))
+                ],
+                search_user_data: true
+            }
+        }
+    }
+}
+
+
+/// Where a field's values are found in an event.
+#[derive(Debug, Clone)]
+struct FieldPath {
+    name: String,
+    pointers: Vec<String>,
+    search_user_data: bool
+}
+
+impl FieldPath {
+    fn get_values<'a>(&self, event: &'a Value) -> Vec<&'a Value> {
+        let mut values: Vec<&Value> = self.pointers.iter()
+            .filter_map(|p| event.pointer(p))
+            .collect();
+
+        if self.search_user_data {
+            if let Some(Value::Object(user_data)) = event.pointer(USER_DATA_POINTER) {
+                for element in user_data.values() {
+                    if let Some(v) = element.get(&self.name) {
+                        values.push(v);
+                    }
+                }
+            }
+        }
+
+        values
+    }
+}
+
+
+/// Get a scalar as the string it is compared as.
+pub(crate) fn get_scalar_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None
+    }
+}
+
+
+/// Convert a Sigma value with `*` and `?` wildcards to an anchored regex.
+/// A backslash escapes a wildcard or backslash and is otherwise literal.
+fn wildcard_to_regex(pattern: &str, prefix_any: bool, suffix_any: bool, cased: bool) -> String {
+    let mut regex = String::from(if cased { "(?s)^" } else { "(?is)^" });
+    if prefix_any {
+        regex.push_str(".*");
+    }
+
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.peek() {
+                Some(next @ '*') | Some(next @ '?') | Some(next @ '\\') => {
+                    regex.push_str(&regex::escape(&next.to_string()));
+                    chars.next();
+                },
+                _ => regex.push_str(r"\\")
+            },
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            other => regex.push_str(&regex::escape(&other.to_string()))
+        }
+    }
+
+    if suffix_any {
+        regex.push_str(".*");
+    }
+    regex.push('$');
+    regex
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CompareOp {
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual
+}
+
+impl CompareOp {
+    pub(crate) fn compare(self, actual: f64, expected: f64) -> bool {
+        match self {
+            CompareOp::Greater => actual > expected,
+            CompareOp::GreaterEqual => actual >= expected,
+            CompareOp::Less => actual < expected,
+            CompareOp::LessEqual => actual <= expected
+        }
+    }
+}
+
+
+/// A compiled Sigma value.
+#[derive(Debug, Clone)]
+enum ValueMatcher {
+    Pattern(Regex),
+    Compare(CompareOp, f64),
+    Null
+}
+
+impl ValueMatcher {
+    fn is_match(&self, value: &Value) -> bool {
+        match (self, value) {
+            (ValueMatcher::Null, v) => v.is_null(),
+            (_, Value::Array(values)) => values.iter().any(|v| self.is_match(v)),
+            (ValueMatcher::Pattern(regex), v) => match get_scalar_string(v) {
+                Some(s) => regex.is_match(&s),
+                None => false
+            },
+            (ValueMatcher::Compare(op, expected), v) => {
+                let actual = match v {
+                    Value::Number(n) => n.as_f64(),
+                    Value::String(s) => s.trim().parse::<f64>().ok(),
+                    _ => None
+                };
+
+                match actual {
+                    Some(a) => op.compare(a, *expected),
+                    None => false
+                }
+            }
+        }
+    }
+
+    /// Does the field, given the values found for it, match. A missing
+    /// field only matches null.
+    fn matches_values(&self, values: &[&Value]) -> bool {
+        match self {
+            ValueMatcher::Null => values.is_empty() || values.iter().any(|v| v.is_null()),
+            _ => values.iter().any(|v| self.is_match(v))
+        }
+    }
+}
+
+
+/// The modifiers of a field, e.g. `CommandLine|contains|all`.
+#[derive(Debug, Clone, Default)]
+struct Modifiers {
+    contains: bool,
+    startswith: bool,
+    endswith: bool,
+    all: bool,
+    re: bool,
+    cased: bool,
+    compare: Option<CompareOp>
+}
+
+impl Modifiers {
+    fn parse(field: &str, modifiers: &[&str]) -> Result<Self, WinThingError> {
+        let mut parsed = Self::default();
+
+        for modifier in modifiers {
+            match *modifier {
+                "contains" => parsed.contains = true,
+                "startswith" => parsed.startswith = true,
+                "endswith" => parsed.endswith = true,
+                "all" => parsed.all = true,
+                "re" => parsed.re = true,
+                "cased" => parsed.cased = true,
+                "gt" => parsed.compare = Some(CompareOp::Greater),
+                "gte" => parsed.compare = Some(CompareOp::GreaterEqual),
+                "lt" => parsed.compare = Some(CompareOp::Less),
+                "lte" => parsed.compare = Some(CompareOp::LessEqual),
+                other => {
+                    return Err(
+                        WinThingError::rule_error(
+                            format!("Unsupported modifier '{}' on {}", other, field)
+                        )
+                    );
+                }
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn compile(&self, value: &Value) -> Result<ValueMatcher, WinThingError> {
+        if value.is_null() {
+            return Ok(ValueMatcher::Null);
+        }
+
+        let string = match get_scalar_string(value) {
+            Some(s) => s,
+            None => {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("Not expecting {} as a value", value)
+                    )
+                );
+            }
+        };
+
+        if let Some(op) = self.compare {
+            return match string.parse::<f64>() {
+                Ok(n) => Ok(ValueMatcher::Compare(op, n)),
+                Err(_) => Err(
+                    WinThingError::rule_error(
+                        format!("Expected a number to compare to, not {}", value)
+                    )
+                )
+            };
+        }
+
+        let regex = match self.re {
+            true => string,
+            false => wildcard_to_regex(
+                &string,
+                self.contains || self.endswith,
+                self.contains || self.startswith,
+                self.cased
+            )
+        };
+
+        Regex::new(&regex)
+            .map(ValueMatcher::Pattern)
+            .map_err(|e| WinThingError::rule_error(
+                format!("Invalid pattern {}: {}", regex, e)
+            ))
+    }
+}
+
+
+/// A field and the values it must match.
+#[derive(Debug, Clone)]
+struct FieldMatcher {
+    path: FieldPath,
+    matchers: Vec<ValueMatcher>,
+    all: bool
+}
+
+impl FieldMatcher {
+    fn compile(key: &str, value: &Value, mapping: &FieldMapping) -> Result<Self, WinThingError> {
+        let mut parts = key.split('|');
+        let field = parts.next().unwrap_or("");
+        if field.is_empty() {
+            return Err(
+                WinThingError::rule_error(
+                    format!("Unsupported field '{}'", key)
+                )
+            );
+        }
+
+        let modifiers: Vec<&str> = parts.collect();
+        let modifiers = Modifiers::parse(field, &modifiers)?;
+
+        let matchers = match value {
+            Value::Array(values) => values.iter()
+                .map(|v| modifiers.compile(v))
+                .collect::<Result<Vec<ValueMatcher>, WinThingError>>()?,
+            v => vec![modifiers.compile(v)?]
+        };
+
+        Ok(
+            Self {
+                path: mapping.get_field_path(field),
+                matchers,
+                all: modifiers.all
+            }
+        )
+    }
+
+    fn is_match(&self, event: &Value) -> bool {
+        let values = self.path.get_values(event);
+
+        match self.all {
+            true => self.matchers.iter().all(|m| m.matches_values(&values)),
+            false => self.matchers.iter().any(|m| m.matches_values(&values))
+        }
+    }
+}
+
+
+/// A compiled detection item: fields that must all match, any of a list of
+/// such maps, or keywords found anywhere in the event.
+#[derive(Debug, Clone)]
+enum Selection {
+    AnyOf(Vec<Vec<FieldMatcher>>),
+    Keywords(Vec<ValueMatcher>)
+}
+
+impl Selection {
+    fn compile(name: &str, value: &Value, mapping: &FieldMapping) -> Result<Self, WinThingError> {
+        let compile_map = |map: &Map<String, Value>| -> Result<Vec<FieldMatcher>, WinThingError> {
+            map.iter()
+                .map(|(k, v)| FieldMatcher::compile(k, v, mapping))
+                .collect()
+        };
+
+        match value {
+            Value::Object(map) => Ok(
+                Selection::AnyOf(vec![compile_map(map)?])
+            ),
+            Value::Array(items) if items.iter().all(|i| i.is_object()) => Ok(
+                Selection::AnyOf(
+                    items.iter()
+                        .filter_map(|i| i.as_object())
+                        .map(compile_map)
+                        .collect::<Result<Vec<_>, WinThingError>>()?
+                )
+            ),
+            Value::Array(items) => Ok(
+                Selection::Keywords(
+                    items.iter()
+                        .map(|i| Modifiers { contains: true, ..Modifiers::default() }.compile(i))
+                        .collect::<Result<Vec<_>, WinThingError>>()?
+                )
+            ),
+            Value::String(_) => Ok(
+                Selection::Keywords(
+                    vec![Modifiers { contains: true, ..Modifiers::default() }.compile(value)?]
+                )
+            ),
+            other => Err(
+                WinThingError::rule_error(
+                    format!("Not expecting {} for selection {}", other, name)
+                )
+            )
+        }
+    }
+
+    fn is_match(&self, event: &Value) -> bool {
+        match self {
+            Selection::AnyOf(groups) => groups.iter()
+                .any(|fields| fields.iter().all(|f| f.is_match(event))),
+            Selection::Keywords(keywords) => {
+                let mut strings: Vec<&Value> = Vec::new();
+                collect_leaves(event, &mut strings);
+                keywords.iter().any(|k| strings.iter().any(|s| k.is_match(s)))
+            }
+        }
+    }
+}
+
+
+fn collect_leaves<'a>(value: &'a Value, leaves: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(map) => map.values().for_each(|v| collect_leaves(v, leaves)),
+        Value::Array(values) => values.iter().for_each(|v| collect_leaves(v, leaves)),
+        Value::Null => {},
+        leaf => leaves.push(leaf)
+    }
+}
+
+
+/// A parsed Sigma condition.
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Selection(String),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    AnyOf(Vec<String>),
+    AllOf(Vec<String>)
+}
+
+impl Condition {
+    fn is_match(&self, selections: &BTreeMap<String, Selection>, event: &Value) -> bool {
+        let selection_match = |name: &String| match selections.get(name) {
+            Some(s) => s.is_match(event),
+            None => false
+        };
+
+        match self {
+            Condition::Selection(name) => selection_match(name),
+            Condition::Not(c) => !c.is_match(selections, event),
+            Condition::And(a, b) => a.is_match(selections, event) && b.is_match(selections, event),
+            Condition::Or(a, b) => a.is_match(selections, event) || b.is_match(selections, event),
+            Condition::AnyOf(names) => names.iter().any(selection_match),
+            Condition::AllOf(names) => names.iter().all(selection_match)
+        }
+    }
+}
+
+
+/// A recursive descent parser for Sigma conditions. `not` binds tighter
+/// than `and`, which binds tighter than `or`.
+struct ConditionParser<'a> {
+    tokens: Vec<String>,
+    position: usize,
+    names: Vec<&'a String>
+}
+
+impl<'a> ConditionParser<'a> {
+    fn parse(condition: &str, names: Vec<&'a String>) -> Result<Condition, WinThingError> {
+        let tokens: Vec<String> = condition.replace('(', " ( ")
+            .replace(')', " ) ")
+            .split_whitespace()
+            .map(|t| t.to_owned())
+            .collect();
+
+        let mut parser = Self {
+            tokens,
+            position: 0,
+            names
+        };
+
+        let parsed = parser.parse_or()?;
+        match parser.peek() {
+            None => Ok(parsed),
+            Some("|") => Err(
+                WinThingError::rule_error(
+                    format!("Aggregations are not supported in conditions: {}", condition)
+                )
+            ),
+            Some(token) => Err(
+                WinThingError::rule_error(
+                    format!("Unexpected '{}' in condition: {}", token, condition)
+                )
+            )
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|t| t.as_str())
+    }
+
+    fn next_token(&mut self) -> Result<String, WinThingError> {
+        let token = self.tokens.get(self.position).cloned().ok_or_else(||
+            WinThingError::rule_error(
+                "Condition ended unexpectedly.".to_owned()
+            )
+        )?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(token) => token.eq_ignore_ascii_case(keyword),
+            None => false
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition, WinThingError> {
+        let mut condition = self.parse_and()?;
+        while self.is_keyword("or") {
+            self.position += 1;
+            condition = Condition::Or(
+                Box::new(condition),
+                Box::new(self.parse_and()?)
+            );
+        }
+        Ok(condition)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition, WinThingError> {
+        let mut condition = self.parse_not()?;
+        while self.is_keyword("and") {
+            self.position += 1;
+            condition = Condition::And(
+                Box::new(condition),
+                Box::new(self.parse_not()?)
+            );
+        }
+        Ok(condition)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition, WinThingError> {
+        if self.is_keyword("not") {
+            self.position += 1;
+            return Ok(
+                Condition::Not(Box::new(self.parse_not()?))
+            );
+        }
+
+        self.parse_atom()
+    }
+
+    fn parse_atom(&mut self) -> Result<Condition, WinThingError> {
+        let token = self.next_token()?;
+
+        if token == "(" {
+            let condition = self.parse_or()?;
+            return match self.next_token()?.as_str() {
+                ")" => Ok(condition),
+                other => Err(
+                    WinThingError::rule_error(
+                        format!("Expected ')' not '{}'", other)
+                    )
+                )
+            };
+        }
+
+        let quantifier = token.to_ascii_lowercase();
+        if (quantifier == "1" || quantifier == "any" || quantifier == "all") && self.is_keyword("of") {
+            self.position += 1;
+            let pattern = self.next_token()?;
+            let names = self.get_matching_names(&pattern)?;
+            return Ok(
+                match quantifier.as_str() {
+                    "all" => Condition::AllOf(names),
+                    _ => Condition::AnyOf(names)
+                }
+            );
+        }
+
+        match self.names.iter().any(|n| **n == token) {
+            true => Ok(Condition::Selection(token)),
+            false => Err(
+                WinThingError::rule_error(
+                    format!("Unknown selection '{}' in condition", token)
+                )
+            )
+        }
+    }
+
+    /// Get the selections for `them` or a pattern like `selection*`.
+    fn get_matching_names(&self, pattern: &str) -> Result<Vec<String>, WinThingError> {
+        let names: Vec<String> = match pattern {
+            "them" => self.names.iter()
+                .filter(|n| !n.starts_with('_'))
+                .map(|n| (*n).to_owned())
+                .collect(),
+            pattern => {
+                let regex = Regex::new(&wildcard_to_regex(pattern, false, false, true))
+                    .map_err(|e| WinThingError::rule_error(format!("{}", e)))?;
+                self.names.iter()
+                    .filter(|n| regex.is_match(n))
+                    .map(|n| (*n).to_owned())
+                    .collect()
+            }
+        };
+
+        match names.is_empty() {
+            true => Err(
+                WinThingError::rule_error(
+                    format!("No selections match '{}'", pattern)
+                )
+            ),
+            false => Ok(names)
+        }
+    }
+}
+
+
+/// The channels, and optionally EventIDs, a rule's logsource applies to.
+#[derive(Debug, Clone)]
+struct LogSourceFilter(Vec<(String, Vec<u64>)>);
+
+impl LogSourceFilter {
+    fn from_logsource(logsource: &LogSource) -> Result<Option<Self>, WinThingError> {
+        if let Some(product) = &logsource.product {
+            if !product.eq_ignore_ascii_case("windows") {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("Unsupported logsource product '{}'", product)
+                    )
+                );
+            }
+        }
+
+        let sources: Vec<(String, Vec<u64>)> = match (&logsource.service, &logsource.category) {
+            (Some(service), _) => SERVICE_CHANNELS.iter()
+                .filter(|(s, _)| s.eq_ignore_ascii_case(service))
+                .map(|(_, channel)| ((*channel).to_owned(), Vec::new()))
+                .collect(),
+            (None, Some(category)) => CATEGORY_SOURCES.iter()
+                .filter(|(c, _, _)| c.eq_ignore_ascii_case(category))
+                .map(|(_, channel, ids)| ((*channel).to_owned(), ids.to_vec()))
+                .collect(),
+            (None, None) => Vec::new()
+        };
+
+        // An unknown service or category would otherwise match every event
+        match (sources.is_empty(), &logsource.service, &logsource.category) {
+            (false, _, _) => Ok(Some(Self(sources))),
+            (true, Some(service), _) => Err(
+                WinThingError::rule_error(
+                    format!("Unknown logsource service '{}'", service)
+                )
+            ),
+            (true, None, Some(category)) => Err(
+                WinThingError::rule_error(
+                    format!("Unknown logsource category '{}'", category)
+                )
+            ),
+            (true, None, None) => Ok(None)
+        }
+    }
+
+    fn is_match(&self, event: &Value) -> bool {
+        let channel = match event.pointer("/Event/System/Channel").and_then(|c| c.as_str()) {
+            Some(c) => c,
+            None => return false
+        };
+        let event_id = event.pointer("/Event/System/EventID").and_then(|i| i.as_u64());
+
+        self.0.iter().any(|(c, ids)| {
+            c.eq_ignore_ascii_case(channel) && (ids.is_empty() || match event_id {
+                Some(id) => ids.contains(&id),
+                None => false
+            })
+        })
+    }
+}
+
+
+/// A Sigma rule compiled against a field mapping.
+///
+#[derive(Debug, Clone)]
+pub struct CompiledRule {
+    meta: RuleMeta,
+    logsource: Option<LogSourceFilter>,
+    selections: BTreeMap<String, Selection>,
+    condition: Condition
+}
+
+impl CompiledRule {
+    pub fn compile(rule: &SigmaRule, mapping: &FieldMapping) -> Result<Self, WinThingError> {
+        let mut selections = BTreeMap::new();
+        for (name, value) in &rule.detection {
+            if name == "condition" || name == "timeframe" {
+                continue;
+            }
+
+            selections.insert(
+                name.to_owned(),
+                Selection::compile(name, value, mapping)?
+            );
+        }
+
+        let names: Vec<&String> = selections.keys().collect();
+        let condition = match rule.detection.get("condition") {
+            Some(Value::String(c)) => ConditionParser::parse(c, names)?,
+            Some(Value::Array(conditions)) => {
+                let mut parsed: Option<Condition> = None;
+                for condition in conditions {
+                    let condition = ConditionParser::parse(
+                        condition.as_str().unwrap_or(""),
+                        names.clone()
+                    )?;
+                    parsed = Some(match parsed {
+                        Some(p) => Condition::Or(Box::new(p), Box::new(condition)),
+                        None => condition
+                    });
+                }
+                parsed.ok_or_else(|| WinThingError::rule_error(
+                    format!("{} has an empty condition list.", rule.title)
+                ))?
+            },
+            _ => {
+                return Err(
+                    WinThingError::rule_error(
+                        format!("{} has no condition.", rule.title)
+                    )
+                );
+            }
+        };
+
+        Ok(
+            Self {
+                meta: rule.get_meta(),
+                logsource: LogSourceFilter::from_logsource(&rule.logsource)?,
+                selections,
+                condition
+            }
+        )
+    }
+
+    pub fn get_meta(&self) -> &RuleMeta {
+        &self.meta
+    }
+
+    /// Does the event's JSON match the rule.
+    pub fn is_match(&self, event: &Value) -> bool {
+        if let Some(logsource) = &self.logsource {
+            if !logsource.is_match(event) {
+                return false;
+            }
+        }
+
+        self.condition.is_match(
+            &self.selections,
+            event
+        )
+    }
+}
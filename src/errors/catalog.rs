@@ -0,0 +1,274 @@
+use crate::errors::WindowsCode;
+#[cfg(windows)]
+use crate::errors::format_system_message;
+
+/// HRESULTs with this bit set wrap an NTSTATUS (HRESULT_FROM_NT).
+const FACILITY_NT_BIT: u32 = 0x1000_0000;
+
+
+/// The symbolic name and message of a status code.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeInfo {
+    pub name: &'static str,
+    pub message: &'static str
+}
+
+
+/// Common Win32 error codes, including the ones returned by the event log,
+/// USN journal and ETW APIs.
+static WIN32_CODES: &[(u32, &str, &str)] = &[
+    (0, "ERROR_SUCCESS", "The operation completed successfully."),
+    (1, "ERROR_INVALID_FUNCTION", "Incorrect function."),
+    (2, "ERROR_FILE_NOT_FOUND", "The system cannot find the file specified."),
+    (3, "ERROR_PATH_NOT_FOUND", "The system cannot find the path specified."),
+    (4, "ERROR_TOO_MANY_OPEN_FILES", "The system cannot open the file."),
+    (5, "ERROR_ACCESS_DENIED", "Access is denied."),
+    (6, "ERROR_INVALID_HANDLE", "The handle is invalid."),
+    (8, "ERROR_NOT_ENOUGH_MEMORY", "Not enough memory resources are available to process this command."),
+    (13, "ERROR_INVALID_DATA", "The data is invalid."),
+    (14, "ERROR_OUTOFMEMORY", "Not enough memory resources are available to complete this operation."),
+    (15, "ERROR_INVALID_DRIVE", "The system cannot find the drive specified."),
+    (18, "ERROR_NO_MORE_FILES", "There are no more files."),
+    (19, "ERROR_WRITE_PROTECT", "The media is write protected."),
+    (21, "ERROR_NOT_READY", "The device is not ready."),
+    (24, "ERROR_BAD_LENGTH", "The program issued a command but the command length is incorrect."),
+    (31, "ERROR_GEN_FAILURE", "A device attached to the system is not functioning."),
+    (32, "ERROR_SHARING_VIOLATION", "The process cannot access the file because it is being used by another process."),
+    (33, "ERROR_LOCK_VIOLATION", "The process cannot access the file because another process has locked a portion of the file."),
+    (38, "ERROR_HANDLE_EOF", "Reached the end of the file."),
+    (50, "ERROR_NOT_SUPPORTED", "The request is not supported."),
+    (53, "ERROR_BAD_NETPATH", "The network path was not found."),
+    (67, "ERROR_BAD_NET_NAME", "The network name cannot be found."),
+    (80, "ERROR_FILE_EXISTS", "The file exists."),
+    (87, "ERROR_INVALID_PARAMETER", "The parameter is incorrect."),
+    (109, "ERROR_BROKEN_PIPE", "The pipe has been ended."),
+    (111, "ERROR_BUFFER_OVERFLOW", "The file name is too long."),
+    (112, "ERROR_DISK_FULL", "There is not enough space on the disk."),
+    (120, "ERROR_CALL_NOT_IMPLEMENTED", "This function is not supported on this system."),
+    (122, "ERROR_INSUFFICIENT_BUFFER", "The data area passed to a system call is too small."),
+    (123, "ERROR_INVALID_NAME", "The filename, directory name, or volume label syntax is incorrect."),
+    (126, "ERROR_MOD_NOT_FOUND", "The specified module could not be found."),
+    (127, "ERROR_PROC_NOT_FOUND", "The specified procedure could not be found."),
+    (161, "ERROR_BAD_PATHNAME", "The specified path is invalid."),
+    (183, "ERROR_ALREADY_EXISTS", "Cannot create a file when that file already exists."),
+    (203, "ERROR_ENVVAR_NOT_FOUND", "The system could not find the environment option that was entered."),
+    (234, "ERROR_MORE_DATA", "More data is available."),
+    (258, "WAIT_TIMEOUT", "The wait operation timed out."),
+    (259, "ERROR_NO_MORE_ITEMS", "No more data is available."),
+    (487, "ERROR_INVALID_ADDRESS", "Attempt to access invalid address."),
+    (995, "ERROR_OPERATION_ABORTED", "The I/O operation has been aborted because of either a thread exit or an application request."),
+    (997, "ERROR_IO_PENDING", "Overlapped I/O operation is in progress."),
+    (1004, "ERROR_INVALID_FLAGS", "Invalid flags."),
+    (1005, "ERROR_UNRECOGNIZED_VOLUME", "The volume does not contain a recognized file system."),
+    (1060, "ERROR_SERVICE_DOES_NOT_EXIST", "The specified service does not exist as an installed service."),
+    (1168, "ERROR_NOT_FOUND", "Element not found."),
+    (1178, "ERROR_JOURNAL_DELETE_IN_PROGRESS", "The volume change journal is being deleted."),
+    (1179, "ERROR_JOURNAL_NOT_ACTIVE", "The volume change journal is not active."),
+    (1181, "ERROR_JOURNAL_ENTRY_DELETED", "The journal entry has been deleted from the journal."),
+    (1223, "ERROR_CANCELLED", "The operation was canceled by the user."),
+    (1314, "ERROR_PRIVILEGE_NOT_HELD", "A required privilege is not held by the client."),
+    (1326, "ERROR_LOGON_FAILURE", "The user name or password is incorrect."),
+    (1355, "ERROR_NO_SUCH_DOMAIN", "The specified domain either does not exist or could not be contacted."),
+    (1450, "ERROR_NO_SYSTEM_RESOURCES", "Insufficient system resources exist to complete the requested service."),
+    (1717, "RPC_S_UNKNOWN_IF", "The interface is unknown."),
+    (1722, "RPC_S_SERVER_UNAVAILABLE", "The RPC server is unavailable."),
+    (1726, "RPC_S_CALL_FAILED", "The remote procedure call failed."),
+    (1753, "EPT_S_NOT_REGISTERED", "There are no more endpoints available from the endpoint mapper."),
+    (1815, "ERROR_RESOURCE_LANG_NOT_FOUND", "The specified resource language ID cannot be found in the image file."),
+    (4201, "ERROR_WMI_INSTANCE_NOT_FOUND", "The instance name passed was not recognized as valid by a WMI data provider."),
+    (4317, "ERROR_INVALID_OPERATION", "The operation identifier is not valid."),
+    (15000, "ERROR_EVT_INVALID_CHANNEL_PATH", "The specified channel path is invalid."),
+    (15001, "ERROR_EVT_INVALID_QUERY", "The specified query is invalid."),
+    (15002, "ERROR_EVT_PUBLISHER_METADATA_NOT_FOUND", "The publisher metadata cannot be found in the resource."),
+    (15003, "ERROR_EVT_EVENT_TEMPLATE_NOT_FOUND", "The template for an event definition cannot be found in the resource."),
+    (15004, "ERROR_EVT_INVALID_PUBLISHER_NAME", "The specified publisher name is invalid."),
+    (15005, "ERROR_EVT_INVALID_EVENT_DATA", "The event data raised by the publisher is not compatible with the event template definition in the publisher's manifest."),
+    (15007, "ERROR_EVT_CHANNEL_NOT_FOUND", "The specified channel could not be found."),
+    (15008, "ERROR_EVT_MALFORMED_XML_TEXT", "The specified XML text was not well-formed."),
+    (15009, "ERROR_EVT_SUBSCRIPTION_TO_DIRECT_CHANNEL", "The events for a direct channel go directly to a log file and cannot be subscribed to."),
+    (15010, "ERROR_EVT_CONFIGURATION_ERROR", "Configuration error."),
+    (15011, "ERROR_EVT_QUERY_RESULT_STALE", "The query result is stale or invalid and must be recreated."),
+    (15012, "ERROR_EVT_QUERY_RESULT_INVALID_POSITION", "The query result is currently at an invalid position."),
+    (15013, "ERROR_EVT_NON_VALIDATING_MSXML", "Registered MSXML doesn't support validation."),
+    (15014, "ERROR_EVT_FILTER_ALREADYSCOPED", "An expression can only be followed by a change-of-scope operation if it evaluates to a node set and is not already part of another change-of-scope operation."),
+    (15015, "ERROR_EVT_FILTER_NOTELTSET", "Can't perform a step operation from a term that does not represent an element set."),
+    (15016, "ERROR_EVT_FILTER_INVARG", "Left hand side arguments to binary operators must be either attributes, nodes or variables and right hand side arguments must be constants."),
+    (15017, "ERROR_EVT_FILTER_INVTEST", "A step operation must involve either a node test or, in the case of a predicate, an algebraic expression against which to test each node in the node set identified by the preceding node set can be evaluated."),
+    (15018, "ERROR_EVT_FILTER_INVTYPE", "This data type is currently unsupported."),
+    (15019, "ERROR_EVT_FILTER_PARSEERR", "A syntax error occurred at the position indicated."),
+    (15020, "ERROR_EVT_FILTER_UNSUPPORTEDOP", "This operator is unsupported by this implementation of the filter."),
+    (15021, "ERROR_EVT_FILTER_UNEXPECTEDTOKEN", "The token encountered was unexpected."),
+    (15022, "ERROR_EVT_INVALID_OPERATION_OVER_ENABLED_DIRECT_CHANNEL", "The requested operation cannot be performed over an enabled direct channel. The channel must first be disabled."),
+    (15023, "ERROR_EVT_INVALID_CHANNEL_PROPERTY_VALUE", "Channel property contains an invalid value."),
+    (15024, "ERROR_EVT_INVALID_PUBLISHER_PROPERTY_VALUE", "Publisher property contains an invalid value."),
+    (15025, "ERROR_EVT_CHANNEL_CANNOT_ACTIVATE", "The channel fails to activate."),
+    (15026, "ERROR_EVT_FILTER_TOO_COMPLEX", "The XPath expression exceeded supported complexity."),
+    (15027, "ERROR_EVT_MESSAGE_NOT_FOUND", "The message resource is present but the message was not found in the message table."),
+    (15028, "ERROR_EVT_MESSAGE_ID_NOT_FOUND", "The message ID for the desired message could not be found."),
+    (15029, "ERROR_EVT_UNRESOLVED_VALUE_INSERT", "The substitution string for the insert index could not be found."),
+    (15030, "ERROR_EVT_UNRESOLVED_PARAMETER_INSERT", "The description string for the parameter reference could not be found."),
+    (15031, "ERROR_EVT_MAX_INSERTS_REACHED", "The maximum number of replacements has been reached."),
+    (15032, "ERROR_EVT_EVENT_DEFINITION_NOT_FOUND", "The event definition could not be found for the event ID."),
+    (15033, "ERROR_EVT_MESSAGE_LOCALE_NOT_FOUND", "The locale specific resource for the desired message is not present."),
+    (15034, "ERROR_EVT_VERSION_TOO_OLD", "The resource is too old to be compatible."),
+    (15035, "ERROR_EVT_VERSION_TOO_NEW", "The resource is too new to be compatible."),
+    (15036, "ERROR_EVT_CANNOT_OPEN_CHANNEL_OF_QUERY", "The channel at index of the query can't be opened."),
+    (15037, "ERROR_EVT_PUBLISHER_DISABLED", "The publisher has been disabled and its resource is not available."),
+    (15038, "ERROR_EVT_FILTER_OUT_OF_RANGE", "Attempted to create a numeric type that is outside of its valid range."),
+    (15100, "ERROR_MUI_FILE_NOT_FOUND", "The resource loader failed to find MUI file."),
+    (15101, "ERROR_MUI_INVALID_FILE", "The resource loader failed to load MUI file because the file fail to pass validation."),
+    (15105, "ERROR_MUI_FILE_NOT_LOADED", "The resource loader cache doesn't have loaded MUI entry.")
+];
+
+
+/// Common NTSTATUS values.
+static NTSTATUS_CODES: &[(u32, &str, &str)] = &[
+    (0x0000_0000, "STATUS_SUCCESS", "The operation completed successfully."),
+    (0x0000_0103, "STATUS_PENDING", "The operation that was requested is pending completion."),
+    (0x8000_0005, "STATUS_BUFFER_OVERFLOW", "The data was too large to fit into the specified buffer."),
+    (0x8000_0006, "STATUS_NO_MORE_FILES", "No more files were found which match the file specification."),
+    (0x8000_001A, "STATUS_NO_MORE_ENTRIES", "No more entries are available from an enumeration operation."),
+    (0xC000_0001, "STATUS_UNSUCCESSFUL", "The requested operation was unsuccessful."),
+    (0xC000_0002, "STATUS_NOT_IMPLEMENTED", "The requested operation is not implemented."),
+    (0xC000_0005, "STATUS_ACCESS_VIOLATION", "The instruction caused a memory access violation."),
+    (0xC000_0008, "STATUS_INVALID_HANDLE", "An invalid HANDLE was specified."),
+    (0xC000_000D, "STATUS_INVALID_PARAMETER", "An invalid parameter was passed to a service or function."),
+    (0xC000_000F, "STATUS_NO_SUCH_FILE", "The file does not exist."),
+    (0xC000_0010, "STATUS_INVALID_DEVICE_REQUEST", "The specified request is not a valid operation for the target device."),
+    (0xC000_0011, "STATUS_END_OF_FILE", "The end-of-file marker has been reached."),
+    (0xC000_0017, "STATUS_NO_MEMORY", "Not enough virtual memory or paging file quota is available to complete the specified operation."),
+    (0xC000_0022, "STATUS_ACCESS_DENIED", "A process has requested access to an object but has not been granted those access rights."),
+    (0xC000_0023, "STATUS_BUFFER_TOO_SMALL", "The buffer is too small to contain the entry."),
+    (0xC000_0024, "STATUS_OBJECT_TYPE_MISMATCH", "There is a mismatch between the type of object that is required by the requested operation and the type of object that is specified in the request."),
+    (0xC000_0034, "STATUS_OBJECT_NAME_NOT_FOUND", "The object name is not found."),
+    (0xC000_0035, "STATUS_OBJECT_NAME_COLLISION", "The object name already exists."),
+    (0xC000_003A, "STATUS_OBJECT_PATH_NOT_FOUND", "The path does not exist."),
+    (0xC000_0043, "STATUS_SHARING_VIOLATION", "A file cannot be opened because the share access flags are incompatible."),
+    (0xC000_0061, "STATUS_PRIVILEGE_NOT_HELD", "A required privilege is not held by the client."),
+    (0xC000_006D, "STATUS_LOGON_FAILURE", "The attempted logon is invalid."),
+    (0xC000_009A, "STATUS_INSUFFICIENT_RESOURCES", "Insufficient system resources exist to complete the API."),
+    (0xC000_00BB, "STATUS_NOT_SUPPORTED", "The request is not supported."),
+    (0xC000_0102, "STATUS_FILE_CORRUPT_ERROR", "The file or directory is corrupt and unreadable."),
+    (0xC000_0120, "STATUS_CANCELLED", "The I/O request was canceled."),
+    (0xC000_0185, "STATUS_IO_DEVICE_ERROR", "The I/O device reported an I/O error."),
+    (0xC000_0225, "STATUS_NOT_FOUND", "The object was not found."),
+    (0xC000_026E, "STATUS_VOLUME_DISMOUNTED", "An operation was attempted to a volume after it was dismounted."),
+    (0xC000_02B7, "STATUS_JOURNAL_DELETE_IN_PROGRESS", "The volume change journal is being deleted."),
+    (0xC000_02B8, "STATUS_JOURNAL_NOT_ACTIVE", "The volume change journal is not active."),
+    (0xC000_02CF, "STATUS_JOURNAL_ENTRY_DELETED", "The journal entry has been deleted from the journal.")
+];
+
+
+/// Common HRESULT values that are not HRESULT_FROM_WIN32 or HRESULT_FROM_NT.
+static HRESULT_CODES: &[(u32, &str, &str)] = &[
+    (0x0000_0000, "S_OK", "The operation completed successfully."),
+    (0x0000_0001, "S_FALSE", "The operation completed successfully but returned false."),
+    (0x8000_4001, "E_NOTIMPL", "Not implemented."),
+    (0x8000_4002, "E_NOINTERFACE", "No such interface supported."),
+    (0x8000_4003, "E_POINTER", "Invalid pointer."),
+    (0x8000_4004, "E_ABORT", "Operation aborted."),
+    (0x8000_4005, "E_FAIL", "Unspecified error."),
+    (0x8000_FFFF, "E_UNEXPECTED", "Catastrophic failure."),
+    (0x8001_0108, "RPC_E_DISCONNECTED", "The object invoked has disconnected from its clients."),
+    (0x8004_01F0, "CO_E_NOTINITIALIZED", "CoInitialize has not been called."),
+    (0x8004_1001, "WBEM_E_FAILED", "Generic failure."),
+    (0x8004_1002, "WBEM_E_NOT_FOUND", "Not found."),
+    (0x8004_1003, "WBEM_E_ACCESS_DENIED", "Access denied."),
+    (0x8004_1010, "WBEM_E_INVALID_CLASS", "Invalid class."),
+    (0x8004_1017, "WBEM_E_INVALID_QUERY", "Invalid query.")
+];
+
+
+fn find_code(table: &'static [(u32, &str, &str)], code: u32) -> Option<CodeInfo> {
+    table.iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|&(_, name, message)| CodeInfo { name, message })
+}
+
+
+fn find_name(table: &'static [(u32, &str, &str)], name: &str) -> Option<u32> {
+    table.iter()
+        .find(|(_, n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(c, _, _)| *c)
+}
+
+
+pub fn get_win32_info(code: u32) -> Option<CodeInfo> {
+    find_code(WIN32_CODES, code)
+}
+
+
+pub fn get_ntstatus_info(code: u32) -> Option<CodeInfo> {
+    find_code(NTSTATUS_CODES, code)
+}
+
+
+/// Look up an HRESULT. HRESULT_FROM_WIN32 and HRESULT_FROM_NT values are
+/// looked up as the code they wrap.
+///
+pub fn get_hresult_info(hresult: i32) -> Option<CodeInfo> {
+    let code = hresult as u32;
+    if let Some(info) = find_code(HRESULT_CODES, code) {
+        return Some(info);
+    }
+
+    if let Some(win32_code) = WindowsCode::HResult(hresult).as_win32() {
+        return get_win32_info(win32_code);
+    }
+
+    if code & FACILITY_NT_BIT != 0 {
+        return get_ntstatus_info(code & !FACILITY_NT_BIT);
+    }
+
+    None
+}
+
+
+pub fn get_code_info(code: WindowsCode) -> Option<CodeInfo> {
+    match code {
+        WindowsCode::Win32(c) => get_win32_info(c),
+        WindowsCode::HResult(c) => get_hresult_info(c),
+        WindowsCode::NtStatus(c) => get_ntstatus_info(c)
+    }
+}
+
+
+/// Find a code by its symbolic name, e.g. "ERROR_EVT_CHANNEL_NOT_FOUND".
+///
+pub fn get_code_by_name(name: &str) -> Option<WindowsCode> {
+    if let Some(c) = find_name(WIN32_CODES, name) {
+        return Some(WindowsCode::Win32(c));
+    }
+
+    if let Some(c) = find_name(NTSTATUS_CODES, name) {
+        return Some(WindowsCode::NtStatus(c));
+    }
+
+    find_name(HRESULT_CODES, name).map(|c| WindowsCode::HResult(c as i32))
+}
+
+
+/// Get the message for a code. The built-in catalog is used first, then
+/// FormatMessageW on Windows.
+///
+pub fn get_code_message(code: WindowsCode) -> String {
+    if let Some(info) = get_code_info(code) {
+        return info.message.to_string();
+    }
+
+    #[cfg(windows)]
+    {
+        let system_code = match code {
+            WindowsCode::Win32(c) => Some(c),
+            WindowsCode::HResult(c) => Some(c as u32),
+            WindowsCode::NtStatus(_) => None
+        };
+
+        if let Some(message) = system_code.and_then(format_system_message) {
+            return message;
+        }
+    }
+
+    format!("Unknown {}", code)
+}
@@ -0,0 +1,429 @@
+pub mod catalog;
+use std::fmt;
+use std::error::Error;
+use mft::err::Error as MftError;
+use std::io::Error as IoError;
+use minidom::Error as MinidomError;
+use quick_xml::Error as QuickXmlError;
+use std::string::FromUtf8Error;
+use std::string::FromUtf16Error;
+use serde_json::Error as SerdeJsonError;
+use serde_yaml::Error as SerdeYamlError;
+use crate::errors::catalog::{get_code_info, get_code_message};
+#[cfg(windows)]
+use std::ptr;
+#[cfg(windows)]
+use winapi::shared::ntdef::WCHAR;
+#[cfg(windows)]
+use winapi::um::winbase::{
+    FormatMessageW,
+    FORMAT_MESSAGE_FROM_SYSTEM,
+    FORMAT_MESSAGE_IGNORE_INSERTS,
+};
+#[cfg(windows)]
+use winapi::um::errhandlingapi::GetLastError;
+
+/// HRESULTs with this facility wrap a Win32 error code.
+pub const FACILITY_WIN32: u32 = 7;
+
+
+/// The kind of a WinThingError. `as_str` gives a stable, machine-readable
+/// code for each kind.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorType {
+    CliError,
+    WinApiError,
+    Utf16Error,
+    Utf8Error,
+    XmlError,
+    UnhandledVariant,
+    OsError,
+    UnhandledLogic,
+    WindowsError,
+    SerdeJsonError,
+    IoError,
+    MftError,
+    InvalidUsnJournalData,
+    JsonPatchError,
+    InvalidMftRecord,
+    StreamError,
+    CredentialError,
+    PolicyError,
+    SddlError,
+    TimeoutError,
+    RuleError,
+    InvalidInput
+}
+
+impl ErrorType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorType::CliError => "cli_error",
+            ErrorType::WinApiError => "winapi_error",
+            ErrorType::Utf16Error => "utf16_error",
+            ErrorType::Utf8Error => "utf8_error",
+            ErrorType::XmlError => "xml_error",
+            ErrorType::UnhandledVariant => "unhandled_variant",
+            ErrorType::OsError => "os_error",
+            ErrorType::UnhandledLogic => "unhandled_logic",
+            ErrorType::WindowsError => "windows_error",
+            ErrorType::SerdeJsonError => "serde_json_error",
+            ErrorType::IoError => "io_error",
+            ErrorType::MftError => "mft_error",
+            ErrorType::InvalidUsnJournalData => "invalid_usn_journal_data",
+            ErrorType::JsonPatchError => "json_patch_error",
+            ErrorType::InvalidMftRecord => "invalid_mft_record",
+            ErrorType::StreamError => "stream_error",
+            ErrorType::CredentialError => "credential_error",
+            ErrorType::PolicyError => "policy_error",
+            ErrorType::SddlError => "sddl_error",
+            ErrorType::TimeoutError => "timeout_error",
+            ErrorType::RuleError => "rule_error",
+            ErrorType::InvalidInput => "invalid_input"
+        }
+    }
+}
+
+impl fmt::Display for ErrorType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+
+/// A status code returned by a Windows API.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowsCode {
+    Win32(u32),
+    HResult(i32),
+    NtStatus(u32)
+}
+
+impl WindowsCode {
+    /// Get the Win32 error code, unwrapping HRESULT_FROM_WIN32 values.
+    pub fn as_win32(&self) -> Option<u32> {
+        match *self {
+            WindowsCode::Win32(code) => Some(code),
+            WindowsCode::HResult(hresult) => {
+                let hresult = hresult as u32;
+                if hresult & 0xFFFF_0000 == 0x8000_0000 | (FACILITY_WIN32 << 16) {
+                    Some(hresult & 0xFFFF)
+                } else {
+                    None
+                }
+            },
+            WindowsCode::NtStatus(_) => None
+        }
+    }
+
+    /// Get the symbolic name of the code (e.g. ERROR_EVT_CHANNEL_NOT_FOUND)
+    /// from the built-in catalog.
+    pub fn get_name(&self) -> Option<&'static str> {
+        get_code_info(*self).map(|info| info.name)
+    }
+}
+
+impl fmt::Display for WindowsCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowsCode::Win32(code) => write!(f, "Win32 error {}", code),
+            WindowsCode::HResult(code) => write!(f, "HRESULT 0x{:08X}", *code as u32),
+            WindowsCode::NtStatus(code) => write!(f, "NTSTATUS 0x{:08X}", code)
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub struct WinThingError {
+    pub message: String,
+    pub kind: ErrorType,
+    pub code: Option<WindowsCode>,
+    source: Option<Box<dyn Error + Send + Sync + 'static>>
+}
+
+impl WinThingError {
+    fn new(kind: ErrorType, message: String) -> Self {
+        Self {
+            message,
+            kind,
+            code: None,
+            source: None
+        }
+    }
+
+    /// Attach the error that caused this one.
+    pub fn with_source<E>(mut self, source: E) -> Self
+        where E: Error + Send + Sync + 'static
+    {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    /// Attach the Windows status code of the failed call.
+    pub fn with_code(mut self, code: WindowsCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Get the Win32 error code if there is one, so callers can match on
+    /// codes like ERROR_NO_MORE_ITEMS.
+    pub fn get_win32_code(&self) -> Option<u32> {
+        match self.code {
+            Some(code) => code.as_win32(),
+            None => None
+        }
+    }
+
+    /// Format the error followed by each of its sources. A source with the
+    /// same message as the error it caused is not repeated.
+    pub fn display_chain(&self) -> String {
+        let mut chain = self.to_string();
+        let mut previous = self.message.trim().to_string();
+        let mut source = self.source();
+        while let Some(s) = source {
+            let message = s.to_string();
+            if message.trim() != previous {
+                chain.push_str(&format!(": {}", message.trim()));
+            }
+
+            previous = message.trim().to_string();
+            source = s.source();
+        }
+
+        chain
+    }
+
+    pub fn cli_error(message: String) -> Self {
+        Self::new(ErrorType::CliError, message)
+    }
+
+    pub fn unhandled_variant(message: String) -> Self {
+        Self::new(ErrorType::UnhandledVariant, message)
+    }
+
+    pub fn utf16_error(message: String) -> Self {
+        Self::new(ErrorType::Utf16Error, message)
+    }
+
+    pub fn winapi_error(message: String) -> Self {
+        Self::new(ErrorType::WinApiError, message)
+    }
+
+    pub fn xml_error(message: String) -> Self {
+        Self::new(ErrorType::XmlError, message)
+    }
+
+    pub fn unhandled(message: String) -> Self {
+        Self::new(ErrorType::UnhandledLogic, message)
+    }
+
+    pub fn from_windows_error_code(err_code: u32) -> Self {
+        let err_str = get_code_message(
+            WindowsCode::Win32(err_code)
+        );
+
+        Self::new(ErrorType::WindowsError, err_str)
+            .with_code(WindowsCode::Win32(err_code))
+    }
+
+    #[cfg(windows)]
+    pub fn from_windows_last_error() -> Self{
+        let err_code = unsafe {
+            GetLastError()
+        };
+
+        Self::from_windows_error_code(err_code)
+    }
+
+    pub fn from_hresult(hresult: i32, message: String) -> Self {
+        Self::new(ErrorType::WindowsError, message)
+            .with_code(WindowsCode::HResult(hresult))
+    }
+
+    pub fn os_error(error_code: i32) -> Self {
+        let error = IoError::from_raw_os_error(
+            error_code
+        );
+
+        Self::new(ErrorType::OsError, error.to_string())
+            .with_code(WindowsCode::Win32(error_code as u32))
+            .with_source(error)
+    }
+
+    pub fn invalid_usn_journal_data(size: usize) -> Self {
+        let err_str = format!("Unknown size for UsnJournalData structure: {}", size);
+
+        Self::new(ErrorType::InvalidUsnJournalData, err_str)
+    }
+
+    pub fn json_patch_error(message: String) -> Self {
+        Self::new(ErrorType::JsonPatchError, message)
+    }
+
+    pub fn invalid_mft_record(message: String) -> Self {
+        Self::new(ErrorType::InvalidMftRecord, message)
+    }
+
+    pub fn stream_error(message: String) -> Self {
+        Self::new(ErrorType::StreamError, message)
+    }
+
+    pub fn credential_error(message: String) -> Self {
+        Self::new(ErrorType::CredentialError, message)
+    }
+
+    pub fn policy_error(message: String) -> Self {
+        Self::new(ErrorType::PolicyError, message)
+    }
+
+    pub fn sddl_error(message: String) -> Self {
+        Self::new(ErrorType::SddlError, message)
+    }
+
+    pub fn timeout_error(message: String) -> Self {
+        Self::new(ErrorType::TimeoutError, message)
+    }
+
+    pub fn rule_error(message: String) -> Self {
+        Self::new(ErrorType::RuleError, message)
+    }
+
+    pub fn invalid_input(message: String) -> Self {
+        Self::new(ErrorType::InvalidInput, message)
+    }
+}
+
+impl fmt::Display for WinThingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "[{}] {} ({})", self.kind, self.message.trim(), code),
+            None => write!(f, "[{}] {}", self.kind, self.message.trim())
+        }
+    }
+}
+
+impl Error for WinThingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.source {
+            Some(source) => Some(source.as_ref()),
+            None => None
+        }
+    }
+}
+
+impl From<IoError> for WinThingError {
+    fn from(err: IoError) -> Self {
+        #[allow(unused_mut)]
+        let mut error = Self::new(ErrorType::IoError, format!("{}", err));
+
+        // Raw OS errors are only Win32 codes on Windows
+        #[cfg(windows)]
+        {
+            if let Some(code) = err.raw_os_error() {
+                error = error.with_code(WindowsCode::Win32(code as u32));
+            }
+        }
+
+        error.with_source(err)
+    }
+}
+
+impl From<FromUtf8Error> for WinThingError {
+    fn from(err: FromUtf8Error) -> Self {
+        Self::new(ErrorType::Utf8Error, format!("{}", err))
+            .with_source(err)
+    }
+}
+
+impl From<FromUtf16Error> for WinThingError {
+    fn from(err: FromUtf16Error) -> Self {
+        Self::new(ErrorType::Utf16Error, format!("{}", err))
+            .with_source(err)
+    }
+}
+
+impl From<MinidomError> for WinThingError {
+    fn from(err: MinidomError) -> Self {
+        let error = Self::new(ErrorType::XmlError, format!("{}", err));
+
+        // minidom and quick_xml errors do not implement std::error::Error so
+        // only the errors they wrap are kept as the source.
+        match err {
+            MinidomError::IoError(e) => error.with_source(e),
+            MinidomError::Utf8Error(e) => error.with_source(e),
+            MinidomError::XmlError(QuickXmlError::Io(e)) => error.with_source(e),
+            MinidomError::XmlError(QuickXmlError::Utf8(e)) => error.with_source(e),
+            _ => error
+        }
+    }
+}
+
+impl From<SerdeJsonError> for WinThingError {
+    fn from(err: SerdeJsonError) -> Self {
+        Self::new(ErrorType::SerdeJsonError, format!("{}", err))
+            .with_source(err)
+    }
+}
+
+impl From<SerdeYamlError> for WinThingError {
+    fn from(err: SerdeYamlError) -> Self {
+        Self::new(ErrorType::RuleError, format!("{}", err))
+            .with_source(err)
+    }
+}
+
+impl From<MftError> for WinThingError {
+    fn from(err: MftError) -> Self {
+        Self::new(ErrorType::MftError, format!("{}", err))
+            .with_source(err)
+    }
+}
+
+
+/// Format a Win32 error code, or the last error if no code is given.
+///
+#[cfg(windows)]
+pub fn format_win_error(error_code: Option<u32>) -> String {
+    let error_num: u32 = match error_code {
+        Some(code) => code,
+        None => unsafe { GetLastError() }
+    };
+
+    get_code_message(
+        WindowsCode::Win32(error_num)
+    )
+}
+
+
+/// Get the system message for an error code with FormatMessageW. Returns
+/// None if the system has no message for it.
+///
+#[cfg(windows)]
+pub fn format_system_message(error_code: u32) -> Option<String> {
+    let mut message_buffer = [0 as WCHAR; 2048];
+
+    let message_size = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            ptr::null_mut(),
+            error_code,
+            0,
+            message_buffer.as_mut_ptr(),
+            message_buffer.len() as u32,
+            ptr::null_mut(),
+        )
+    };
+
+    if message_size == 0 {
+        return None;
+    }
+
+    Some(
+        String::from_utf16_lossy(
+            &message_buffer[..message_size as usize]
+        ).trim().to_string()
+    )
+}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+use minidom::Element;
+use serde::{Serialize, Deserialize};
+use crate::errors::WinThingError;
+
+
+/// A field of an event template, from one of its `data` elements.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateField {
+    pub name: String,
+    pub in_type: Option<String>,
+    pub out_type: Option<String>,
+    pub count: Option<String>,
+    pub length: Option<String>
+}
+
+impl TemplateField {
+    fn from_element(element: &Element, prefix: Option<&str>) -> Self {
+        let name = element.attr("name").unwrap_or("");
+
+        Self {
+            name: match prefix {
+                Some(p) => format!("{}.{}", p, name),
+                None => name.to_owned()
+            },
+            in_type: element.attr("inType").map(|s| s.to_owned()),
+            out_type: element.attr("outType").map(|s| s.to_owned()),
+            count: element.attr("count").map(|s| s.to_owned()),
+            length: element.attr("length").map(|s| s.to_owned())
+        }
+    }
+}
+
+
+/// Parse the fields of an event template as returned for
+/// `EventMetadataEventTemplate`. The fields of a `struct` are named
+/// `Struct.Field`.
+///
+/// ```xml
+/// <template xmlns="http://schemas.microsoft.com/win/2004/08/events">
+///     <data name="SubjectUserSid" inType="win:SID" outType="xs:string"/>
+/// </template>
+/// ```
+///
+pub fn parse_template_fields(template: &str) -> Result<Vec<TemplateField>, WinThingError> {
+    let root = Element::from_str(template)?;
+    let mut fields = Vec::new();
+
+    for child in root.children() {
+        match child.name() {
+            "data" => fields.push(
+                TemplateField::from_element(child, None)
+            ),
+            "struct" => {
+                let prefix = child.attr("name").unwrap_or("");
+                for data in child.children().filter(|c| c.name() == "data") {
+                    fields.push(
+                        TemplateField::from_element(data, Some(prefix))
+                    );
+                }
+            },
+            other => debug!("Skipping template element {}", other)
+        }
+    }
+
+    Ok(fields)
+}
+
+
+/// An event a publisher defines, from `EvtOpenEventMetadataEnum`. `message`
+/// is the message template with `%1` style inserts for the template fields.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EventDefinition {
+    pub id: u32,
+    pub version: u32,
+    pub channel: u32,
+    pub level: u32,
+    pub opcode: u32,
+    pub task: u32,
+    pub keywords: u64,
+    pub message_id: Option<u32>,
+    pub message: Option<String>,
+    pub template: Option<String>,
+    pub fields: Vec<TemplateField>
+}
+
+impl EventDefinition {
+    /// The EventID as it appears in the System section of an event, without
+    /// the severity, customer and facility bits.
+    pub fn get_event_id(&self) -> u16 {
+        (self.id & 0xffff) as u16
+    }
+
+    /// Get the names of the message inserts. Each top level `data` or
+    /// `struct` element of the template is one insert, so a struct is
+    /// named as a whole.
+    fn get_insert_names(&self) -> Vec<String> {
+        let root = match &self.template {
+            Some(template) => match Element::from_str(template) {
+                Ok(root) => root,
+                Err(_) => return Vec::new()
+            },
+            None => {
+                return self.fields.iter()
+                    .map(|field| field.name.to_owned())
+                    .collect();
+            }
+        };
+
+        root.children()
+            .filter(|child| child.name() == "data" || child.name() == "struct")
+            .map(|child| child.attr("name").unwrap_or("").to_owned())
+            .collect()
+    }
+
+    /// Get the message with each `%n` insert replaced by `{FieldName}`, or
+    /// `{StructName}` for a struct. Inserts without a field and `%%n`
+    /// parameter references are kept.
+    pub fn get_named_message(&self) -> Option<String> {
+        let message = self.message.as_ref()?;
+        let inserts = self.get_insert_names();
+        let chars: Vec<char> = message.chars().collect();
+        let mut named = String::with_capacity(message.len());
+        let mut index = 0;
+
+        while index < chars.len() {
+            if chars[index] != '%' {
+                named.push(chars[index]);
+                index += 1;
+                continue;
+            }
+
+            // Parameter reference, e.g. %%1833
+            if chars.get(index + 1) == Some(&'%') {
+                named.push_str("%%");
+                index += 2;
+                continue;
+            }
+
+            let mut end = index + 1;
+            while end < chars.len() && chars[end].is_ascii_digit() {
+                end += 1;
+            }
+            let number: String = chars[index + 1..end].iter().collect();
+            let field = number.parse::<usize>().ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|n| inserts.get(n));
+
+            match field {
+                Some(field) => {
+                    named.push_str(&format!("{{{}}}", field));
+
+                    // Skip a printf style format, e.g. %1!S!
+                    if chars.get(end) == Some(&'!') {
+                        if let Some(close) = chars[end + 1..].iter().position(|c| *c == '!') {
+                            end += close + 2;
+                        }
+                    }
+                    index = end;
+                },
+                None => {
+                    named.push('%');
+                    index += 1;
+                }
+            }
+        }
+
+        Some(named)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod definition;
+pub mod query;
+pub mod stream;
\ No newline at end of file
@@ -0,0 +1,230 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use crate::errors::WinThingError;
+
+/// The SystemTime format used in event log XPath queries.
+const XPATH_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+
+/// Builds the XPath query for a bounded event query from time range,
+/// EventID, provider and extra XPath filters.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryFilter {
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    event_ids: Vec<u32>,
+    providers: Vec<String>,
+    xpath: Option<String>
+}
+
+impl QueryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only events created at or after `start_time`.
+    pub fn with_start_time(mut self, start_time: DateTime<Utc>) -> Self {
+        self.start_time = Some(start_time);
+        self
+    }
+
+    /// Only events created at or before `end_time`.
+    pub fn with_end_time(mut self, end_time: DateTime<Utc>) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    pub fn with_event_id(mut self, event_id: u32) -> Self {
+        self.event_ids.push(event_id);
+        self
+    }
+
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.providers.push(provider.to_owned());
+        self
+    }
+
+    /// An extra XPath predicate on the event, e.g.
+    /// `EventData[Data[@Name='LogonType']='3']`. A full `*[...]` query is
+    /// also accepted and its predicate used.
+    pub fn with_xpath(mut self, xpath: &str) -> Self {
+        self.xpath = Some(xpath.to_owned());
+        self
+    }
+
+    /// Get the XPath query. Without filters this is `*`.
+    pub fn to_xpath(&self) -> String {
+        let mut system_filters: Vec<String> = Vec::new();
+
+        if !self.providers.is_empty() {
+            let names: Vec<String> = self.providers.iter()
+                .map(|p| format!("@Name={}", xpath_literal(p)))
+                .collect();
+            system_filters.push(
+                format!("Provider[{}]", names.join(" or "))
+            );
+        }
+
+        if !self.event_ids.is_empty() {
+            let ids: Vec<String> = self.event_ids.iter()
+                .map(|id| format!("EventID={}", id))
+                .collect();
+            system_filters.push(
+                format!("({})", ids.join(" or "))
+            );
+        }
+
+        let mut time_filters: Vec<String> = Vec::new();
+        if let Some(start_time) = &self.start_time {
+            time_filters.push(
+                format!("@SystemTime>='{}'", start_time.format(XPATH_TIME_FORMAT))
+            );
+        }
+        if let Some(end_time) = &self.end_time {
+            time_filters.push(
+                format!("@SystemTime<='{}'", end_time.format(XPATH_TIME_FORMAT))
+            );
+        }
+        if !time_filters.is_empty() {
+            system_filters.push(
+                format!("TimeCreated[{}]", time_filters.join(" and "))
+            );
+        }
+
+        let mut filters: Vec<String> = Vec::new();
+        if !system_filters.is_empty() {
+            filters.push(
+                format!("System[{}]", system_filters.join(" and "))
+            );
+        }
+
+        // The predicate is bracketed so an `or` in it can't take in the
+        // other filters
+        if let Some(xpath) = &self.xpath {
+            filters.push(
+                format!("({})", get_xpath_predicate(xpath))
+            );
+        }
+
+        match filters.is_empty() {
+            true => "*".to_owned(),
+            false => format!("*[{}]", filters.join(" and "))
+        }
+    }
+}
+
+
+/// Options for running a query.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryOptions {
+    /// Newest events first.
+    pub reverse: bool,
+    /// Stop after this many events.
+    pub max_count: Option<usize>,
+    /// The number of events to fetch at once.
+    pub batch_size: usize
+}
+
+impl QueryOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+
+    pub fn with_max_count(mut self, max_count: usize) -> Self {
+        self.max_count = Some(max_count);
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        Self {
+            reverse: false,
+            max_count: None,
+            batch_size: 64
+        }
+    }
+}
+
+
+/// Get the predicate of a full `*[...]` query, or the XPath as it is if it
+/// is not one. The bracket after `*` has to close at the end, so queries
+/// like `*[A] and *[B]` are left as they are.
+fn get_xpath_predicate(xpath: &str) -> &str {
+    let xpath = xpath.trim();
+    if !xpath.starts_with("*[") || !xpath.ends_with(']') {
+        return xpath;
+    }
+
+    let mut depth: usize = 0;
+    let mut quote: Option<char> = None;
+    for (index, c) in xpath.char_indices().skip(1) {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {},
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return match index == xpath.len() - 1 {
+                        true => &xpath[2..index],
+                        false => xpath
+                    };
+                }
+            },
+            _ => {}
+        }
+    }
+
+    xpath
+}
+
+
+/// Quote a string for XPath. XPath 1.0 has no escapes, so strings with a
+/// single quote are double quoted and strings with both kinds of quote are
+/// built with `concat()`.
+fn xpath_literal(value: &str) -> String {
+    match (value.contains('\''), value.contains('"')) {
+        (false, _) => format!("'{}'", value),
+        (true, false) => format!("\"{}\"", value),
+        (true, true) => {
+            let parts: Vec<String> = value.split('\'')
+                .map(|p| format!("'{}'", p))
+                .collect();
+            format!("concat({})", parts.join(", \"'\", "))
+        }
+    }
+}
+
+
+/// Parse a query time as RFC 3339 (`2020-01-20T10:15:00Z`) or a date
+/// (`2020-01-20`, midnight UTC).
+///
+pub fn parse_query_time(time_str: &str) -> Result<DateTime<Utc>, WinThingError> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(time_str) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    match NaiveDate::parse_from_str(time_str, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0)) {
+        Some(midnight) => Ok(
+            Utc.from_utc_datetime(&midnight)
+        ),
+        None => Err(
+            WinThingError::cli_error(
+                format!("Unable to parse time '{}'. Expected RFC 3339 or YYYY-MM-DD.", time_str)
+            )
+        )
+    }
+}
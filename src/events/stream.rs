@@ -0,0 +1,227 @@
+use std::fs;
+use std::thread;
+use std::time::Duration;
+use std::sync::mpsc::{
+    sync_channel,
+    Receiver,
+    RecvTimeoutError,
+    SyncSender,
+    TrySendError
+};
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::utils::xmltojson::xml_string_to_json;
+
+/// The number of events buffered before producers block.
+pub const DEFAULT_STREAM_CAPACITY: usize = 1024;
+/// How long the stream waits for an event before maintaining its producer.
+const MAINTAIN_INTERVAL: Duration = Duration::from_millis(500);
+
+type StreamResult = Result<StreamEvent, WinThingError>;
+
+
+/// A rendered event and the source (channel or file) it came from.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEvent {
+    pub source: String,
+    pub xml: String
+}
+
+impl StreamEvent {
+    pub fn new(source: &str, xml: String) -> Self {
+        Self {
+            source: source.to_owned(),
+            xml
+        }
+    }
+
+    /// Get the event as JSON.
+    pub fn to_json_value(&self) -> Result<Value, WinThingError> {
+        xml_string_to_json(
+            self.xml.clone()
+        )
+    }
+}
+
+
+/// Something that produces the events of a stream and has to be kept alive,
+/// and periodically maintained, while the stream is read. Live subscriptions
+/// use this to re-establish themselves.
+///
+pub trait StreamProducer {
+    fn maintain(&mut self) {}
+}
+
+
+/// The sending half of an EventStream. Sends block while the stream is
+/// full and fail once the stream has been dropped.
+///
+#[derive(Clone)]
+pub struct EventSender {
+    sender: SyncSender<StreamResult>
+}
+
+impl EventSender {
+    pub fn send_event(&self, event: StreamEvent) -> Result<(), WinThingError> {
+        self.send(Ok(event))
+    }
+
+    pub fn send_xml(&self, source: &str, xml: String) -> Result<(), WinThingError> {
+        self.send(Ok(StreamEvent::new(source, xml)))
+    }
+
+    pub fn send_error(&self, error: WinThingError) -> Result<(), WinThingError> {
+        self.send(Err(error))
+    }
+
+    /// Send without blocking. Returns false if the stream is full.
+    pub fn try_send_xml(&self, source: &str, xml: String) -> Result<bool, WinThingError> {
+        match self.sender.try_send(Ok(StreamEvent::new(source, xml))) {
+            Ok(_) => Ok(true),
+            Err(TrySendError::Full(_)) => Ok(false),
+            Err(TrySendError::Disconnected(_)) => Err(stream_closed_error())
+        }
+    }
+
+    fn send(&self, item: StreamResult) -> Result<(), WinThingError> {
+        self.sender.send(item)
+            .map_err(|_| stream_closed_error())
+    }
+}
+
+
+fn stream_closed_error() -> WinThingError {
+    WinThingError::stream_error(
+        "The event stream was closed.".to_owned()
+    )
+}
+
+
+/// A bounded stream of events. Iterating blocks until the next event is
+/// available and ends once every sender is gone. Live subscriptions, EVTX
+/// files (see `winevt::stream`) and XML fixtures all produce the same
+/// stream.
+///
+pub struct EventStream {
+    receiver: Receiver<StreamResult>,
+    producer: Option<Box<dyn StreamProducer>>
+}
+
+impl EventStream {
+    /// Create a stream and its sender. At most `capacity` events are
+    /// buffered before `EventSender` blocks.
+    pub fn channel(capacity: usize) -> (EventSender, Self) {
+        let (sender, receiver) = sync_channel(capacity);
+
+        (
+            EventSender { sender },
+            Self {
+                receiver,
+                producer: None
+            }
+        )
+    }
+
+    /// Keep `producer` alive for as long as the stream.
+    pub fn with_producer(mut self, producer: Box<dyn StreamProducer>) -> Self {
+        self.producer = Some(producer);
+        self
+    }
+
+    /// Create a stream of XML records sent from a background thread.
+    pub fn from_xml_records(
+        source: &str,
+        records: Vec<String>,
+        capacity: usize
+    ) -> Self {
+        let (sender, stream) = Self::channel(capacity);
+        let source = source.to_owned();
+
+        thread::spawn(move || {
+            for xml in records {
+                if sender.send_xml(&source, xml).is_err() {
+                    break;
+                }
+            }
+        });
+
+        stream
+    }
+
+    /// Create a stream from a file of concatenated XML events, such as a
+    /// test fixture or an export of rendered events.
+    pub fn from_xml_path(path: &str, capacity: usize) -> Result<Self, WinThingError> {
+        let content = fs::read_to_string(path)?;
+
+        Ok(
+            Self::from_xml_records(
+                path,
+                split_xml_events(&content),
+                capacity
+            )
+        )
+    }
+
+    /// Get the next item, waiting at most `timeout`. None if nothing
+    /// arrived in time or the stream has ended.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<StreamResult> {
+        self.receiver.recv_timeout(timeout).ok()
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = StreamResult;
+
+    fn next(&mut self) -> Option<StreamResult> {
+        loop {
+            match self.receiver.recv_timeout(MAINTAIN_INTERVAL) {
+                Ok(item) => return Some(item),
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Some(producer) = self.producer.as_mut() {
+                        producer.maintain();
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => return None
+            }
+        }
+    }
+}
+
+
+/// Split concatenated XML documents into their `<Event>` elements.
+///
+pub fn split_xml_events(content: &str) -> Vec<String> {
+    const END_TAG: &str = "</Event>";
+    let mut events: Vec<String> = Vec::new();
+    let mut remaining = content;
+
+    while let Some(start) = find_event_start(remaining) {
+        let end = match remaining[start..].find(END_TAG) {
+            Some(e) => start + e + END_TAG.len(),
+            None => break
+        };
+
+        events.push(remaining[start..end].to_owned());
+        remaining = &remaining[end..];
+    }
+
+    events
+}
+
+
+/// Find the next `<Event` start tag that is not a longer element name such
+/// as `<EventData`.
+fn find_event_start(content: &str) -> Option<usize> {
+    let mut offset = 0;
+
+    while let Some(index) = content[offset..].find("<Event") {
+        let start = offset + index;
+        match content[start + "<Event".len()..].chars().next() {
+            Some(c) if c == '>' || c == '/' || c.is_whitespace() => return Some(start),
+            _ => offset = start + 1
+        }
+    }
+
+    None
+}
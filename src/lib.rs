@@ -4,9 +4,16 @@ extern crate minidom;
 extern crate quick_xml;
 
 pub mod errors;
+pub mod events;
+pub mod channels;
+pub mod detection;
+pub mod publishers;
+pub mod timeline;
 pub mod utils;
 pub mod winevt;
+#[cfg(windows)]
 pub mod winetl;
+#[cfg(windows)]
 pub mod file;
 pub mod volume;
 pub mod mft;
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use mft::MftEntry;
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::mft::{get_entry_file_name, LogicalEntry};
+use crate::mft::raw::{
+    get_entry_flags,
+    get_extension_entries_with_reader,
+    get_file_names,
+    RawAttribute,
+    RawFileName,
+    ENTRY_DIRECTORY,
+    ENTRY_IN_USE
+};
+use crate::utils::csv::get_csv_line;
+
+/// The entry of the root directory.
+pub const ROOT_ENTRY: u64 = 5;
+
+/// The directory of entries whose parent is gone or was reused.
+const ORPHAN_DIRECTORY: &str = "\\$OrphanFiles";
+
+/// Parents deeper than this are treated as a loop.
+const MAX_PATH_DEPTH: usize = 255;
+
+/// The CSV header of `get_entry_csv_line`.
+pub const ENTRY_CSV_HEADER: &str = "entry,sequence,in_use,is_directory,path,size,si_created,si_modified,si_mft_modified,si_accessed,fn_created,fn_modified,fn_mft_modified,fn_accessed";
+
+
+/// A source of MFT records: a live volume, an NTFS image or a $MFT file.
+///
+pub trait MftSource {
+    /// Get the number of records in the MFT.
+    fn get_entry_count(&self) -> u64;
+
+    /// Get the record of an entry, or None if the record has no FILE
+    /// signature (it was never used or is damaged).
+    fn get_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError>;
+
+    /// Read the value of a non-resident attribute, such as the
+    /// $ATTRIBUTE_LIST of an entry with many extension records, from the
+    /// volume's clusters. A $MFT file has no clusters to read it from.
+    fn read_attribute_value(&mut self, attribute: &RawAttribute) -> Result<Vec<u8>, WinThingError> {
+        Err(
+            WinThingError::stream_error(
+                format!("Non-resident attribute {:#x} can not be read without the volume.", attribute.type_code)
+            )
+        )
+    }
+}
+
+
+/// Which records to enumerate. With neither in use nor deleted set, records
+/// of both are included.
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EntryFilter {
+    in_use: bool,
+    deleted: bool,
+    directories: bool
+}
+
+impl EntryFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_in_use(mut self, in_use: bool) -> Self {
+        self.in_use = in_use;
+        self
+    }
+
+    pub fn with_deleted(mut self, deleted: bool) -> Self {
+        self.deleted = deleted;
+        self
+    }
+
+    /// Only include directories.
+    pub fn with_directories(mut self, directories: bool) -> Self {
+        self.directories = directories;
+        self
+    }
+
+    pub fn is_match(&self, in_use: bool, is_directory: bool) -> bool {
+        let allocation_match = match (self.in_use, self.deleted) {
+            (false, false) | (true, true) => true,
+            (true, false) => in_use,
+            (false, true) => !in_use
+        };
+
+        allocation_match && (!self.directories || is_directory)
+    }
+}
+
+
+/// The name and parent of an entry, as used to build paths.
+struct EntryName {
+    sequence: u16,
+    file_name: RawFileName
+}
+
+
+/// Resolves the paths of entries by following their $FILE_NAME parents.
+/// Directory paths are cached as many entries share them.
+///
+#[derive(Default)]
+struct PathResolver {
+    directories: HashMap<u64, (u16, String)>
+}
+
+impl PathResolver {
+    fn get_entry_name<S: MftSource>(source: &mut S, entry: u64) -> Option<EntryName> {
+        let record = match source.get_record(entry) {
+            Ok(Some(record)) => record,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Unable to read parent entry {}: {}", entry, e.display_chain());
+                return None;
+            }
+        };
+
+        let file_name = get_file_names(&record.data).ok()?
+            .into_iter()
+            .next()?;
+
+        Some(
+            EntryName {
+                sequence: record.header.sequence,
+                file_name
+            }
+        )
+    }
+
+    /// Get the path of a directory. The sequence must match the directory's
+    /// record, otherwise the entry was reused and the orphan directory is
+    /// used.
+    fn get_directory_path<S: MftSource>(
+        &mut self,
+        source: &mut S,
+        entry: u64,
+        sequence: u16,
+        depth: usize
+    ) -> String {
+        if entry == ROOT_ENTRY {
+            return String::new();
+        }
+
+        if let Some((cached_sequence, path)) = self.directories.get(&entry) {
+            if *cached_sequence == sequence {
+                return path.to_owned();
+            }
+        }
+
+        if depth > MAX_PATH_DEPTH {
+            return ORPHAN_DIRECTORY.to_owned();
+        }
+
+        let entry_name = match Self::get_entry_name(source, entry) {
+            Some(n) if n.sequence == sequence => n,
+            _ => return ORPHAN_DIRECTORY.to_owned()
+        };
+
+        let parent_path = self.get_directory_path(
+            source,
+            entry_name.file_name.parent_entry,
+            entry_name.file_name.parent_sequence,
+            depth + 1
+        );
+        let path = format!("{}\\{}", parent_path, entry_name.file_name.name);
+
+        self.directories.insert(
+            entry,
+            (sequence, path.clone())
+        );
+
+        path
+    }
+
+    /// Get the path of an entry from the $FILE_NAME attributes of its
+    /// record, or None if it has none.
+    fn get_entry_path<S: MftSource>(
+        &mut self,
+        source: &mut S,
+        entry: u64,
+        record: &MftEntry
+    ) -> Option<String> {
+        if entry == ROOT_ENTRY {
+            return Some("\\".to_owned());
+        }
+
+        let file_name = get_file_names(&record.data).ok()?
+            .into_iter()
+            .next()?;
+
+        let directory = self.get_directory_path(
+            source,
+            file_name.parent_entry,
+            file_name.parent_sequence,
+            0
+        );
+
+        Some(format!("{}\\{}", directory, file_name.name))
+    }
+}
+
+
+/// Resolve the path of an entry relative to the volume root (e.g.
+/// `\Users\alice\notes.txt`) the way the `MftEnumerator` does. None if
+/// the record has no FILE signature or no $FILE_NAME attribute.
+///
+pub fn get_entry_path<S: MftSource>(source: &mut S, entry: u64) -> Result<Option<String>, WinThingError> {
+    let record = match source.get_record(entry)? {
+        Some(record) => record,
+        None => return Ok(None)
+    };
+
+    Ok(
+        PathResolver::default().get_entry_path(source, entry, &record)
+    )
+}
+
+
+/// An entry found by the `MftEnumerator` with its path relative to the
+/// volume root (e.g. `\Users\alice\notes.txt`).
+///
+pub struct EnumeratedEntry {
+    pub entry: u64,
+    pub sequence: u16,
+    pub in_use: bool,
+    pub is_directory: bool,
+    pub path: Option<String>,
+    pub logical_entry: LogicalEntry
+}
+
+impl EnumeratedEntry {
+    /// Generate the custom JSON view of the entry with its `path`, `in_use`
+    /// and `is_directory`.
+    pub fn into_json_value(self) -> Result<Value, WinThingError> {
+        let mut value = self.logical_entry.into_json_value()?;
+        value["path"] = json!(self.path);
+        value["in_use"] = json!(self.in_use);
+        value["is_directory"] = json!(self.is_directory);
+        Ok(value)
+    }
+}
+
+
+/// Iterates every base record of an MFT that matches a filter. Extension
+/// records are resolved into their base entries and are not returned on
+/// their own. Records without a FILE signature are skipped.
+///
+pub struct MftEnumerator<'a, S: MftSource> {
+    source: &'a mut S,
+    filter: EntryFilter,
+    resolver: PathResolver,
+    next_entry: u64,
+    entry_count: u64
+}
+
+impl<'a, S: MftSource> MftEnumerator<'a, S> {
+    pub fn new(source: &'a mut S) -> Self {
+        let entry_count = source.get_entry_count();
+
+        Self {
+            source,
+            filter: EntryFilter::default(),
+            resolver: PathResolver::default(),
+            next_entry: 0,
+            entry_count
+        }
+    }
+
+    pub fn with_filter(mut self, filter: EntryFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Get the entry that was read last, e.g. to report an error returned
+    /// by `next`.
+    pub fn get_last_entry(&self) -> u64 {
+        self.next_entry.saturating_sub(1)
+    }
+
+    fn read_entry(&mut self, entry: u64) -> Result<Option<EnumeratedEntry>, WinThingError> {
+        let record = match self.source.get_record(entry)? {
+            Some(record) => record,
+            None => return Ok(None)
+        };
+
+        // Extension records point at their base record. Those of the $MFT
+        // point at entry 0, but with a non-zero sequence
+        let base_reference = &record.header.base_reference;
+        if base_reference.entry != 0 || base_reference.sequence != 0 {
+            return Ok(None);
+        }
+
+        let flags = get_entry_flags(&record.data);
+        let in_use = flags & ENTRY_IN_USE != 0;
+        let is_directory = flags & ENTRY_DIRECTORY != 0;
+        if !self.filter.is_match(in_use, is_directory) {
+            return Ok(None);
+        }
+
+        let path = self.resolver.get_entry_path(
+            self.source,
+            entry,
+            &record
+        );
+        let sequence = record.header.sequence;
+
+        // The extension records of a deleted entry may have been reused,
+        // which LogicalEntry checks for, or wiped. Without all of them the
+        // entry is resolved from its base record alone.
+        let source = &mut *self.source;
+        let extension_entries = match get_extension_entries_with_reader(
+            &record.data,
+            entry,
+            |attribute| source.read_attribute_value(attribute)
+        ) {
+            Ok(extension_entries) => extension_entries,
+            Err(e) => {
+                warn!(
+                    "Unable to read the attribute list of entry {}, using the base record alone: {}",
+                    entry,
+                    e.display_chain()
+                );
+                Vec::new()
+            }
+        };
+
+        let mut extensions: HashMap<u64, MftEntry> = HashMap::new();
+        for extension_entry in extension_entries.iter() {
+            match self.source.get_record(*extension_entry) {
+                Ok(Some(extension)) => {
+                    extensions.insert(*extension_entry, extension);
+                },
+                Ok(None) => {
+                    warn!(
+                        "Extension entry {} of entry {} is not a FILE record, using the base record alone.",
+                        extension_entry,
+                        entry
+                    );
+                    break;
+                },
+                Err(e) => {
+                    warn!(
+                        "Unable to read extension entry {} of entry {}, using the base record alone: {}",
+                        extension_entry,
+                        entry,
+                        e.display_chain()
+                    );
+                    break;
+                }
+            }
+        }
+
+        let logical_entry = match extensions.len() == extension_entries.len() {
+            true => LogicalEntry::from_extension_entries(
+                record,
+                extension_entries,
+                |extension_entry| extensions.remove(&extension_entry).ok_or_else(||
+                    WinThingError::invalid_mft_record(
+                        format!("Extension entry {} was not read.", extension_entry)
+                    )
+                )
+            )?,
+            false => LogicalEntry::from_base(record)
+        };
+
+        Ok(
+            Some(
+                EnumeratedEntry {
+                    entry,
+                    sequence,
+                    in_use,
+                    is_directory,
+                    path,
+                    logical_entry
+                }
+            )
+        )
+    }
+}
+
+impl<'a, S: MftSource> Iterator for MftEnumerator<'a, S> {
+    type Item = Result<EnumeratedEntry, WinThingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.next_entry < self.entry_count {
+            let entry = self.next_entry;
+            self.next_entry += 1;
+
+            match self.read_entry(entry) {
+                Ok(Some(e)) => return Some(Ok(e)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e))
+            }
+        }
+
+        None
+    }
+}
+
+
+/// Format an entry value from `EnumeratedEntry::into_json_value` as a CSV
+/// line (see `ENTRY_CSV_HEADER`). The $FILE_NAME times are those of the
+/// entry's long name.
+///
+pub fn get_entry_csv_line(entry_value: &Value) -> String {
+    let get_string = |value: &Value| -> String {
+        match value {
+            Value::Null => String::new(),
+            Value::String(s) => s.to_owned(),
+            v => v.to_string()
+        }
+    };
+
+    let attributes = &entry_value["attributes"];
+    let standard_info = attributes["StandardInformation"].as_object()
+        .and_then(|a| a.values().next())
+        .map(|a| &a["data"]);
+
+    let name = get_entry_file_name(entry_value);
+    let file_name = attributes["FileName"].as_object()
+        .and_then(|a| a.values().find(|a| a["data"]["name"].as_str() == name.as_deref()))
+        .map(|a| &a["data"]);
+
+    let mut fields = vec![
+        get_string(&entry_value["header"]["record_number"]),
+        get_string(&entry_value["header"]["sequence"]),
+        get_string(&entry_value["in_use"]),
+        get_string(&entry_value["is_directory"]),
+        get_string(&entry_value["path"]),
+        get_string(&entry_value["streams"]["$DATA"]["size"])
+    ];
+
+    for data in &[standard_info, file_name] {
+        for time in &["created", "modified", "mft_modified", "accessed"] {
+            fields.push(
+                data.map(|d| get_string(&d[*time])).unwrap_or_default()
+            );
+        }
+    }
+
+    get_csv_line(&fields)
+}
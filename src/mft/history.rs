@@ -0,0 +1,255 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::utils::json::JsonPatch;
+
+
+/// The change stored in a HistoryRecord. A snapshot holds the full entry
+/// value and a diff holds the patch from the previous state.
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum HistoryChange {
+    Snapshot { value: Value },
+    Diff { patch: JsonPatch }
+}
+
+
+/// A single line in the history journal, keyed by the file reference
+/// (entry and sequence) and the USN that triggered it.
+///
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryRecord {
+    pub entry: u64,
+    pub sequence: u16,
+    pub usn: u64,
+    pub recorded: DateTime<Utc>,
+    #[serde(flatten)]
+    pub change: HistoryChange
+}
+
+
+/// The point in time to reconstruct an entry at.
+///
+#[derive(Debug, Clone, Copy)]
+pub enum HistoryPoint {
+    Time(DateTime<Utc>),
+    Usn(u64)
+}
+
+impl HistoryPoint {
+    fn includes(&self, record: &HistoryRecord) -> bool {
+        match self {
+            HistoryPoint::Time(t) => record.recorded <= *t,
+            HistoryPoint::Usn(u) => record.usn <= *u
+        }
+    }
+}
+
+
+/// Append-only JSONL journal of entry snapshots and diffs.
+///
+pub struct HistoryJournal<W: Write> {
+    writer: W
+}
+
+impl HistoryJournal<File> {
+    /// Open a journal file for appending, creating it if needed.
+    pub fn open(path: &str) -> Result<Self, WinThingError> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+
+        Ok(
+            Self::new(file)
+        )
+    }
+}
+
+impl<W: Write> HistoryJournal<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer
+        }
+    }
+
+    pub fn append(&mut self, record: &HistoryRecord) -> Result<(), WinThingError> {
+        let line = serde_json::to_string(record)?;
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn append_snapshot(
+        &mut self,
+        entry: u64,
+        sequence: u16,
+        usn: u64,
+        value: &Value
+    ) -> Result<(), WinThingError> {
+        self.append(&HistoryRecord {
+            entry,
+            sequence,
+            usn,
+            recorded: Utc::now(),
+            change: HistoryChange::Snapshot {
+                value: value.to_owned()
+            }
+        })
+    }
+
+    pub fn append_diff(
+        &mut self,
+        entry: u64,
+        sequence: u16,
+        usn: u64,
+        patch: &JsonPatch
+    ) -> Result<(), WinThingError> {
+        self.append(&HistoryRecord {
+            entry,
+            sequence,
+            usn,
+            recorded: Utc::now(),
+            change: HistoryChange::Diff {
+                patch: patch.to_owned()
+            }
+        })
+    }
+}
+
+
+/// The records of a history journal, used to look back at the state of
+/// an entry at any recorded point.
+///
+pub struct HistoryTimeline {
+    records: Vec<HistoryRecord>
+}
+
+impl HistoryTimeline {
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let file = File::open(path)?;
+        Self::from_reader(
+            BufReader::new(file)
+        )
+    }
+
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, WinThingError> {
+        let mut records: Vec<HistoryRecord> = Vec::new();
+
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            records.push(
+                serde_json::from_str(&line)?
+            );
+        }
+
+        Ok(
+            Self {
+                records
+            }
+        )
+    }
+
+    /// Get the records of every entry in the order they were recorded.
+    pub fn get_all_records(&self) -> &[HistoryRecord] {
+        &self.records
+    }
+
+    /// Get the records for an entry in the order they were recorded. This
+    /// includes the records of every sequence the entry was used with.
+    pub fn get_records(&self, entry: u64) -> Vec<&HistoryRecord> {
+        self.records.iter()
+            .filter(|r| r.entry == entry)
+            .collect()
+    }
+
+    /// Get the file references (entry and sequence) in the timeline in the
+    /// order they were first recorded.
+    pub fn get_references(&self) -> Vec<(u64, u16)> {
+        let mut references: Vec<(u64, u16)> = Vec::new();
+        for record in self.records.iter() {
+            let reference = (record.entry, record.sequence);
+            if !references.contains(&reference) {
+                references.push(reference);
+            }
+        }
+
+        references
+    }
+
+    /// Get the records for a file reference in the order they were
+    /// recorded. Records of a reused entry (another sequence) are not
+    /// included.
+    pub fn get_reference_records(&self, entry: u64, sequence: u16) -> Vec<&HistoryRecord> {
+        self.records.iter()
+            .filter(|r| r.entry == entry && r.sequence == sequence)
+            .collect()
+    }
+
+    /// Reconstruct the value of an entry at a given point, using the last
+    /// sequence the entry was recorded with at or before that point. Returns
+    /// None if no snapshot of it was recorded at or before that point.
+    pub fn get_state(
+        &self,
+        entry: u64,
+        point: HistoryPoint
+    ) -> Result<Option<Value>, WinThingError> {
+        let sequence = self.get_records(entry)
+            .into_iter()
+            .take_while(|r| point.includes(r))
+            .last()
+            .map(|r| r.sequence);
+
+        match sequence {
+            Some(sequence) => self.get_reference_state(entry, sequence, point),
+            None => Ok(None)
+        }
+    }
+
+    /// Reconstruct the value of a file reference at a given point. Returns
+    /// None if no snapshot of it was recorded at or before that point.
+    pub fn get_reference_state(
+        &self,
+        entry: u64,
+        sequence: u16,
+        point: HistoryPoint
+    ) -> Result<Option<Value>, WinThingError> {
+        let mut state: Option<Value> = None;
+
+        for record in self.get_reference_records(entry, sequence) {
+            if !point.includes(record) {
+                break;
+            }
+
+            match &record.change {
+                HistoryChange::Snapshot { value } => {
+                    state = Some(value.to_owned());
+                },
+                HistoryChange::Diff { patch } => {
+                    match state.as_mut() {
+                        Some(value) => patch.apply(value)?,
+                        None => {
+                            warn!(
+                                "Skipping diff for entry {}-{} at usn {} with no prior snapshot.",
+                                record.entry,
+                                record.sequence,
+                                record.usn
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(state)
+    }
+}
@@ -0,0 +1,320 @@
+pub mod enumerate;
+pub mod history;
+pub mod offline;
+pub mod raw;
+pub mod stream;
+use serde_json::Value;
+use serde_json::to_value;
+use mft::entry::MftEntry;
+use mft::attribute::{MftAttribute, MftAttributeType};
+use mft::err::Error as MftError;
+use crate::errors::WinThingError;
+use crate::mft::raw::{
+    get_raw_attributes,
+    get_extension_entries,
+    DATA_TYPE
+};
+#[cfg(windows)]
+use crate::volume::liventfs::WindowsLiveNtfs;
+#[cfg(windows)]
+use crate::file::helper::{
+    get_entry_from_path,
+    get_volume_path_from_path
+};
+
+
+fn get_attr_name(attribute: &MftAttributeType) -> String {
+    match attribute {
+        MftAttributeType::StandardInformation => "StandardInformation".to_string(),
+        MftAttributeType::AttributeList => "AttributeList".to_string(),
+        MftAttributeType::FileName => "FileName".to_string(),
+        MftAttributeType::ObjectId => "ObjectId".to_string(),
+        MftAttributeType::SecurityDescriptor => "SecurityDescriptor".to_string(),
+        MftAttributeType::VolumeName => "VolumeName".to_string(),
+        MftAttributeType::VolumeInformation => "VolumeInformation".to_string(),
+        MftAttributeType::DATA => "DATA".to_string(),
+        MftAttributeType::IndexRoot => "IndexRoot".to_string(),
+        MftAttributeType::IndexAllocation => "IndexAllocation".to_string(),
+        MftAttributeType::BITMAP => "BITMAP".to_string(),
+        MftAttributeType::ReparsePoint => "ReparsePoint".to_string()
+    }
+}
+
+
+/// Add the attributes of a record to the entry view. Base record attributes
+/// are keyed by instance and extension record attributes by
+/// "<record number>:<instance>" as instances are only unique per record.
+fn add_record_attributes(
+    entry_value: &mut Value,
+    entry: &MftEntry,
+    is_extension: bool
+) -> Result<(), WinThingError> {
+    let get_key = |instance: u16| -> String {
+        match is_extension {
+            true => format!("{}:{}", entry.header.record_number, instance),
+            false => instance.to_string()
+        }
+    };
+
+    let attributes: Vec<MftAttribute> = entry.iter_attributes().filter_map(Result::ok).collect();
+    for attribute in attributes {
+        let attr_type_str = get_attr_name(&attribute.header.type_code);
+        let key = get_key(attribute.header.instance);
+
+        entry_value["attributes"][&attr_type_str][&key] = to_value(&attribute)?;
+    }
+
+    // Data streams are collected by name so alternate data streams are easy to find
+    let raw_attributes = match get_raw_attributes(&entry.data) {
+        Ok(a) => a,
+        Err(e) => {
+            error!("Error getting raw attributes for entry {}: {:?}", entry.header.record_number, e);
+            return Ok(());
+        }
+    };
+
+    for attribute in raw_attributes {
+        if attribute.type_code != DATA_TYPE {
+            continue;
+        }
+
+        let stream_name = match attribute.name.as_str() {
+            "" => "$DATA".to_string(),
+            name => name.to_string()
+        };
+
+        let stream_value = &mut entry_value["streams"][&stream_name];
+        stream_value["resident"] = json!(attribute.is_resident());
+
+        // Only the first piece of a non-resident stream holds the size
+        let is_first_piece = match &attribute.non_resident_header {
+            Some(header) => header.lowest_vcn == 0,
+            None => true
+        };
+        if is_first_piece {
+            stream_value["size"] = json!(attribute.get_data_size());
+        }
+
+        let key = json!(get_key(attribute.instance));
+        match stream_value["instances"].as_array_mut() {
+            Some(instances) => instances.push(key),
+            None => {
+                stream_value["instances"] = json!([key]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Generate a custom JSON view of the mft entry
+pub fn custom_entry_value(entry: MftEntry) -> Result<Value, WinThingError> {
+    custom_entry_value_with_extensions(
+        entry,
+        Vec::new()
+    )
+}
+
+
+/// Generate a custom JSON view of the mft entry that includes the
+/// attributes held in its extension records.
+pub fn custom_entry_value_with_extensions(
+    entry: MftEntry,
+    extensions: Vec<MftEntry>
+) -> Result<Value, WinThingError> {
+    let mut entry_value = json!({});
+    
+    entry_value["header"] = to_value(&entry.header)?;
+    entry_value["attributes"] = json!({});
+    entry_value["streams"] = json!({});
+
+    add_record_attributes(
+        &mut entry_value,
+        &entry,
+        false
+    )?;
+
+    if !extensions.is_empty() {
+        let mut extension_records: Vec<u64> = Vec::new();
+        for extension in extensions {
+            add_record_attributes(
+                &mut entry_value,
+                &extension,
+                true
+            )?;
+            extension_records.push(extension.header.record_number);
+        }
+        entry_value["extensions"] = json!(extension_records);
+    }
+    
+    Ok(entry_value)
+}
+
+
+/// Get the name of an entry from the FileName attributes of its custom
+/// JSON view, preferring a long name over a DOS 8.3 name.
+pub fn get_entry_file_name(entry_value: &Value) -> Option<String> {
+    let file_names = entry_value["attributes"]["FileName"].as_object()?;
+
+    let mut names: Vec<(&str, &str)> = file_names.values()
+        .filter_map(|attribute| {
+            let data = &attribute["data"];
+            Some((data["name"].as_str()?, data["namespace"].as_str().unwrap_or("")))
+        })
+        .collect();
+    names.sort_by_key(|(_, namespace)| *namespace == "DOS");
+
+    names.first().map(|(name, _)| (*name).to_owned())
+}
+
+
+/// An entry as a whole: the base record and the extension records that hold
+/// the rest of its attributes when it has an $ATTRIBUTE_LIST.
+///
+pub struct LogicalEntry {
+    pub base: MftEntry,
+    pub extensions: Vec<MftEntry>
+}
+
+impl LogicalEntry {
+    pub fn from_base(base: MftEntry) -> Self {
+        Self {
+            base,
+            extensions: Vec::new()
+        }
+    }
+
+    /// Resolve the extension records of a base record. `get_record` is
+    /// used to look up an entry by number (from a live volume or an image).
+    /// Records that no longer point back at the base record are skipped.
+    /// Only a resident $ATTRIBUTE_LIST is read, see `from_extension_entries`.
+    pub fn resolve<F>(base: MftEntry, get_record: F) -> Result<Self, WinThingError>
+        where F: FnMut(u64) -> Result<MftEntry, WinThingError>
+    {
+        let extension_entries = get_extension_entries(
+            &base.data,
+            base.header.record_number
+        )?;
+
+        Self::from_extension_entries(
+            base,
+            extension_entries,
+            get_record
+        )
+    }
+
+    /// Resolve the given extension records of a base record, as returned by
+    /// `get_extension_entries_with_reader` for a non-resident
+    /// $ATTRIBUTE_LIST.
+    pub fn from_extension_entries<F>(
+        base: MftEntry,
+        extension_entries: Vec<u64>,
+        mut get_record: F
+    ) -> Result<Self, WinThingError>
+        where F: FnMut(u64) -> Result<MftEntry, WinThingError>
+    {
+        let base_entry = base.header.record_number;
+
+        let mut extensions: Vec<MftEntry> = Vec::new();
+        for extension_entry in extension_entries {
+            let extension = get_record(extension_entry)?;
+            if extension.header.base_reference.entry != base_entry {
+                warn!(
+                    "Entry {} is listed as an extension of {} but its base reference is {}.",
+                    extension_entry,
+                    base_entry,
+                    extension.header.base_reference.entry
+                );
+                continue;
+            }
+
+            extensions.push(extension);
+        }
+
+        Ok(
+            Self {
+                base,
+                extensions
+            }
+        )
+    }
+
+    /// Iterate the attributes of the base record followed by those of the
+    /// extension records.
+    pub fn iter_attributes(&self) -> impl Iterator<Item = Result<MftAttribute, MftError>> + '_ {
+        std::iter::once(&self.base)
+            .chain(self.extensions.iter())
+            .flat_map(|e| e.iter_attributes())
+    }
+
+    /// Get the raw record buffers, base record first.
+    pub fn get_record_buffers(&self) -> Vec<Vec<u8>> {
+        std::iter::once(&self.base)
+            .chain(self.extensions.iter())
+            .map(|e| e.data.clone())
+            .collect()
+    }
+
+    /// Generate the custom JSON view of the whole entry.
+    pub fn into_json_value(self) -> Result<Value, WinThingError> {
+        custom_entry_value_with_extensions(
+            self.base,
+            self.extensions
+        )
+    }
+}
+
+
+#[cfg(windows)]
+pub struct EntryListener {
+    live_volume: WindowsLiveNtfs,
+    pub path_to_monitor: String,
+    pub entry_to_monitor: i64
+}
+#[cfg(windows)]
+impl EntryListener {
+    pub fn new(path_to_monitor: &str) -> Result<Self, WinThingError> {
+        let entry = get_entry_from_path(
+            path_to_monitor
+        )?;
+
+        let volume = get_volume_path_from_path(
+            path_to_monitor
+        )?;
+
+        let live_volume = WindowsLiveNtfs::from_volume_path(
+            &volume
+        )?;
+
+        Ok(
+            Self {
+                live_volume: live_volume,
+                path_to_monitor: path_to_monitor.to_string(),
+                entry_to_monitor: entry as i64
+            }
+        )
+    }
+
+    pub fn get_volume_string(&self) -> Result<String, WinThingError> {
+        get_volume_path_from_path(
+            &self.path_to_monitor
+        )
+    }
+
+    /// Get the next USN of the volume's journal.
+    pub fn get_next_usn(&mut self) -> Result<u64, WinThingError> {
+        Ok(
+            self.live_volume.query_usn_journal()?.get_next_usn()
+        )
+    }
+
+    pub fn get_current_value(&mut self) -> Result<Value, WinThingError> {
+        let logical_entry = self.live_volume.get_logical_entry(
+            self.entry_to_monitor,
+            true
+        )?;
+
+        logical_entry.into_json_value()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use mft::MftEntry;
+use byteorder::{ByteOrder, LittleEndian};
+use crate::errors::WinThingError;
+use crate::mft::enumerate::MftSource;
+use crate::mft::raw::{apply_fixups, is_file_record};
+
+/// The largest file record size accepted.
+const MAX_RECORD_SIZE: u32 = 0x10000;
+
+
+/// Struct for reading the records of a $MFT file copied off a volume. The
+/// record size is read from the header of entry 0.
+///
+pub struct OfflineMft<R: Read + Seek> {
+    reader: R,
+    record_size: u32,
+    bytes_per_sector: usize,
+    entry_count: u64
+}
+
+impl OfflineMft<File> {
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let file = File::open(path)?;
+        Self::from_reader(file)
+    }
+}
+
+impl<R: Read + Seek> OfflineMft<R> {
+    pub fn from_reader(mut reader: R) -> Result<Self, WinThingError> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut header = vec![0u8; 0x30];
+        reader.read_exact(&mut header)?;
+        if !is_file_record(&header) {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    "Entry 0 of the $MFT is not a FILE record.".to_owned()
+                )
+            );
+        }
+
+        let record_size = LittleEndian::read_u32(&header[0x1C..0x20]);
+        if !(0x100..=MAX_RECORD_SIZE).contains(&record_size) || !record_size.is_power_of_two() {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Invalid file record size {}.", record_size)
+                )
+            );
+        }
+
+        // The update sequence array has an entry for each sector
+        let usa_count = LittleEndian::read_u16(&header[0x06..0x08]) as u32;
+        let bytes_per_sector = match usa_count {
+            0 | 1 => 512,
+            count => record_size / (count - 1)
+        };
+
+        Ok(
+            Self {
+                reader,
+                record_size,
+                bytes_per_sector: bytes_per_sector as usize,
+                entry_count: length / record_size as u64
+            }
+        )
+    }
+
+    pub fn get_record_size(&self) -> u32 {
+        self.record_size
+    }
+}
+
+impl<R: Read + Seek> MftSource for OfflineMft<R> {
+    fn get_entry_count(&self) -> u64 {
+        self.entry_count
+    }
+
+    fn get_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError> {
+        let mut buffer = vec![0u8; self.record_size as usize];
+        self.reader.seek(SeekFrom::Start(entry * self.record_size as u64))?;
+        self.reader.read_exact(&mut buffer)?;
+
+        if !is_file_record(&buffer) {
+            return Ok(None);
+        }
+
+        apply_fixups(
+            &mut buffer,
+            self.bytes_per_sector
+        )?;
+
+        Ok(Some(MftEntry::from_buffer_skip_fixup(
+            buffer,
+            entry
+        )?))
+    }
+}
@@ -0,0 +1,633 @@
+use byteorder::{ByteOrder, LittleEndian};
+use crate::errors::WinThingError;
+
+pub const ATTRIBUTE_LIST_TYPE: u32 = 0x20;
+pub const FILE_NAME_TYPE: u32 = 0x30;
+pub const DATA_TYPE: u32 = 0x80;
+const END_OF_ATTRIBUTES: u32 = 0xFFFF_FFFF;
+
+/// File record header flags.
+pub const ENTRY_IN_USE: u16 = 0x0001;
+pub const ENTRY_DIRECTORY: u16 = 0x0002;
+
+/// The $FILE_NAME namespace of DOS 8.3 names.
+pub const DOS_NAMESPACE: u8 = 2;
+
+
+/// Mask off the sequence number of a file reference.
+///
+pub fn get_reference_entry(reference: u64) -> u64 {
+    reference & 0x0000_FFFF_FFFF_FFFF
+}
+
+
+/// Get the sequence number of a file reference.
+///
+pub fn get_reference_sequence(reference: u64) -> u16 {
+    (reference >> 48) as u16
+}
+
+
+fn read_utf16(buffer: &[u8], offset: usize, char_count: usize) -> Result<String, WinThingError> {
+    let end = offset + char_count * 2;
+    if end > buffer.len() {
+        return Err(
+            WinThingError::invalid_mft_record(
+                format!("Name at offset {} runs past the end of the buffer.", offset)
+            )
+        );
+    }
+
+    let name_u16: Vec<u16> = buffer[offset..end]
+        .chunks(2)
+        .map(LittleEndian::read_u16)
+        .collect();
+
+    Ok(String::from_utf16(&name_u16)?)
+}
+
+
+/// The non-resident part of an attribute header.
+///
+#[derive(Debug, Clone)]
+pub struct RawNonResidentHeader {
+    pub lowest_vcn: u64,
+    pub highest_vcn: u64,
+    pub data_runs_offset: u16,
+    pub compression_unit: u16,
+    pub allocated_size: u64,
+    pub data_size: u64,
+    pub initialized_size: u64
+}
+
+
+/// An attribute as it is laid out in a file record. The mft crate parses
+/// attribute content but does not give access to resident values or data
+/// runs, which is what this is used for.
+///
+#[derive(Debug, Clone)]
+pub struct RawAttribute {
+    pub type_code: u32,
+    pub record_offset: usize,
+    pub length: u32,
+    pub flags: u16,
+    pub instance: u16,
+    pub name: String,
+    pub resident_value: Option<Vec<u8>>,
+    pub non_resident_header: Option<RawNonResidentHeader>,
+    pub buffer: Vec<u8>
+}
+
+impl RawAttribute {
+    pub fn from_buffer(buffer: &[u8], record_offset: usize) -> Result<Self, WinThingError> {
+        if buffer.len() < 0x18 {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Attribute at offset {} is too small.", record_offset)
+                )
+            );
+        }
+
+        let type_code = LittleEndian::read_u32(&buffer[0x00..0x04]);
+        let length = LittleEndian::read_u32(&buffer[0x04..0x08]);
+        let non_resident = buffer[0x08] != 0;
+        let name_length = buffer[0x09] as usize;
+        let name_offset = LittleEndian::read_u16(&buffer[0x0A..0x0C]) as usize;
+        let flags = LittleEndian::read_u16(&buffer[0x0C..0x0E]);
+        let instance = LittleEndian::read_u16(&buffer[0x0E..0x10]);
+
+        let name = match name_length {
+            0 => String::new(),
+            _ => read_utf16(buffer, name_offset, name_length)?
+        };
+
+        let (resident_value, non_resident_header) = if non_resident {
+            if buffer.len() < 0x40 {
+                return Err(
+                    WinThingError::invalid_mft_record(
+                        format!("Non-resident attribute at offset {} is too small.", record_offset)
+                    )
+                );
+            }
+
+            (None, Some(RawNonResidentHeader {
+                lowest_vcn: LittleEndian::read_u64(&buffer[0x10..0x18]),
+                highest_vcn: LittleEndian::read_u64(&buffer[0x18..0x20]),
+                data_runs_offset: LittleEndian::read_u16(&buffer[0x20..0x22]),
+                compression_unit: LittleEndian::read_u16(&buffer[0x22..0x24]),
+                allocated_size: LittleEndian::read_u64(&buffer[0x28..0x30]),
+                data_size: LittleEndian::read_u64(&buffer[0x30..0x38]),
+                initialized_size: LittleEndian::read_u64(&buffer[0x38..0x40])
+            }))
+        } else {
+            let value_length = LittleEndian::read_u32(&buffer[0x10..0x14]) as usize;
+            let value_offset = LittleEndian::read_u16(&buffer[0x14..0x16]) as usize;
+            if value_offset + value_length > buffer.len() {
+                return Err(
+                    WinThingError::invalid_mft_record(
+                        format!("Resident value of attribute at offset {} is out of bounds.", record_offset)
+                    )
+                );
+            }
+
+            (Some(buffer[value_offset..value_offset + value_length].to_vec()), None)
+        };
+
+        Ok(
+            Self {
+                type_code,
+                record_offset,
+                length,
+                flags,
+                instance,
+                name,
+                resident_value,
+                non_resident_header,
+                buffer: buffer.to_vec()
+            }
+        )
+    }
+
+    pub fn is_resident(&self) -> bool {
+        self.non_resident_header.is_none()
+    }
+
+    /// Decode the data runs of a non-resident attribute. Resident
+    /// attributes have none.
+    pub fn get_data_runs(&self) -> Result<Vec<DataRun>, WinThingError> {
+        match &self.non_resident_header {
+            Some(header) => {
+                let offset = header.data_runs_offset as usize;
+                if offset > self.buffer.len() {
+                    return Err(
+                        WinThingError::invalid_mft_record(
+                            format!("Data runs offset {} is out of bounds.", offset)
+                        )
+                    );
+                }
+
+                decode_data_runs(&self.buffer[offset..])
+            },
+            None => Ok(Vec::new())
+        }
+    }
+
+    /// The logical size of the attribute's value.
+    pub fn get_data_size(&self) -> u64 {
+        match (&self.resident_value, &self.non_resident_header) {
+            (Some(value), _) => value.len() as u64,
+            (None, Some(header)) => header.data_size,
+            (None, None) => 0
+        }
+    }
+}
+
+
+/// A run of clusters from a non-resident attribute. A sparse run has no
+/// logical cluster number and reads as zeros.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataRun {
+    pub lcn: Option<u64>,
+    pub length: u64
+}
+
+
+/// Decode a mapping pairs array. Each run starts with a header byte whose
+/// low nibble is the size of the length field and high nibble the size of
+/// the (signed, previous run relative) offset field.
+///
+pub fn decode_data_runs(buffer: &[u8]) -> Result<Vec<DataRun>, WinThingError> {
+    let mut runs: Vec<DataRun> = Vec::new();
+    let mut offset: usize = 0;
+    let mut current_lcn: i64 = 0;
+
+    while offset < buffer.len() && buffer[offset] != 0 {
+        let length_size = (buffer[offset] & 0x0F) as usize;
+        let offset_size = (buffer[offset] >> 4) as usize;
+        offset += 1;
+
+        if length_size == 0 || length_size > 8 || offset_size > 8 ||
+            offset + length_size + offset_size > buffer.len() {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Invalid data run header at offset {}.", offset - 1)
+                )
+            );
+        }
+
+        let length = LittleEndian::read_uint(
+            &buffer[offset..offset + length_size],
+            length_size
+        );
+        offset += length_size;
+
+        let lcn = match offset_size {
+            0 => None,
+            _ => {
+                let relative_lcn = LittleEndian::read_int(
+                    &buffer[offset..offset + offset_size],
+                    offset_size
+                );
+                current_lcn = current_lcn.checked_add(relative_lcn)
+                    .ok_or_else(|| WinThingError::invalid_mft_record(
+                        format!("Data run lcn overflows at offset {}.", offset)
+                    ))?;
+                offset += offset_size;
+
+                if current_lcn < 0 {
+                    return Err(
+                        WinThingError::invalid_mft_record(
+                            format!("Data run has a negative lcn {}.", current_lcn)
+                        )
+                    );
+                }
+
+                Some(current_lcn as u64)
+            }
+        };
+
+        runs.push(DataRun {
+            lcn,
+            length
+        });
+    }
+
+    Ok(runs)
+}
+
+
+/// Apply the update sequence array of a multi sector record (FILE or INDX)
+/// read from disk. The last two bytes of every sector are checked against
+/// the update sequence number and replaced with the saved values.
+///
+pub fn apply_fixups(record: &mut [u8], bytes_per_sector: usize) -> Result<(), WinThingError> {
+    if record.len() < 8 || bytes_per_sector < 2 {
+        return Err(
+            WinThingError::invalid_mft_record(
+                "Record is too small to hold an update sequence array.".to_owned()
+            )
+        );
+    }
+
+    let usa_offset = LittleEndian::read_u16(&record[0x04..0x06]) as usize;
+    let usa_count = LittleEndian::read_u16(&record[0x06..0x08]) as usize;
+    if usa_count == 0 {
+        return Ok(());
+    }
+
+    if usa_offset + usa_count * 2 > record.len() ||
+        (usa_count - 1) * bytes_per_sector > record.len() {
+        return Err(
+            WinThingError::invalid_mft_record(
+                format!("Update sequence array (offset {}, count {}) is out of bounds.", usa_offset, usa_count)
+            )
+        );
+    }
+
+    let usn = [record[usa_offset], record[usa_offset + 1]];
+    for i in 1..usa_count {
+        let sector_end = i * bytes_per_sector;
+        if record[sector_end - 2..sector_end] != usn {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Fixup mismatch in sector {}.", i - 1)
+                )
+            );
+        }
+
+        let saved = usa_offset + i * 2;
+        record[sector_end - 2] = record[saved];
+        record[sector_end - 1] = record[saved + 1];
+    }
+
+    Ok(())
+}
+
+
+/// Does a buffer start with the FILE signature. Records that were never
+/// used are zeroed and damaged records are marked BAAD.
+///
+pub fn is_file_record(record: &[u8]) -> bool {
+    record.len() >= 0x30 && &record[0..4] == b"FILE"
+}
+
+
+/// Get the flags of a file record header (see `ENTRY_IN_USE` and
+/// `ENTRY_DIRECTORY`).
+///
+pub fn get_entry_flags(record: &[u8]) -> u16 {
+    match record.len() >= 0x18 {
+        true => LittleEndian::read_u16(&record[0x16..0x18]),
+        false => 0
+    }
+}
+
+
+/// Get the attributes of a file record buffer (with fixups applied).
+///
+pub fn get_raw_attributes(record: &[u8]) -> Result<Vec<RawAttribute>, WinThingError> {
+    if record.len() < 0x30 || &record[0..4] != b"FILE" {
+        return Err(
+            WinThingError::invalid_mft_record(
+                "Buffer is not a FILE record.".to_owned()
+            )
+        );
+    }
+
+    let mut attributes: Vec<RawAttribute> = Vec::new();
+    let used_size = (LittleEndian::read_u32(&record[0x18..0x1C]) as usize).min(record.len());
+    let mut offset = LittleEndian::read_u16(&record[0x14..0x16]) as usize;
+
+    while offset + 8 <= used_size {
+        let type_code = LittleEndian::read_u32(&record[offset..offset + 4]);
+        if type_code == END_OF_ATTRIBUTES {
+            break;
+        }
+
+        let length = LittleEndian::read_u32(&record[offset + 4..offset + 8]) as usize;
+        if length == 0 || offset + length > used_size {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Invalid attribute length {} at offset {}.", length, offset)
+                )
+            );
+        }
+
+        attributes.push(
+            RawAttribute::from_buffer(
+                &record[offset..offset + length],
+                offset
+            )?
+        );
+
+        offset += length;
+    }
+
+    Ok(attributes)
+}
+
+
+/// An entry of the $ATTRIBUTE_LIST attribute. It points to the record
+/// (segment) that holds an attribute of the file.
+///
+#[derive(Debug, Clone)]
+pub struct AttributeListEntry {
+    pub type_code: u32,
+    pub lowest_vcn: u64,
+    pub segment_entry: u64,
+    pub segment_sequence: u16,
+    pub instance: u16,
+    pub name: String
+}
+
+
+/// Parse the value of an $ATTRIBUTE_LIST attribute.
+///
+pub fn parse_attribute_list(buffer: &[u8]) -> Result<Vec<AttributeListEntry>, WinThingError> {
+    let mut entries: Vec<AttributeListEntry> = Vec::new();
+    let mut offset: usize = 0;
+
+    while offset + 0x1A <= buffer.len() {
+        let entry_buffer = &buffer[offset..];
+        let record_length = LittleEndian::read_u16(&entry_buffer[0x04..0x06]) as usize;
+        if record_length < 0x1A || record_length > entry_buffer.len() {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("Invalid attribute list entry length {} at offset {}.", record_length, offset)
+                )
+            );
+        }
+
+        let name_length = entry_buffer[0x06] as usize;
+        let name_offset = entry_buffer[0x07] as usize;
+        let segment_reference = LittleEndian::read_u64(&entry_buffer[0x10..0x18]);
+
+        let name = match name_length {
+            0 => String::new(),
+            _ => read_utf16(&entry_buffer[..record_length], name_offset, name_length)?
+        };
+
+        entries.push(AttributeListEntry {
+            type_code: LittleEndian::read_u32(&entry_buffer[0x00..0x04]),
+            lowest_vcn: LittleEndian::read_u64(&entry_buffer[0x08..0x10]),
+            segment_entry: get_reference_entry(segment_reference),
+            segment_sequence: get_reference_sequence(segment_reference),
+            instance: LittleEndian::read_u16(&entry_buffer[0x18..0x1A]),
+            name
+        });
+
+        offset += record_length;
+    }
+
+    Ok(entries)
+}
+
+
+/// Get the entry numbers of the extension records listed in a base record's
+/// resident $ATTRIBUTE_LIST. The base entry itself is not included. A
+/// non-resident $ATTRIBUTE_LIST can only be read from the volume, see
+/// `get_extension_entries_with_reader`.
+///
+pub fn get_extension_entries(
+    record: &[u8],
+    base_entry: u64
+) -> Result<Vec<u64>, WinThingError> {
+    read_extension_entries(
+        record,
+        base_entry,
+        None
+    )
+}
+
+
+/// Get the entry numbers of the extension records listed in a base record's
+/// $ATTRIBUTE_LIST. The value of a non-resident list (as used when there
+/// are many extension records) is read with `read_value`, e.g. with
+/// `ClusterReader::read_stream`.
+///
+pub fn get_extension_entries_with_reader<F>(
+    record: &[u8],
+    base_entry: u64,
+    mut read_value: F
+) -> Result<Vec<u64>, WinThingError>
+    where F: FnMut(&RawAttribute) -> Result<Vec<u8>, WinThingError>
+{
+    read_extension_entries(
+        record,
+        base_entry,
+        Some(&mut read_value)
+    )
+}
+
+
+/// Reads the value of a non-resident attribute.
+type ValueReader<'a> = &'a mut dyn FnMut(&RawAttribute) -> Result<Vec<u8>, WinThingError>;
+
+
+fn read_extension_entries(
+    record: &[u8],
+    base_entry: u64,
+    mut read_value: Option<ValueReader>
+) -> Result<Vec<u64>, WinThingError> {
+    let mut extension_entries: Vec<u64> = Vec::new();
+
+    for attribute in get_raw_attributes(record)? {
+        if attribute.type_code != ATTRIBUTE_LIST_TYPE {
+            continue;
+        }
+
+        let value = match (&attribute.resident_value, read_value.as_mut()) {
+            (Some(v), _) => v.to_owned(),
+            (None, Some(read_value)) => read_value(&attribute)?,
+            (None, None) => {
+                warn!("Non-resident $ATTRIBUTE_LIST in entry {} can not be read without the volume.", base_entry);
+                continue;
+            }
+        };
+
+        for list_entry in parse_attribute_list(&value)? {
+            if list_entry.segment_entry != base_entry &&
+                !extension_entries.contains(&list_entry.segment_entry) {
+                extension_entries.push(list_entry.segment_entry);
+            }
+        }
+    }
+
+    Ok(extension_entries)
+}
+
+
+/// Get the data runs of the $MFT's unnamed $DATA attribute and its
+/// initialized size from the $MFT's record (entry 0). A fragmented $MFT
+/// keeps the rest of its runs in the extension records listed in its
+/// $ATTRIBUTE_LIST (see `get_extension_entries_with_reader`). These are read
+/// with `read_record`, given the entry and the runs known so far, and are
+/// expected to have their fixups applied.
+///
+pub fn get_mft_data_runs<F>(
+    mft_record: &[u8],
+    extension_entries: &[u64],
+    mut read_record: F
+) -> Result<(Vec<DataRun>, u64), WinThingError>
+    where F: FnMut(u64, &[DataRun]) -> Result<Vec<u8>, WinThingError>
+{
+    // The pieces of the attribute by their lowest VCN
+    let mut pieces: Vec<(u64, Vec<DataRun>)> = Vec::new();
+    let mut initialized_size: u64 = 0;
+
+    let mut add_pieces = |record: &[u8], pieces: &mut Vec<(u64, Vec<DataRun>)>| -> Result<(), WinThingError> {
+        for attribute in get_raw_attributes(record)? {
+            if attribute.type_code != DATA_TYPE || !attribute.name.is_empty() {
+                continue;
+            }
+
+            if let Some(header) = &attribute.non_resident_header {
+                if header.lowest_vcn == 0 {
+                    initialized_size = header.initialized_size;
+                }
+
+                pieces.push((header.lowest_vcn, attribute.get_data_runs()?));
+            }
+        }
+
+        pieces.sort_by_key(|(lowest_vcn, _)| *lowest_vcn);
+        Ok(())
+    };
+
+    add_pieces(mft_record, &mut pieces)?;
+
+    for extension_entry in extension_entries {
+        let runs: Vec<DataRun> = pieces.iter()
+            .flat_map(|(_, runs)| runs.iter().cloned())
+            .collect();
+
+        let record = match read_record(*extension_entry, &runs) {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("Unable to read $MFT extension entry {}: {}", extension_entry, e.display_chain());
+                continue;
+            }
+        };
+
+        // The base reference of an extension record of the $MFT is entry 0
+        // with a non-zero sequence
+        let base_reference = match record.get(0x20..0x28) {
+            Some(b) => LittleEndian::read_u64(b),
+            None => 0
+        };
+        if !is_file_record(&record) || base_reference == 0 || get_reference_entry(base_reference) != 0 {
+            warn!("Entry {} is listed as an extension of the $MFT but is not one.", extension_entry);
+            continue;
+        }
+
+        add_pieces(&record, &mut pieces)?;
+    }
+
+    let runs: Vec<DataRun> = pieces.into_iter()
+        .flat_map(|(_, runs)| runs)
+        .collect();
+
+    Ok((runs, initialized_size))
+}
+
+
+/// The parent and name of a $FILE_NAME attribute.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawFileName {
+    pub parent_entry: u64,
+    pub parent_sequence: u16,
+    pub namespace: u8,
+    pub name: String
+}
+
+impl RawFileName {
+    pub fn from_buffer(buffer: &[u8]) -> Result<Self, WinThingError> {
+        if buffer.len() < 0x42 {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    format!("$FILE_NAME value of {} bytes is too small.", buffer.len())
+                )
+            );
+        }
+
+        let parent_reference = LittleEndian::read_u64(&buffer[0x00..0x08]);
+        let name_length = buffer[0x40] as usize;
+
+        Ok(
+            Self {
+                parent_entry: get_reference_entry(parent_reference),
+                parent_sequence: get_reference_sequence(parent_reference),
+                namespace: buffer[0x41],
+                name: read_utf16(buffer, 0x42, name_length)?
+            }
+        )
+    }
+
+    pub fn is_dos(&self) -> bool {
+        self.namespace == DOS_NAMESPACE
+    }
+}
+
+
+/// Get the $FILE_NAME attributes of a file record, long names first.
+///
+pub fn get_file_names(record: &[u8]) -> Result<Vec<RawFileName>, WinThingError> {
+    let mut file_names: Vec<RawFileName> = Vec::new();
+
+    for attribute in get_raw_attributes(record)? {
+        if attribute.type_code != FILE_NAME_TYPE {
+            continue;
+        }
+
+        if let Some(value) = attribute.resident_value {
+            file_names.push(
+                RawFileName::from_buffer(&value)?
+            );
+        }
+    }
+
+    file_names.sort_by_key(|f| f.is_dos());
+    Ok(file_names)
+}
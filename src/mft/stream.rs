@@ -0,0 +1,341 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Sha256, Digest};
+use serde::Serialize;
+use crate::errors::WinThingError;
+use crate::mft::raw::{
+    get_raw_attributes,
+    DataRun,
+    RawAttribute,
+    DATA_TYPE
+};
+
+/// Attribute flags that change how the runs have to be read.
+const ATTRIBUTE_FLAG_COMPRESSION_MASK: u16 = 0x00FF;
+const ATTRIBUTE_FLAG_ENCRYPTED: u16 = 0x4000;
+/// The most clusters read from the source at once.
+const MAX_CLUSTERS_PER_READ: u64 = 256;
+
+
+/// Hashes of an extracted stream.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StreamHashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String
+}
+
+
+/// A writer that hashes everything written through it.
+///
+pub struct HashWriter<W: Write> {
+    writer: W,
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            md5: Md5::new(),
+            sha1: Sha1::new(),
+            sha256: Sha256::new()
+        }
+    }
+
+    /// Get the inner writer and the hashes of the data written to it.
+    pub fn finalize(self) -> (W, StreamHashes) {
+        let hashes = StreamHashes {
+            md5: hex::encode(self.md5.result()),
+            sha1: hex::encode(self.sha1.result()),
+            sha256: hex::encode(self.sha256.result())
+        };
+
+        (self.writer, hashes)
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.md5.input(&buf[..written]);
+        self.sha1.input(&buf[..written]);
+        self.sha256.input(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+
+/// Get the pieces of a $DATA stream from a file's records (the base record
+/// followed by its extension records) ordered by their starting VCN. An
+/// empty name is the unnamed stream.
+///
+pub fn get_stream_attributes(
+    records: &[Vec<u8>],
+    stream_name: &str
+) -> Result<Vec<RawAttribute>, WinThingError> {
+    let mut attributes: Vec<RawAttribute> = Vec::new();
+
+    for record in records {
+        for attribute in get_raw_attributes(record)? {
+            if attribute.type_code == DATA_TYPE && attribute.name == stream_name {
+                attributes.push(attribute);
+            }
+        }
+    }
+
+    if attributes.is_empty() {
+        return Err(
+            WinThingError::stream_error(
+                format!("No $DATA stream named '{}' found.", stream_name)
+            )
+        );
+    }
+
+    attributes.sort_by_key(|a| {
+        match &a.non_resident_header {
+            Some(header) => header.lowest_vcn,
+            None => 0
+        }
+    });
+
+    Ok(attributes)
+}
+
+
+/// Reads clusters from a volume. The source can be a live volume handle or
+/// an image with the volume starting at `volume_offset`.
+///
+pub struct ClusterReader<R: Read + Seek> {
+    reader: R,
+    bytes_per_cluster: u64,
+    volume_offset: u64
+}
+
+impl<R: Read + Seek> ClusterReader<R> {
+    pub fn new(reader: R, bytes_per_cluster: u32) -> Self {
+        Self {
+            reader,
+            bytes_per_cluster: bytes_per_cluster as u64,
+            volume_offset: 0
+        }
+    }
+
+    pub fn with_volume_offset(mut self, volume_offset: u64) -> Self {
+        self.volume_offset = volume_offset;
+        self
+    }
+
+    pub fn get_bytes_per_cluster(&self) -> u64 {
+        self.bytes_per_cluster
+    }
+
+    /// Get the size in bytes of a number of clusters. Cluster counts and
+    /// numbers come from the volume, so a corrupt one is an error rather
+    /// than an overflow.
+    fn get_cluster_bytes(&self, clusters: u64) -> Result<u64, WinThingError> {
+        clusters.checked_mul(self.bytes_per_cluster)
+            .ok_or_else(|| WinThingError::stream_error(
+                format!("{} clusters of {} bytes overflow.", clusters, self.bytes_per_cluster)
+            ))
+    }
+
+    /// Read whole clusters starting at a logical cluster number.
+    pub fn read_clusters(&mut self, lcn: u64, count: u64) -> Result<Vec<u8>, WinThingError> {
+        let position = self.get_cluster_bytes(lcn)?
+            .checked_add(self.volume_offset)
+            .ok_or_else(|| WinThingError::stream_error(
+                format!("LCN {} is past the end of any volume.", lcn)
+            ))?;
+        let mut buffer = vec![0u8; self.get_cluster_bytes(count)? as usize];
+
+        self.reader.seek(
+            SeekFrom::Start(position)
+        )?;
+        self.reader.read_exact(&mut buffer)?;
+
+        Ok(buffer)
+    }
+
+    /// Read `length` bytes at `offset` of the stream described by `runs`.
+    /// Reads are always whole clusters so they stay sector aligned on live
+    /// volumes.
+    pub fn read_run_range(
+        &mut self,
+        runs: &[DataRun],
+        offset: u64,
+        length: usize
+    ) -> Result<Vec<u8>, WinThingError> {
+        let mut buffer: Vec<u8> = Vec::with_capacity(length);
+        let mut position = offset;
+
+        while buffer.len() < length {
+            let vcn = position / self.bytes_per_cluster;
+            let cluster_offset = (position % self.bytes_per_cluster) as usize;
+
+            let mut run_start: u64 = 0;
+            let mut found: Option<&DataRun> = None;
+            for run in runs {
+                let run_end = run_start.checked_add(run.length)
+                    .ok_or_else(|| WinThingError::stream_error(
+                        "Data run lengths overflow.".to_owned()
+                    ))?;
+                if vcn < run_end {
+                    found = Some(run);
+                    break;
+                }
+                run_start = run_end;
+            }
+
+            let run = match found {
+                Some(r) => r,
+                None => {
+                    return Err(
+                        WinThingError::stream_error(
+                            format!("VCN {} is past the end of the data runs.", vcn)
+                        )
+                    );
+                }
+            };
+
+            let remaining = length - buffer.len();
+            let range_end = (cluster_offset + remaining) as u64;
+            let clusters_needed = (range_end - 1) / self.bytes_per_cluster + 1;
+            let cluster_count = clusters_needed
+                .min(run_start + run.length - vcn)
+                .min(MAX_CLUSTERS_PER_READ);
+
+            let available = self.get_cluster_bytes(cluster_count)? as usize - cluster_offset;
+            let take = available.min(remaining);
+
+            match run.lcn {
+                Some(lcn) => {
+                    let lcn = lcn.checked_add(vcn - run_start)
+                        .ok_or_else(|| WinThingError::stream_error(
+                            format!("LCN {} of VCN {} overflows.", lcn, vcn)
+                        ))?;
+                    let clusters = self.read_clusters(lcn, cluster_count)?;
+                    buffer.extend_from_slice(
+                        &clusters[cluster_offset..cluster_offset + take]
+                    );
+                },
+                // A sparse run only adds the zeros that are needed
+                None => buffer.resize(buffer.len() + take, 0)
+            }
+            position += take as u64;
+        }
+
+        Ok(buffer)
+    }
+
+    /// Write the content of a stream to `writer` and return the number of
+    /// bytes written. `attributes` are the pieces of a single stream as
+    /// returned by `get_stream_attributes`.
+    pub fn write_stream<W: Write>(
+        &mut self,
+        attributes: &[RawAttribute],
+        writer: &mut W
+    ) -> Result<u64, WinThingError> {
+        let first = match attributes.first() {
+            Some(a) => a,
+            None => {
+                return Err(
+                    WinThingError::stream_error(
+                        "No attributes given for the stream.".to_owned()
+                    )
+                );
+            }
+        };
+
+        if let Some(value) = &first.resident_value {
+            writer.write_all(value)?;
+            return Ok(value.len() as u64);
+        }
+
+        let header = match &first.non_resident_header {
+            Some(h) => h,
+            None => {
+                return Err(
+                    WinThingError::stream_error(
+                        "Stream has no resident value or non-resident header.".to_owned()
+                    )
+                );
+            }
+        };
+
+        if first.flags & (ATTRIBUTE_FLAG_COMPRESSION_MASK | ATTRIBUTE_FLAG_ENCRYPTED) != 0 {
+            return Err(
+                WinThingError::stream_error(
+                    format!("Compressed or encrypted streams are not supported (flags 0x{:04x}).", first.flags)
+                )
+            );
+        }
+
+        let mut runs: Vec<DataRun> = Vec::new();
+        for attribute in attributes {
+            runs.extend(attribute.get_data_runs()?);
+        }
+
+        // The sizes in the header are not trusted past what the runs hold,
+        // so a corrupt header can't make the stream (or its zeros) any
+        // larger than the clusters it was given
+        let run_clusters = runs.iter()
+            .try_fold(0u64, |total, run| total.checked_add(run.length))
+            .ok_or_else(|| WinThingError::stream_error(
+                "Data run lengths overflow.".to_owned()
+            ))?;
+        let run_size = self.get_cluster_bytes(run_clusters)?;
+        if header.data_size > run_size {
+            return Err(
+                WinThingError::stream_error(
+                    format!("Stream size {} is past the {} bytes of its data runs.", header.data_size, run_size)
+                )
+            );
+        }
+
+        let data_size = header.data_size;
+        let initialized_size = header.initialized_size.min(data_size);
+        let chunk_size = self.get_cluster_bytes(MAX_CLUSTERS_PER_READ)?;
+
+        let mut position: u64 = 0;
+        while position < initialized_size {
+            let length = chunk_size.min(initialized_size - position) as usize;
+            let buffer = self.read_run_range(
+                &runs,
+                position,
+                length
+            )?;
+
+            writer.write_all(&buffer)?;
+            position += length as u64;
+        }
+
+        // Anything past the initialized size reads as zeros
+        while position < data_size {
+            let length = chunk_size.min(data_size - position) as usize;
+            writer.write_all(&vec![0u8; length])?;
+            position += length as u64;
+        }
+
+        Ok(data_size)
+    }
+
+    /// Read the content of a stream into memory.
+    pub fn read_stream(&mut self, attributes: &[RawAttribute]) -> Result<Vec<u8>, WinThingError> {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write_stream(
+            attributes,
+            &mut buffer
+        )?;
+
+        Ok(buffer)
+    }
+}
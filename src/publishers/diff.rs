@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use crate::errors::WinThingError;
+use crate::publishers::{PUBLISHER_EVENTS_KEY, PUBLISHER_NAME_KEY};
+use crate::utils::json::{
+    get_difference_value_with_options,
+    parse_pointer,
+    DiffOptions
+};
+
+
+/// Get the options used to diff publisher records. Channel references are
+/// matched by path and levels, tasks, opcodes and keywords by name, so a
+/// new entry doesn't show every later entry as changed.
+///
+pub fn get_publisher_diff_options() -> DiffOptions {
+    DiffOptions::new()
+        .with_array_key("/path")
+        .with_array_key("/name")
+}
+
+
+/// Index publisher records, as output by `PublisherMeta::to_json_value`, by
+/// name. Only the given properties are kept, or all of them if none are
+/// given.
+///
+pub fn index_publishers(
+    publishers: &[Value],
+    properties: &[String]
+) -> Result<BTreeMap<String, Value>, WinThingError> {
+    let mut index = BTreeMap::new();
+
+    for publisher in publishers {
+        let mut record = match publisher {
+            Value::Object(record) => record.clone(),
+            other => {
+                return Err(
+                    WinThingError::invalid_input(
+                        format!("Expected a publisher record object, not {}", other)
+                    )
+                );
+            }
+        };
+
+        let name = match record.remove(PUBLISHER_NAME_KEY) {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(
+                    WinThingError::invalid_input(
+                        format!("Publisher record has no {}.", PUBLISHER_NAME_KEY)
+                    )
+                );
+            }
+        };
+
+        if !properties.is_empty() {
+            record = record.into_iter()
+                .filter(|(key, _)| properties.contains(key))
+                .collect::<Map<String, Value>>();
+        }
+
+        index.insert(name, Value::Object(record));
+    }
+
+    Ok(index)
+}
+
+
+/// A publisher that exists in both snapshots but differs. `differences`
+/// maps JSON Pointers to changes as returned by `get_difference_value`.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PublisherChange {
+    pub name: String,
+    pub differences: Value
+}
+
+impl PublisherChange {
+    /// Get the top level properties that changed, e.g.
+    /// `EvtPublisherMetadataChannelReferences`.
+    pub fn get_changed_properties(&self) -> Vec<String> {
+        let mut properties: Vec<String> = Vec::new();
+
+        if let Some(differences) = self.differences.as_object() {
+            for pointer in differences.keys() {
+                let property = match parse_pointer(pointer) {
+                    Ok(tokens) => tokens.into_iter().next(),
+                    Err(_) => None
+                };
+
+                if let Some(property) = property {
+                    if !properties.contains(&property) {
+                        properties.push(property);
+                    }
+                }
+            }
+        }
+
+        properties
+    }
+}
+
+
+/// The providers added, removed and changed between two sets of publisher
+/// records. `unavailable` are the providers that couldn't be read for one
+/// of the sets, so whether they changed is unknown.
+///
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct PublisherDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<PublisherChange>,
+    pub unavailable: Vec<String>
+}
+
+impl PublisherDiff {
+    /// Compare publisher records. Only `properties` are compared, or every
+    /// property if none are given. Event definitions are only compared if
+    /// both records have them, as they are only output with `--events`.
+    pub fn from_values(
+        before: &[Value],
+        after: &[Value],
+        properties: &[String]
+    ) -> Result<Self, WinThingError> {
+        let before = index_publishers(before, properties)?;
+        let after = index_publishers(after, properties)?;
+        let options = get_publisher_diff_options();
+
+        let mut diff = Self::default();
+
+        for (name, before_record) in &before {
+            let after_record = match after.get(name) {
+                Some(r) => r,
+                None => {
+                    diff.removed.push(name.to_owned());
+                    continue;
+                }
+            };
+
+            let differences = match (
+                before_record.get(PUBLISHER_EVENTS_KEY),
+                after_record.get(PUBLISHER_EVENTS_KEY)
+            ) {
+                (Some(_), None) | (None, Some(_)) => get_difference_value_with_options(
+                    &without_events(before_record),
+                    &without_events(after_record),
+                    &options
+                ),
+                _ => get_difference_value_with_options(
+                    before_record,
+                    after_record,
+                    &options
+                )
+            };
+
+            let is_empty = match differences.as_object() {
+                Some(d) => d.is_empty(),
+                None => true
+            };
+
+            if !is_empty {
+                diff.changed.push(
+                    PublisherChange {
+                        name: name.to_owned(),
+                        differences
+                    }
+                );
+            }
+        }
+
+        diff.added = after.keys()
+            .filter(|name| !before.contains_key(*name))
+            .cloned()
+            .collect();
+
+        Ok(diff)
+    }
+
+    /// Mark publishers that couldn't be read for either set as unavailable
+    /// rather than added or removed.
+    pub fn with_unavailable(mut self, names: &[String]) -> Self {
+        self.added.retain(|name| !names.contains(name));
+        self.removed.retain(|name| !names.contains(name));
+        self.unavailable = names.to_vec();
+        self.unavailable.sort();
+        self
+    }
+
+    /// Check if there are no differences. Unavailable publishers are not
+    /// differences.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Get a record for each added, removed, changed and unavailable
+    /// publisher.
+    pub fn to_records(&self) -> Vec<Value> {
+        let mut records: Vec<Value> = Vec::new();
+
+        for name in &self.added {
+            records.push(json!({"change": "added", "name": name}));
+        }
+        for name in &self.removed {
+            records.push(json!({"change": "removed", "name": name}));
+        }
+        for change in &self.changed {
+            records.push(json!({
+                "change": "changed",
+                "name": change.name,
+                "properties": change.get_changed_properties(),
+                "differences": change.differences
+            }));
+        }
+        for name in &self.unavailable {
+            records.push(json!({"change": "unavailable", "name": name}));
+        }
+
+        records
+    }
+}
+
+
+/// Get a publisher record without its event definitions.
+fn without_events(record: &Value) -> Value {
+    let mut record = record.clone();
+    if let Value::Object(properties) = &mut record {
+        properties.remove(PUBLISHER_EVENTS_KEY);
+    }
+
+    record
+}
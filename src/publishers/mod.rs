@@ -0,0 +1,71 @@
+pub mod diff;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use serde_json::Value;
+use crate::errors::WinThingError;
+
+/// The key of the publisher name in a `print_publishers -f jsonl` record.
+pub const PUBLISHER_NAME_KEY: &str = "Name";
+/// The key of the publisher's event definitions, output with `--events`.
+pub const PUBLISHER_EVENTS_KEY: &str = "Events";
+
+
+/// The key of the status of a `print_publishers --jobs` error record.
+pub const PUBLISHER_STATUS_KEY: &str = "status";
+/// The key of the publisher name in an error record.
+pub const PUBLISHER_ERROR_NAME_KEY: &str = "name";
+
+
+/// The publisher records of a dump, and the names of the publishers that
+/// couldn't be read when it was taken.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PublisherDump {
+    pub publishers: Vec<Value>,
+    pub unavailable: Vec<String>
+}
+
+
+/// Read publisher records from a `print_publishers -f jsonl` dump. Error
+/// records, output with `--jobs` for the publishers that failed, are not
+/// publishers and only their names are kept as unavailable.
+///
+pub fn read_publisher_dump<R: BufRead>(reader: R) -> Result<PublisherDump, WinThingError> {
+    let mut dump = PublisherDump::default();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: Value = serde_json::from_str(&line)?;
+        if record.get(PUBLISHER_STATUS_KEY).is_none() {
+            dump.publishers.push(record);
+            continue;
+        }
+
+        match record.get(PUBLISHER_ERROR_NAME_KEY) {
+            Some(Value::String(name)) => dump.unavailable.push(name.to_owned()),
+            _ => {
+                return Err(
+                    WinThingError::invalid_input(
+                        format!("Publisher error record has no {}.", PUBLISHER_ERROR_NAME_KEY)
+                    )
+                );
+            }
+        }
+    }
+
+    Ok(dump)
+}
+
+
+/// Read publisher records from a `print_publishers -f jsonl` dump file.
+///
+pub fn read_publisher_dump_path(path: &str) -> Result<PublisherDump, WinThingError> {
+    let file = File::open(path)?;
+    read_publisher_dump(
+        BufReader::new(file)
+    )
+}
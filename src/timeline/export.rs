@@ -0,0 +1,622 @@
+use std::io::{Read, Seek, Write};
+use std::str::FromStr;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::mft::get_entry_file_name;
+use crate::utils::csv::get_csv_line;
+use crate::mft::history::{HistoryPoint, HistoryTimeline};
+use crate::mft::enumerate::{get_entry_path, MftEnumerator};
+use crate::timeline::record::{normalize_path, TimelineRecord, TimelineSource};
+use crate::volume::image::NtfsImage;
+
+/// Added to the file name of an entry whose path was not resolved.
+const UNRESOLVED_PATH_NOTE: &str = "(path not resolved)";
+
+/// The log2timeline CSV header line.
+pub const L2T_CSV_HEADER: &str = "date,time,timezone,MACB,source,sourcetype,type,user,host,short,desc,version,filename,inode,notes,format,extra";
+
+/// The MACB flags and what each time is called in log2timeline output.
+const MACB_DESCRIPTIONS: [(char, &str); 4] = [
+    ('M', "Content Modification Time"),
+    ('A', "Last Access Time"),
+    ('C', "Metadata Modification Time"),
+    ('B', "Creation Time")
+];
+
+
+/// The super-timeline formats records can be exported to.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Bodyfile,
+    L2tCsv,
+    Tln
+}
+
+impl FromStr for ExportFormat {
+    type Err = WinThingError;
+
+    fn from_str(format: &str) -> Result<Self, WinThingError> {
+        match format.to_ascii_lowercase().as_str() {
+            "bodyfile" => Ok(ExportFormat::Bodyfile),
+            "l2tcsv" => Ok(ExportFormat::L2tCsv),
+            "tln" => Ok(ExportFormat::Tln),
+            _ => Err(
+                WinThingError::cli_error(
+                    format!("Unknown export format '{}'. Expected bodyfile, l2tcsv or tln.", format)
+                )
+            )
+        }
+    }
+}
+
+
+/// A time range to export. Both ends are inclusive and either can be open.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>
+}
+
+impl TimeRange {
+    pub fn new(start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> Self {
+        Self {
+            start,
+            end
+        }
+    }
+
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.start.map(|s| time >= s).unwrap_or(true) &&
+            self.end.map(|e| time <= e).unwrap_or(true)
+    }
+}
+
+
+/// What an exported item's times come from.
+///
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportKind {
+    StandardInformation,
+    FileName,
+    UsnChange,
+    EventRecord,
+    MftChange
+}
+
+impl ExportKind {
+    /// Get the log2timeline and TLN source.
+    pub fn get_source(self) -> &'static str {
+        match self {
+            ExportKind::EventRecord => "EVT",
+            _ => "FILE"
+        }
+    }
+
+    /// Get the log2timeline source type.
+    pub fn get_source_type(self) -> &'static str {
+        match self {
+            ExportKind::StandardInformation => "NTFS $STANDARD_INFORMATION",
+            ExportKind::FileName => "NTFS $FILE_NAME",
+            ExportKind::UsnChange => "NTFS USN change",
+            ExportKind::EventRecord => "Event Log",
+            ExportKind::MftChange => "NTFS MFT change"
+        }
+    }
+
+    pub fn get_short_name(self) -> &'static str {
+        match self {
+            ExportKind::StandardInformation => "$SI",
+            ExportKind::FileName => "$FN",
+            ExportKind::UsnChange => "USN",
+            ExportKind::EventRecord => "EVT",
+            ExportKind::MftChange => "MFT"
+        }
+    }
+
+    /// MFT attribute times have MACB meanings. Records only have the time
+    /// they were written.
+    pub fn has_macb(self) -> bool {
+        matches!(self, ExportKind::StandardInformation | ExportKind::FileName)
+    }
+}
+
+
+/// The modified, accessed, changed ($MFT modified) and born times of an
+/// item.
+///
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MacbTimes {
+    pub modified: Option<DateTime<Utc>>,
+    pub accessed: Option<DateTime<Utc>>,
+    pub changed: Option<DateTime<Utc>>,
+    pub created: Option<DateTime<Utc>>
+}
+
+impl MacbTimes {
+    /// All four times set to one time, as used for records.
+    pub fn from_time(time: DateTime<Utc>) -> Self {
+        Self {
+            modified: Some(time),
+            accessed: Some(time),
+            changed: Some(time),
+            created: Some(time)
+        }
+    }
+
+    /// Read the times of a $STANDARD_INFORMATION or $FILE_NAME attribute's
+    /// data from the `custom_entry_value` view.
+    pub fn from_attribute_data(data: &Value) -> Self {
+        let get_time = |name: &str| -> Option<DateTime<Utc>> {
+            data[name].as_str()
+                .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+                .map(|t| t.with_timezone(&Utc))
+        };
+
+        Self {
+            modified: get_time("modified"),
+            accessed: get_time("accessed"),
+            changed: get_time("mft_modified"),
+            created: get_time("created")
+        }
+    }
+
+    fn get_times(&self) -> [Option<DateTime<Utc>>; 4] {
+        [self.modified, self.accessed, self.changed, self.created]
+    }
+
+    /// Group equal times together, giving each distinct time in order with
+    /// its MACB flags (e.g. `M.C.`).
+    pub fn get_groups(&self) -> Vec<(DateTime<Utc>, String)> {
+        let times = self.get_times();
+
+        let mut distinct: Vec<DateTime<Utc>> = times.iter().filter_map(|t| *t).collect();
+        distinct.sort();
+        distinct.dedup();
+
+        distinct.into_iter()
+            .map(|time| {
+                let flags: String = MACB_DESCRIPTIONS.iter()
+                    .zip(times.iter())
+                    .map(|((flag, _), t)| match *t == Some(time) {
+                        true => *flag,
+                        false => '.'
+                    })
+                    .collect();
+                (time, flags)
+            })
+            .collect()
+    }
+
+    /// Is any of the times in the range.
+    pub fn is_in_range(&self, range: &TimeRange) -> bool {
+        self.get_times().iter()
+            .filter_map(|t| *t)
+            .any(|t| range.contains(t))
+    }
+}
+
+
+/// An item to export: the times of one MFT attribute, or one USN, event or
+/// MFT change record.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExportItem {
+    pub kind: ExportKind,
+    pub host: Option<String>,
+    pub user: Option<String>,
+    pub path: Option<String>,
+    pub inode: Option<String>,
+    pub size: Option<u64>,
+    pub is_directory: bool,
+    pub short: String,
+    pub description: String,
+    pub times: MacbTimes
+}
+
+impl ExportItem {
+    /// Create an item from a timeline record. Its time is used for all four
+    /// times.
+    pub fn from_timeline_record(record: &TimelineRecord) -> Self {
+        let path = record.path.clone();
+        let label = record.path.as_ref().or(record.name.as_ref());
+        let data = &record.data;
+
+        let (kind, user, inode, description) = match record.source {
+            TimelineSource::Usn => (
+                ExportKind::UsnChange,
+                None,
+                data["file_reference"]["entry"].as_u64().map(|e| e.to_string()),
+                match label {
+                    Some(l) => format!("{} {}", l, record.description),
+                    None => record.description.to_owned()
+                }
+            ),
+            TimelineSource::EventLog => (
+                ExportKind::EventRecord,
+                data["Event"]["System"]["Security_attributes"]["UserID"].as_str()
+                    .map(|u| u.to_owned()),
+                None,
+                get_event_description(record)
+            ),
+            TimelineSource::Mft => (
+                ExportKind::MftChange,
+                None,
+                None,
+                match label {
+                    Some(l) => format!("{} {}", l, record.description),
+                    None => record.description.to_owned()
+                }
+            )
+        };
+
+        Self {
+            kind,
+            host: record.host.clone(),
+            user,
+            path,
+            inode,
+            size: None,
+            is_directory: false,
+            short: record.description.to_owned(),
+            description,
+            times: MacbTimes::from_time(record.timestamp)
+        }
+    }
+
+    /// Get the log2timeline and TLN rows: one for each distinct time.
+    /// Records have a single row with no MACB flags.
+    pub fn get_rows(&self) -> Vec<(DateTime<Utc>, String)> {
+        match self.kind.has_macb() {
+            true => self.times.get_groups(),
+            false => self.times.modified
+                .map(|t| vec![(t, "....".to_owned())])
+                .unwrap_or_default()
+        }
+    }
+}
+
+
+/// Describe an event with its provider and EventData values.
+fn get_event_description(record: &TimelineRecord) -> String {
+    let event = &record.data["Event"];
+    let mut description = record.description.to_owned();
+
+    if let Some(provider) = event["System"]["Provider_attributes"]["Name"].as_str() {
+        description = format!("{} {}", description, provider);
+    }
+
+    if let Some(event_data) = event["EventData"].as_object() {
+        let fields: Vec<String> = event_data.iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| match v.as_str() {
+                Some(s) => format!("{}: {}", k, s),
+                None => format!("{}: {}", k, v)
+            })
+            .collect();
+        if !fields.is_empty() {
+            description = format!("{} [{}]", description, fields.join("; "));
+        }
+    }
+
+    description
+}
+
+
+/// Replace the last component of a path with `name`.
+fn replace_file_name(path: &str, name: &str) -> String {
+    match path.rfind('\\') {
+        Some(index) => format!("{}\\{}", &path[..index], name),
+        None => name.to_owned()
+    }
+}
+
+
+/// Get an item for the $STANDARD_INFORMATION and each $FILE_NAME attribute
+/// of an entry's `custom_entry_value` view. `path` is the entry's full
+/// path; without one the items have no path and their description is the
+/// file name marked as not resolved. $FILE_NAME items use their own name in
+/// place of the path's file name.
+///
+pub fn get_entry_export_items(
+    entry_value: &Value,
+    host: Option<&str>,
+    path: Option<&str>
+) -> Vec<ExportItem> {
+    let header = &entry_value["header"];
+    let inode = header["record_number"].as_u64().map(|entry| {
+        match header["sequence"].as_u64() {
+            Some(sequence) => format!("{}-{}", entry, sequence),
+            None => entry.to_string()
+        }
+    });
+    let is_directory = header["flags"].as_str()
+        .map(|f| f.contains("INDEX_PRESENT"))
+        .unwrap_or(false);
+    let size = entry_value["streams"]["$DATA"]["size"].as_u64();
+
+    let entry_name = path.map(|p| p.rsplit('\\').next().unwrap_or(p).to_owned())
+        .or_else(|| get_entry_file_name(entry_value));
+
+    let new_item = |kind: ExportKind, data: &Value, name: Option<String>| -> ExportItem {
+        let short = name.unwrap_or_default();
+        let (item_path, description) = match path {
+            Some(p) => {
+                let item_path = replace_file_name(p, &short);
+                (Some(item_path.clone()), item_path)
+            },
+            None => (None, format!("{} {}", short, UNRESOLVED_PATH_NOTE).trim_start().to_owned())
+        };
+
+        ExportItem {
+            kind,
+            host: host.map(|h| h.to_owned()),
+            user: None,
+            path: item_path,
+            inode: inode.clone(),
+            size: size.or_else(|| data["logical_size"].as_u64()),
+            is_directory,
+            short,
+            description,
+            times: MacbTimes::from_attribute_data(data)
+        }
+    };
+
+    let mut items = Vec::new();
+    let attributes = &entry_value["attributes"];
+
+    if let Some(standard_info) = attributes["StandardInformation"].as_object() {
+        for attribute in standard_info.values() {
+            items.push(
+                new_item(ExportKind::StandardInformation, &attribute["data"], entry_name.clone())
+            );
+        }
+    }
+
+    if let Some(file_names) = attributes["FileName"].as_object() {
+        for attribute in file_names.values() {
+            let data = &attribute["data"];
+            let name = data["name"].as_str()
+                .map(|n| n.to_owned())
+                .or_else(|| entry_name.clone());
+            items.push(
+                new_item(ExportKind::FileName, data, name)
+            );
+        }
+    }
+
+    items
+}
+
+
+/// Get the export items of an entry value with its resolved `path` (see
+/// `EnumeratedEntry::into_json_value`) on `volume`. Entries without one,
+/// such as journal snapshots, have no path.
+fn get_volume_entry_items(
+    entry_value: &Value,
+    host: Option<&str>,
+    volume: Option<&str>
+) -> Vec<ExportItem> {
+    let path = entry_value["path"].as_str()
+        .map(|p| normalize_path(p, volume));
+
+    get_entry_export_items(
+        entry_value,
+        host,
+        path.as_deref()
+    )
+}
+
+
+/// Get the export items of the last recorded state of every file reference
+/// (entry and sequence) in an MFT history journal.
+///
+pub fn get_history_export_items(
+    history: &HistoryTimeline,
+    host: Option<&str>,
+    volume: Option<&str>
+) -> Result<Vec<ExportItem>, WinThingError> {
+    let mut items = Vec::new();
+    for (entry, sequence) in history.get_references() {
+        if let Some(value) = history.get_reference_state(entry, sequence, HistoryPoint::Usn(u64::MAX))? {
+            items.extend(
+                get_volume_entry_items(&value, host, volume)
+            );
+        }
+    }
+
+    Ok(items)
+}
+
+
+/// Get the export items of entries read from an NTFS image with their
+/// resolved paths: the given entries, or every base entry of the MFT if
+/// `entries` is None. Entries of the MFT that can't be read are skipped.
+///
+pub fn get_image_export_items<R: Read + Seek>(
+    image: &mut NtfsImage<R>,
+    entries: Option<&[u64]>,
+    host: Option<&str>,
+    volume: Option<&str>
+) -> Result<Vec<ExportItem>, WinThingError> {
+    let mut items = Vec::new();
+
+    let entries = match entries {
+        Some(entries) => entries,
+        None => {
+            for result in MftEnumerator::new(image) {
+                match result.and_then(|e| e.into_json_value()) {
+                    Ok(value) => items.extend(
+                        get_volume_entry_items(&value, host, volume)
+                    ),
+                    Err(e) => warn!("Skipping entry: {}", e.display_chain())
+                }
+            }
+
+            return Ok(items);
+        }
+    };
+
+    for entry in entries {
+        let mut value = image.get_logical_entry(*entry, true)?
+            .into_json_value()?;
+        value["path"] = json!(get_entry_path(image, *entry)?);
+        items.extend(
+            get_volume_entry_items(&value, host, volume)
+        );
+    }
+
+    Ok(items)
+}
+
+
+/// Make a value safe for a `|` separated line.
+fn clean_field(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+        .replace(" | ", ", ")
+        .replace('|', ",")
+}
+
+
+fn get_epoch(time: Option<DateTime<Utc>>) -> i64 {
+    time.map(|t| t.timestamp()).unwrap_or(0)
+}
+
+
+/// Format an item as a Sleuthkit bodyfile (3.x) line:
+/// `MD5|name|inode|mode|UID|GID|size|atime|mtime|ctime|crtime`.
+///
+pub fn get_bodyfile_line(item: &ExportItem) -> String {
+    let name = match (item.kind, &item.path) {
+        (ExportKind::StandardInformation, Some(path)) => path.to_owned(),
+        (ExportKind::FileName, Some(path)) => format!("{} ($FILE_NAME)", path),
+        (_, Some(path)) => format!("{} ({})", path, item.short),
+        (ExportKind::FileName, None) => format!("{} ($FILE_NAME)", item.description),
+        (_, None) => item.description.to_owned()
+    };
+
+    let mode = match (item.kind, item.is_directory) {
+        (ExportKind::EventRecord, _) => "-/----------",
+        (_, true) => "d/drwxrwxrwx",
+        (_, false) => "r/rrwxrwxrwx"
+    };
+
+    format!(
+        "0|{}|{}|{}|0|0|{}|{}|{}|{}|{}",
+        clean_field(&name),
+        item.inode.as_deref().unwrap_or("0"),
+        mode,
+        item.size.unwrap_or(0),
+        get_epoch(item.times.accessed),
+        get_epoch(item.times.modified),
+        get_epoch(item.times.changed),
+        get_epoch(item.times.created)
+    )
+}
+
+
+/// Format a row of an item as a log2timeline CSV line.
+///
+pub fn get_l2t_csv_line(item: &ExportItem, time: DateTime<Utc>, macb: &str) -> String {
+    let time_type = match item.kind.has_macb() {
+        true => MACB_DESCRIPTIONS.iter()
+            .zip(macb.chars())
+            .filter(|((flag, _), c)| flag == c)
+            .map(|((_, description), _)| *description)
+            .collect::<Vec<&str>>()
+            .join("; "),
+        false => "Written Time".to_owned()
+    };
+
+    let fields = [
+        time.format("%m/%d/%Y").to_string(),
+        time.format("%H:%M:%S").to_string(),
+        "UTC".to_owned(),
+        macb.to_owned(),
+        item.kind.get_source().to_owned(),
+        item.kind.get_source_type().to_owned(),
+        time_type,
+        item.user.clone().unwrap_or_else(|| "-".to_owned()),
+        item.host.clone().unwrap_or_else(|| "-".to_owned()),
+        format!("{} {}", item.kind.get_short_name(), item.short),
+        item.description.to_owned(),
+        "2".to_owned(),
+        item.path.clone().unwrap_or_else(|| "-".to_owned()),
+        item.inode.clone().unwrap_or_else(|| "-".to_owned()),
+        "-".to_owned(),
+        "rswinthings".to_owned(),
+        "-".to_owned()
+    ];
+
+    get_csv_line(&fields)
+}
+
+
+/// Format a row of an item as a TLN line:
+/// `Time|Source|System|User|Description`.
+///
+pub fn get_tln_line(item: &ExportItem, time: DateTime<Utc>, macb: &str) -> String {
+    let description = match item.kind.has_macb() {
+        true => format!("{} [{}] {}", macb, item.kind.get_short_name(), item.description),
+        false => format!("[{}] {}", item.kind.get_short_name(), item.description)
+    };
+
+    format!(
+        "{}|{}|{}|{}|{}",
+        time.timestamp(),
+        item.kind.get_source(),
+        clean_field(item.host.as_deref().unwrap_or("")),
+        clean_field(item.user.as_deref().unwrap_or("")),
+        clean_field(&description)
+    )
+}
+
+
+/// Write items in a super-timeline format. Bodyfile lines are written for
+/// items with any time in the range, in the order given (`mactime` sorts
+/// them). log2timeline CSV and TLN rows are written for each time in the
+/// range, ordered by time. Returns the number of lines written.
+///
+pub fn export_items<W: Write>(
+    writer: &mut W,
+    items: &[ExportItem],
+    format: ExportFormat,
+    range: &TimeRange
+) -> Result<usize, WinThingError> {
+    if format == ExportFormat::Bodyfile {
+        let mut count = 0;
+        for item in items.iter().filter(|i| i.times.is_in_range(range)) {
+            writeln!(writer, "{}", get_bodyfile_line(item))?;
+            count += 1;
+        }
+        return Ok(count);
+    }
+
+    let mut rows: Vec<(DateTime<Utc>, String, &ExportItem)> = items.iter()
+        .flat_map(|item| {
+            item.get_rows()
+                .into_iter()
+                .map(move |(time, macb)| (time, macb, item))
+        })
+        .filter(|(time, _, _)| range.contains(*time))
+        .collect();
+    rows.sort_by_key(|(time, _, _)| *time);
+
+    if format == ExportFormat::L2tCsv {
+        writeln!(writer, "{}", L2T_CSV_HEADER)?;
+    }
+
+    for (time, macb, item) in &rows {
+        let line = match format {
+            ExportFormat::L2tCsv => get_l2t_csv_line(item, *time, macb),
+            _ => get_tln_line(item, *time, macb)
+        };
+        writeln!(writer, "{}", line)?;
+    }
+
+    Ok(rows.len())
+}
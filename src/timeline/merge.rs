@@ -0,0 +1,93 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use crate::timeline::record::TimelineRecord;
+
+
+/// The next record of a stream, ordered so the earliest record (then the
+/// lowest stream index) is at the top of a `BinaryHeap`.
+struct StreamHead {
+    record: TimelineRecord,
+    stream: usize
+}
+
+impl PartialEq for StreamHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StreamHead {}
+
+impl PartialOrd for StreamHead {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StreamHead {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.record.timestamp.cmp(&self.record.timestamp)
+            .then_with(|| other.stream.cmp(&self.stream))
+    }
+}
+
+
+/// Merges streams of timeline records that are each in time order into one
+/// stream in time order. Records with the same timestamp come out in the
+/// order of their streams.
+///
+pub struct TimelineMerger<I: Iterator<Item = TimelineRecord>> {
+    streams: Vec<I>,
+    heads: BinaryHeap<StreamHead>
+}
+
+impl<I: Iterator<Item = TimelineRecord>> TimelineMerger<I> {
+    pub fn new(streams: Vec<I>) -> Self {
+        let mut merger = Self {
+            streams,
+            heads: BinaryHeap::new()
+        };
+
+        for stream in 0..merger.streams.len() {
+            merger.advance(stream);
+        }
+
+        merger
+    }
+
+    fn advance(&mut self, stream: usize) {
+        if let Some(record) = self.streams[stream].next() {
+            self.heads.push(
+                StreamHead {
+                    record,
+                    stream
+                }
+            );
+        }
+    }
+}
+
+impl<I: Iterator<Item = TimelineRecord>> Iterator for TimelineMerger<I> {
+    type Item = TimelineRecord;
+
+    fn next(&mut self) -> Option<TimelineRecord> {
+        let head = self.heads.pop()?;
+        self.advance(head.stream);
+        Some(head.record)
+    }
+}
+
+
+/// Sort each set of records by time and merge them.
+///
+pub fn merge_timelines(timelines: Vec<Vec<TimelineRecord>>) -> Vec<TimelineRecord> {
+    let streams = timelines.into_iter()
+        .map(|mut records| {
+            records.sort_by_key(|r| r.timestamp);
+            records.into_iter()
+        })
+        .collect();
+
+    TimelineMerger::new(streams).collect()
+}
+
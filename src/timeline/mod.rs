@@ -0,0 +1,3 @@
+pub mod export;
+pub mod merge;
+pub mod record;
\ No newline at end of file
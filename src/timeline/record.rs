@@ -0,0 +1,319 @@
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use crate::errors::WinThingError;
+use crate::mft::get_entry_file_name;
+use crate::mft::history::{HistoryChange, HistoryRecord, HistoryTimeline};
+use crate::events::stream::split_xml_events;
+use crate::utils::time::get_event_timestamp;
+use crate::utils::xmltojson::xml_string_to_json;
+
+/// EventData fields that hold a file path, in the order they are used.
+const EVENT_PATH_FIELDS: [&str; 8] = [
+    "TargetFilename",
+    "Path",
+    "ScriptPath",
+    "ImageLoaded",
+    "Image",
+    "ObjectName",
+    "ServiceFileName",
+    "FileName"
+];
+
+/// Timestamp formats of USN records, besides RFC 3339.
+const USN_TIMESTAMP_FORMATS: [&str; 2] = [
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f"
+];
+
+
+/// Where a timeline record came from.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineSource {
+    EventLog,
+    Usn,
+    Mft
+}
+
+
+/// Normalise a path so the paths of different sources compare equal:
+/// backslash separators, no `\\?\` or `\\.\` prefix, and the volume
+/// (e.g. `C:`) prefixed to volume relative paths.
+///
+pub fn normalize_path(path: &str, volume: Option<&str>) -> String {
+    let mut path = path.replace('/', "\\");
+    for prefix in &["\\\\?\\", "\\\\.\\", "\\??\\"] {
+        if path.starts_with(prefix) {
+            path = path[prefix.len()..].to_owned();
+        }
+    }
+
+    let has_drive = path.len() >= 2 && path.as_bytes()[1] == b':';
+    match (has_drive, volume) {
+        (false, Some(volume)) => {
+            let volume = normalize_path(volume, None);
+            let volume = volume.trim_end_matches('\\');
+            match path.starts_with('\\') {
+                true => format!("{}{}", volume, path),
+                false => format!("{}\\{}", volume, path)
+            }
+        },
+        _ => path
+    }
+}
+
+
+/// Parse a USN record timestamp.
+fn parse_usn_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(timestamp) {
+        return Some(t.with_timezone(&Utc));
+    }
+
+    let timestamp = timestamp.trim_end_matches(" UTC").trim_end_matches('Z');
+    USN_TIMESTAMP_FORMATS.iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(timestamp, f).ok())
+        .map(|t| Utc.from_utc_datetime(&t))
+}
+
+
+/// A record of any source on a common timeline: event log records, USN
+/// records and MFT entry snapshots and diffs. `path` is only set when the
+/// full path is known; `name` is the file name when only that is known.
+/// `data` is the original record.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TimelineRecord {
+    pub timestamp: DateTime<Utc>,
+    pub host: Option<String>,
+    pub source: TimelineSource,
+    pub path: Option<String>,
+    pub name: Option<String>,
+    pub description: String,
+    pub data: Value
+}
+
+impl TimelineRecord {
+    /// Create a record from the JSON of an event, as output by
+    /// `listen_events` or `query_events`. The host is the event's
+    /// `source_host`, or its computer.
+    pub fn from_event_value(event: &Value) -> Result<Self, WinThingError> {
+        let system = &event["Event"]["System"];
+        let timestamp = get_event_timestamp(event).ok_or_else(||
+            WinThingError::unhandled(
+                "Event has no TimeCreated SystemTime.".to_owned()
+            )
+        )?;
+
+        let host = event["source_host"].as_str()
+            .or_else(|| system["Computer"].as_str())
+            .map(|h| h.to_owned());
+
+        let event_data = &event["Event"]["EventData"];
+        let path = EVENT_PATH_FIELDS.iter()
+            .filter_map(|f| event_data[*f].as_str())
+            .find(|p| !p.is_empty())
+            .map(|p| normalize_path(p, None));
+
+        let description = format!(
+            "{} {}",
+            system["Channel"].as_str().unwrap_or(""),
+            system["EventID"]
+        );
+
+        Ok(
+            Self {
+                timestamp,
+                host,
+                source: TimelineSource::EventLog,
+                path,
+                name: None,
+                description: description.trim().to_owned(),
+                data: event.to_owned()
+            }
+        )
+    }
+
+    /// Create a record from the JSON of a USN record (see
+    /// `get_usn_record_value`). The path is the record's `full_path` on
+    /// `volume` if it was resolved; the name is its file name.
+    pub fn from_usn_value(
+        record: &Value,
+        host: Option<&str>,
+        volume: Option<&str>
+    ) -> Result<Self, WinThingError> {
+        let timestamp = record["timestamp"].as_str()
+            .and_then(parse_usn_timestamp)
+            .ok_or_else(|| WinThingError::unhandled(
+                format!("Unable to get timestamp from usn record: {}", record)
+            ))?;
+
+        let path = record["full_path"].as_str()
+            .map(|p| normalize_path(p, volume));
+        let name = record["file_name"].as_str()
+            .map(|n| n.to_owned());
+
+        Ok(
+            Self {
+                timestamp,
+                host: host.map(|h| h.to_owned()),
+                source: TimelineSource::Usn,
+                path,
+                name,
+                description: record["reason"].as_str().unwrap_or("").to_owned(),
+                data: record.to_owned()
+            }
+        )
+    }
+
+    /// Create a record from an MFT history journal record (see
+    /// `listen_mft --journal`). Journal entries have no parent path, so
+    /// only the entry's file name is known.
+    pub fn from_history_record(
+        record: &HistoryRecord,
+        host: Option<&str>,
+        name: Option<&str>
+    ) -> Result<Self, WinThingError> {
+        let (description, data) = match &record.change {
+            HistoryChange::Snapshot { value } => (
+                format!("Entry {} snapshot", record.entry),
+                value.to_owned()
+            ),
+            HistoryChange::Diff { patch } => (
+                format!("Entry {} changed", record.entry),
+                patch.to_json_value()?
+            )
+        };
+
+        Ok(
+            Self {
+                timestamp: record.recorded,
+                host: host.map(|h| h.to_owned()),
+                source: TimelineSource::Mft,
+                path: None,
+                name: name.map(|n| n.to_owned()),
+                description,
+                data
+            }
+        )
+    }
+}
+
+
+/// Create timeline records from MFT history journal records. A diff has no
+/// file name, so it uses the name of the last snapshot of its file, by entry
+/// and sequence so a reused entry does not take the previous file's name.
+///
+pub fn get_history_timeline(
+    records: &[HistoryRecord],
+    host: Option<&str>
+) -> Result<Vec<TimelineRecord>, WinThingError> {
+    let mut names: HashMap<(u64, u16), String> = HashMap::new();
+    let mut timeline = Vec::new();
+
+    for record in records {
+        let reference = (record.entry, record.sequence);
+        if let HistoryChange::Snapshot { value } = &record.change {
+            match get_entry_file_name(value) {
+                Some(name) => names.insert(reference, name),
+                None => names.remove(&reference)
+            };
+        }
+
+        timeline.push(
+            TimelineRecord::from_history_record(
+                record,
+                host,
+                names.get(&reference).map(|n| n.as_str())
+            )?
+        );
+    }
+
+    Ok(timeline)
+}
+
+
+/// Read the JSON values of a JSONL file.
+fn read_jsonl<R: BufRead>(reader: R) -> Result<Vec<Value>, WinThingError> {
+    let mut values = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        values.push(
+            serde_json::from_str(&line)?
+        );
+    }
+
+    Ok(values)
+}
+
+
+/// Read timeline records from the events of a JSONL file (`listen_events`
+/// or `query_events` output) or, if the path ends in `.xml`, an XML file.
+/// Records that are not events, such as alerts and status records, and
+/// events without a time are skipped.
+///
+pub fn read_event_timeline_path(path: &str) -> Result<Vec<TimelineRecord>, WinThingError> {
+    let events = match path.to_ascii_lowercase().ends_with(".xml") {
+        true => {
+            let content = fs::read_to_string(path)?;
+            split_xml_events(&content)
+                .into_iter()
+                .map(xml_string_to_json)
+                .collect::<Result<Vec<Value>, WinThingError>>()?
+        },
+        false => read_jsonl(
+            BufReader::new(File::open(path)?)
+        )?
+    };
+
+    let mut timeline = Vec::new();
+    for event in events.iter().filter(|e| e.get("Event").is_some()) {
+        match TimelineRecord::from_event_value(event) {
+            Ok(record) => timeline.push(record),
+            Err(e) => warn!("Skipping event in {}: {}", path, e.display_chain())
+        }
+    }
+
+    Ok(timeline)
+}
+
+
+/// Read timeline records from a JSONL file of USN records.
+///
+pub fn read_usn_timeline_path(
+    path: &str,
+    host: Option<&str>,
+    volume: Option<&str>
+) -> Result<Vec<TimelineRecord>, WinThingError> {
+    let records = read_jsonl(
+        BufReader::new(File::open(path)?)
+    )?;
+
+    records.iter()
+        .map(|r| TimelineRecord::from_usn_value(r, host, volume))
+        .collect()
+}
+
+
+/// Read timeline records from an MFT history journal.
+///
+pub fn read_history_timeline_path(
+    path: &str,
+    host: Option<&str>
+) -> Result<Vec<TimelineRecord>, WinThingError> {
+    let history = HistoryTimeline::from_path(path)?;
+    get_history_timeline(
+        history.get_all_records(),
+        host
+    )
+}
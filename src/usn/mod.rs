@@ -1,3 +1,6 @@
 pub mod structs;
+#[cfg(windows)]
 pub mod winioctrl;
-pub mod listener;
\ No newline at end of file
+#[cfg(windows)]
+pub mod listener;
+pub mod record;
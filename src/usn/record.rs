@@ -0,0 +1,39 @@
+use serde_json::Value;
+use rusty_usn::record::UsnEntry;
+use crate::errors::WinThingError;
+
+
+/// Get the JSON value of the record held by a UsnEntry.
+///
+pub fn get_usn_record_value(usn_entry: &UsnEntry) -> Result<Value, WinThingError> {
+    let value = serde_json::to_value(&usn_entry.record)?;
+
+    // Unwrap versioned records (e.g. {"V2": {...}}) to the record itself
+    if let Some(map) = value.as_object() {
+        if map.len() == 1 && !map.contains_key("usn") {
+            if let Some(inner) = map.values().next() {
+                if inner.is_object() {
+                    return Ok(inner.to_owned());
+                }
+            }
+        }
+    }
+
+    Ok(value)
+}
+
+
+/// Get the update sequence number of a UsnEntry.
+///
+pub fn get_usn(usn_entry: &UsnEntry) -> Result<u64, WinThingError> {
+    let value = get_usn_record_value(usn_entry)?;
+
+    match value["usn"].as_u64() {
+        Some(usn) => Ok(usn),
+        None => Err(
+            WinThingError::unhandled(
+                format!("Unable to get usn from record: {}", value)
+            )
+        )
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+
+/// Exponential backoff for reconnect attempts. The delay doubles with each
+/// failure up to `max`.
+///
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    failures: u32
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            failures: 0
+        }
+    }
+
+    pub fn get_failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Record a failure and get how long to wait before the next attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        // Cap the shift so the multiplier can't overflow
+        let multiplier = 1u32 << self.failures.min(16);
+        self.failures = self.failures.saturating_add(1);
+
+        self.initial
+            .checked_mul(multiplier)
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(
+            Duration::from_secs(5),
+            Duration::from_secs(300)
+        )
+    }
+}
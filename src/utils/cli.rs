@@ -1,24 +1,22 @@
 use std::process::exit;
 use clap::{App, Arg, ArgMatches};
-use winapi::um::winevt::{
-    EvtRpcLoginAuthDefault,
-    EvtRpcLoginAuthNegotiate,
-    EvtRpcLoginAuthKerberos,
-    EvtRpcLoginAuthNTLM
-};
 use winapi::um::winevt::EVT_RPC_LOGIN_FLAGS;
 use crate::errors::WinThingError;
-use crate::winevt::session::RemoteSession;
+use crate::winevt::session::{
+    login_flag_from_str,
+    RemoteSession
+};
+use crate::utils::credentials::{
+    get_credential_provider,
+    DEFAULT_PASSWORD_ENV
+};
 
 
 fn flag_from_str(flag_str: &str) -> EVT_RPC_LOGIN_FLAGS {
-    match flag_str {
-        "Default" => EvtRpcLoginAuthDefault,
-        "Negotiate" => EvtRpcLoginAuthNegotiate,
-        "Kerberos" => EvtRpcLoginAuthKerberos,
-        "NTLM" => EvtRpcLoginAuthNTLM,
-        other => {
-            eprintln!("{} Is not a handled flag for EVT_RPC_LOGIN_FLAGS!", other);
+    match login_flag_from_str(flag_str) {
+        Ok(flag) => flag,
+        Err(e) => {
+            eprintln!("{}", e.message);
             exit(-1);
         }
     }
@@ -51,10 +49,40 @@ pub fn add_session_options_to_app<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
         .possible_values(&["Default", "Negotiate", "Kerberos", "NTLM"])
         .help("The authentication method to use to authenticate the user when connecting to the remote computer.");
 
+    let password_source = Arg::with_name("password_source")
+        .long("password-source")
+        .value_name("PASSWORD_SOURCE")
+        .takes_value(true)
+        .possible_values(&["Prompt", "Env", "File", "Stdin", "CredManager"])
+        .default_value("Prompt")
+        .help("Where to get the password for --user from.");
+
+    let password_env = Arg::with_name("password_env")
+        .long("password-env")
+        .value_name("PASSWORD_ENV")
+        .takes_value(true)
+        .help("The environment variable holding the password for the Env source. [default: RSWINTHINGS_PASSWORD]");
+
+    let password_file = Arg::with_name("password_file")
+        .long("password-file")
+        .value_name("PASSWORD_FILE")
+        .takes_value(true)
+        .help("A file, readable only by the current user, whose first line is the password for the File source.");
+
+    let credential_target = Arg::with_name("credential_target")
+        .long("credential-target")
+        .value_name("CREDENTIAL_TARGET")
+        .takes_value(true)
+        .help("The generic Credential Manager target holding the password for the CredManager source.");
+
     app.arg(server)
         .arg(user)
         .arg(domain)
         .arg(sflag)
+        .arg(password_source)
+        .arg(password_env)
+        .arg(password_file)
+        .arg(credential_target)
 }
 
 pub fn get_session_from_matches<'n>(
@@ -75,16 +103,31 @@ pub fn get_session_from_matches<'n>(
         None => None
     };
 
-    let flags = match options.value_of("flags") {
+    let flags = match options.value_of("sflag") {
         Some(s) => Some(flag_from_str(s)),
         None => None
     };
 
-    let remote_session = RemoteSession::from_prompt_password(
+    let source = options.value_of("password_source").unwrap_or("Prompt");
+    let source_value = match source {
+        "Env" => Some(
+            options.value_of("password_env").unwrap_or(DEFAULT_PASSWORD_ENV)
+        ),
+        "File" => options.value_of("password_file"),
+        "CredManager" => options.value_of("credential_target"),
+        _ => None
+    };
+    let provider = get_credential_provider(
+        source,
+        source_value
+    )?;
+
+    let remote_session = RemoteSession::from_credentials(
         server,
         user,
         domain,
-        flags
+        flags,
+        provider.as_ref()
     )?;
 
     Ok(
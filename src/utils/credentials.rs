@@ -0,0 +1,292 @@
+use std::fmt;
+use std::fs::File;
+use std::path::PathBuf;
+use std::io::{stdin, BufRead, BufReader};
+use zeroize::Zeroizing;
+use crate::errors::WinThingError;
+#[cfg(windows)]
+use std::ptr;
+#[cfg(windows)]
+use winapi::um::wincred::{
+    CredFree,
+    CredReadW,
+    CRED_TYPE_GENERIC,
+    PCREDENTIALW
+};
+
+/// The environment variable read by `EnvCredentials` if none is given.
+pub const DEFAULT_PASSWORD_ENV: &str = "RSWINTHINGS_PASSWORD";
+
+
+/// A password that is zeroed in memory when dropped.
+///
+pub struct Password(Zeroizing<String>);
+
+impl Password {
+    pub fn new(password: String) -> Self {
+        Self(Zeroizing::new(password))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Get the password as a NULL terminated UTF-16 buffer for the Windows
+    /// API. The buffer is also zeroed when dropped.
+    pub fn to_wide(&self) -> Zeroizing<Vec<u16>> {
+        let mut wide: Vec<u16> = Vec::with_capacity(self.0.len() + 1);
+        wide.extend(self.0.encode_utf16());
+        wide.push(0);
+        Zeroizing::new(wide)
+    }
+}
+
+impl fmt::Debug for Password {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Password(<redacted>)")
+    }
+}
+
+
+/// Something that can supply the password for a remote session.
+///
+pub trait CredentialProvider {
+    fn get_password(&self) -> Result<Password, WinThingError>;
+}
+
+
+/// Prompt for the password on the terminal.
+///
+pub struct PromptCredentials;
+
+impl CredentialProvider for PromptCredentials {
+    fn get_password(&self) -> Result<Password, WinThingError> {
+        let password = rpassword::read_password_from_tty(
+            Some("Password: ")
+        )?;
+
+        Ok(Password::new(password))
+    }
+}
+
+
+/// Read the password from an environment variable.
+///
+pub struct EnvCredentials {
+    variable: String
+}
+
+impl EnvCredentials {
+    pub fn new(variable: &str) -> Self {
+        Self {
+            variable: variable.to_owned()
+        }
+    }
+}
+
+impl CredentialProvider for EnvCredentials {
+    fn get_password(&self) -> Result<Password, WinThingError> {
+        match std::env::var(&self.variable) {
+            Ok(password) => Ok(Password::new(password)),
+            Err(e) => Err(
+                WinThingError::credential_error(
+                    format!("Unable to read password from environment variable {}.", self.variable)
+                ).with_source(e)
+            )
+        }
+    }
+}
+
+
+/// Read the password from the first line of a file. On Unix the file must
+/// not be accessible by group or others. On Windows restrict the file's ACL
+/// to the account the tool runs as.
+///
+pub struct FileCredentials {
+    path: PathBuf
+}
+
+impl FileCredentials {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: PathBuf::from(path)
+        }
+    }
+
+    #[cfg(unix)]
+    fn check_permissions(&self, file: &File) -> Result<(), WinThingError> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = file.metadata()?.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(
+                WinThingError::credential_error(
+                    format!(
+                        "Credentials file {} is accessible by other users (mode {:o}).",
+                        self.path.display(), mode & 0o777
+                    )
+                )
+            );
+        }
+
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn check_permissions(&self, _file: &File) -> Result<(), WinThingError> {
+        Ok(())
+    }
+}
+
+impl CredentialProvider for FileCredentials {
+    fn get_password(&self) -> Result<Password, WinThingError> {
+        let file = File::open(&self.path)?;
+        self.check_permissions(&file)?;
+
+        read_password_line(
+            &mut BufReader::new(file)
+        )
+    }
+}
+
+
+/// Read the password from the first line of stdin.
+///
+pub struct StdinCredentials;
+
+impl CredentialProvider for StdinCredentials {
+    fn get_password(&self) -> Result<Password, WinThingError> {
+        let stdin = stdin();
+        let mut handle = stdin.lock();
+        read_password_line(&mut handle)
+    }
+}
+
+
+/// Read a generic credential from the Windows Credential Manager, e.g. one
+/// stored with `cmdkey /generic:<target> /user:<user> /pass`.
+///
+#[cfg(windows)]
+pub struct CredentialManagerCredentials {
+    target: String
+}
+
+#[cfg(windows)]
+impl CredentialManagerCredentials {
+    pub fn new(target: &str) -> Self {
+        Self {
+            target: target.to_owned()
+        }
+    }
+}
+
+#[cfg(windows)]
+impl CredentialProvider for CredentialManagerCredentials {
+    fn get_password(&self) -> Result<Password, WinThingError> {
+        let mut target_u16: Vec<u16> = self.target.encode_utf16().collect();
+        target_u16.push(0);
+
+        let mut credential: PCREDENTIALW = ptr::null_mut();
+        let result = unsafe {
+            CredReadW(
+                target_u16.as_ptr(),
+                CRED_TYPE_GENERIC,
+                0,
+                &mut credential
+            )
+        };
+
+        if result == 0 {
+            return Err(
+                WinThingError::credential_error(
+                    format!("Unable to read credential {} from the Credential Manager.", self.target)
+                ).with_source(WinThingError::from_windows_last_error())
+            );
+        }
+
+        // The blob of a generic credential is the UTF-16 password
+        let password = unsafe {
+            let blob_size = (*credential).CredentialBlobSize as usize;
+            let blob = std::slice::from_raw_parts(
+                (*credential).CredentialBlob as *const u16,
+                blob_size / 2
+            );
+            let password = String::from_utf16_lossy(blob);
+
+            ptr::write_bytes(
+                (*credential).CredentialBlob,
+                0,
+                blob_size
+            );
+            CredFree(credential as *mut _);
+
+            password
+        };
+
+        Ok(Password::new(password))
+    }
+}
+
+
+/// Read a password from the first line of a reader without the line ending.
+///
+pub fn read_password_line<R: BufRead>(reader: &mut R) -> Result<Password, WinThingError> {
+    let mut line = Zeroizing::new(String::new());
+    let size = reader.read_line(&mut line)?;
+    if size == 0 {
+        return Err(
+            WinThingError::credential_error(
+                "No password was provided.".to_owned()
+            )
+        );
+    }
+
+    while line.ends_with('\n') || line.ends_with('\r') {
+        line.pop();
+    }
+
+    Ok(Password(line))
+}
+
+
+/// Get a credential provider by name. `value` is the environment variable,
+/// file path or Credential Manager target the source needs.
+///
+pub fn get_credential_provider(
+    source: &str,
+    value: Option<&str>
+) -> Result<Box<dyn CredentialProvider>, WinThingError> {
+    match source {
+        "Prompt" => Ok(Box::new(PromptCredentials)),
+        "Stdin" => Ok(Box::new(StdinCredentials)),
+        "Env" => Ok(
+            Box::new(
+                EnvCredentials::new(
+                    value.unwrap_or(DEFAULT_PASSWORD_ENV)
+                )
+            )
+        ),
+        "File" => match value {
+            Some(path) => Ok(Box::new(FileCredentials::new(path))),
+            None => Err(
+                WinThingError::cli_error(
+                    "A password file is required for the File password source.".to_owned()
+                )
+            )
+        },
+        #[cfg(windows)]
+        "CredManager" => match value {
+            Some(target) => Ok(Box::new(CredentialManagerCredentials::new(target))),
+            None => Err(
+                WinThingError::cli_error(
+                    "A credential target is required for the CredManager password source.".to_owned()
+                )
+            )
+        },
+        other => Err(
+            WinThingError::cli_error(
+                format!("{} is not a known password source.", other)
+            )
+        )
+    }
+}
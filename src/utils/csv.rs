@@ -0,0 +1,20 @@
+/// Quote a CSV field if it holds a comma or quote. Line breaks are replaced
+/// with spaces so every record stays on one line.
+///
+pub fn escape_csv_field(value: &str) -> String {
+    let value = value.replace(['\r', '\n'], " ");
+    match value.contains([',', '"']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value
+    }
+}
+
+
+/// Join fields into a CSV line.
+///
+pub fn get_csv_line<S: AsRef<str>>(fields: &[S]) -> String {
+    fields.iter()
+        .map(|f| escape_csv_field(f.as_ref()))
+        .collect::<Vec<String>>()
+        .join(",")
+}
@@ -0,0 +1,183 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use crate::errors::WinThingError;
+use crate::utils::credentials::{
+    get_credential_provider,
+    CredentialProvider
+};
+
+/// The authentication methods a host can use.
+pub const LOGIN_FLAGS: [&str; 4] = ["Default", "Negotiate", "Kerberos", "NTLM"];
+/// The password sources a host can use.
+pub const PASSWORD_SOURCES: [&str; 5] = ["Prompt", "Env", "File", "Stdin", "CredManager"];
+
+
+/// A channel to subscribe to on a host. Given as just the channel name or
+/// as an object with its own XPath query.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum HostChannel {
+    Name(String),
+    Query {
+        channel: String,
+        query: Option<String>
+    }
+}
+
+impl HostChannel {
+    pub fn get_channel(&self) -> &str {
+        match self {
+            HostChannel::Name(channel) => channel,
+            HostChannel::Query { channel, .. } => channel
+        }
+    }
+
+    pub fn get_query(&self) -> Option<&str> {
+        match self {
+            HostChannel::Name(_) => None,
+            HostChannel::Query { query, .. } => query.as_deref()
+        }
+    }
+}
+
+
+/// A host to collect events from. `name` tags every event from the host.
+/// Without a `server` the local machine is used. Without `channels` every
+/// channel on the host that can be subscribed to is used.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostEntry {
+    pub name: String,
+    #[serde(default)]
+    pub server: Option<String>,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub domain: Option<String>,
+    #[serde(default)]
+    pub sflag: Option<String>,
+    #[serde(default)]
+    pub password_source: Option<String>,
+    /// The environment variable, file or Credential Manager target for the
+    /// password source.
+    #[serde(default)]
+    pub password_value: Option<String>,
+    #[serde(default)]
+    pub channels: Vec<HostChannel>,
+    /// The query used for channels that don't have their own.
+    #[serde(default)]
+    pub query: Option<String>
+}
+
+impl HostEntry {
+    /// Get the query to use for a channel.
+    pub fn get_channel_query(&self, channel: &HostChannel) -> Option<String> {
+        channel.get_query()
+            .or(self.query.as_deref())
+            .map(|q| q.to_owned())
+    }
+
+    pub fn get_credential_provider(&self) -> Result<Box<dyn CredentialProvider>, WinThingError> {
+        get_credential_provider(
+            self.password_source.as_deref().unwrap_or("Prompt"),
+            self.password_value.as_deref()
+        )
+    }
+
+    fn validate(&self) -> Result<(), WinThingError> {
+        if self.name.is_empty() {
+            return Err(
+                WinThingError::cli_error(
+                    "Inventory host is missing a name.".to_owned()
+                )
+            );
+        }
+
+        if let Some(flag) = &self.sflag {
+            if !LOGIN_FLAGS.contains(&flag.as_str()) {
+                return Err(
+                    WinThingError::cli_error(
+                        format!("Host {} has an unknown sflag: {}", self.name, flag)
+                    )
+                );
+            }
+        }
+
+        if let Some(source) = &self.password_source {
+            if !PASSWORD_SOURCES.contains(&source.as_str()) {
+                return Err(
+                    WinThingError::cli_error(
+                        format!("Host {} has an unknown password_source: {}", self.name, source)
+                    )
+                );
+            }
+
+            // Stdin can only be read once, but every host and every
+            // reconnect reads the password again
+            if source == "Stdin" {
+                return Err(
+                    WinThingError::cli_error(
+                        format!("Host {} can not use the Stdin password_source in an inventory, use Env, File or CredManager.", self.name)
+                    )
+                );
+            }
+        }
+
+        if self.user.is_some() && self.server.is_none() {
+            return Err(
+                WinThingError::cli_error(
+                    format!("Host {} has a user but no server.", self.name)
+                )
+            );
+        }
+
+        Ok(())
+    }
+}
+
+
+/// The hosts for `listen_events` to collect from.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HostInventory {
+    pub hosts: Vec<HostEntry>
+}
+
+impl HostInventory {
+    pub fn from_path(path: &str) -> Result<Self, WinThingError> {
+        let file = File::open(path)?;
+        let inventory: Self = serde_json::from_reader(
+            BufReader::new(file)
+        )?;
+
+        inventory.validate()?;
+        Ok(inventory)
+    }
+
+    pub fn from_json_str(json_str: &str) -> Result<Self, WinThingError> {
+        let inventory: Self = serde_json::from_str(json_str)?;
+
+        inventory.validate()?;
+        Ok(inventory)
+    }
+
+    fn validate(&self) -> Result<(), WinThingError> {
+        let mut names: HashSet<&str> = HashSet::new();
+        for host in &self.hosts {
+            host.validate()?;
+
+            if !names.insert(&host.name) {
+                return Err(
+                    WinThingError::cli_error(
+                        format!("Host {} is in the inventory more than once.", host.name)
+                    )
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
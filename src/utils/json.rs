@@ -1,60 +1,674 @@
-use treediff;
-use serde_json::Value;
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use crate::errors::WinThingError;
 
-use treediff::tools::ChangeType::*;
-use treediff::tools::Recorder;
 
+/// A single RFC 6902 JSON Patch operation.
+/// https://tools.ietf.org/html/rfc6902
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value }
+}
 
+
+/// A RFC 6902 JSON Patch. Patches generated by `get_json_patch` precede every
+/// `remove` and `replace` with a `test` of the old value, which is what makes
+/// them reversible.
+///
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct JsonPatch(pub Vec<PatchOperation>);
+
+impl JsonPatch {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn to_json_value(&self) -> Result<Value, WinThingError> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    /// Apply the patch to a Value. The target is left partially patched if
+    /// an operation fails.
+    pub fn apply(&self, target: &mut Value) -> Result<(), WinThingError> {
+        for operation in &self.0 {
+            apply_operation(
+                target,
+                operation
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the patch that undoes this patch. Every `remove` and `replace` must
+    /// be preceded by a `test` holding the old value.
+    pub fn reversed(&self) -> Result<JsonPatch, WinThingError> {
+        let mut groups: Vec<Vec<PatchOperation>> = Vec::new();
+        let mut previous_test: Option<(String, Value)> = None;
+
+        for operation in &self.0 {
+            let prior = previous_test.take();
+
+            // Tests that do not guard a remove or replace are kept as is
+            let (old_value, standalone) = match (operation, prior) {
+                (PatchOperation::Remove { path }, Some((p, v))) |
+                (PatchOperation::Replace { path, .. }, Some((p, v))) if &p == path => {
+                    (Some(v), None)
+                },
+                (_, Some((p, v))) => {
+                    (None, Some(PatchOperation::Test { path: p, value: v }))
+                },
+                (_, None) => (None, None)
+            };
+
+            if let Some(test) = standalone {
+                groups.push(vec![test]);
+            }
+
+            let group = match operation {
+                PatchOperation::Test { path, value } => {
+                    previous_test = Some((path.to_owned(), value.to_owned()));
+                    continue;
+                },
+                PatchOperation::Add { path, value } => vec![
+                    PatchOperation::Test { path: path.to_owned(), value: value.to_owned() },
+                    PatchOperation::Remove { path: path.to_owned() }
+                ],
+                PatchOperation::Remove { path } => {
+                    let old_value = match old_value {
+                        Some(v) => v,
+                        None => return Err(
+                            WinThingError::json_patch_error(
+                                format!("remove at '{}' has no preceding test and can not be reversed.", path)
+                            )
+                        )
+                    };
+
+                    vec![
+                        PatchOperation::Add { path: path.to_owned(), value: old_value }
+                    ]
+                },
+                PatchOperation::Replace { path, value } => {
+                    let old_value = match old_value {
+                        Some(v) => v,
+                        None => return Err(
+                            WinThingError::json_patch_error(
+                                format!("replace at '{}' has no preceding test and can not be reversed.", path)
+                            )
+                        )
+                    };
+
+                    vec![
+                        PatchOperation::Test { path: path.to_owned(), value: value.to_owned() },
+                        PatchOperation::Replace { path: path.to_owned(), value: old_value }
+                    ]
+                },
+                PatchOperation::Move { from, path } => vec![
+                    PatchOperation::Move { from: path.to_owned(), path: from.to_owned() }
+                ],
+                PatchOperation::Copy { path, .. } => vec![
+                    PatchOperation::Remove { path: path.to_owned() }
+                ]
+            };
+
+            groups.push(group);
+        }
+
+        if let Some((path, value)) = previous_test {
+            groups.push(vec![PatchOperation::Test { path, value }]);
+        }
+
+        Ok(
+            JsonPatch(
+                groups.into_iter()
+                    .rev()
+                    .flatten()
+                    .collect()
+            )
+        )
+    }
+}
+
+
+/// Options for generating differences between two Values.
+///
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    array_keys: Vec<String>
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match array elements by the value found at a JSON Pointer within each
+    /// element (for example "/header/instance") instead of by position. Keys
+    /// are tried in the order they were added and a key is only used for an
+    /// array when every element has a unique value for it.
+    pub fn with_array_key(mut self, pointer: &str) -> Self {
+        self.array_keys.push(pointer.to_string());
+        self
+    }
+}
+
+
+/// Escape a key for use as a JSON Pointer reference token (RFC 6901).
+///
+pub fn escape_pointer_token(token: &str) -> String {
+    token.replace("~", "~0").replace("/", "~1")
+}
+
+
+/// Split a JSON Pointer (RFC 6901) into its unescaped reference tokens.
+///
+pub fn parse_pointer(pointer: &str) -> Result<Vec<String>, WinThingError> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if !pointer.starts_with('/') {
+        return Err(
+            WinThingError::json_patch_error(
+                format!("Invalid JSON Pointer '{}'. Pointers must start with '/'.", pointer)
+            )
+        );
+    }
+
+    Ok(
+        pointer[1..].split('/')
+            .map(|t| t.replace("~1", "/").replace("~0", "~"))
+            .collect()
+    )
+}
+
+
+/// Generate the RFC 6902 patch that transforms `cmp1` into `cmp2`.
+///
+pub fn get_json_patch(
+    cmp1: &Value,
+    cmp2: &Value,
+    options: &DiffOptions
+) -> JsonPatch {
+    let mut operations: Vec<PatchOperation> = Vec::new();
+
+    diff_values(
+        "",
+        cmp1,
+        cmp2,
+        options,
+        &mut operations
+    );
+
+    JsonPatch(operations)
+}
+
+
+/// Get a mapping of JSON Pointer paths to the changes made at each path.
+///
 pub fn get_difference_value(
-    cmp1: &Value, 
+    cmp1: &Value,
     cmp2: &Value
 ) -> Value {
-    let mut recorder = Recorder::default();
-    let mut return_val = json!({});
+    get_difference_value_with_options(
+        cmp1,
+        cmp2,
+        &DiffOptions::default()
+    )
+}
+
 
-    treediff::diff(
-        cmp1, 
-        cmp2, 
-        &mut recorder
+/// Get a mapping of JSON Pointer paths to the changes made at each path
+/// using the given DiffOptions.
+///
+pub fn get_difference_value_with_options(
+    cmp1: &Value,
+    cmp2: &Value,
+    options: &DiffOptions
+) -> Value {
+    let patch = get_json_patch(
+        cmp1,
+        cmp2,
+        options
     );
 
-    for call in recorder.calls {
-        match call {
-            Modified(keys, v1, v2, ) => {
-                let mut path_list: Vec<String> = Vec::new();
-                for key in keys {
-                    path_list.push(key.to_string())
+    let mut return_map = Map::new();
+    let mut old_value: Option<Value> = None;
+
+    for operation in patch.0 {
+        let (path, change) = match operation {
+            PatchOperation::Test { value, .. } => {
+                old_value = Some(value);
+                continue;
+            },
+            PatchOperation::Replace { path, value } => {
+                (path, json!({
+                    "before": old_value.take(),
+                    "after": value
+                }))
+            },
+            PatchOperation::Add { path, value } => {
+                (path, json!({
+                    "created": value
+                }))
+            },
+            PatchOperation::Remove { path } => {
+                (path, json!({
+                    "removed": old_value.take()
+                }))
+            },
+            PatchOperation::Move { from, path } => {
+                (path, json!({
+                    "moved_from": from
+                }))
+            },
+            PatchOperation::Copy { from, path } => {
+                (path, json!({
+                    "copied_from": from
+                }))
+            }
+        };
+
+        // An index can be removed and then reused by a new element
+        match return_map.get_mut(&path) {
+            Some(Value::Object(existing)) => {
+                if let Value::Object(change_map) = change {
+                    existing.extend(change_map);
                 }
-                let path_str = path_list.join(".");
-                
-                return_val[path_str] = json!({
-                    "before": v1.to_owned(),
-                    "after": v2.to_owned()
-                });
             },
-            Added(keys, value) => {
-                let mut path_list: Vec<String> = Vec::new();
-                for key in keys {
-                    path_list.push(key.to_string())
+            _ => {
+                return_map.insert(path, change);
+            }
+        }
+    }
+
+    Value::Object(return_map)
+}
+
+
+fn child_path(path: &str, token: &str) -> String {
+    format!("{}/{}", path, escape_pointer_token(token))
+}
+
+
+fn diff_values(
+    path: &str,
+    before: &Value,
+    after: &Value,
+    options: &DiffOptions,
+    operations: &mut Vec<PatchOperation>
+) {
+    if before == after {
+        return;
+    }
+
+    match (before, after) {
+        (Value::Object(before_map), Value::Object(after_map)) => {
+            for (key, before_value) in before_map {
+                let key_path = child_path(path, key);
+                match after_map.get(key) {
+                    Some(after_value) => {
+                        diff_values(
+                            &key_path,
+                            before_value,
+                            after_value,
+                            options,
+                            operations
+                        );
+                    },
+                    None => {
+                        operations.push(PatchOperation::Test { path: key_path.clone(), value: before_value.to_owned() });
+                        operations.push(PatchOperation::Remove { path: key_path });
+                    }
                 }
-                let path_str = path_list.join(".");
+            }
 
-                return_val[path_str] = json!({
-                    "created": value.to_owned()
-                });
-            },
-            Removed(keys, _value) => {
-                let mut path_list: Vec<String> = Vec::new();
-                for key in keys {
-                    path_list.push(key.to_string())
+            for (key, after_value) in after_map {
+                if !before_map.contains_key(key) {
+                    operations.push(PatchOperation::Add {
+                        path: child_path(path, key),
+                        value: after_value.to_owned()
+                    });
                 }
-                let path_str = path_list.join(".");
+            }
+        },
+        (Value::Array(before_list), Value::Array(after_list)) => {
+            match get_array_keys(before_list, after_list, options) {
+                Some((before_keys, after_keys)) => diff_keyed_arrays(
+                    path,
+                    before_list,
+                    after_list,
+                    &before_keys,
+                    &after_keys,
+                    options,
+                    operations
+                ),
+                None => diff_positional_arrays(
+                    path,
+                    before_list,
+                    after_list,
+                    options,
+                    operations
+                )
+            }
+        },
+        _ => {
+            operations.push(PatchOperation::Test { path: path.to_owned(), value: before.to_owned() });
+            operations.push(PatchOperation::Replace { path: path.to_owned(), value: after.to_owned() });
+        }
+    }
+}
+
+
+/// Get the unique key of every element or None if any element is missing
+/// the key or shares it with another element.
+fn get_element_keys(list: &[Value], pointer: &str) -> Option<Vec<String>> {
+    let mut keys: Vec<String> = Vec::with_capacity(list.len());
+
+    for element in list {
+        let key = element.pointer(pointer)?.to_string();
+        if keys.contains(&key) {
+            return None;
+        }
+        keys.push(key);
+    }
+
+    Some(keys)
+}
+
+
+fn get_array_keys(
+    before: &[Value],
+    after: &[Value],
+    options: &DiffOptions
+) -> Option<(Vec<String>, Vec<String>)> {
+    for pointer in &options.array_keys {
+        if let (Some(b), Some(a)) = (get_element_keys(before, pointer), get_element_keys(after, pointer)) {
+            return Some((b, a));
+        }
+    }
+
+    // Arrays holding the same unique elements in a different order are
+    // matched on the elements themselves so reordering shows up as moves.
+    let mut before_keys = get_element_keys(before, "")?;
+    let mut after_keys = get_element_keys(after, "")?;
+    let keys = (before_keys.clone(), after_keys.clone());
+
+    before_keys.sort();
+    after_keys.sort();
+    if before_keys == after_keys {
+        return Some(keys);
+    }
 
-                return_val[path_str] = json!("removed");
+    None
+}
+
+
+fn diff_keyed_arrays(
+    path: &str,
+    before: &[Value],
+    after: &[Value],
+    before_keys: &[String],
+    after_keys: &[String],
+    options: &DiffOptions,
+    operations: &mut Vec<PatchOperation>
+) {
+    // Remove elements whose key is gone, last first so indexes stay valid
+    for (index, key) in before_keys.iter().enumerate().rev() {
+        if !after_keys.contains(key) {
+            let index_path = child_path(path, &index.to_string());
+            operations.push(PatchOperation::Test { path: index_path.clone(), value: before[index].to_owned() });
+            operations.push(PatchOperation::Remove { path: index_path });
+        }
+    }
+
+    let mut current: Vec<&String> = before_keys.iter()
+        .filter(|k| after_keys.contains(k))
+        .collect();
+
+    // Move or add elements so they end up in the order of `after`
+    for (index, key) in after_keys.iter().enumerate() {
+        match current.iter().position(|k| *k == key) {
+            Some(current_index) => {
+                if current_index != index {
+                    operations.push(PatchOperation::Move {
+                        from: child_path(path, &current_index.to_string()),
+                        path: child_path(path, &index.to_string())
+                    });
+                    let moved = current.remove(current_index);
+                    current.insert(index, moved);
+                }
+            },
+            None => {
+                operations.push(PatchOperation::Add {
+                    path: child_path(path, &index.to_string()),
+                    value: after[index].to_owned()
+                });
+                current.insert(index, key);
             }
-            _ => {}
         }
     }
 
-    return_val
-}
\ No newline at end of file
+    // The matched elements now line up, so compare them in place
+    for (index, key) in after_keys.iter().enumerate() {
+        if let Some(before_index) = before_keys.iter().position(|k| k == key) {
+            diff_values(
+                &child_path(path, &index.to_string()),
+                &before[before_index],
+                &after[index],
+                options,
+                operations
+            );
+        }
+    }
+}
+
+
+fn diff_positional_arrays(
+    path: &str,
+    before: &[Value],
+    after: &[Value],
+    options: &DiffOptions,
+    operations: &mut Vec<PatchOperation>
+) {
+    let common = before.len().min(after.len());
+
+    for index in 0..common {
+        diff_values(
+            &child_path(path, &index.to_string()),
+            &before[index],
+            &after[index],
+            options,
+            operations
+        );
+    }
+
+    for index in (common..before.len()).rev() {
+        let index_path = child_path(path, &index.to_string());
+        operations.push(PatchOperation::Test { path: index_path.clone(), value: before[index].to_owned() });
+        operations.push(PatchOperation::Remove { path: index_path });
+    }
+
+    for (index, value) in after.iter().enumerate().skip(common) {
+        operations.push(PatchOperation::Add {
+            path: child_path(path, &index.to_string()),
+            value: value.to_owned()
+        });
+    }
+}
+
+
+fn get_array_index(token: &str, len: usize, allow_end: bool) -> Result<usize, WinThingError> {
+    if allow_end && token == "-" {
+        return Ok(len);
+    }
+
+    let index = match token.parse::<usize>() {
+        Ok(i) => i,
+        Err(_) => return Err(
+            WinThingError::json_patch_error(
+                format!("'{}' is not a valid array index.", token)
+            )
+        )
+    };
+
+    let in_bounds = if allow_end { index <= len } else { index < len };
+    if !in_bounds {
+        return Err(
+            WinThingError::json_patch_error(
+                format!("Array index {} is out of bounds (length {}).", index, len)
+            )
+        );
+    }
+
+    Ok(index)
+}
+
+
+fn get_target<'a>(
+    root: &'a mut Value,
+    tokens: &[String]
+) -> Result<&'a mut Value, WinThingError> {
+    let mut target = root;
+
+    for token in tokens {
+        target = match target {
+            Value::Object(map) => match map.get_mut(token) {
+                Some(v) => v,
+                None => return Err(
+                    WinThingError::json_patch_error(
+                        format!("Key '{}' does not exist.", token)
+                    )
+                )
+            },
+            Value::Array(list) => {
+                let index = get_array_index(token, list.len(), false)?;
+                &mut list[index]
+            },
+            other => return Err(
+                WinThingError::json_patch_error(
+                    format!("Can not reference '{}' in a scalar value {}.", token, other)
+                )
+            )
+        };
+    }
+
+    Ok(target)
+}
+
+
+fn add_value(root: &mut Value, path: &str, value: Value) -> Result<(), WinThingError> {
+    let tokens = parse_pointer(path)?;
+    let (last, parent_tokens) = match tokens.split_last() {
+        Some(s) => s,
+        None => {
+            *root = value;
+            return Ok(());
+        }
+    };
+
+    match get_target(root, parent_tokens)? {
+        Value::Object(map) => {
+            map.insert(last.to_owned(), value);
+        },
+        Value::Array(list) => {
+            let index = get_array_index(last, list.len(), true)?;
+            list.insert(index, value);
+        },
+        other => return Err(
+            WinThingError::json_patch_error(
+                format!("Can not add '{}' to a scalar value {}.", path, other)
+            )
+        )
+    }
+
+    Ok(())
+}
+
+
+fn remove_value(root: &mut Value, path: &str) -> Result<Value, WinThingError> {
+    let tokens = parse_pointer(path)?;
+    let (last, parent_tokens) = match tokens.split_last() {
+        Some(s) => s,
+        None => return Err(
+            WinThingError::json_patch_error(
+                "Can not remove the root of a document.".to_owned()
+            )
+        )
+    };
+
+    match get_target(root, parent_tokens)? {
+        Value::Object(map) => match map.remove(last) {
+            Some(v) => Ok(v),
+            None => Err(
+                WinThingError::json_patch_error(
+                    format!("Can not remove '{}' as it does not exist.", path)
+                )
+            )
+        },
+        Value::Array(list) => {
+            let index = get_array_index(last, list.len(), false)?;
+            Ok(list.remove(index))
+        },
+        other => Err(
+            WinThingError::json_patch_error(
+                format!("Can not remove '{}' from a scalar value {}.", path, other)
+            )
+        )
+    }
+}
+
+
+fn apply_operation(root: &mut Value, operation: &PatchOperation) -> Result<(), WinThingError> {
+    match operation {
+        PatchOperation::Add { path, value } => {
+            add_value(root, path, value.to_owned())
+        },
+        PatchOperation::Remove { path } => {
+            remove_value(root, path)?;
+            Ok(())
+        },
+        PatchOperation::Replace { path, value } => {
+            let tokens = parse_pointer(path)?;
+            let target = get_target(root, &tokens)?;
+            *target = value.to_owned();
+            Ok(())
+        },
+        PatchOperation::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(
+                    WinThingError::json_patch_error(
+                        format!("Can not move '{}' into one of its children '{}'.", from, path)
+                    )
+                );
+            }
+
+            let value = remove_value(root, from)?;
+            add_value(root, path, value)
+        },
+        PatchOperation::Copy { from, path } => {
+            let tokens = parse_pointer(from)?;
+            let value = get_target(root, &tokens)?.to_owned();
+            add_value(root, path, value)
+        },
+        PatchOperation::Test { path, value } => {
+            let tokens = parse_pointer(path)?;
+            let target = get_target(root, &tokens)?;
+            if target != value {
+                return Err(
+                    WinThingError::json_patch_error(
+                        format!("Test failed at '{}'. Expected {} but found {}.", path, value, target)
+                    )
+                );
+            }
+            Ok(())
+        }
+    }
+}
@@ -1,4 +1,11 @@
+#[cfg(windows)]
 pub mod cli;
+pub mod backoff;
+pub mod credentials;
+pub mod csv;
+pub mod inventory;
 pub mod debug;
 pub mod xmltojson;
-pub mod json;
\ No newline at end of file
+pub mod json;
+pub mod pool;
+pub mod time;
\ No newline at end of file
@@ -0,0 +1,459 @@
+use std::thread;
+use std::vec::IntoIter;
+use std::collections::{HashMap, HashSet};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use std::sync::mpsc::{
+    channel,
+    Receiver,
+    RecvTimeoutError,
+    Sender
+};
+use serde::Serialize;
+use crate::errors::{ErrorType, WinThingError};
+
+/// The number of workers used if not given.
+pub const DEFAULT_WORKERS: usize = 8;
+/// How long a task can run before it is reported as timed out.
+pub const DEFAULT_TASK_TIMEOUT: Duration = Duration::from_secs(30);
+/// How many more times `run_shared_tasks` runs the tasks cancelled along
+/// with a timed out one.
+pub const CANCELLED_RETRIES: usize = 2;
+
+/// The Win32 error of a call cancelled with EvtCancel.
+const ERROR_CANCELLED: u32 = 1223;
+
+
+/// Options of `run_tasks`.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolOptions {
+    pub workers: usize,
+    pub timeout: Option<Duration>
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            workers: DEFAULT_WORKERS,
+            timeout: Some(DEFAULT_TASK_TIMEOUT)
+        }
+    }
+}
+
+impl PoolOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Set the per task timeout. `None` waits for every task.
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+
+/// The result of a task and how long it ran (or was waited on).
+///
+#[derive(Debug)]
+pub struct TaskOutcome<T> {
+    pub name: String,
+    pub result: Result<T, WinThingError>,
+    pub elapsed: Duration
+}
+
+impl<T> TaskOutcome<T> {
+    /// Whether the task failed because its call was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        match &self.result {
+            Err(e) => e.get_win32_code() == Some(ERROR_CANCELLED),
+            Ok(_) => false
+        }
+    }
+
+    /// Get a record of the task's error, if it failed.
+    pub fn get_error_record(&self, status: &'static str) -> Option<TaskErrorRecord> {
+        match &self.result {
+            Ok(_) => None,
+            Err(e) => Some(
+                TaskErrorRecord {
+                    status,
+                    name: self.name.to_owned(),
+                    error_type: e.kind.as_str(),
+                    code: e.get_win32_code(),
+                    code_name: e.code.and_then(|c| c.get_name()),
+                    message: e.message.trim().to_owned(),
+                    elapsed_ms: self.elapsed.as_millis() as u64
+                }
+            )
+        }
+    }
+}
+
+
+/// A structured record of a failed or timed out task, output along with
+/// the results.
+///
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TaskErrorRecord {
+    pub status: &'static str,
+    pub name: String,
+    pub error_type: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_name: Option<&'static str>,
+    pub message: String,
+    pub elapsed_ms: u64
+}
+
+impl TaskErrorRecord {
+    pub fn is_timeout(&self) -> bool {
+        self.error_type == ErrorType::TimeoutError.as_str()
+    }
+}
+
+
+/// Run `task` for each name on a pool of workers. Outcomes are sent as
+/// tasks finish, so they are not in the order of `names`, and the receiver
+/// ends once every task has an outcome.
+///
+/// A task that times out is reported as a timeout but can't be stopped, so
+/// it keeps its worker until it returns. Use `run_cancellable_tasks` for
+/// tasks that can be cancelled.
+///
+pub fn run_tasks<T, F>(
+    names: Vec<String>,
+    options: PoolOptions,
+    task: F
+) -> Receiver<TaskOutcome<T>>
+    where T: Send + 'static,
+          F: Fn(&str) -> Result<T, WinThingError> + Send + Sync + 'static
+{
+    run_cancellable_tasks(names, options, task, |_: &str| {})
+}
+
+
+/// Run `task` for each name on a fixed pool of `options.workers` threads,
+/// calling `cancel` with the name of each task that times out so it can
+/// return and free its worker. The result of a timed out task is
+/// discarded.
+///
+/// If every worker is still running a timed out task a timeout after it
+/// was cancelled, the tasks that were not started are reported as timed
+/// out rather than waiting on the stuck workers.
+///
+pub fn run_cancellable_tasks<T, F, C>(
+    names: Vec<String>,
+    options: PoolOptions,
+    task: F,
+    cancel: C
+) -> Receiver<TaskOutcome<T>>
+    where T: Send + 'static,
+          F: Fn(&str) -> Result<T, WinThingError> + Send + Sync + 'static,
+          C: Fn(&str) + Send + 'static
+{
+    let (outcome_tx, outcome_rx) = channel();
+    let (event_tx, event_rx) = channel();
+    let total = names.len();
+    let workers = options.workers.max(1);
+    let queue = Arc::new(Mutex::new(names.into_iter()));
+    let task = Arc::new(task);
+
+    for worker in 0..workers {
+        let queue = Arc::clone(&queue);
+        let task = Arc::clone(&task);
+        let event_tx = event_tx.clone();
+
+        thread::Builder::new()
+            .name(format!("pool-worker-{}", worker))
+            .spawn(move || run_worker(worker, &queue, task.as_ref(), &event_tx))
+            .expect("Error spawning pool worker");
+    }
+    drop(event_tx);
+
+    let watchdog = Watchdog {
+        workers,
+        timeout: options.timeout,
+        queue,
+        running: HashMap::new(),
+        cancelled: HashMap::new(),
+        stuck: HashSet::new(),
+        outcome_tx
+    };
+    thread::Builder::new()
+        .name("pool-watchdog".to_owned())
+        .spawn(move || watchdog.run(total, event_rx, cancel))
+        .expect("Error spawning pool watchdog");
+
+    outcome_rx
+}
+
+
+/// Run `task` for each name like `run_cancellable_tasks`, for tasks that
+/// share what `cancel` cancels, such as the calls on one remote session.
+/// Cancelling a timed out task then also cancels the tasks running beside
+/// it, so the ones that fail with ERROR_CANCELLED after a cancel are run
+/// again, up to `CANCELLED_RETRIES` times. A task still cancelled after
+/// that is reported with its error, see `TaskOutcome::is_cancelled`.
+///
+pub fn run_shared_tasks<T, F, C>(
+    names: Vec<String>,
+    options: PoolOptions,
+    task: F,
+    cancel: C
+) -> Receiver<TaskOutcome<T>>
+    where T: Send + 'static,
+          F: Fn(&str) -> Result<T, WinThingError> + Send + Sync + 'static,
+          C: Fn(&str) + Send + Sync + 'static
+{
+    let (outcome_tx, outcome_rx) = channel();
+    let task = Arc::new(task);
+    let cancel = Arc::new(cancel);
+
+    thread::Builder::new()
+        .name("pool-rounds".to_owned())
+        .spawn(move || {
+            let mut names = names;
+
+            for round in 0..=CANCELLED_RETRIES {
+                let round_task = Arc::clone(&task);
+                let round_cancel = Arc::clone(&cancel);
+                // Set before cancelling, so it is seen by any task that
+                // fails because of it
+                let cancelled = Arc::new(AtomicBool::new(false));
+                let round_cancelled = Arc::clone(&cancelled);
+
+                let outcomes = run_cancellable_tasks(
+                    names,
+                    options,
+                    move |name| round_task(name),
+                    move |name| {
+                        round_cancelled.store(true, Ordering::SeqCst);
+                        round_cancel(name);
+                    }
+                );
+
+                let mut retries = Vec::new();
+                for outcome in outcomes {
+                    if round < CANCELLED_RETRIES &&
+                        outcome.is_cancelled() &&
+                        cancelled.load(Ordering::SeqCst)
+                    {
+                        debug!("Retrying task {} cancelled with a timed out task", outcome.name);
+                        retries.push(outcome.name);
+                        continue;
+                    }
+
+                    // The receiver was dropped
+                    if outcome_tx.send(outcome).is_err() {
+                        return;
+                    }
+                }
+
+                if retries.is_empty() {
+                    break;
+                }
+                names = retries;
+            }
+        })
+        .expect("Error spawning pool rounds");
+
+    outcome_rx
+}
+
+
+/// What a worker reports to the watchdog.
+enum TaskEvent<T> {
+    Started(usize, String, Instant),
+    Finished(usize, TaskOutcome<T>)
+}
+
+
+/// Run tasks from the queue until it is empty or the outcomes are no longer
+/// received.
+fn run_worker<T, F>(
+    worker: usize,
+    queue: &Mutex<IntoIter<String>>,
+    task: &F,
+    event_tx: &Sender<TaskEvent<T>>
+)
+    where F: Fn(&str) -> Result<T, WinThingError>
+{
+    loop {
+        let name = match queue.lock() {
+            Ok(mut q) => match q.next() {
+                Some(n) => n,
+                None => break
+            },
+            Err(_) => break
+        };
+
+        let start = Instant::now();
+        if event_tx.send(TaskEvent::Started(worker, name.clone(), start)).is_err() {
+            break;
+        }
+
+        // A panicking task is an error, the worker carries on
+        let result = match catch_unwind(AssertUnwindSafe(|| task(&name))) {
+            Ok(r) => r,
+            Err(_) => Err(
+                WinThingError::unhandled(
+                    format!("{} panicked.", name)
+                )
+            )
+        };
+
+        let outcome = TaskOutcome {
+            name,
+            result,
+            elapsed: start.elapsed()
+        };
+        if event_tx.send(TaskEvent::Finished(worker, outcome)).is_err() {
+            break;
+        }
+    }
+}
+
+
+/// Forwards outcomes and times out the tasks the workers are running.
+struct Watchdog<T> {
+    workers: usize,
+    timeout: Option<Duration>,
+    queue: Arc<Mutex<IntoIter<String>>>,
+    /// The task each worker is running, by worker
+    running: HashMap<usize, (String, Instant)>,
+    /// When each worker with a cancelled task is considered stuck
+    cancelled: HashMap<usize, Instant>,
+    /// The workers still running a task a timeout after it was cancelled
+    stuck: HashSet<usize>,
+    outcome_tx: Sender<TaskOutcome<T>>
+}
+
+impl<T> Watchdog<T> {
+    fn run<C: Fn(&str)>(mut self, total: usize, event_rx: Receiver<TaskEvent<T>>, cancel: C) {
+        let mut reported = 0;
+
+        while reported < total {
+            let event = match self.get_next_deadline() {
+                Some(deadline) => event_rx.recv_timeout(
+                    deadline.saturating_duration_since(Instant::now())
+                ),
+                None => event_rx.recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected)
+            };
+
+            let outcomes = match event {
+                Ok(TaskEvent::Started(worker, name, start)) => {
+                    self.running.insert(worker, (name, start));
+                    Vec::new()
+                },
+                Ok(TaskEvent::Finished(worker, outcome)) => {
+                    self.running.remove(&worker);
+                    self.stuck.remove(&worker);
+                    match self.cancelled.remove(&worker) {
+                        // Already reported as timed out
+                        Some(_) => Vec::new(),
+                        None => vec![outcome]
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => self.time_out(&cancel),
+                // Every worker has stopped
+                Err(RecvTimeoutError::Disconnected) => break
+            };
+
+            for outcome in outcomes {
+                reported += 1;
+                // The receiver was dropped
+                if self.outcome_tx.send(outcome).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Get when the next running task times out or the next cancelled one
+    /// is considered stuck.
+    fn get_next_deadline(&self) -> Option<Instant> {
+        let timeout = self.timeout?;
+
+        self.running.iter()
+            .filter(|(worker, _)| !self.cancelled.contains_key(worker))
+            .map(|(_, (_, start))| *start + timeout)
+            .chain(
+                self.cancelled.iter()
+                    .filter(|(worker, _)| !self.stuck.contains(worker))
+                    .map(|(_, deadline)| *deadline)
+            )
+            .min()
+    }
+
+    /// Cancel the tasks that ran past the timeout, and give up on the queue
+    /// if every worker is stuck on a cancelled task.
+    fn time_out<C: Fn(&str)>(&mut self, cancel: &C) -> Vec<TaskOutcome<T>> {
+        let timeout = match self.timeout {
+            Some(t) => t,
+            None => return Vec::new()
+        };
+        let now = Instant::now();
+        let mut outcomes = Vec::new();
+
+        for (worker, (name, start)) in &self.running {
+            if self.cancelled.contains_key(worker) || now < *start + timeout {
+                continue;
+            }
+
+            debug!("Cancelling task {} after {:?}", name, timeout);
+            cancel(name);
+            self.cancelled.insert(*worker, now + timeout);
+            outcomes.push(
+                TaskOutcome {
+                    name: name.to_owned(),
+                    result: Err(
+                        WinThingError::timeout_error(
+                            format!("{} did not finish within {:?}.", name, timeout)
+                        )
+                    ),
+                    elapsed: now - *start
+                }
+            );
+        }
+
+        for (worker, deadline) in &self.cancelled {
+            if *deadline <= now {
+                self.stuck.insert(*worker);
+            }
+        }
+
+        if self.stuck.len() >= self.workers {
+            let queued: Vec<String> = match self.queue.lock() {
+                Ok(mut q) => q.by_ref().collect(),
+                Err(_) => Vec::new()
+            };
+
+            for name in queued {
+                outcomes.push(
+                    TaskOutcome {
+                        result: Err(
+                            WinThingError::timeout_error(
+                                format!("{} was not started, every worker is stuck on a timed out task.", name)
+                            )
+                        ),
+                        name,
+                        elapsed: Duration::from_secs(0)
+                    }
+                );
+            }
+        }
+
+        outcomes
+    }
+}
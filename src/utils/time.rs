@@ -0,0 +1,17 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Where the time an event was created is found in its JSON.
+const SYSTEM_TIME_POINTER: &str = "/Event/System/TimeCreated_attributes/SystemTime";
+
+
+/// Get the time an event was created from its JSON.
+///
+pub fn get_event_timestamp(event: &Value) -> Option<DateTime<Utc>> {
+    let system_time = event.pointer(SYSTEM_TIME_POINTER)?
+        .as_str()?;
+
+    DateTime::parse_from_rfc3339(system_time)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+}
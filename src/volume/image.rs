@@ -0,0 +1,318 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use mft::MftEntry;
+use byteorder::{ByteOrder, LittleEndian};
+use crate::errors::WinThingError;
+use crate::mft::LogicalEntry;
+use crate::mft::enumerate::MftSource;
+use crate::mft::raw::{
+    apply_fixups,
+    get_extension_entries_with_reader,
+    get_mft_data_runs,
+    is_file_record,
+    DataRun,
+    RawAttribute
+};
+use crate::mft::stream::{
+    get_stream_attributes,
+    ClusterReader
+};
+
+
+/// The largest power of two accepted for the cluster and file record sizes.
+const MAX_SIZE_EXPONENT: u32 = 31;
+
+
+/// Get a power of two size of the boot sector. Corrupt boot sectors can
+/// hold any exponent, so it is checked instead of shifted blindly.
+fn get_power_of_two_size(exponent: u32, field: &str) -> Result<u32, WinThingError> {
+    let size = match exponent {
+        e if e <= MAX_SIZE_EXPONENT => 1u32.checked_shl(e),
+        _ => None
+    };
+
+    size.ok_or_else(||
+        WinThingError::invalid_mft_record(
+            format!("Boot sector {} exponent {} is out of range.", field, exponent)
+        )
+    )
+}
+
+
+/// The parts of the NTFS boot sector needed to find the MFT.
+///
+#[derive(Debug, Clone)]
+pub struct VolumeBootRecord {
+    pub bytes_per_sector: u16,
+    pub bytes_per_cluster: u32,
+    pub total_sectors: u64,
+    pub mft_lcn: u64,
+    pub bytes_per_file_record: u32
+}
+
+impl VolumeBootRecord {
+    pub fn from_buffer(buffer: &[u8]) -> Result<Self, WinThingError> {
+        if buffer.len() < 0x48 || &buffer[0x03..0x0B] != b"NTFS    " {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    "Buffer is not an NTFS boot sector.".to_owned()
+                )
+            );
+        }
+
+        let bytes_per_sector = LittleEndian::read_u16(&buffer[0x0B..0x0D]);
+
+        // Values above 0x80 are a negative power of two
+        let sectors_per_cluster: u32 = match buffer[0x0D] {
+            v if v > 0x80 => get_power_of_two_size(256 - v as u32, "sectors per cluster")?,
+            v => v as u32
+        };
+        let bytes_per_cluster = (bytes_per_sector as u32).checked_mul(sectors_per_cluster)
+            .ok_or_else(|| WinThingError::invalid_mft_record(
+                format!("Boot sector cluster size of {} sectors is too large.", sectors_per_cluster)
+            ))?;
+
+        // Positive values are clusters, negative values a power of two in bytes
+        let bytes_per_file_record: u32 = match buffer[0x40] as i8 {
+            v if v < 0 => get_power_of_two_size(-(v as i32) as u32, "file record size")?,
+            v => (v as u32).checked_mul(bytes_per_cluster)
+                .ok_or_else(|| WinThingError::invalid_mft_record(
+                    format!("Boot sector file record size of {} clusters is too large.", v)
+                ))?
+        };
+
+        if bytes_per_cluster == 0 || bytes_per_file_record == 0 {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    "Boot sector has a zero cluster or file record size.".to_owned()
+                )
+            );
+        }
+
+        Ok(
+            Self {
+                bytes_per_sector,
+                bytes_per_cluster,
+                total_sectors: LittleEndian::read_u64(&buffer[0x28..0x30]),
+                mft_lcn: LittleEndian::read_u64(&buffer[0x30..0x38]),
+                bytes_per_file_record
+            }
+        )
+    }
+}
+
+
+/// Struct for reading an NTFS volume from a raw image. The volume can start
+/// at an offset in the image (e.g. a partition in a disk image).
+///
+pub struct NtfsImage<R: Read + Seek> {
+    cluster_reader: ClusterReader<R>,
+    boot_record: VolumeBootRecord,
+    mft_runs: Vec<DataRun>,
+    mft_size: u64
+}
+
+impl NtfsImage<File> {
+    pub fn from_path(path: &str, volume_offset: u64) -> Result<Self, WinThingError> {
+        let file = File::open(path)?;
+        Self::from_reader(
+            file,
+            volume_offset
+        )
+    }
+}
+
+impl<R: Read + Seek> NtfsImage<R> {
+    pub fn from_reader(mut reader: R, volume_offset: u64) -> Result<Self, WinThingError> {
+        let mut boot_sector = vec![0u8; 512];
+        reader.seek(SeekFrom::Start(volume_offset))?;
+        reader.read_exact(&mut boot_sector)?;
+
+        let boot_record = VolumeBootRecord::from_buffer(
+            &boot_sector
+        )?;
+
+        let mut cluster_reader = ClusterReader::new(
+            reader,
+            boot_record.bytes_per_cluster
+        ).with_volume_offset(volume_offset);
+
+        // Entry 0 ($MFT) describes where the rest of the MFT is
+        let record_size = boot_record.bytes_per_file_record as usize;
+        let mft_start = vec![
+            DataRun {
+                lcn: Some(boot_record.mft_lcn),
+                length: (record_size as u64 / boot_record.bytes_per_cluster as u64).max(1)
+            }
+        ];
+        let mut mft_record = cluster_reader.read_run_range(
+            &mft_start,
+            0,
+            record_size
+        )?;
+        apply_fixups(
+            &mut mft_record,
+            boot_record.bytes_per_sector as usize
+        )?;
+
+        // A fragmented $MFT has the rest of its runs in extension records.
+        // The records past the initialized size of $DATA were never written.
+        let extension_entries = get_extension_entries_with_reader(
+            &mft_record,
+            0,
+            |attribute| cluster_reader.read_stream(std::slice::from_ref(attribute))
+        )?;
+        let bytes_per_sector = boot_record.bytes_per_sector as usize;
+        let (mft_runs, mft_size) = get_mft_data_runs(
+            &mft_record,
+            &extension_entries,
+            |entry, runs| {
+                let mut buffer = cluster_reader.read_run_range(
+                    runs,
+                    entry * record_size as u64,
+                    record_size
+                )?;
+                apply_fixups(&mut buffer, bytes_per_sector)?;
+                Ok(buffer)
+            }
+        )?;
+
+        if mft_runs.is_empty() {
+            return Err(
+                WinThingError::invalid_mft_record(
+                    "$MFT has no non-resident $DATA attribute.".to_owned()
+                )
+            );
+        }
+
+        Ok(
+            Self {
+                cluster_reader,
+                boot_record,
+                mft_runs,
+                mft_size
+            }
+        )
+    }
+
+    pub fn get_boot_record(&self) -> &VolumeBootRecord {
+        &self.boot_record
+    }
+
+    fn read_record_buffer(&mut self, entry: u64) -> Result<Vec<u8>, WinThingError> {
+        let record_size = self.boot_record.bytes_per_file_record as usize;
+        self.cluster_reader.read_run_range(
+            &self.mft_runs,
+            entry * record_size as u64,
+            record_size
+        )
+    }
+
+    /// Get the raw buffer of a file record with fixups applied.
+    pub fn get_entry_buffer(&mut self, entry: u64) -> Result<Vec<u8>, WinThingError> {
+        let mut buffer = self.read_record_buffer(entry)?;
+
+        apply_fixups(
+            &mut buffer,
+            self.boot_record.bytes_per_sector as usize
+        )?;
+
+        Ok(buffer)
+    }
+
+    pub fn get_entry(&mut self, entry: u64) -> Result<MftEntry, WinThingError> {
+        let buffer = self.get_entry_buffer(entry)?;
+        Ok(MftEntry::from_buffer_skip_fixup(
+            buffer,
+            entry
+        )?)
+    }
+
+    /// Get an entry along with, if `resolve_extensions` is set, the
+    /// extension records listed in its $ATTRIBUTE_LIST.
+    pub fn get_logical_entry(
+        &mut self,
+        entry: u64,
+        resolve_extensions: bool
+    ) -> Result<LogicalEntry, WinThingError> {
+        let base = self.get_entry(entry)?;
+        if !resolve_extensions {
+            return Ok(
+                LogicalEntry::from_base(base)
+            );
+        }
+
+        let cluster_reader = &mut self.cluster_reader;
+        let extension_entries = get_extension_entries_with_reader(
+            &base.data,
+            entry,
+            |attribute| cluster_reader.read_stream(std::slice::from_ref(attribute))
+        )?;
+
+        LogicalEntry::from_extension_entries(
+            base,
+            extension_entries,
+            |extension_entry| self.get_entry(extension_entry)
+        )
+    }
+
+    /// Write a $DATA stream of an entry to `writer`. An empty name is the
+    /// unnamed stream.
+    pub fn write_stream<W: Write>(
+        &mut self,
+        entry: u64,
+        stream_name: &str,
+        writer: &mut W
+    ) -> Result<u64, WinThingError> {
+        let records = self.get_logical_entry(entry, true)?.get_record_buffers();
+        let attributes = get_stream_attributes(
+            &records,
+            stream_name
+        )?;
+
+        self.cluster_reader.write_stream(
+            &attributes,
+            writer
+        )
+    }
+
+    pub fn read_stream(&mut self, entry: u64, stream_name: &str) -> Result<Vec<u8>, WinThingError> {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write_stream(
+            entry,
+            stream_name,
+            &mut buffer
+        )?;
+
+        Ok(buffer)
+    }
+}
+
+impl<R: Read + Seek> MftSource for NtfsImage<R> {
+    fn get_entry_count(&self) -> u64 {
+        self.mft_size / self.boot_record.bytes_per_file_record as u64
+    }
+
+    fn get_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError> {
+        let mut buffer = self.read_record_buffer(entry)?;
+        if !is_file_record(&buffer) {
+            return Ok(None);
+        }
+
+        apply_fixups(
+            &mut buffer,
+            self.boot_record.bytes_per_sector as usize
+        )?;
+
+        Ok(Some(MftEntry::from_buffer_skip_fixup(
+            buffer,
+            entry
+        )?))
+    }
+
+    fn read_attribute_value(&mut self, attribute: &RawAttribute) -> Result<Vec<u8>, WinThingError> {
+        self.cluster_reader.read_stream(
+            std::slice::from_ref(attribute)
+        )
+    }
+}
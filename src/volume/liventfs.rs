@@ -1,6 +1,6 @@
 use std::ptr;
 use std::fs::File;
-use std::io::Read;
+use std::io::{Read, Write};
 use mft::MftEntry;
 use std::os::windows::io::AsRawHandle;
 use byteorder::{ReadBytesExt, LittleEndian};
@@ -12,6 +12,21 @@ use crate::errors::WinThingError;
 use crate::file::helper::query_file_record;
 use crate::usn::winioctrl::query_usn_journal;
 use crate::usn::structs::UsnJournalData;
+use crate::mft::LogicalEntry;
+use crate::mft::enumerate::MftSource;
+use crate::mft::raw::{
+    apply_fixups,
+    get_extension_entries_with_reader,
+    get_mft_data_runs,
+    get_reference_entry,
+    is_file_record,
+    DataRun,
+    RawAttribute
+};
+use crate::mft::stream::{
+    get_stream_attributes,
+    ClusterReader
+};
 
 
 /// Query FSCTL_GET_NTFS_VOLUME_DATA to get the NTFS volume data.
@@ -96,7 +111,8 @@ impl MftOutputBuffer {
 pub struct WindowsLiveNtfs {
     _volume_path: String,
     volume_handle: File,
-    ntfs_volume_data: NTFS_VOLUME_DATA_BUFFER
+    ntfs_volume_data: NTFS_VOLUME_DATA_BUFFER,
+    mft_runs: Option<Vec<DataRun>>
 }
 impl WindowsLiveNtfs {
     pub fn from_volume_path(volume_path: &str) -> Result<Self, WinThingError> {
@@ -112,7 +128,8 @@ impl WindowsLiveNtfs {
             WindowsLiveNtfs {
                 _volume_path: volume_path.to_string(),
                 volume_handle: file_handle,
-                ntfs_volume_data: ntfs_volume_data
+                ntfs_volume_data: ntfs_volume_data,
+                mft_runs: None
             }
         )
     }
@@ -139,4 +156,171 @@ impl WindowsLiveNtfs {
         let mft_buffer = self.get_entry_buffer(entry)?;
         mft_buffer.as_entry()
     }
+
+    /// Get an entry along with, if `resolve_extensions` is set, the
+    /// extension records listed in its $ATTRIBUTE_LIST.
+    pub fn get_logical_entry(
+        &mut self,
+        entry: i64,
+        resolve_extensions: bool
+    ) -> Result<LogicalEntry, WinThingError> {
+        let base = self.get_entry(entry)?;
+        if !resolve_extensions {
+            return Ok(
+                LogicalEntry::from_base(base)
+            );
+        }
+
+        let mut cluster_reader = ClusterReader::new(
+            &self.volume_handle,
+            self.get_bytes_per_cluster()
+        );
+        let extension_entries = get_extension_entries_with_reader(
+            &base.data,
+            entry as u64,
+            |attribute| cluster_reader.read_stream(std::slice::from_ref(attribute))
+        )?;
+
+        LogicalEntry::from_extension_entries(
+            base,
+            extension_entries,
+            |extension_entry| self.get_entry(extension_entry as i64)
+        )
+    }
+
+    /// Read the data runs of the $MFT once, from entry 0 and the extension
+    /// records of its $ATTRIBUTE_LIST (a fragmented $MFT has them).
+    fn load_mft_runs(&mut self) -> Result<(), WinThingError> {
+        if self.mft_runs.is_some() {
+            return Ok(());
+        }
+
+        let mft_buffer = self.get_entry_buffer(0)?;
+        let mut cluster_reader = ClusterReader::new(
+            &self.volume_handle,
+            self.get_bytes_per_cluster()
+        );
+        let extension_entries = get_extension_entries_with_reader(
+            &mft_buffer.file_record_buffer,
+            0,
+            |attribute| cluster_reader.read_stream(std::slice::from_ref(attribute))
+        )?;
+
+        // The extension records are in use, so they are read with
+        // FSCTL_GET_NTFS_FILE_RECORD, which applies the fixups
+        let (runs, _) = get_mft_data_runs(
+            &mft_buffer.file_record_buffer,
+            &extension_entries,
+            |extension_entry, _| {
+                let extension_buffer = self.get_entry_buffer(extension_entry as i64)?;
+                Ok(extension_buffer.file_record_buffer)
+            }
+        )?;
+
+        self.mft_runs = Some(runs);
+        Ok(())
+    }
+
+    /// Read a record from the clusters of the $MFT. This is how records that
+    /// are not in use are read, as FSCTL_GET_NTFS_FILE_RECORD returns the
+    /// closest in use record before them instead.
+    fn read_mft_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError> {
+        self.load_mft_runs()?;
+        let runs = self.mft_runs.as_deref().unwrap_or(&[]);
+        let record_size = self.ntfs_volume_data.BytesPerFileRecordSegment as usize;
+
+        let mut cluster_reader = ClusterReader::new(
+            &self.volume_handle,
+            self.get_bytes_per_cluster()
+        );
+        let mut buffer = cluster_reader.read_run_range(
+            runs,
+            entry * record_size as u64,
+            record_size
+        )?;
+
+        if !is_file_record(&buffer) {
+            return Ok(None);
+        }
+
+        apply_fixups(
+            &mut buffer,
+            self.ntfs_volume_data.BytesPerSector as usize
+        )?;
+
+        Ok(Some(MftEntry::from_buffer_skip_fixup(
+            buffer,
+            entry
+        )?))
+    }
+
+    pub fn get_bytes_per_cluster(&self) -> u32 {
+        self.ntfs_volume_data.BytesPerCluster
+    }
+
+    /// Write a $DATA stream of an entry to `writer` by reading its clusters
+    /// from the volume. An empty name is the unnamed stream.
+    pub fn write_stream<W: Write>(
+        &mut self,
+        entry: i64,
+        stream_name: &str,
+        writer: &mut W
+    ) -> Result<u64, WinThingError> {
+        let records = self.get_logical_entry(entry, true)?.get_record_buffers();
+        let attributes = get_stream_attributes(
+            &records,
+            stream_name
+        )?;
+
+        let mut cluster_reader = ClusterReader::new(
+            &self.volume_handle,
+            self.get_bytes_per_cluster()
+        );
+
+        cluster_reader.write_stream(
+            &attributes,
+            writer
+        )
+    }
+
+    pub fn read_stream(&mut self, entry: i64, stream_name: &str) -> Result<Vec<u8>, WinThingError> {
+        let mut buffer: Vec<u8> = Vec::new();
+        self.write_stream(
+            entry,
+            stream_name,
+            &mut buffer
+        )?;
+
+        Ok(buffer)
+    }
+}
+
+
+impl MftSource for WindowsLiveNtfs {
+    /// The number of records up to the MftValidDataLength.
+    fn get_entry_count(&self) -> u64 {
+        let valid_data_length = unsafe {
+            *self.ntfs_volume_data.MftValidDataLength.QuadPart()
+        };
+
+        valid_data_length as u64 / self.ntfs_volume_data.BytesPerFileRecordSegment as u64
+    }
+
+    fn get_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError> {
+        let mft_buffer = self.get_entry_buffer(entry as i64)?;
+        if get_reference_entry(mft_buffer.file_reference_number) == entry {
+            return Ok(Some(mft_buffer.as_entry()?));
+        }
+
+        self.read_mft_record(entry)
+    }
+
+    fn read_attribute_value(&mut self, attribute: &RawAttribute) -> Result<Vec<u8>, WinThingError> {
+        ClusterReader::new(
+            &self.volume_handle,
+            self.get_bytes_per_cluster()
+        ).read_stream(
+            std::slice::from_ref(attribute)
+        )
+    }
 }
@@ -1 +1,3 @@
-pub mod liventfs;
\ No newline at end of file
+#[cfg(windows)]
+pub mod liventfs;
+pub mod image;
\ No newline at end of file
@@ -12,6 +12,11 @@ use crate::winevt::wevtapi::evt_next_publisher_id;
 use crate::winevt::wevtapi::evt_get_object_array_size;
 use crate::winevt::wevtapi::evt_get_object_array_property;
 use crate::winevt::wevtapi::evt_format_message;
+use crate::winevt::wevtapi::evt_open_event_metadata_enum;
+use crate::winevt::wevtapi::evt_next_event_metadata;
+use crate::winevt::wevtapi::evt_get_event_metadata_property;
+use crate::events::definition::{parse_template_fields, EventDefinition};
+use crate::publishers::PUBLISHER_EVENTS_KEY;
 
 
 #[allow(dead_code)]
@@ -628,6 +633,119 @@ impl MetadataKeyword {
 }
 
 
+#[derive(Serialize, Debug)]
+pub struct MetadataEvents(pub Vec<EventDefinition>);
+
+impl MetadataEvents {
+    pub fn new(metadata_handle: &EvtHandle) -> Result<Self, WinThingError> {
+        let mut events: Vec<EventDefinition> = Vec::new();
+
+        let enum_handle = evt_open_event_metadata_enum(
+            &metadata_handle
+        )?;
+
+        while let Some(event_handle) = evt_next_event_metadata(&enum_handle)? {
+            // Skip only the definition that failed
+            match get_event_definition(&metadata_handle, &event_handle) {
+                Ok(definition) => events.push(definition),
+                Err(e) => warn!("Error reading event definition: {}", e.display_chain())
+            }
+        }
+
+        Ok(
+            Self(events)
+        )
+    }
+}
+
+
+fn get_event_uint(
+    event_handle: &EvtHandle,
+    id: EVT_EVENT_METADATA_PROPERTY_ID,
+    name: &str
+) -> Result<u64, WinThingError> {
+    match evt_get_event_metadata_property(
+        &event_handle, id
+    )?.get_variant_value()? {
+        VariantValue::UInt(i) => Ok(i),
+        other => Err(
+            WinThingError::unhandled(
+                format!("Expected {} property to contain a UInt VariantValue, not {:?}.", name, other)
+            )
+        )
+    }
+}
+
+
+/// Get the definition of an event from its EvtNextEventMetadata handle.
+fn get_event_definition(
+    metadata_handle: &EvtHandle,
+    event_handle: &EvtHandle
+) -> Result<EventDefinition, WinThingError> {
+    let id = get_event_uint(&event_handle, EventMetadataEventID, "EventMetadataEventID")? as u32;
+    let version = get_event_uint(&event_handle, EventMetadataEventVersion, "EventMetadataEventVersion")? as u32;
+    let channel = get_event_uint(&event_handle, EventMetadataEventChannel, "EventMetadataEventChannel")? as u32;
+    let level = get_event_uint(&event_handle, EventMetadataEventLevel, "EventMetadataEventLevel")? as u32;
+    let opcode = get_event_uint(&event_handle, EventMetadataEventOpcode, "EventMetadataEventOpcode")? as u32;
+    let task = get_event_uint(&event_handle, EventMetadataEventTask, "EventMetadataEventTask")? as u32;
+    let keywords = get_event_uint(&event_handle, EventMetadataEventKeyword, "EventMetadataEventKeyword")?;
+
+    let message_id = match get_event_uint(&event_handle, EventMetadataEventMessageID, "EventMetadataEventMessageID")? as u32 {
+        0xffffffff => None,
+        id => Some(id)
+    };
+
+    let message = match message_id {
+        Some(message_id) => match evt_format_message(
+            Some(&metadata_handle),
+            None,
+            message_id
+        ) {
+            Ok(m) => Some(m),
+            Err(e) => {
+                info!("Error formatting message {} of event {}: {}", message_id, id, e.message.trim());
+                None
+            }
+        },
+        None => None
+    };
+
+    let template = match evt_get_event_metadata_property(
+        &event_handle, EventMetadataEventTemplate
+    )?.get_variant_value()? {
+        VariantValue::String(t) if !t.is_empty() => Some(t),
+        _ => None
+    };
+
+    let fields = match &template {
+        Some(t) => match parse_template_fields(t) {
+            Ok(f) => f,
+            Err(e) => {
+                info!("Error parsing template of event {}: {}", id, e.message);
+                Vec::new()
+            }
+        },
+        None => Vec::new()
+    };
+
+    Ok(
+        EventDefinition {
+            id,
+            version,
+            channel,
+            level,
+            opcode,
+            task,
+            keywords,
+            message_id,
+            message,
+            template,
+            fields
+        }
+    )
+}
+
+
 #[derive(Debug)]
 pub struct PublisherMeta {
     pub name: String,
@@ -759,6 +877,31 @@ impl PublisherMeta {
         )
     }
 
+    pub fn get_metadata_events(&self) -> Result<MetadataEvents, WinThingError> {
+        MetadataEvents::new(
+            &self.handle
+        )
+    }
+
+    /// Get the publisher as JSON, with its event definitions under
+    /// `PUBLISHER_EVENTS_KEY` if `with_events` is set.
+    pub fn get_json_value(&self, with_events: bool) -> Result<Value, WinThingError> {
+        let mut value = self.to_json_value()?;
+
+        if with_events {
+            match self.get_metadata_events() {
+                Ok(events) => value[PUBLISHER_EVENTS_KEY] = serde_json::to_value(
+                    &events
+                )?,
+                Err(e) => {
+                    info!("[{}] Error retrieving events: {}", self.name, e.message.trim());
+                }
+            }
+        }
+
+        Ok(value)
+    }
+
     pub fn to_json_value(&self) -> Result<Value, WinThingError> {
         let mut mapping = json!({
             "Name": self.name
@@ -894,6 +1037,25 @@ impl PublisherMeta {
 }
 
 
+/// Get the names of the publishers on the session's computer.
+///
+pub fn get_publisher_name_list(
+    session: &Option<EvtHandle>
+) -> Result<Vec<String>, WinThingError> {
+    let mut names: Vec<String> = Vec::new();
+
+    let pub_enum_handle = evt_open_publisher_enum(
+        &session
+    )?;
+
+    while let Some(name) = evt_next_publisher_id(&pub_enum_handle)? {
+        names.push(name);
+    }
+
+    Ok(names)
+}
+
+
 #[derive(Debug)]
 pub struct PublisherEnumerator {
     session: Option<EvtHandle>,
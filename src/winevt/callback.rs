@@ -1,13 +1,44 @@
+use std::sync::Arc;
+use serde_json::Value;
+use crate::detection::engine::RuleEngine;
 use crate::utils::xmltojson::xml_string_to_json;
+use crate::winevt::subscription::SubscriptionStatus;
 
 
+/// Receives the events and status of subscriptions. Subscriptions share
+/// their handler through an `Arc` and call it from Windows event log
+/// threads, so it must be `Send + Sync`. Any `Fn(String)` closure is a
+/// handler that logs status events.
+///
+pub trait EventHandler: Send + Sync {
+    /// Handle a rendered XML event.
+    fn on_event(&self, xml_event: String);
+
+    /// Handle a subscription status, such as an error reported by the
+    /// subscription or it being re-established.
+    fn on_error(&self, status: &SubscriptionStatus) {
+        error!("[{}] {}: {}", status.channel, status.status, status.message);
+    }
+}
+
+impl<F> EventHandler for F where F: Fn(String) + Send + Sync {
+    fn on_event(&self, xml_event: String) {
+        self(xml_event)
+    }
+}
+
+
+#[derive(Debug, Clone, Copy)]
 pub enum OutputFormat {
     XmlFormat,
     JsonlFormat
 }
 
 pub struct CallbackContext {
-    format: OutputFormat
+    format: OutputFormat,
+    host: Option<String>,
+    rules: Option<Arc<RuleEngine>>,
+    alerts_only: bool
 }
 
 impl CallbackContext {
@@ -20,30 +51,167 @@ impl CallbackContext {
         self
     }
 
+    /// Tag every record with the host it came from.
+    pub fn with_host(mut self, host: String) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    /// Evaluate every event against the rules and correlations and output
+    /// their alerts.
+    pub fn with_rules(mut self, rules: Arc<RuleEngine>) -> Self {
+        self.rules = Some(rules);
+        self
+    }
+
+    /// Only output alerts, not the events themselves.
+    pub fn with_alerts_only(mut self, alerts_only: bool) -> Self {
+        self.alerts_only = alerts_only;
+        self
+    }
+
+    pub fn get_format(&self) -> OutputFormat {
+        self.format
+    }
+
+    pub fn get_host(&self) -> Option<&str> {
+        self.host.as_deref()
+    }
+
+    fn get_event_value(&self, xml_string: String) -> Option<Value> {
+        let mut value = match xml_string_to_json(xml_string) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error converting XML string to Value: {:?}", e);
+                return None;
+            }
+        };
+
+        if let (Some(host), Some(map)) = (&self.host, value.as_object_mut()) {
+            map.insert("source_host".to_owned(), json!(host));
+        }
+
+        Some(value)
+    }
+
+    /// Output the alerts and correlation alerts for the event. Alerts are
+    /// JSON lines, so `--alerts-only` output can be piped the same way with
+    /// either format. When XML events are output too, the alerts go to
+    /// stderr to keep stdout valid XML records.
+    fn handle_alerts(&self, rules: &RuleEngine, value: &Value) {
+        let alert_values = match rules.process(value).to_json_values() {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error serializing alerts: {}", e);
+                return;
+            }
+        };
+
+        for alert_value in alert_values {
+            match (self.format, self.alerts_only) {
+                (OutputFormat::XmlFormat, false) => eprintln!("{}", alert_value),
+                _ => println!("{}", alert_value)
+            }
+        }
+    }
+
     pub fn handle_record(&self, xml_string: String) {
         match self.format {
             OutputFormat::JsonlFormat => {
-                let value = match xml_string_to_json(xml_string) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        eprintln!("Error converting XML string to Value: {:?}", e);
-                        return;
-                    }
+                let value = match self.get_event_value(xml_string) {
+                    Some(v) => v,
+                    None => return
                 };
 
-                println!("{}", &value.to_string());
+                if let Some(rules) = &self.rules {
+                    self.handle_alerts(rules, &value);
+                }
+
+                if !self.alerts_only {
+                    println!("{}", &value.to_string());
+                }
             },
             OutputFormat::XmlFormat => {
-                println!("{}", xml_string);
+                if let Some(rules) = &self.rules {
+                    if let Some(value) = self.get_event_value(xml_string.clone()) {
+                        self.handle_alerts(rules, &value);
+                    }
+                }
+
+                if !self.alerts_only {
+                    self.print_xml(&xml_string);
+                }
             }
         }
     }
+
+    fn print_xml(&self, xml_string: &str) {
+        match &self.host {
+            Some(host) => println!("{}", tag_xml_event(xml_string, host)),
+            None => println!("{}", xml_string)
+        }
+    }
+
+    /// Output a subscription status event. Status events are JSON, so with
+    /// the XML format they go to stderr to keep stdout valid XML records.
+    pub fn handle_status(&self, status: &SubscriptionStatus) {
+        let mut value = match serde_json::to_value(status) {
+            Ok(v) => v,
+            Err(e) => {
+                eprintln!("Error serializing subscription status: {}", e);
+                return;
+            }
+        };
+
+        if let (Some(host), Some(map)) = (&self.host, value.as_object_mut()) {
+            map.insert("source_host".to_owned(), json!(host));
+        }
+
+        match self.format {
+            OutputFormat::JsonlFormat => println!("{}", value),
+            OutputFormat::XmlFormat => eprintln!("{}", value)
+        }
+    }
+}
+
+impl EventHandler for CallbackContext {
+    fn on_event(&self, xml_event: String) {
+        self.handle_record(xml_event);
+    }
+
+    fn on_error(&self, status: &SubscriptionStatus) {
+        self.handle_status(status);
+    }
 }
 
 impl Default for CallbackContext {
     fn default() -> Self {
         Self {
-            format: OutputFormat::JsonlFormat
+            format: OutputFormat::JsonlFormat,
+            host: None,
+            rules: None,
+            alerts_only: false
         }
     }
+}
+
+
+/// Add a SourceHost attribute to the root Event element.
+fn tag_xml_event(xml_string: &str, host: &str) -> String {
+    let host = host.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;");
+
+    match xml_string.find("<Event") {
+        Some(index) => {
+            let insert_at = index + "<Event".len();
+            format!(
+                "{} SourceHost=\"{}\"{}",
+                &xml_string[..insert_at],
+                host,
+                &xml_string[insert_at..]
+            )
+        },
+        None => xml_string.to_owned()
+    }
 }
\ No newline at end of file
@@ -15,6 +15,7 @@ use crate::winevt::EvtHandle;
 use crate::errors::WinThingError;
 use crate::winevt::variant::EvtVariant;
 use crate::winevt::variant::VariantValue;
+use crate::channels::info::ChannelConfigInfo;
 
 const CHANNEL_PROPERTIES: [(&str, u32); 21] = [
     ("EvtChannelConfigEnabled", EvtChannelConfigEnabled),
@@ -41,14 +42,25 @@ const CHANNEL_PROPERTIES: [(&str, u32); 21] = [
 ];
 
 
-#[allow(dead_code)]
 pub struct ChannelConfig {
     name: String,
     handle: EvtHandle
 }
 impl ChannelConfig {
     pub fn new(channel: String) -> Result<Self, WinThingError> {
+        Self::from_session(
+            &None,
+            channel
+        )
+    }
+
+    /// Open the config of a channel on the session's computer.
+    pub fn from_session(
+        session: &Option<EvtHandle>,
+        channel: String
+    ) -> Result<Self, WinThingError> {
         let handle = evt_open_channel_config(
+            session,
             &channel
         )?;
 
@@ -223,6 +235,14 @@ impl ChannelConfig {
 
         Ok(mapping)
     }
+
+    /// Get the typed configuration of the channel.
+    pub fn get_info(&self) -> Result<ChannelConfigInfo, WinThingError> {
+        ChannelConfigInfo::from_json_value(
+            &self.name,
+            &self.to_json_value()?
+        )
+    }
 }
 
 
@@ -246,6 +266,34 @@ pub fn get_channel_name_list(
 }
 
 
+/// Get the channels on the session's computer that are enabled and can be
+/// subscribed to.
+///
+pub fn get_subscribable_channels(
+    session: &Option<EvtHandle>
+) -> Result<Vec<String>, WinThingError> {
+    let mut channels: Vec<String> = Vec::new();
+
+    for channel in get_channel_name_list(session)? {
+        let channel_config = match ChannelConfig::from_session(session, channel.clone()) {
+            Ok(c) => c,
+            Err(e) => {
+                debug!("Error getting ChannelConfig for {}: {}", channel, e.display_chain());
+                continue;
+            }
+        };
+
+        // Cutting out config types of 2 or more seems to resolve
+        // observed Subscription issues
+        if channel_config.can_subscribe() && channel_config.is_enabled() {
+            channels.push(channel);
+        }
+    }
+
+    Ok(channels)
+}
+
+
 /// EVT_HANDLE EvtOpenChannelEnum(
 ///   EVT_HANDLE Session,
 ///   DWORD      Flags
@@ -323,14 +371,8 @@ fn evt_get_channel_config_property(evt_handle: &EvtHandle, property_id: EVT_CHAN
                     // to work. For now, we do nothing...
                 },
                 _ => {
-                    let variant : EVT_VARIANT = unsafe {
-                        std::ptr::read(
-                            buffer.as_ptr() as *const _
-                        ) 
-                    };
-
                     return Some(
-                        EvtVariant(variant)
+                        EvtVariant::from_buffer(buffer)
                     );
                 }
             }
@@ -346,7 +388,15 @@ fn evt_get_channel_config_property(evt_handle: &EvtHandle, property_id: EVT_CHAN
 ///   LPCWSTR    ChannelPath,
 ///   DWORD      Flags
 /// );
-fn evt_open_channel_config(channel_path: &String) -> Result<EvtHandle, WinThingError> {
+fn evt_open_channel_config(
+    session: &Option<EvtHandle>,
+    channel_path: &String
+) -> Result<EvtHandle, WinThingError> {
+    let session = match session {
+        Some(s) => s.0,
+        None => null_mut()
+    };
+
     // Create the wide string buffer
     let mut channel_path_u16 : Vec<u16> = channel_path.encode_utf16().collect();
 
@@ -355,7 +405,7 @@ fn evt_open_channel_config(channel_path: &String) -> Result<EvtHandle, WinThingE
 
     let result = unsafe {
         EvtOpenChannelConfig(
-            null_mut(), 
+            session, 
             channel_path_u16.as_ptr(), 
             0
         )
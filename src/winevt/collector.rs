@@ -0,0 +1,310 @@
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use winapi::um::winevt::EvtSubscribeToFutureEvents;
+use crate::winevt::EvtHandle;
+use crate::errors::WinThingError;
+use crate::detection::engine::RuleEngine;
+use crate::utils::backoff::Backoff;
+use crate::utils::inventory::{HostChannel, HostEntry};
+use crate::winevt::callback::{CallbackContext, OutputFormat};
+use crate::winevt::channels::{get_channel_name_list, get_subscribable_channels};
+use crate::winevt::session::{login_flag_from_str, RemoteSession};
+use crate::winevt::subscription::ChannelSubscription;
+
+/// How often a connected remote host's session is checked.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+
+/// Collects events from one inventory host. It keeps the host's session and
+/// subscriptions and reconnects with backoff when the host fails, without
+/// affecting other hosts. Channels continue after the last event they
+/// delivered before the host failed. Channels that fail to subscribe while
+/// the host's other channels succeed are retried on their own backoff.
+///
+pub struct HostCollector {
+    host: HostEntry,
+    // Subscriptions must be closed before the session they use
+    subscriptions: Vec<ChannelSubscription>,
+    session: Option<EvtHandle>,
+    // The bookmark XML of the last delivered event by channel
+    bookmarks: HashMap<String, String>,
+    // The channels of a connected host that could not be subscribed to
+    failed_channels: Vec<HostChannel>,
+    context: Arc<CallbackContext>,
+    flags: Option<u32>,
+    connected: bool,
+    has_connected: bool,
+    backoff: Backoff,
+    next_attempt: Instant,
+    channel_backoff: Backoff,
+    next_channel_attempt: Instant,
+    last_check: Instant
+}
+
+impl HostCollector {
+    pub fn new(
+        host: HostEntry,
+        format: OutputFormat,
+        flags: Option<u32>
+    ) -> Self {
+        let context = CallbackContext::new()
+            .with_format(format)
+            .with_host(host.name.clone());
+
+        Self {
+            host,
+            subscriptions: Vec::new(),
+            session: None,
+            bookmarks: HashMap::new(),
+            failed_channels: Vec::new(),
+            context: Arc::new(context),
+            flags,
+            connected: false,
+            has_connected: false,
+            backoff: Backoff::default(),
+            next_attempt: Instant::now(),
+            channel_backoff: Backoff::default(),
+            next_channel_attempt: Instant::now(),
+            last_check: Instant::now()
+        }
+    }
+
+    /// Evaluate the host's events against the rules. Must be set before the
+    /// host is connected.
+    pub fn with_rules(mut self, rules: Arc<RuleEngine>, alerts_only: bool) -> Self {
+        let context = CallbackContext::new()
+            .with_format(self.context.get_format())
+            .with_host(self.host.name.clone())
+            .with_rules(rules)
+            .with_alerts_only(alerts_only);
+
+        self.context = Arc::new(context);
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.host.name
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn get_subscription_count(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    /// Connect if the host is not connected and its backoff has passed, and
+    /// check the session of a connected remote host. Call this periodically.
+    pub fn maintain(&mut self) {
+        let now = Instant::now();
+
+        if !self.connected {
+            if now < self.next_attempt {
+                return;
+            }
+
+            match self.connect() {
+                Ok(_) => {
+                    eprintln!(
+                        "[{}] listening to {} channels",
+                        self.host.name, self.subscriptions.len()
+                    );
+                    self.backoff.reset();
+                    self.last_check = now;
+                    self.channel_backoff.reset();
+                    self.schedule_channel_retry();
+                },
+                Err(e) => self.schedule_reconnect(e)
+            }
+            return;
+        }
+
+        // Re-establish subscriptions whose callbacks reported errors
+        for subscription in self.subscriptions.iter_mut() {
+            subscription.maintain();
+        }
+
+        if !self.failed_channels.is_empty() && now >= self.next_channel_attempt {
+            self.retry_failed_channels();
+        }
+
+        if self.session.is_some() && now.duration_since(self.last_check) >= HEALTH_CHECK_INTERVAL {
+            self.last_check = now;
+            if let Err(e) = get_channel_name_list(&self.session) {
+                self.schedule_reconnect(e);
+            }
+        }
+    }
+
+    /// Subscribe to the channels that failed before. The ones that fail
+    /// again are retried after the next backoff delay.
+    fn retry_failed_channels(&mut self) {
+        let channels = std::mem::take(&mut self.failed_channels);
+        let flags = self.get_subscribe_flags();
+
+        for channel in channels {
+            match self.subscribe(&channel, flags) {
+                Ok(subscription) => {
+                    eprintln!("[{}] listening to {}", self.host.name, channel.get_channel());
+                    self.subscriptions.push(subscription);
+                },
+                Err(e) => {
+                    eprintln!(
+                        "[{}] Error creating subscription for {}: {}",
+                        self.host.name, channel.get_channel(), e.display_chain()
+                    );
+                    self.failed_channels.push(channel);
+                }
+            }
+        }
+
+        match self.failed_channels.is_empty() {
+            true => self.channel_backoff.reset(),
+            false => self.schedule_channel_retry()
+        }
+    }
+
+    fn schedule_channel_retry(&mut self) {
+        if self.failed_channels.is_empty() {
+            return;
+        }
+
+        let delay = self.channel_backoff.next_delay();
+        eprintln!(
+            "[{}] retrying {} channels in {}s",
+            self.host.name, self.failed_channels.len(), delay.as_secs()
+        );
+        self.next_channel_attempt = Instant::now() + delay;
+    }
+
+    fn schedule_reconnect(&mut self, error: WinThingError) {
+        self.disconnect();
+
+        let delay = self.backoff.next_delay();
+        eprintln!(
+            "[{}] {}; retrying in {}s",
+            self.host.name, error.display_chain(), delay.as_secs()
+        );
+        self.next_attempt = Instant::now() + delay;
+    }
+
+    fn disconnect(&mut self) {
+        for subscription in &self.subscriptions {
+            match subscription.get_bookmark_xml() {
+                Ok(Some(xml)) => {
+                    self.bookmarks.insert(subscription.get_channel().to_owned(), xml);
+                },
+                Ok(None) => {},
+                Err(e) => eprintln!(
+                    "[{}] Error saving bookmark for {}: {}",
+                    self.host.name, subscription.get_channel(), e.display_chain()
+                )
+            }
+        }
+
+        self.subscriptions.clear();
+        // A reconnect subscribes to every channel again
+        self.failed_channels.clear();
+        self.session = None;
+        self.connected = false;
+    }
+
+    /// Only the first connection reads historical records. Reconnects
+    /// continue after the last delivered event, or start at new events for
+    /// channels that had not delivered any.
+    fn get_subscribe_flags(&self) -> Option<u32> {
+        match self.has_connected {
+            false => self.flags,
+            true => Some(EvtSubscribeToFutureEvents)
+        }
+    }
+
+    fn subscribe(
+        &self,
+        channel: &HostChannel,
+        flags: Option<u32>
+    ) -> Result<ChannelSubscription, WinThingError> {
+        match self.bookmarks.get(channel.get_channel()) {
+            Some(bookmark_xml) => ChannelSubscription::after_bookmark(
+                &self.session,
+                channel.get_channel().to_string(),
+                self.host.get_channel_query(channel),
+                bookmark_xml,
+                self.context.clone()
+            ),
+            None => ChannelSubscription::new(
+                &self.session,
+                channel.get_channel().to_string(),
+                self.host.get_channel_query(channel),
+                flags,
+                self.context.clone()
+            )
+        }
+    }
+
+    fn connect(&mut self) -> Result<(), WinThingError> {
+        self.session = match &self.host.server {
+            Some(server) => {
+                let flags = match &self.host.sflag {
+                    Some(f) => Some(login_flag_from_str(f)?),
+                    None => None
+                };
+                let provider = self.host.get_credential_provider()?;
+
+                let session = RemoteSession::from_credentials(
+                    server,
+                    self.host.user.as_deref(),
+                    self.host.domain.as_deref(),
+                    flags,
+                    provider.as_ref()
+                )?;
+                Some(session.0)
+            },
+            None => None
+        };
+
+        let channels: Vec<HostChannel> = match self.host.channels.is_empty() {
+            true => get_subscribable_channels(&self.session)?
+                .into_iter()
+                .map(HostChannel::Name)
+                .collect(),
+            false => self.host.channels.clone()
+        };
+
+        let flags = self.get_subscribe_flags();
+
+        let mut last_error: Option<WinThingError> = None;
+        for channel in channels {
+            match self.subscribe(&channel, flags) {
+                Ok(s) => self.subscriptions.push(s),
+                Err(e) => {
+                    eprintln!(
+                        "[{}] Error creating subscription for {}: {}",
+                        self.host.name, channel.get_channel(), e.display_chain()
+                    );
+                    self.failed_channels.push(channel);
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        if self.subscriptions.is_empty() {
+            return Err(
+                match last_error {
+                    Some(e) => WinThingError::winapi_error(
+                        "No subscriptions could be created.".to_owned()
+                    ).with_source(e),
+                    None => WinThingError::winapi_error(
+                        "No channels to subscribe to.".to_owned()
+                    )
+                }
+            );
+        }
+
+        self.connected = true;
+        self.has_connected = true;
+        Ok(())
+    }
+}
@@ -1,21 +1,38 @@
+#[cfg(windows)]
 pub mod channels;
 pub mod variant;
+#[cfg(windows)]
 pub mod wevtapi;
+#[cfg(windows)]
 pub mod callback;
+#[cfg(windows)]
 pub mod subscription;
+#[cfg(windows)]
 pub mod session;
+#[cfg(windows)]
+pub mod collector;
+#[cfg(windows)]
+pub mod stream;
+#[cfg(windows)]
+pub mod query;
+#[cfg(windows)]
 use winapi::um::winevt::EvtClose;
+#[cfg(windows)]
 use winapi::um::winevt::EVT_HANDLE;
+#[cfg(windows)]
 use crate::errors::WinThingError;
 
 
+#[cfg(windows)]
 #[derive(Debug)]
 pub struct EvtHandle(pub EVT_HANDLE);
+#[cfg(windows)]
 impl EvtHandle {
     pub fn is_null(&self) -> bool {
         self.0.is_null()
     }
 }
+#[cfg(windows)]
 impl Drop for EvtHandle {
     fn drop(&mut self) {
         let result = unsafe {
@@ -0,0 +1,123 @@
+use std::collections::VecDeque;
+use winapi::um::winevt::{
+    EvtQueryChannelPath,
+    EvtQueryFilePath,
+    EvtQueryForwardDirection,
+    EvtQueryReverseDirection
+};
+use winapi::um::winbase::INFINITE;
+use crate::winevt::EvtHandle;
+use crate::errors::WinThingError;
+use crate::events::query::QueryOptions;
+use crate::winevt::wevtapi::{evt_next, evt_query, evt_render};
+
+/// EvtNext waits until there are events or the result set ends. With a
+/// timeout, slow (e.g. remote) queries would fail partway.
+const QUERY_TIMEOUT: u32 = INFINITE;
+
+
+/// A bounded query over a channel or an EVTX file. Iterating yields each
+/// matching event rendered as XML and ends once the result set is exhausted
+/// or `max_count` events have been returned.
+///
+pub struct EventQuery {
+    result_set: EvtHandle,
+    options: QueryOptions,
+    pending: VecDeque<EvtHandle>,
+    returned: usize,
+    finished: bool
+}
+
+impl EventQuery {
+    /// Query a channel, optionally on a remote session.
+    pub fn from_channel(
+        session: &Option<EvtHandle>,
+        channel: &str,
+        query: Option<&str>,
+        options: QueryOptions
+    ) -> Result<Self, WinThingError> {
+        Self::new(session, channel, query, EvtQueryChannelPath, options)
+    }
+
+    /// Query an EVTX file.
+    pub fn from_file(
+        path: &str,
+        query: Option<&str>,
+        options: QueryOptions
+    ) -> Result<Self, WinThingError> {
+        Self::new(&None, path, query, EvtQueryFilePath, options)
+    }
+
+    fn new(
+        session: &Option<EvtHandle>,
+        path: &str,
+        query: Option<&str>,
+        path_flag: u32,
+        options: QueryOptions
+    ) -> Result<Self, WinThingError> {
+        let direction = match options.reverse {
+            true => EvtQueryReverseDirection,
+            false => EvtQueryForwardDirection
+        };
+
+        let result_set = evt_query(
+            session,
+            path,
+            query,
+            path_flag | direction
+        )?;
+
+        Ok(
+            Self {
+                result_set,
+                options,
+                pending: VecDeque::new(),
+                returned: 0,
+                finished: false
+            }
+        )
+    }
+
+    /// The number of events returned so far.
+    pub fn get_returned(&self) -> usize {
+        self.returned
+    }
+}
+
+impl Iterator for EventQuery {
+    type Item = Result<String, WinThingError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(max_count) = self.options.max_count {
+            if self.returned >= max_count {
+                return None;
+            }
+        }
+
+        if self.pending.is_empty() {
+            if self.finished {
+                return None;
+            }
+
+            match evt_next(&self.result_set, self.options.batch_size, QUERY_TIMEOUT) {
+                Ok(events) => {
+                    if events.is_empty() {
+                        self.finished = true;
+                        return None;
+                    }
+                    self.pending.extend(events);
+                },
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let event = self.pending.pop_front()?;
+        self.returned += 1;
+        Some(
+            evt_render(event.0)
+        )
+    }
+}
@@ -2,7 +2,65 @@ use std::ptr::null_mut;
 use winapi::um::winevt::*;
 use winapi::ctypes::c_void;
 use crate::winevt::EvtHandle;
+use crate::winevt::wevtapi::evt_cancel;
+use zeroize::Zeroizing;
 use crate::errors::WinThingError;
+use crate::utils::credentials::{
+    CredentialProvider,
+    PromptCredentials
+};
+
+
+/// Get the EVT_RPC_LOGIN_FLAGS for an authentication method name.
+///
+pub fn login_flag_from_str(flag_str: &str) -> Result<EVT_RPC_LOGIN_FLAGS, WinThingError> {
+    match flag_str {
+        "Default" => Ok(EvtRpcLoginAuthDefault),
+        "Negotiate" => Ok(EvtRpcLoginAuthNegotiate),
+        "Kerberos" => Ok(EvtRpcLoginAuthKerberos),
+        "NTLM" => Ok(EvtRpcLoginAuthNTLM),
+        other => Err(
+            WinThingError::cli_error(
+                format!("{} Is not a handled flag for EVT_RPC_LOGIN_FLAGS!", other)
+            )
+        )
+    }
+}
+
+
+/// A session that can be shared between threads, such as the workers that
+/// open publisher metadata concurrently. `None` is the local computer.
+///
+pub struct SharedSession(Option<EvtHandle>);
+
+// SAFETY: EvtHandle only holds the EVT_HANDLE, an opaque value that the
+// Event Log API resolves on each call, so no memory is shared through it.
+// The API does not tie a session to the thread that opened it, and
+// EvtCancel is meant to be called on a session from another thread while
+// calls on it are pending. The handle is never replaced, and it is only
+// closed when the SharedSession is dropped, after every thread that can
+// reach it through `get` is done with it.
+unsafe impl Send for SharedSession {}
+unsafe impl Sync for SharedSession {}
+
+impl SharedSession {
+    pub fn new(session: Option<EvtHandle>) -> Self {
+        Self(session)
+    }
+
+    pub fn get(&self) -> &Option<EvtHandle> {
+        &self.0
+    }
+
+    /// Cancel the pending calls on a remote session, which then fail with
+    /// ERROR_CANCELLED. The local session has no handle to cancel.
+    pub fn cancel(&self) -> Result<(), WinThingError> {
+        match &self.0 {
+            Some(session) => evt_cancel(session),
+            None => Ok(())
+        }
+    }
+}
 
 
 pub struct RemoteSession(
@@ -14,6 +72,24 @@ impl RemoteSession {
         username: Option<&str>, 
         domain: Option<&str>,
         flags: Option<EVT_RPC_LOGIN_FLAGS>
+    ) -> Result<Self, WinThingError> {
+        Self::from_credentials(
+            hostname,
+            username,
+            domain,
+            flags,
+            &PromptCredentials
+        )
+    }
+
+    /// Open a session to `hostname`. The password is only requested from
+    /// `provider` when a username is given.
+    pub fn from_credentials(
+        hostname: &str, 
+        username: Option<&str>, 
+        domain: Option<&str>,
+        flags: Option<EVT_RPC_LOGIN_FLAGS>,
+        provider: &dyn CredentialProvider
     ) -> Result<Self, WinThingError> {
         // NULL terminated buffers are needed
         let mut hostname_u16: Vec<u16> = hostname.encode_utf16().collect();
@@ -21,16 +97,16 @@ impl RemoteSession {
 
         let mut username_u16: Vec<u16>;
         let mut domain_u16: Vec<u16>;
-        let mut password_u16: Vec<u16>;
-        let password: String;
+        // Zeroed when dropped, after the session is opened
+        let mut password_u16: Zeroizing<Vec<u16>>;
 
         let flags = match flags {
             Some(f) => f,
             None => EvtRpcLoginAuthNegotiate
         };
 
-        let evt_rpc_login = if username.is_some(){
-            username_u16 = username.expect("Expected Username").encode_utf16().collect();
+        let evt_rpc_login = if let Some(username) = username {
+            username_u16 = username.encode_utf16().collect();
             username_u16.resize(username_u16.len() + 1, 0);
 
             let domain_ptr = match domain {
@@ -42,14 +118,10 @@ impl RemoteSession {
                 None => null_mut()
             };
 
-            password = rpassword::read_password_from_tty(
-                Some("Password: ")
-            )?;
             // Does this need to be RPC_UNICODE_STRING like seen here?
             // https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-dtyp/50e9ef83-d6fd-4e22-a34a-2c6b4e3c24f3
             // https://docs.microsoft.com/en-us/openspecs/windows_protocols/ms-dtyp/94a16bb6-c610-4cb9-8db6-26f15f560061
-            password_u16 = password.encode_utf16().collect();
-            password_u16.resize(password_u16.len() + 1, 0);
+            password_u16 = provider.get_password()?.to_wide();
             // https://github.com/MicrosoftDocs/win32/blob/03b5f241e441e4f60f47b7f1d57b7e8ac3dd72e0/desktop-src/WES/accessing-remote-computers.md            
 
             EVT_RPC_LOGIN {
@@ -0,0 +1,172 @@
+use std::thread;
+use std::sync::{Arc, Mutex};
+use winapi::um::winevt::{
+    EvtQueryFilePath,
+    EvtQueryForwardDirection
+};
+use winapi::um::winbase::INFINITE;
+use crate::winevt::EvtHandle;
+use crate::errors::WinThingError;
+use crate::events::stream::{
+    EventSender,
+    EventStream,
+    StreamProducer
+};
+use crate::winevt::callback::EventHandler;
+use crate::winevt::subscription::{ChannelSubscription, SubscriptionStatus};
+use crate::winevt::wevtapi::{evt_next, evt_query, evt_render};
+
+/// The number of events requested from EvtNext at once.
+const QUERY_BATCH_SIZE: usize = 64;
+/// EvtNext waits until there are events or the result set ends. With a
+/// timeout, slow (e.g. remote) queries would fail partway.
+const QUERY_TIMEOUT: u32 = INFINITE;
+
+
+/// An EventHandler that sends a subscription's events to an EventStream.
+/// Sending blocks the subscription's callback while the stream is full.
+///
+pub struct StreamHandler {
+    channel: String,
+    sender: Mutex<EventSender>
+}
+
+impl StreamHandler {
+    pub fn new(channel: &str, sender: EventSender) -> Self {
+        Self {
+            channel: channel.to_owned(),
+            sender: Mutex::new(sender)
+        }
+    }
+}
+
+impl EventHandler for StreamHandler {
+    fn on_event(&self, xml_event: String) {
+        if let Ok(sender) = self.sender.lock() {
+            if let Err(e) = sender.send_xml(&self.channel, xml_event) {
+                debug!("Dropping event for {}: {}", self.channel, e.message);
+            }
+        }
+    }
+
+    fn on_error(&self, status: &SubscriptionStatus) {
+        // Re-established subscriptions are not errors
+        if status.status == "resubscribed" {
+            return;
+        }
+
+        let mut error = WinThingError::winapi_error(
+            format!("[{}] {}: {}", status.channel, status.status, status.message)
+        );
+        if let Some(code) = status.code {
+            error = error.with_source(
+                WinThingError::from_windows_error_code(code)
+            );
+        }
+
+        if let Ok(sender) = self.sender.lock() {
+            let _ = sender.send_error(error);
+        }
+    }
+}
+
+
+impl StreamProducer for Vec<ChannelSubscription> {
+    fn maintain(&mut self) {
+        for subscription in self.iter_mut() {
+            subscription.maintain();
+        }
+    }
+}
+
+
+/// Subscribe to channels, each with an optional query, and get their
+/// events as a stream. The subscriptions live as long as the stream.
+///
+pub fn subscribe_stream(
+    session: &Option<EvtHandle>,
+    channels: Vec<(String, Option<String>)>,
+    flags: Option<u32>,
+    capacity: usize
+) -> Result<EventStream, WinThingError> {
+    let (sender, stream) = EventStream::channel(capacity);
+
+    let mut subscriptions: Vec<ChannelSubscription> = Vec::new();
+    for (channel, query) in channels {
+        let handler = Arc::new(
+            StreamHandler::new(
+                &channel,
+                sender.clone()
+            )
+        );
+
+        subscriptions.push(
+            ChannelSubscription::new(
+                session,
+                channel,
+                query,
+                flags,
+                handler
+            )?
+        );
+    }
+
+    Ok(
+        stream.with_producer(
+            Box::new(subscriptions)
+        )
+    )
+}
+
+
+/// Read the events of an EVTX file as a stream. The file is read on a
+/// background thread that waits while the stream is full.
+///
+pub fn open_evtx_stream(
+    path: &str,
+    query: Option<String>,
+    capacity: usize
+) -> EventStream {
+    let (sender, stream) = EventStream::channel(capacity);
+    let path = path.to_owned();
+
+    thread::spawn(move || {
+        if let Err(e) = send_evtx_events(&path, query.as_deref(), &sender) {
+            let _ = sender.send_error(e);
+        }
+    });
+
+    stream
+}
+
+
+fn send_evtx_events(
+    path: &str,
+    query: Option<&str>,
+    sender: &EventSender
+) -> Result<(), WinThingError> {
+    let result_set = evt_query(
+        &None,
+        path,
+        query,
+        EvtQueryFilePath | EvtQueryForwardDirection
+    )?;
+
+    loop {
+        let events = evt_next(
+            &result_set,
+            QUERY_BATCH_SIZE,
+            QUERY_TIMEOUT
+        )?;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        for event in events {
+            match evt_render(event.0) {
+                Ok(xml) => sender.send_xml(path, xml)?,
+                Err(e) => sender.send_error(e)?
+            }
+        }
+    }
+}
@@ -1,33 +1,351 @@
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use winapi::ctypes::c_void;
+use winapi::um::winevt::{
+    EVT_HANDLE,
+    EvtSubscribeStartAfterBookmark
+};
 use crate::winevt::EvtHandle;
-use crate::errors::WinThingError;
-use crate::winevt::callback::CallbackContext;
-use crate::winevt::wevtapi::register_event_callback;
+use crate::errors::{WinThingError, WindowsCode};
+use crate::utils::backoff::Backoff;
+use crate::winevt::callback::EventHandler;
+use crate::winevt::wevtapi::{
+    evt_create_bookmark,
+    evt_create_bookmark_from_xml,
+    evt_render,
+    evt_render_bookmark,
+    evt_update_bookmark,
+    register_event_callback
+};
 
 
+/// Health counters of a subscription.
+///
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct SubscriptionHealth {
+    pub events_delivered: u64,
+    pub render_errors: u64,
+    pub subscription_errors: u64,
+    pub resubscribes: u64,
+    pub failed_resubscribes: u64,
+    pub last_error_code: Option<u32>,
+    pub last_event_time: Option<DateTime<Utc>>,
+    pub last_error_time: Option<DateTime<Utc>>
+}
+
+
+/// A status event for a subscription, emitted alongside the event records.
+/// `status` is one of `subscription_error`, `resubscribed` or
+/// `resubscribe_failed`.
+///
+#[derive(Serialize, Debug, Clone)]
+pub struct SubscriptionStatus {
+    pub status: &'static str,
+    pub channel: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_name: Option<&'static str>,
+    pub message: String,
+    pub timestamp: DateTime<Utc>
+}
+
+impl SubscriptionStatus {
+    pub fn new(status: &'static str, channel: &str, message: String) -> Self {
+        Self {
+            status,
+            channel: channel.to_owned(),
+            code: None,
+            code_name: None,
+            message,
+            timestamp: Utc::now()
+        }
+    }
+
+    /// A status for an error code delivered to the subscription callback.
+    pub fn from_error_code(channel: &str, code: u32) -> Self {
+        let error = WinThingError::from_windows_error_code(code);
+        let mut status = Self::new(
+            "subscription_error",
+            channel,
+            error.message
+        );
+        status.code = Some(code);
+        status.code_name = WindowsCode::Win32(code).get_name();
+        status
+    }
+}
+
+
+/// State shared between a ChannelSubscription and its EvtSubscribe
+/// callback.
+///
+pub struct SubscriptionState {
+    channel: String,
+    handler: Arc<dyn EventHandler>,
+    bookmark: Mutex<EvtHandle>,
+    has_bookmark: AtomicBool,
+    failed: AtomicBool,
+    health: Mutex<SubscriptionHealth>
+}
+
+impl SubscriptionState {
+    fn emit_status(&self, status: SubscriptionStatus) {
+        self.handler.on_error(&status);
+    }
+
+    /// Handle an event delivered to the callback.
+    pub fn on_event(&self, event_handle: EVT_HANDLE) {
+        match evt_render(event_handle) {
+            Ok(xml_event) => {
+                self.handler.on_event(
+                    xml_event
+                );
+
+                if let Ok(mut health) = self.health.lock() {
+                    health.events_delivered += 1;
+                    health.last_event_time = Some(Utc::now());
+                }
+            },
+            Err(e) => {
+                error!("Error calling evt_render(): {}", e.display_chain());
+                if let Ok(mut health) = self.health.lock() {
+                    health.render_errors += 1;
+                }
+            }
+        }
+
+        if let Ok(bookmark) = self.bookmark.lock() {
+            match evt_update_bookmark(&bookmark, event_handle) {
+                Ok(_) => self.has_bookmark.store(true, Ordering::SeqCst),
+                Err(e) => error!("Error updating bookmark for {}: {}", self.channel, e.display_chain())
+            }
+        }
+    }
+
+    /// Handle an error delivered to the callback. The subscription is
+    /// re-established by `ChannelSubscription::maintain`.
+    pub fn on_error(&self, code: u32) {
+        if let Ok(mut health) = self.health.lock() {
+            health.subscription_errors += 1;
+            health.last_error_code = Some(code);
+            health.last_error_time = Some(Utc::now());
+        }
+
+        self.failed.store(true, Ordering::SeqCst);
+        self.emit_status(
+            SubscriptionStatus::from_error_code(&self.channel, code)
+        );
+    }
+}
+
+
+/// A subscription to a channel. Events are rendered and passed to the
+/// handler, which the subscription keeps alive until it is dropped. When
+/// the callback reports an error the subscription is re-established from
+/// the last delivered event by `maintain`.
+///
 pub struct ChannelSubscription {
-    _channel: String,
-    _subscription_handle: EvtHandle
+    // Closed first so no callback can run once the state and its handler
+    // are dropped
+    subscription_handle: Option<EvtHandle>,
+    state: Box<SubscriptionState>,
+    // The session must outlive the subscription
+    session: EVT_HANDLE,
+    query: Option<String>,
+    flags: Option<u32>,
+    backoff: Backoff,
+    next_attempt: Instant
 }
 
 impl ChannelSubscription {
     pub fn new(
         session: &Option<EvtHandle>,
-        channel: String, 
-        query: Option<String>, 
-        flags: Option<u32>, 
-        context: &CallbackContext
+        channel: String,
+        query: Option<String>,
+        flags: Option<u32>,
+        handler: Arc<dyn EventHandler>
+    ) -> Result<Self, WinThingError> {
+        Self::create(
+            session,
+            channel,
+            query,
+            flags,
+            None,
+            handler
+        )
+    }
+
+    /// Subscribe to a channel starting after the event of a bookmark from
+    /// `get_bookmark_xml`, e.g. to continue a subscription on a new session
+    /// without losing the events logged in between.
+    pub fn after_bookmark(
+        session: &Option<EvtHandle>,
+        channel: String,
+        query: Option<String>,
+        bookmark_xml: &str,
+        handler: Arc<dyn EventHandler>
     ) -> Result<Self, WinThingError> {
-        let handle = register_event_callback(
-            &session,
-            &channel,
+        Self::create(
+            session,
+            channel,
+            query,
+            None,
+            Some(bookmark_xml),
+            handler
+        )
+    }
+
+    fn create(
+        session: &Option<EvtHandle>,
+        channel: String,
+        query: Option<String>,
+        flags: Option<u32>,
+        bookmark_xml: Option<&str>,
+        handler: Arc<dyn EventHandler>
+    ) -> Result<Self, WinThingError> {
+        let session = match session {
+            Some(s) => s.0,
+            None => null_mut()
+        };
+
+        let bookmark = match bookmark_xml {
+            Some(xml) => evt_create_bookmark_from_xml(xml)?,
+            None => evt_create_bookmark()?
+        };
+
+        let state = Box::new(
+            SubscriptionState {
+                channel,
+                handler,
+                bookmark: Mutex::new(bookmark),
+                has_bookmark: AtomicBool::new(bookmark_xml.is_some()),
+                failed: AtomicBool::new(false),
+                health: Mutex::new(SubscriptionHealth::default())
+            }
+        );
+
+        let mut subscription = ChannelSubscription {
+            subscription_handle: None,
+            state,
+            session,
             query,
             flags,
-            context
-        )?;
+            backoff: Backoff::default(),
+            next_attempt: Instant::now()
+        };
+        subscription.subscribe()?;
+
+        Ok(subscription)
+    }
+
+    pub fn get_channel(&self) -> &str {
+        &self.state.channel
+    }
+
+    /// Check if the subscription is active and has not reported an error.
+    pub fn is_healthy(&self) -> bool {
+        self.subscription_handle.is_some() && !self.state.failed.load(Ordering::SeqCst)
+    }
+
+    /// Get the bookmark of the last delivered event as XML, or None if no
+    /// event was delivered.
+    pub fn get_bookmark_xml(&self) -> Result<Option<String>, WinThingError> {
+        if !self.state.has_bookmark.load(Ordering::SeqCst) {
+            return Ok(None);
+        }
 
-        Ok(ChannelSubscription {
-            _channel: channel,
-            _subscription_handle: handle
-        })
+        let bookmark = match self.state.bookmark.lock() {
+            Ok(b) => b,
+            Err(poisoned) => poisoned.into_inner()
+        };
+
+        Ok(Some(evt_render_bookmark(&bookmark)?))
+    }
+
+    pub fn get_health(&self) -> SubscriptionHealth {
+        match self.state.health.lock() {
+            Ok(health) => health.clone(),
+            Err(poisoned) => poisoned.into_inner().clone()
+        }
     }
-}
\ No newline at end of file
+
+    fn subscribe(&mut self) -> Result<(), WinThingError> {
+        // Close the old subscription before starting a new one
+        self.subscription_handle = None;
+
+        let state_ptr = &*self.state as *const SubscriptionState as *mut c_void;
+        let handle = {
+            let bookmark = match self.state.bookmark.lock() {
+                Ok(b) => b,
+                Err(poisoned) => poisoned.into_inner()
+            };
+
+            // Continue after the last delivered event if there is one
+            let (bookmark, flags) = match self.state.has_bookmark.load(Ordering::SeqCst) {
+                true => (Some(&*bookmark), Some(EvtSubscribeStartAfterBookmark)),
+                false => (None, self.flags)
+            };
+
+            register_event_callback(
+                self.session,
+                &self.state.channel,
+                self.query.clone(),
+                bookmark,
+                flags,
+                state_ptr
+            )?
+        };
+
+        self.subscription_handle = Some(handle);
+        self.state.failed.store(false, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Re-establish the subscription if its callback reported an error and
+    /// the backoff has passed. Call this periodically.
+    pub fn maintain(&mut self) {
+        if self.is_healthy() || Instant::now() < self.next_attempt {
+            return;
+        }
+
+        match self.subscribe() {
+            Ok(_) => {
+                self.backoff.reset();
+                if let Ok(mut health) = self.state.health.lock() {
+                    health.resubscribes += 1;
+                }
+
+                self.state.emit_status(
+                    SubscriptionStatus::new(
+                        "resubscribed",
+                        &self.state.channel,
+                        "Subscription re-established.".to_owned()
+                    )
+                );
+            },
+            Err(e) => {
+                let delay = self.backoff.next_delay();
+                self.next_attempt = Instant::now() + delay;
+                if let Ok(mut health) = self.state.health.lock() {
+                    health.failed_resubscribes += 1;
+                    health.last_error_code = e.get_win32_code();
+                    health.last_error_time = Some(Utc::now());
+                }
+
+                let mut status = SubscriptionStatus::new(
+                    "resubscribe_failed",
+                    &self.state.channel,
+                    format!("{}; retrying in {}s", e.display_chain(), delay.as_secs())
+                );
+                status.code = e.get_win32_code();
+                status.code_name = e.code.and_then(|c| c.get_name());
+                self.state.emit_status(status);
+            }
+        }
+    }
+}
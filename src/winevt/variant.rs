@@ -1,16 +1,51 @@
 use hex;
 use std::fmt;
+use std::mem::size_of;
 use serde::Serialize;
 use serde_json::Value;
-use std::ffi::OsString;
 use serde_json::Number;
-use std::os::windows::prelude::*;
-use winapi::shared::guiddef::GUID;
-use winapi::um::winevt::*;
+use byteorder::{ByteOrder, LittleEndian, BigEndian};
+use chrono::{DateTime, NaiveDate, SecondsFormat, TimeZone, Utc};
 use crate::errors::WinThingError;
 
+// EVT_VARIANT_TYPE
+pub const EVT_VAR_TYPE_NULL: u32 = 0;
+pub const EVT_VAR_TYPE_STRING: u32 = 1;
+pub const EVT_VAR_TYPE_ANSI_STRING: u32 = 2;
+pub const EVT_VAR_TYPE_SBYTE: u32 = 3;
+pub const EVT_VAR_TYPE_BYTE: u32 = 4;
+pub const EVT_VAR_TYPE_INT16: u32 = 5;
+pub const EVT_VAR_TYPE_UINT16: u32 = 6;
+pub const EVT_VAR_TYPE_INT32: u32 = 7;
+pub const EVT_VAR_TYPE_UINT32: u32 = 8;
+pub const EVT_VAR_TYPE_INT64: u32 = 9;
+pub const EVT_VAR_TYPE_UINT64: u32 = 10;
+pub const EVT_VAR_TYPE_SINGLE: u32 = 11;
+pub const EVT_VAR_TYPE_DOUBLE: u32 = 12;
+pub const EVT_VAR_TYPE_BOOLEAN: u32 = 13;
+pub const EVT_VAR_TYPE_BINARY: u32 = 14;
+pub const EVT_VAR_TYPE_GUID: u32 = 15;
+pub const EVT_VAR_TYPE_SIZE_T: u32 = 16;
+pub const EVT_VAR_TYPE_FILE_TIME: u32 = 17;
+pub const EVT_VAR_TYPE_SYS_TIME: u32 = 18;
+pub const EVT_VAR_TYPE_SID: u32 = 19;
+pub const EVT_VAR_TYPE_HEX_INT32: u32 = 20;
+pub const EVT_VAR_TYPE_HEX_INT64: u32 = 21;
+pub const EVT_VAR_TYPE_EVT_HANDLE: u32 = 32;
+pub const EVT_VAR_TYPE_EVT_XML: u32 = 35;
+/// Set on the type of variants that hold an array of `Count` values.
+pub const EVT_VARIANT_TYPE_ARRAY: u32 = 128;
+pub const EVT_VARIANT_TYPE_MASK: u32 = 0x7f;
 
-#[derive(Serialize, Debug)]
+/// The size of an EVT_VARIANT: an 8 byte value union, then the DWORD Count
+/// and the DWORD Type.
+pub const EVT_VARIANT_SIZE: usize = 16;
+const POINTER_SIZE: usize = size_of::<usize>();
+/// The number of 100 nanosecond intervals from 1601-01-01 to 1970-01-01.
+const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum VariantValue {
     Null,
@@ -21,21 +56,13 @@ pub enum VariantValue {
     Double(f64),
     Boolean(bool),
     Binary(Vec<u8>),
+    Guid(String),
+    Sid(String),
+    DateTime(DateTime<Utc>),
+    Handle(u64),
+    Array(Vec<VariantValue>)
 }
 impl VariantValue {
-    pub fn to_string(&self) -> String {
-        match self {
-            VariantValue::Null => "Null".to_string(),
-            VariantValue::String(s) => s.clone(),
-            VariantValue::UInt(v) => v.to_string(),
-            VariantValue::Int(v) => v.to_string(),
-            VariantValue::Single(v) => v.to_string(),
-            VariantValue::Double(v) => v.to_string(),
-            VariantValue::Boolean(v) => v.to_string(),
-            VariantValue::Binary(v) => hex::encode(&v)
-        }
-    }
-
     pub fn to_json_value(&self) -> Value {
         match self {
             VariantValue::Null => Value::Null,
@@ -52,7 +79,7 @@ impl VariantValue {
                     None => {
                         eprintln!("Could not convert VariantValue::Single {} to Number from_f64.", v);
                         Value::Null
-                    } 
+                    }
                 }
             },
             VariantValue::Double(v) => {
@@ -61,219 +88,408 @@ impl VariantValue {
                     None => {
                         eprintln!("Could not convert VariantValue::Double {} to Number from_f64.", v);
                         Value::Null
-                    } 
+                    }
                 }
             },
-            VariantValue::Boolean(v) => Value::Bool(v.clone()),
+            VariantValue::Boolean(v) => Value::Bool(*v),
             VariantValue::Binary(v) => Value::String(
-                hex::encode(&v)
+                hex::encode(v)
+            ),
+            VariantValue::Array(v) => Value::Array(
+                v.iter()
+                    .map(|e| e.to_json_value())
+                    .collect()
+            ),
+            other => Value::String(
+                other.to_string()
             )
         }
     }
 
-    pub fn from_variant(variant: &EVT_VARIANT) -> Result<Self, WinThingError> {
-        #[allow(non_upper_case_globals)]
-        let value = match variant.Type {
-            EvtVarTypeNull => VariantValue::Null,
-            EvtVarTypeString => {
-                let slice: &[u16];
-                unsafe {
-                    let ptr = variant.u.StringVal();
-                    let len = (0..).take_while(
-                        |&i| *ptr.offset(i) != 0
-                    ).count();
-                    slice = std::slice::from_raw_parts(
-                        *ptr, 
-                        len
-                    );
-                }
+    /// Decode the EVT_VARIANT at the start of `buffer`. See
+    /// `decode_variants`.
+    pub fn from_buffer(buffer: &[u8], base_address: u64) -> Result<Self, WinThingError> {
+        VariantBuffer::new(buffer, base_address)
+            .decode_variant(0)
+    }
+}
+impl fmt::Display for VariantValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VariantValue::Null => write!(f, "Null"),
+            VariantValue::String(s) => write!(f, "{}", s),
+            VariantValue::UInt(v) => write!(f, "{}", v),
+            VariantValue::Int(v) => write!(f, "{}", v),
+            VariantValue::Single(v) => write!(f, "{}", v),
+            VariantValue::Double(v) => write!(f, "{}", v),
+            VariantValue::Boolean(v) => write!(f, "{}", v),
+            VariantValue::Binary(v) => write!(f, "{}", hex::encode(v)),
+            VariantValue::Guid(v) => write!(f, "{}", v),
+            VariantValue::Sid(v) => write!(f, "{}", v),
+            VariantValue::DateTime(v) => write!(f, "{}", v.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            VariantValue::Handle(v) => write!(f, "0x{:x}", v),
+            VariantValue::Array(v) => {
+                let values: Vec<String> = v.iter()
+                    .map(|e| e.to_string())
+                    .collect();
+                write!(f, "{}", values.join(", "))
+            }
+        }
+    }
+}
 
-                let string = OsString::from_wide(
-                    &slice[..]
-                ).to_string_lossy().to_string();
 
-                VariantValue::String(string)
-            },
-            EvtVarTypeAnsiString => {
-                let slice : &[u8];
-                unsafe {
-                    let ptr = variant.u.AnsiStringVal();
-                    let len = (0..).take_while(|&i| *ptr.offset(i) != 0).count();
-                    slice = std::slice::from_raw_parts(*ptr as *const u8, len);
-                }
-                VariantValue::String(
-                    String::from_utf8(
-                        slice.to_vec()
-                    )?
-                )
-            },
-            EvtVarTypeSByte => {
-                let val: &i8 = unsafe {
-                    variant.u.SByteVal()
-                };
-                VariantValue::Int(
-                    *val as i64
-                )
-            },
-            EvtVarTypeByte => {
-                let val: &u8 = unsafe {
-                    variant.u.ByteVal()
-                };
-                VariantValue::UInt(
-                    *val as u64
-                )
-            },
-            EvtVarTypeInt16 => {
-                let val: &i16 = unsafe {
-                    variant.u.Int16Val()
-                };
-                VariantValue::Int(
-                    *val as i64
-                )
-            },
-            EvtVarTypeUInt16 => {
-                let val: &u16 = unsafe {
-                    variant.u.UInt16Val()
-                };
-                VariantValue::UInt(
-                    *val as u64
-                )
-            },
-            EvtVarTypeInt32 => {
-                let val: &i32 = unsafe {
-                    variant.u.Int32Val()
-                };
-                VariantValue::Int(
-                    *val as i64
-                )
-            },
-            EvtVarTypeUInt32 => {
-                let val: &u32 = unsafe {
-                    variant.u.UInt32Val()
-                };
-                VariantValue::UInt(
-                    *val as u64
-                )
-            },
-            EvtVarTypeInt64 => {
-                let val: &i64 = unsafe {
-                    variant.u.Int64Val()
-                };
-                VariantValue::Int(
-                    *val as i64
-                )
-            },
-            EvtVarTypeUInt64 => {
-                let val: &u64 = unsafe {
-                    variant.u.UInt64Val()
-                };
-                VariantValue::UInt(
-                    *val as u64
-                )
-            },
-            EvtVarTypeSingle => {
-                let val: &f32 = unsafe {
-                    variant.u.SingleVal()
-                };
-                VariantValue::Single(
-                    *val
-                )
-            },
-            EvtVarTypeDouble => {
-                let val: &f64 = unsafe {
-                    variant.u.DoubleVal()
-                };
-                VariantValue::Double(
-                    *val
+/// Decode `count` consecutive EVT_VARIANTs from a buffer filled by the
+/// event log API, such as by EvtGetChannelConfigProperty or EvtRender.
+/// Pointers in the variants point into the buffer and are resolved
+/// relative to `base_address`, the address the buffer was at when it was
+/// filled.
+///
+pub fn decode_variants(
+    buffer: &[u8],
+    count: usize,
+    base_address: u64
+) -> Result<Vec<VariantValue>, WinThingError> {
+    let variant_buffer = VariantBuffer::new(buffer, base_address);
+
+    (0..count)
+        .map(|i| variant_buffer.decode_variant(i * EVT_VARIANT_SIZE))
+        .collect()
+}
+
+
+/// Convert a FILETIME (100 nanosecond intervals since 1601-01-01) to a
+/// DateTime.
+///
+pub fn filetime_to_datetime(filetime: u64) -> Option<DateTime<Utc>> {
+    let unix_time = filetime as i128 - FILETIME_UNIX_EPOCH as i128;
+    let seconds = unix_time.div_euclid(10_000_000) as i64;
+    let nanoseconds = unix_time.rem_euclid(10_000_000) as u32 * 100;
+
+    Utc.timestamp_opt(seconds, nanoseconds).single()
+}
+
+
+/// Format the 16 bytes of a GUID as `{XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}`.
+///
+pub fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{{{:08X}-{:04X}-{:04X}-{}-{}}}",
+        LittleEndian::read_u32(&bytes[0..4]),
+        LittleEndian::read_u16(&bytes[4..6]),
+        LittleEndian::read_u16(&bytes[6..8]),
+        hex::encode_upper(&bytes[8..10]),
+        hex::encode_upper(&bytes[10..16])
+    )
+}
+
+
+/// Format a binary SID as a SID string (`S-1-5-18`). Returns the string and
+/// the size of the SID.
+///
+pub fn format_sid(bytes: &[u8]) -> Result<(String, usize), WinThingError> {
+    if bytes.len() < 8 {
+        return Err(
+            WinThingError::unhandled_variant(
+                format!("SID is {} bytes, expected at least 8.", bytes.len())
+            )
+        );
+    }
+
+    let sub_authority_count = bytes[1] as usize;
+    let size = 8 + sub_authority_count * 4;
+    if bytes.len() < size {
+        return Err(
+            WinThingError::unhandled_variant(
+                format!("SID with {} sub authorities is truncated.", sub_authority_count)
+            )
+        );
+    }
+
+    let authority = BigEndian::read_uint(&bytes[2..8], 6);
+    let mut sid = match authority >> 32 {
+        0 => format!("S-{}-{}", bytes[0], authority),
+        _ => format!("S-{}-0x{:012X}", bytes[0], authority)
+    };
+    for i in 0..sub_authority_count {
+        let offset = 8 + i * 4;
+        sid.push_str(
+            &format!("-{}", LittleEndian::read_u32(&bytes[offset..offset + 4]))
+        );
+    }
+
+    Ok((sid, size))
+}
+
+
+/// A buffer of EVT_VARIANTs and the values they point to.
+struct VariantBuffer<'a> {
+    buffer: &'a [u8],
+    base_address: u64
+}
+
+impl<'a> VariantBuffer<'a> {
+    fn new(buffer: &'a [u8], base_address: u64) -> Self {
+        Self {
+            buffer,
+            base_address
+        }
+    }
+
+    fn get_slice(&self, offset: usize, length: usize) -> Result<&'a [u8], WinThingError> {
+        self.buffer.get(offset..offset + length).ok_or_else(||
+            WinThingError::unhandled_variant(
+                format!("Variant data at offset {} ({} bytes) is outside the buffer.", offset, length)
+            )
+        )
+    }
+
+    /// Get the buffer offset a pointer points to.
+    fn resolve(&self, pointer: u64) -> Result<usize, WinThingError> {
+        match pointer.checked_sub(self.base_address) {
+            Some(offset) if (offset as usize) < self.buffer.len() => Ok(offset as usize),
+            _ => Err(
+                WinThingError::unhandled_variant(
+                    format!("Variant pointer 0x{:x} is outside the buffer.", pointer)
                 )
-            },
-            EvtVarTypeBoolean => {
-                let val: &i32 = unsafe {
-                    variant.u.BooleanVal()
+            )
+        }
+    }
+
+    /// Get the data a pointer points to.
+    fn get_pointed(&self, pointer: u64, length: usize) -> Result<&'a [u8], WinThingError> {
+        let offset = self.resolve(pointer)?;
+        self.get_slice(offset, length)
+    }
+
+    fn get_remaining(&self, pointer: u64) -> Result<&'a [u8], WinThingError> {
+        let offset = self.resolve(pointer)?;
+        Ok(&self.buffer[offset..])
+    }
+
+    fn read_wide_string(&self, pointer: u64) -> Result<String, WinThingError> {
+        let data = self.get_remaining(pointer)?;
+        let wide: Vec<u16> = data.chunks_exact(2)
+            .map(LittleEndian::read_u16)
+            .take_while(|&c| c != 0)
+            .collect();
+
+        Ok(String::from_utf16(&wide)?)
+    }
+
+    fn read_ansi_string(&self, pointer: u64) -> Result<String, WinThingError> {
+        let data = self.get_remaining(pointer)?;
+        let length = data.iter()
+            .position(|&b| b == 0)
+            .unwrap_or(data.len());
+
+        Ok(String::from_utf8(data[..length].to_vec())?)
+    }
+
+    fn decode_variant(&self, offset: usize) -> Result<VariantValue, WinThingError> {
+        let variant = self.get_slice(offset, EVT_VARIANT_SIZE)?;
+        let value = &variant[0..8];
+        let count = LittleEndian::read_u32(&variant[8..12]);
+        let variant_type = LittleEndian::read_u32(&variant[12..16]);
+
+        if variant_type & EVT_VARIANT_TYPE_ARRAY != 0 {
+            return self.decode_array(
+                variant_type & EVT_VARIANT_TYPE_MASK,
+                read_pointer(value),
+                count as usize
+            );
+        }
+
+        self.decode_value(variant_type, value, count, false)
+    }
+
+    fn decode_array(
+        &self,
+        variant_type: u32,
+        pointer: u64,
+        count: usize
+    ) -> Result<VariantValue, WinThingError> {
+        if pointer == 0 || count == 0 {
+            return Ok(VariantValue::Array(Vec::new()));
+        }
+
+        let size = match get_element_size(variant_type) {
+            Some(s) => s,
+            None => {
+                return Err(
+                    WinThingError::unhandled_variant(
+                        format!("Unhandled EVT_VARIANT array type {}", variant_type)
+                    )
+                );
+            }
+        };
+
+        let data = self.get_pointed(pointer, size * count)?;
+        let values = data.chunks_exact(size)
+            .map(|element| self.decode_value(variant_type, element, 0, true))
+            .collect::<Result<Vec<VariantValue>, WinThingError>>()?;
+
+        Ok(VariantValue::Array(values))
+    }
+
+    /// Decode a value from its inline data, which is the variant's value
+    /// union or, if `is_element`, an element of an array.
+    fn decode_value(
+        &self,
+        variant_type: u32,
+        data: &[u8],
+        count: u32,
+        is_element: bool
+    ) -> Result<VariantValue, WinThingError> {
+        // Types that point to their value are Null for a null pointer. Array
+        // elements of GUIDs and SYSTEMTIMEs hold the value itself.
+        let is_pointer_type = match variant_type {
+            EVT_VAR_TYPE_STRING | EVT_VAR_TYPE_ANSI_STRING | EVT_VAR_TYPE_SID |
+            EVT_VAR_TYPE_EVT_XML => true,
+            EVT_VAR_TYPE_BINARY | EVT_VAR_TYPE_GUID | EVT_VAR_TYPE_SYS_TIME => !is_element,
+            _ => false
+        };
+        if is_pointer_type && read_pointer(data) == 0 {
+            return Ok(VariantValue::Null);
+        }
+
+        let value = match variant_type {
+            EVT_VAR_TYPE_NULL => VariantValue::Null,
+            EVT_VAR_TYPE_STRING | EVT_VAR_TYPE_EVT_XML => VariantValue::String(
+                self.read_wide_string(read_pointer(data))?
+            ),
+            EVT_VAR_TYPE_ANSI_STRING => VariantValue::String(
+                self.read_ansi_string(read_pointer(data))?
+            ),
+            EVT_VAR_TYPE_SBYTE => VariantValue::Int(data[0] as i8 as i64),
+            EVT_VAR_TYPE_BYTE => VariantValue::UInt(data[0] as u64),
+            EVT_VAR_TYPE_INT16 => VariantValue::Int(LittleEndian::read_i16(data) as i64),
+            EVT_VAR_TYPE_UINT16 => VariantValue::UInt(LittleEndian::read_u16(data) as u64),
+            EVT_VAR_TYPE_INT32 => VariantValue::Int(LittleEndian::read_i32(data) as i64),
+            EVT_VAR_TYPE_UINT32 | EVT_VAR_TYPE_HEX_INT32 => VariantValue::UInt(
+                LittleEndian::read_u32(data) as u64
+            ),
+            EVT_VAR_TYPE_INT64 => VariantValue::Int(LittleEndian::read_i64(data)),
+            EVT_VAR_TYPE_UINT64 | EVT_VAR_TYPE_HEX_INT64 => VariantValue::UInt(
+                LittleEndian::read_u64(data)
+            ),
+            EVT_VAR_TYPE_SINGLE => VariantValue::Single(LittleEndian::read_f32(data)),
+            EVT_VAR_TYPE_DOUBLE => VariantValue::Double(LittleEndian::read_f64(data)),
+            EVT_VAR_TYPE_BOOLEAN => VariantValue::Boolean(LittleEndian::read_i32(data) != 0),
+            EVT_VAR_TYPE_SIZE_T => VariantValue::UInt(read_pointer(data)),
+            EVT_VAR_TYPE_EVT_HANDLE => VariantValue::Handle(read_pointer(data)),
+            // The size of a binary value is its count
+            EVT_VAR_TYPE_BINARY => VariantValue::Binary(
+                self.get_pointed(read_pointer(data), count as usize)?.to_vec()
+            ),
+            EVT_VAR_TYPE_GUID => {
+                let guid = match is_element {
+                    true => data,
+                    false => self.get_pointed(read_pointer(data), 16)?
                 };
-                VariantValue::Boolean(
-                    *val != 0
-                )
+                VariantValue::Guid(format_guid(guid))
             },
-            EvtVarTypeBinary => {
-                let val: u8 = unsafe {
-                    **variant.u.BinaryVal()
-                };
-                VariantValue::String(
-                    format!("{:02X}", val)
-                )
+            EVT_VAR_TYPE_FILE_TIME => {
+                let filetime = LittleEndian::read_u64(data);
+                match filetime_to_datetime(filetime) {
+                    Some(dt) => VariantValue::DateTime(dt),
+                    None => {
+                        return Err(
+                            WinThingError::unhandled_variant(
+                                format!("Invalid FILETIME {}", filetime)
+                            )
+                        );
+                    }
+                }
             },
-            EvtVarTypeGuid => {
-                let val: GUID = unsafe {
-                    std::ptr::read(
-                        *variant.u.GuidVal()
-                    )
+            EVT_VAR_TYPE_SYS_TIME => {
+                let system_time = match is_element {
+                    true => data,
+                    false => self.get_pointed(read_pointer(data), 16)?
                 };
-                VariantValue::String(
-                    format!(
-                        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
-                        val.Data1, val.Data2, val.Data3, 
-                        val.Data4[0], val.Data4[1], val.Data4[2],
-                        val.Data4[3], val.Data4[4], val.Data4[5], 
-                        val.Data4[6], val.Data4[7]
-                    )
-                )
+                VariantValue::DateTime(systemtime_to_datetime(system_time)?)
             },
-            EvtVarTypeHexInt64 => {
-                let val: u64 = unsafe {
-                    std::ptr::read(
-                        &variant.u as *const _ as *const u64
-                    )
-                };
-                VariantValue::UInt(val)
+            EVT_VAR_TYPE_SID => {
+                let (sid, _) = format_sid(
+                    self.get_remaining(read_pointer(data))?
+                )?;
+                VariantValue::Sid(sid)
             },
-            EvtVarTypeHexInt32 => {
-                let val: u32 = unsafe {
-                    std::ptr::read(
-                        &variant.u as *const _ as *const u32
-                    )
-                };
-                VariantValue::UInt(
-                    val as u64
-                )
-            }
             unknown => {
-                let message = format!(
-                    "Unhandled EVT_VARIANT type {} (count {}) (contents {})",
-                    unknown, 
-                    variant.Count, 
-                    unsafe {
-                        std::ptr::read(
-                            &variant.u as *const _ as *const u64
-                        )
-                    }
-                );
-
                 return Err(
                     WinThingError::unhandled_variant(
-                        message
+                        format!("Unhandled EVT_VARIANT type {} (count {})", unknown, count)
                     )
-                )
-            },
+                );
+            }
         };
 
         Ok(value)
     }
 }
-impl fmt::Display for VariantValue {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+
+
+/// The size of an array element. Arrays of GUIDs and SYSTEMTIMEs hold the
+/// values, arrays of strings and SIDs hold pointers.
+fn get_element_size(variant_type: u32) -> Option<usize> {
+    match variant_type {
+        EVT_VAR_TYPE_SBYTE | EVT_VAR_TYPE_BYTE => Some(1),
+        EVT_VAR_TYPE_INT16 | EVT_VAR_TYPE_UINT16 => Some(2),
+        EVT_VAR_TYPE_INT32 | EVT_VAR_TYPE_UINT32 | EVT_VAR_TYPE_HEX_INT32 |
+        EVT_VAR_TYPE_SINGLE | EVT_VAR_TYPE_BOOLEAN => Some(4),
+        EVT_VAR_TYPE_INT64 | EVT_VAR_TYPE_UINT64 | EVT_VAR_TYPE_HEX_INT64 |
+        EVT_VAR_TYPE_DOUBLE | EVT_VAR_TYPE_FILE_TIME => Some(8),
+        EVT_VAR_TYPE_GUID | EVT_VAR_TYPE_SYS_TIME => Some(16),
+        EVT_VAR_TYPE_STRING | EVT_VAR_TYPE_ANSI_STRING | EVT_VAR_TYPE_SID |
+        EVT_VAR_TYPE_SIZE_T | EVT_VAR_TYPE_EVT_HANDLE | EVT_VAR_TYPE_EVT_XML => Some(POINTER_SIZE),
+        _ => None
+    }
+}
+
+
+fn read_pointer(data: &[u8]) -> u64 {
+    match POINTER_SIZE {
+        4 => LittleEndian::read_u32(data) as u64,
+        _ => LittleEndian::read_u64(data)
     }
 }
 
 
-pub struct EvtVariant(
-    pub EVT_VARIANT
-);
+/// Convert a SYSTEMTIME (eight WORDs: year, month, day of week, day, hour,
+/// minute, second, milliseconds) to a DateTime.
+fn systemtime_to_datetime(data: &[u8]) -> Result<DateTime<Utc>, WinThingError> {
+    let field = |index: usize| LittleEndian::read_u16(&data[index * 2..index * 2 + 2]) as u32;
+
+    NaiveDate::from_ymd_opt(field(0) as i32, field(1), field(3))
+        .and_then(|date| date.and_hms_milli_opt(field(4), field(5), field(6), field(7)))
+        .map(|dt| Utc.from_utc_datetime(&dt))
+        .ok_or_else(||
+            WinThingError::unhandled_variant(
+                "Invalid SYSTEMTIME".to_owned()
+            )
+        )
+}
+
+
+/// An EVT_VARIANT and the buffer holding the values it points to, as filled
+/// by one of the EvtGet*Property functions.
+///
+pub struct EvtVariant {
+    buffer: Vec<u8>
+}
 impl EvtVariant {
+    /// Take ownership of a filled buffer. The buffer's data must not have
+    /// moved since it was filled.
+    pub fn from_buffer(buffer: Vec<u8>) -> Self {
+        Self {
+            buffer
+        }
+    }
+
     pub fn get_variant_value(&self) -> Result<VariantValue, WinThingError> {
-        VariantValue::from_variant(
-            &self.0
+        VariantValue::from_buffer(
+            &self.buffer,
+            self.buffer.as_ptr() as u64
         )
     }
 
@@ -288,10 +504,7 @@ impl fmt::Display for EvtVariant {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self.get_variant_value() {
             Ok(v) => write!(f, "{}", v),
-            Err(e) => {
-                eprintln!("Error formating EvtVariant {:?}", e);
-                write!(f, "{:?}", e)
-            }
+            Err(e) => write!(f, "{:?}", e)
         }
     }
-}
\ No newline at end of file
+}
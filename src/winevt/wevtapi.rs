@@ -2,7 +2,6 @@ use std::ffi::OsString;
 use std::ptr::null_mut;
 use winapi::um::winevt::*;
 use winapi::ctypes::c_void;
-use winapi::um::winevt::EvtClose;
 use std::os::windows::prelude::*;
 use winapi::shared::minwindef::{DWORD};
 use winapi::um::errhandlingapi::GetLastError;
@@ -11,7 +10,7 @@ use winapi::shared::winerror::ERROR_INSUFFICIENT_BUFFER;
 use crate::winevt::EvtHandle;
 use crate::errors::WinThingError;
 use crate::winevt::variant::EvtVariant;
-use crate::winevt::callback::CallbackContext;
+use crate::winevt::subscription::SubscriptionState;
 
 
 /// BOOL EvtRender(
@@ -25,12 +24,34 @@ use crate::winevt::callback::CallbackContext;
 /// );
 pub fn evt_render(
     event_handle: EVT_HANDLE
+) -> Result<String, WinThingError> {
+    render_xml(
+        event_handle,
+        EvtRenderEventXml
+    )
+}
+
+
+/// Render a bookmark as XML, which `evt_create_bookmark_from_xml` can
+/// create it again from.
+pub fn evt_render_bookmark(
+    bookmark: &EvtHandle
+) -> Result<String, WinThingError> {
+    render_xml(
+        bookmark.0,
+        EvtRenderBookmark
+    )
+}
+
+
+fn render_xml(
+    event_handle: EVT_HANDLE,
+    flags: DWORD
 ) -> Result<String, WinThingError> {
     let mut buffer_used: DWORD = 0;
     let mut property_count: DWORD = 0;
 
     let context = null_mut();
-    let flags = EvtRenderEventXml;
 
     let result = unsafe {
         EvtRender(
@@ -115,35 +136,27 @@ pub extern "system" fn evt_subscribe_callback(
     user_context: *mut c_void, 
     event_handle: EVT_HANDLE
 ) -> u32 {
-    if action != EvtSubscribeActionDeliver {
-        error!("Expected EvtSubscribeActionDeliver for evt_subscribe_callback but found {:?}", action);
-        return 0;
-    }
-
-    let user_context: Box<&CallbackContext> = unsafe {
-        Box::from_raw(user_context as *mut _)
+    // The state is owned by the ChannelSubscription, which closes the
+    // subscription before the state is dropped.
+    let state = unsafe {
+        &*(user_context as *const SubscriptionState)
     };
 
-    match evt_render(event_handle) {
-        Ok(xml_event) => {
-            user_context.handle_record(
-                xml_event
-            );
+    match action {
+        EvtSubscribeActionDeliver => {
+            // The event handle is owned by the service and must not be
+            // closed here
+            state.on_event(event_handle);
+        },
+        EvtSubscribeActionError => {
+            // For errors the event handle is the Win32 error code
+            state.on_error(event_handle as usize as u32);
         },
-        Err(e) => {
-            error!("Error calling evt_render(): {:?}", e);
+        other => {
+            error!("Unhandled EVT_SUBSCRIBE_NOTIFY_ACTION for evt_subscribe_callback: {:?}", other);
         }
     }
 
-    // Close the EVT_HANDLE
-    unsafe {
-        EvtClose(event_handle);
-    }
-
-    // Prevent double-free of reference
-    // If you dont do this, you will get an app crash
-    Box::leak(user_context);
-
     return 0;
 }
 
@@ -159,17 +172,13 @@ pub extern "system" fn evt_subscribe_callback(
 ///   DWORD                  Flags
 /// );
 pub fn register_event_callback(
-        session: &Option<EvtHandle>,
+        session: EVT_HANDLE,
         channel_path: &String, 
         query: Option<String>,
+        bookmark: Option<&EvtHandle>,
         flags: Option<u32>,
-        context: &CallbackContext
+        user_context: *mut c_void
 ) -> Result<EvtHandle, WinThingError> {
-    let session = match session {
-        Some(s) => s.0,
-        None => null_mut()
-    };
-    
     // This is null becuase we are using a callback
     let signal_event = null_mut();
 
@@ -185,12 +194,10 @@ pub fn register_event_callback(
     let mut query_str_u16 : Vec<u16> = query_str.encode_utf16().collect();
     query_str_u16.resize(query_str.len() + 1, 0);
 
-    let context = Box::into_raw(
-        Box::from(context)
-    );
-
-    // Bookmarks are not currently implemented
-    let bookmark = null_mut();
+    let bookmark = match bookmark {
+        Some(b) => b.0,
+        None => null_mut()
+    };
 
     let flags = match flags {
         Some(f) => f,
@@ -205,7 +212,7 @@ pub fn register_event_callback(
             channel_path_u16.as_ptr(),
             query_str_u16.as_ptr(),
             bookmark,
-            context as *mut c_void,
+            user_context,
             Some(evt_subscribe_callback),
             flags
         )
@@ -225,6 +232,187 @@ pub fn register_event_callback(
 }
 
 
+/// EVT_HANDLE EvtQuery(
+///   EVT_HANDLE Session,
+///   LPCWSTR    Path,
+///   LPCWSTR    Query,
+///   DWORD      Flags
+/// );
+pub fn evt_query(
+    session: &Option<EvtHandle>,
+    path: &str,
+    query: Option<&str>,
+    flags: u32
+) -> Result<EvtHandle, WinThingError> {
+    let session = match session {
+        Some(s) => s.0,
+        None => null_mut()
+    };
+
+    let mut path_u16: Vec<u16> = path.encode_utf16().collect();
+    path_u16.push(0);
+
+    let mut query_u16: Vec<u16> = query.unwrap_or("*").encode_utf16().collect();
+    query_u16.push(0);
+
+    let result_set = unsafe {
+        EvtQuery(
+            session,
+            path_u16.as_ptr(),
+            query_u16.as_ptr(),
+            flags
+        )
+    };
+
+    if result_set.is_null() {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(EvtHandle(result_set))
+}
+
+
+/// BOOL EvtNext(
+///   EVT_HANDLE  ResultSet,
+///   DWORD       EventsSize,
+///   PEVT_HANDLE Events,
+///   DWORD       Timeout,
+///   DWORD       Flags,
+///   PDWORD      Returned
+/// );
+/// Returns an empty list once the result set has no more events.
+pub fn evt_next(
+    result_set: &EvtHandle,
+    batch_size: usize,
+    timeout: u32
+) -> Result<Vec<EvtHandle>, WinThingError> {
+    let mut events: Vec<EVT_HANDLE> = vec![null_mut(); batch_size];
+    let mut returned: DWORD = 0;
+
+    let result = unsafe {
+        EvtNext(
+            result_set.0,
+            events.len() as DWORD,
+            events.as_mut_ptr(),
+            timeout,
+            0,
+            &mut returned
+        )
+    };
+
+    if result == 0 {
+        let last_error: DWORD = unsafe {
+            GetLastError()
+        };
+
+        if last_error == ERROR_NO_MORE_ITEMS {
+            return Ok(Vec::new());
+        }
+
+        return Err(
+            WinThingError::from_windows_error_code(last_error)
+        );
+    }
+
+    Ok(
+        events.into_iter()
+            .take(returned as usize)
+            .map(EvtHandle)
+            .collect()
+    )
+}
+
+
+/// EVT_HANDLE EvtCreateBookmark(
+///   LPCWSTR BookmarkXml
+/// );
+pub fn evt_create_bookmark() -> Result<EvtHandle, WinThingError> {
+    let bookmark = unsafe {
+        EvtCreateBookmark(
+            null_mut()
+        )
+    };
+
+    if bookmark.is_null() {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(EvtHandle(bookmark))
+}
+
+
+/// Create a bookmark from the XML of `evt_render_bookmark`.
+pub fn evt_create_bookmark_from_xml(
+    bookmark_xml: &str
+) -> Result<EvtHandle, WinThingError> {
+    let mut bookmark_xml_u16: Vec<u16> = bookmark_xml.encode_utf16().collect();
+    bookmark_xml_u16.push(0);
+
+    let bookmark = unsafe {
+        EvtCreateBookmark(
+            bookmark_xml_u16.as_ptr()
+        )
+    };
+
+    if bookmark.is_null() {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(EvtHandle(bookmark))
+}
+
+
+/// BOOL EvtUpdateBookmark(
+///   EVT_HANDLE Bookmark,
+///   EVT_HANDLE Event
+/// );
+pub fn evt_update_bookmark(
+    bookmark: &EvtHandle,
+    event_handle: EVT_HANDLE
+) -> Result<(), WinThingError> {
+    let result = unsafe {
+        EvtUpdateBookmark(
+            bookmark.0,
+            event_handle
+        )
+    };
+
+    if result == 0 {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(())
+}
+
+
+/// BOOL EvtCancel(
+///   EVT_HANDLE Object
+/// );
+pub fn evt_cancel(handle: &EvtHandle) -> Result<(), WinThingError> {
+    let result = unsafe {
+        EvtCancel(
+            handle.0
+        )
+    };
+
+    if result == 0 {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(())
+}
+
+
 /// EVT_HANDLE EvtOpenPublisherEnum(
 ///   EVT_HANDLE Session,
 ///   DWORD      Flags
@@ -433,14 +621,8 @@ pub fn evt_get_publisher_metadata_property(
             };
 
             if result != 0 {
-                let variant: EVT_VARIANT = unsafe {
-                    std::ptr::read(
-                        buffer.as_ptr() as *const _
-                    ) 
-                };
-
                 return Ok(
-                    EvtVariant(variant)
+                    EvtVariant::from_buffer(buffer)
                 );
             }
             else {
@@ -467,6 +649,142 @@ pub fn evt_get_publisher_metadata_property(
 }
 
 
+/// EVT_HANDLE EvtOpenEventMetadataEnum(
+///   EVT_HANDLE PublisherMetadata,
+///   DWORD      Flags
+/// );
+pub fn evt_open_event_metadata_enum(
+    publisher_metadata: &EvtHandle
+) -> Result<EvtHandle, WinThingError> {
+    let enum_handle = unsafe {
+        EvtOpenEventMetadataEnum(
+            publisher_metadata.0,
+            0
+        )
+    };
+
+    if enum_handle.is_null() {
+        return Err(
+            WinThingError::from_windows_last_error()
+        );
+    }
+
+    Ok(
+        EvtHandle(enum_handle)
+    )
+}
+
+
+/// EVT_HANDLE EvtNextEventMetadata(
+///   EVT_HANDLE EventMetadataEnum,
+///   DWORD      Flags
+/// );
+pub fn evt_next_event_metadata(
+    event_metadata_enum: &EvtHandle
+) -> Result<Option<EvtHandle>, WinThingError> {
+    let event_metadata = unsafe {
+        EvtNextEventMetadata(
+            event_metadata_enum.0,
+            0
+        )
+    };
+
+    if event_metadata.is_null() {
+        let last_error: DWORD = unsafe {
+            GetLastError()
+        };
+
+        if last_error == ERROR_NO_MORE_ITEMS {
+            return Ok(None);
+        }
+
+        return Err(
+            WinThingError::from_windows_error_code(
+                last_error
+            )
+        );
+    }
+
+    Ok(
+        Some(EvtHandle(event_metadata))
+    )
+}
+
+
+/// BOOL EvtGetEventMetadataProperty(
+///   EVT_HANDLE                     EventMetadata,
+///   EVT_EVENT_METADATA_PROPERTY_ID PropertyId,
+///   DWORD                          Flags,
+///   DWORD                          EventMetadataPropertyBufferSize,
+///   PEVT_VARIANT                   EventMetadataPropertyBuffer,
+///   PDWORD                         EventMetadataPropertyBufferUsed
+/// );
+pub fn evt_get_event_metadata_property(
+    event_metadata: &EvtHandle,
+    property_id: EVT_EVENT_METADATA_PROPERTY_ID,
+) -> Result<EvtVariant, WinThingError> {
+    let mut buffer_used: DWORD = 0;
+
+    let result = unsafe {
+        EvtGetEventMetadataProperty(
+            event_metadata.0,
+            property_id,
+            0,
+            0,
+            null_mut(),
+            &mut buffer_used
+        )
+    };
+
+    // We expect this to fail but return the buffer size needed.
+    if result == 0 {
+        let last_error: DWORD = unsafe {
+            GetLastError()
+        };
+
+        if last_error == ERROR_INSUFFICIENT_BUFFER {
+            let mut buffer: Vec<u8> = vec![0; buffer_used as usize];
+
+            let result = unsafe {
+                EvtGetEventMetadataProperty(
+                    event_metadata.0,
+                    property_id,
+                    0,
+                    buffer.len() as _,
+                    buffer.as_mut_ptr() as *mut EVT_VARIANT,
+                    &mut buffer_used
+                )
+            };
+
+            if result != 0 {
+                return Ok(
+                    EvtVariant::from_buffer(buffer)
+                );
+            }
+            else {
+                return Err(
+                    WinThingError::from_windows_last_error()
+                );
+            }
+        }
+        else {
+            return Err(
+                WinThingError::from_windows_error_code(
+                    last_error
+                )
+            );
+        }
+    }
+    else {
+        Err(
+            WinThingError::unhandled(
+                "Expected Error on first EvtGetEventMetadataProperty call.".to_owned()
+            )
+        )
+    }
+}
+
+
 /// BOOL EvtGetObjectArraySize(
 ///   EVT_OBJECT_ARRAY_PROPERTY_HANDLE ObjectArray,
 ///   PDWORD                           ObjectArraySize
@@ -543,16 +861,8 @@ pub fn evt_get_object_array_property(
             };
 
             if result != 0 {
-                let variant: EVT_VARIANT = unsafe {
-                    std::ptr::read(
-                        buffer.as_ptr() as *const _
-                    ) 
-                };
-
                 return Ok(
-                    EvtVariant(
-                        variant
-                    )
+                    EvtVariant::from_buffer(buffer)
                 );
             }
             else {
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use serde_json::json;
+use rswinthings::errors::ErrorType;
+use rswinthings::channels::graph::{
+    ChannelGraph,
+    DisabledPublisher
+};
+use rswinthings::channels::policy::read_channel_dump_path;
+use rswinthings::publishers::read_publisher_dump_path;
+
+
+fn get_fixture_graph() -> ChannelGraph {
+    let publishers = read_publisher_dump_path("tests/fixtures/publishers.jsonl")
+        .expect("Error reading publisher dump")
+        .publishers;
+    let channels = read_channel_dump_path("tests/fixtures/channels.jsonl")
+        .expect("Error reading channel dump");
+
+    ChannelGraph::from_values(&publishers, &channels)
+        .expect("Error building channel graph")
+}
+
+
+#[test]
+fn channel_graph_lookup_test() {
+    let graph = get_fixture_graph();
+
+    // Service Control Manager only comes from the channel's publisher list
+    assert_eq!(
+        graph.get_channel_publishers("System"),
+        vec!["Microsoft-Windows-Eventlog", "Service Control Manager"]
+    );
+    assert_eq!(
+        graph.get_channel_publishers("Security"),
+        vec!["Microsoft-Windows-Eventlog", "Microsoft-Windows-Security-Auditing"]
+    );
+    assert_eq!(
+        graph.get_publisher_channels("Microsoft-Windows-WinRM"),
+        vec!["Microsoft-Windows-PowerShell/Operational", "Microsoft-Windows-WinRM/Operational"]
+    );
+    assert!(graph.get_publisher_channels("Unknown-Publisher").is_empty());
+
+    // Only referenced by a publisher, so its config is unknown
+    let winrm = &graph.channels["Microsoft-Windows-WinRM/Operational"];
+    assert_eq!(winrm.enabled, None);
+    assert_eq!(
+        graph.channels["Security"].owning_publisher,
+        Some("Microsoft-Windows-Eventlog".to_owned())
+    );
+}
+
+
+#[test]
+fn channel_graph_disabled_test() {
+    let graph = get_fixture_graph();
+
+    assert_eq!(
+        graph.get_disabled_publishers(),
+        vec![
+            DisabledPublisher {
+                publisher: "Microsoft-Windows-PowerShell".to_owned(),
+                disabled_channels: vec!["Microsoft-Windows-PowerShell/Operational".to_owned()],
+                all_disabled: true
+            },
+            DisabledPublisher {
+                publisher: "Microsoft-Windows-WinRM".to_owned(),
+                disabled_channels: vec!["Microsoft-Windows-PowerShell/Operational".to_owned()],
+                all_disabled: false
+            }
+        ]
+    );
+
+    let value = graph.to_json_value();
+    assert_eq!(value["channels"]["Microsoft-Windows-PowerShell/Operational"]["enabled"], json!(false));
+    assert_eq!(value["disabled_publishers"].as_array().map(|d| d.len()), Some(2));
+}
+
+
+#[test]
+fn channel_graph_dot_test() {
+    let mut graph = ChannelGraph::new();
+    graph.add_edge("Quoted \"Publisher\"", "Application");
+    graph.add_publisher_value(&json!({
+        "Name": "No-Channels",
+        "EvtPublisherMetadataChannelReferences": null
+    })).expect("Error adding publisher");
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph channels {"));
+    assert!(dot.contains("\"p:Quoted \\\"Publisher\\\"\" -> \"c:Application\";"));
+    assert!(dot.contains("\"p:No-Channels\" [label=\"No-Channels\", shape=box];"));
+    assert_eq!(
+        graph.add_publisher_value(&json!({})).unwrap_err().kind,
+        ErrorType::InvalidInput
+    );
+}
+
+
+#[test]
+fn channel_graph_bad_channel_test() {
+    let mut channels = BTreeMap::new();
+    // Only the publisher list, enabled flag and owning publisher are read
+    channels.insert("Undecodable".to_owned(), json!({
+        "EvtChannelConfigEnabled": false,
+        "EvtChannelConfigAccess": "not an SDDL string",
+        "EvtChannelConfigType": "Operational",
+        "EvtChannelPublisherList": "Some-Publisher"
+    }));
+    channels.insert("Malformed".to_owned(), json!({
+        "EvtChannelConfigEnabled": "yes"
+    }));
+
+    let graph = ChannelGraph::from_values(&[], &channels)
+        .expect("Error building channel graph");
+    assert_eq!(graph.channels["Undecodable"].enabled, Some(false));
+    assert_eq!(graph.get_channel_publishers("Undecodable"), vec!["Some-Publisher"]);
+    assert!(!graph.channels.contains_key("Malformed"));
+
+    let mut graph = ChannelGraph::new();
+    assert_eq!(
+        graph.add_channel_config("Malformed", &channels["Malformed"]).unwrap_err().kind,
+        ErrorType::InvalidInput
+    );
+}
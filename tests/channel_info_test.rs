@@ -0,0 +1,100 @@
+use serde_json::json;
+use rswinthings::channels::policy::read_channel_dump_path;
+use rswinthings::channels::info::{
+    format_bytes,
+    ByteSize,
+    ChannelConfigInfo,
+    ChannelIsolation,
+    ChannelType
+};
+use rswinthings::channels::sddl::{
+    get_access_right_names,
+    AccessEntry,
+    SecurityDescriptor
+};
+
+
+#[test]
+fn channel_config_info_test() {
+    let configs = read_channel_dump_path("tests/fixtures/channels.jsonl")
+        .expect("Error reading channel dump");
+
+    let info = ChannelConfigInfo::from_json_value("Security", &configs["Security"])
+        .expect("Error decoding Security config");
+    assert_eq!(info.enabled, Some(true));
+    assert_eq!(info.channel_type, Some(ChannelType::Admin));
+    assert_eq!(info.isolation, Some(ChannelIsolation::Custom));
+    assert_eq!(info.max_size, Some(ByteSize(20971520)));
+    assert_eq!(info.publishers.len(), 2);
+    assert!(info.can_subscribe());
+
+    let access = info.access.as_ref().expect("Security should have access");
+    assert_eq!(access.owner, Some("Administrators".to_owned()));
+    assert_eq!(access.dacl.len(), 3);
+    assert_eq!(access.dacl[2].trustee, "Event Log Readers");
+    assert_eq!(access.dacl[2].to_string(), "Allow Event Log Readers: Read");
+
+    let value = json!(info);
+    assert_eq!(value["channel_type"], json!("Admin"));
+    assert_eq!(value["isolation"], json!("Custom"));
+    assert_eq!(value["clock_type"], json!("SystemTime"));
+    assert_eq!(value["sid_type"], json!("Publishing"));
+    assert_eq!(value["max_size"], json!({"bytes": 20971520, "human": "20 MiB"}));
+
+    let info = ChannelConfigInfo::from_json_value(
+        "Microsoft-Windows-PowerShell/Operational",
+        &configs["Microsoft-Windows-PowerShell/Operational"]
+    ).expect("Error decoding PowerShell config");
+    assert_eq!(info.channel_type, Some(ChannelType::Operational));
+    assert_eq!(info.max_size.map(|s| s.to_string()), Some("15 MiB".to_owned()));
+
+    // Properties of the wrong type are an error
+    assert!(ChannelConfigInfo::from_json_value("Bad", &json!({"EvtChannelConfigType": "Admin"})).is_err());
+    let empty = ChannelConfigInfo::from_json_value("Empty", &json!({})).unwrap();
+    assert_eq!(empty.channel_type, None);
+    assert_eq!(empty.enabled, None);
+}
+
+
+#[test]
+fn sddl_test() {
+    let descriptor = SecurityDescriptor::from_sddl(
+        "O:BAG:SYD:PAI(A;;0xf0007;;;SY)(D;CIOI;GA;;;S-1-5-21-1-2-3-500)S:(AU;SAFA;0x2;;;WD)"
+    ).expect("Error parsing SDDL");
+
+    assert_eq!(descriptor.owner, Some("Administrators".to_owned()));
+    assert_eq!(descriptor.group, Some("Local System".to_owned()));
+    assert_eq!(descriptor.dacl.len(), 2);
+    assert_eq!(
+        descriptor.dacl[0].rights,
+        vec!["Read", "Write", "Clear", "Delete", "ReadControl", "WriteDac", "WriteOwner"]
+    );
+    assert_eq!(descriptor.dacl[1].ace_type, "Deny");
+    assert_eq!(descriptor.dacl[1].flags, vec!["ContainerInherit", "ObjectInherit"]);
+    assert_eq!(descriptor.dacl[1].trustee, "S-1-5-21-1-2-3-500");
+    assert_eq!(descriptor.dacl[1].rights, vec!["GenericAll"]);
+    assert_eq!(descriptor.sacl[0].to_string(), "Audit Everyone: Write");
+
+    assert_eq!(get_access_right_names(0x8000_0001), vec!["Read", "GenericRead"]);
+    assert_eq!(get_access_right_names(0x10), vec!["0x10"]);
+    assert!(AccessEntry::from_ace_str("A;;0x1").is_err());
+    assert!(AccessEntry::from_ace_str("A;Aé;0x1;;;BA").is_err());
+
+    // The directory, file and registry codes map to their masks
+    let entry = AccessEntry::from_ace_str("A;;CCDCLC;;;BA").expect("Error parsing ACE");
+    assert_eq!(entry.access_mask, 0x7);
+    assert_eq!(entry.rights, vec!["Read", "Write", "Clear"]);
+    assert_eq!(AccessEntry::from_ace_str("A;;FA;;;BA").unwrap().access_mask, 0x001f_01ff);
+    assert_eq!(AccessEntry::from_ace_str("A;;KRKW;;;BA").unwrap().access_mask, 0x0002_001f);
+
+    // Unknown codes are kept as named rights
+    let entry = AccessEntry::from_ace_str("A;;GRXYZ;;;BA").expect("Error parsing ACE");
+    assert_eq!(entry.access_mask, 0x8000_0000);
+    assert_eq!(entry.rights, vec!["GenericRead", "Unknown(XY)", "Unknown(Z)"]);
+    assert!(AccessEntry::from_ace_str("A;;GRé;;;BA").is_err());
+    assert!(SecurityDescriptor::from_sddl("not sddl").is_err());
+
+    assert_eq!(format_bytes(512), "512 B");
+    assert_eq!(format_bytes(1073741824), "1 GiB");
+    assert_eq!(format_bytes(1536), "1.5 KiB");
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use serde_json::json;
+use rswinthings::channels::policy::{
+    read_channel_dump_path,
+    ChannelPolicy
+};
+
+
+#[test]
+fn channel_policy_audit_test() {
+    let policy = ChannelPolicy::from_path("tests/fixtures/channel_policy.json")
+        .expect("Error reading policy");
+    let configs = read_channel_dump_path("tests/fixtures/channels.jsonl")
+        .expect("Error reading channel dump");
+    assert_eq!(configs.len(), 3);
+    assert!(configs["Security"].get("ChannelName").is_none());
+
+    let report = policy.audit(&configs);
+    assert_eq!(report.channels.len(), 4);
+    assert_eq!(report.get_deviation_count(), 3);
+    assert!(!report.is_compliant());
+
+    let audits: BTreeMap<&str, _> = report.channels.iter()
+        .map(|a| (a.channel.as_str(), a))
+        .collect();
+
+    assert_eq!(
+        audits["Security"].deviations,
+        json!({
+            "/EvtChannelLoggingConfigMaxSize": {
+                "min": 1073741824,
+                "max": null,
+                "actual": 20971520
+            }
+        })
+    );
+    assert!(audits["System"].is_compliant());
+    assert_eq!(
+        audits["Microsoft-Windows-PowerShell/Operational"].deviations,
+        json!({
+            "/EvtChannelConfigEnabled": {
+                "expected": true,
+                "actual": false
+            }
+        })
+    );
+    assert!(!audits["Microsoft-Windows-Sysmon/Operational"].present);
+    assert_eq!(audits["Microsoft-Windows-Sysmon/Operational"].get_deviation_count(), 1);
+}
+
+
+#[test]
+fn channel_policy_missing_property_test() {
+    let policy = ChannelPolicy::from_json_str(r#"{
+        "channels": {"Security": {"EvtChannelLoggingConfigAutoBackup": true}}
+    }"#).expect("Error parsing policy");
+
+    let audit = policy.audit_channel("Security", Some(&json!({})));
+    assert_eq!(
+        audit.deviations,
+        json!({
+            "/EvtChannelLoggingConfigAutoBackup": {
+                "expected": true,
+                "actual": null
+            }
+        })
+    );
+
+    // A range needs a bound
+    assert!(ChannelPolicy::from_json_str(r#"{"channels": {"Security": {"EvtChannelLoggingConfigMaxSize": {}}}}"#).is_err());
+}
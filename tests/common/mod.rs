@@ -0,0 +1,113 @@
+//! Builders of MFT records shared by the MFT tests.
+#![allow(dead_code)]
+use byteorder::{ByteOrder, LittleEndian};
+use rswinthings::mft::raw::decode_data_runs;
+
+
+pub fn align8(buffer: &mut Vec<u8>) {
+    let padding = (8 - buffer.len() % 8) % 8;
+    buffer.resize(buffer.len() + padding, 0);
+}
+
+
+pub fn resident_attribute(type_code: u32, instance: u16, name: &str, value: &[u8]) -> Vec<u8> {
+    let name_u16: Vec<u16> = name.encode_utf16().collect();
+    let mut buffer = vec![0u8; 0x18];
+    LittleEndian::write_u32(&mut buffer[0x00..0x04], type_code);
+    buffer[0x09] = name_u16.len() as u8;
+    LittleEndian::write_u16(&mut buffer[0x0A..0x0C], 0x18);
+    LittleEndian::write_u16(&mut buffer[0x0E..0x10], instance);
+    for c in name_u16 {
+        buffer.extend_from_slice(&c.to_le_bytes());
+    }
+    align8(&mut buffer);
+
+    let value_offset = buffer.len();
+    LittleEndian::write_u32(&mut buffer[0x10..0x14], value.len() as u32);
+    LittleEndian::write_u16(&mut buffer[0x14..0x16], value_offset as u16);
+    buffer.extend_from_slice(value);
+    align8(&mut buffer);
+
+    let length = buffer.len() as u32;
+    LittleEndian::write_u32(&mut buffer[0x04..0x08], length);
+    buffer
+}
+
+
+pub fn non_resident_attribute(type_code: u32, instance: u16, name: &str, runs: &[u8], data_size: u64, initialized_size: u64) -> Vec<u8> {
+    let name_u16: Vec<u16> = name.encode_utf16().collect();
+    let mut buffer = vec![0u8; 0x40];
+    LittleEndian::write_u32(&mut buffer[0x00..0x04], type_code);
+    buffer[0x08] = 1;
+    buffer[0x09] = name_u16.len() as u8;
+    LittleEndian::write_u16(&mut buffer[0x0A..0x0C], 0x40);
+    LittleEndian::write_u16(&mut buffer[0x0E..0x10], instance);
+    for c in name_u16 {
+        buffer.extend_from_slice(&c.to_le_bytes());
+    }
+    align8(&mut buffer);
+
+    let runs_offset = buffer.len() as u16;
+    let cluster_count: u64 = decode_data_runs(runs).unwrap().iter().map(|r| r.length).sum();
+    LittleEndian::write_u64(&mut buffer[0x18..0x20], cluster_count.saturating_sub(1));
+    LittleEndian::write_u16(&mut buffer[0x20..0x22], runs_offset);
+    LittleEndian::write_u64(&mut buffer[0x28..0x30], cluster_count * 512);
+    LittleEndian::write_u64(&mut buffer[0x30..0x38], data_size);
+    LittleEndian::write_u64(&mut buffer[0x38..0x40], initialized_size);
+    buffer.extend_from_slice(runs);
+    buffer.push(0);
+    align8(&mut buffer);
+
+    let length = buffer.len() as u32;
+    LittleEndian::write_u32(&mut buffer[0x04..0x08], length);
+    buffer
+}
+
+
+pub fn attribute_list_entry(type_code: u32, entry: u64, sequence: u16, instance: u16, name: &str) -> Vec<u8> {
+    let name_u16: Vec<u16> = name.encode_utf16().collect();
+    let mut buffer = vec![0u8; 0x1A];
+    LittleEndian::write_u32(&mut buffer[0x00..0x04], type_code);
+    buffer[0x06] = name_u16.len() as u8;
+    buffer[0x07] = 0x1A;
+    LittleEndian::write_u64(&mut buffer[0x10..0x18], entry | ((sequence as u64) << 48));
+    LittleEndian::write_u16(&mut buffer[0x18..0x1A], instance);
+    for c in name_u16 {
+        buffer.extend_from_slice(&c.to_le_bytes());
+    }
+    align8(&mut buffer);
+
+    let length = buffer.len() as u16;
+    LittleEndian::write_u16(&mut buffer[0x04..0x06], length);
+    buffer
+}
+
+
+/// Build a 1024 byte FILE record of `entry` with the given attributes.
+pub fn file_record_with_header(entry: u32, sequence: u16, flags: u16, base_entry: u64, attributes: &[Vec<u8>]) -> Vec<u8> {
+    let mut buffer = vec![0u8; 0x38];
+    buffer[0..4].copy_from_slice(b"FILE");
+    LittleEndian::write_u16(&mut buffer[0x10..0x12], sequence);
+    LittleEndian::write_u16(&mut buffer[0x14..0x16], 0x38);
+    LittleEndian::write_u16(&mut buffer[0x16..0x18], flags);
+    LittleEndian::write_u32(&mut buffer[0x1C..0x20], 1024);
+    LittleEndian::write_u64(&mut buffer[0x20..0x28], base_entry);
+    LittleEndian::write_u32(&mut buffer[0x2C..0x30], entry);
+    for attribute in attributes {
+        buffer.extend_from_slice(attribute);
+    }
+    buffer.extend_from_slice(&0xFFFF_FFFFu32.to_le_bytes());
+    buffer.extend_from_slice(&[0u8; 4]);
+
+    let used = buffer.len() as u32;
+    LittleEndian::write_u32(&mut buffer[0x18..0x1C], used);
+    buffer.resize(1024, 0);
+    buffer
+}
+
+
+/// Build a 1024 byte FILE record with the given attributes and an empty
+/// header.
+pub fn file_record(attributes: &[Vec<u8>]) -> Vec<u8> {
+    file_record_with_header(0, 0, 0, 0, attributes)
+}
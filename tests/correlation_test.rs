@@ -0,0 +1,276 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+use rswinthings::detection::correlation::{
+    get_event_timestamp,
+    parse_timespan,
+    CorrelationType
+};
+use rswinthings::detection::engine::RuleEngine;
+
+const FAILED_LOGON_RULES: &str = r"
+title: Failed Logon
+name: failed_logon
+logsource:
+    product: windows
+    service: security
+detection:
+    selection:
+        EventID: 4625
+    condition: selection
+level: low
+---
+title: Logon Brute Force
+correlation:
+    type: event_count
+    rules: failed_logon
+    group-by:
+        - IpAddress
+    timespan: 1m
+    condition:
+        gte: 3
+level: high
+---
+title: Password Spray
+correlation:
+    type: value_count
+    rules: failed_logon
+    group-by: IpAddress
+    timespan: 1m
+    condition:
+        field: TargetUserName
+        gte: 3
+    generate: true
+level: high
+";
+
+
+fn get_start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2020, 1, 20, 10, 15, 0).unwrap()
+}
+
+
+fn failed_logon(user: &str, ip: &str) -> Value {
+    json!({"Event": {
+        "System": {"Channel": "Security", "EventID": 4625},
+        "EventData": {"TargetUserName": user, "IpAddress": ip}
+    }})
+}
+
+
+fn logon_event(event_id: u64, data: Value) -> Value {
+    json!({"Event": {
+        "System": {"Channel": "Security", "EventID": event_id},
+        "EventData": data
+    }})
+}
+
+
+#[test]
+fn event_count_test() {
+    let mut engine = RuleEngine::new();
+    assert_eq!(engine.add_rule_str(FAILED_LOGON_RULES).unwrap(), 3);
+    assert_eq!(engine.get_rule_count(), 1);
+    assert_eq!(engine.get_correlation_count(), 2);
+
+    let start = get_start();
+    let seconds = |s| start + Duration::seconds(s);
+
+    // Spread out too much for the window
+    assert!(engine.process_at(&failed_logon("bob", "10.0.0.9"), seconds(0)).correlations.is_empty());
+    assert!(engine.process_at(&failed_logon("bob", "10.0.0.9"), seconds(50)).correlations.is_empty());
+    // Another group
+    assert!(engine.process_at(&failed_logon("bob", "10.0.0.7"), seconds(80)).correlations.is_empty());
+
+    assert!(engine.process_at(&failed_logon("bob", "10.0.0.9"), seconds(100)).correlations.is_empty());
+
+    let detections = engine.process_at(&failed_logon("bob", "10.0.0.9"), seconds(105));
+    assert_eq!(detections.correlations.len(), 1);
+    // The spray correlation generates the failed_logon alerts
+    assert_eq!(detections.alerts.len(), 1);
+
+    let alert = &detections.correlations[0];
+    assert_eq!(alert.rule.title, "Logon Brute Force");
+    assert_eq!(alert.correlation.correlation_type, CorrelationType::EventCount);
+    assert_eq!(alert.correlation.count, 3);
+    assert_eq!(alert.correlation.group, json!({"IpAddress": "10.0.0.9"}).as_object().unwrap().to_owned());
+    assert_eq!(alert.correlation.first_time, seconds(50));
+    assert_eq!(alert.correlation.last_time, seconds(105));
+    assert_eq!(alert.events.len(), 3);
+
+    let value = alert.to_json_value().unwrap();
+    assert_eq!(value["alert"]["level"], "high");
+    assert_eq!(value["correlation"]["type"], "event_count");
+    assert_eq!(value["correlation"]["first_time"], "2020-01-20T10:15:50Z");
+
+    // The window starts over after an alert
+    assert!(engine.process_at(&failed_logon("bob", "10.0.0.9"), seconds(106)).correlations.is_empty());
+}
+
+
+#[test]
+fn value_count_test() {
+    let mut engine = RuleEngine::new();
+    engine.add_rule_str(FAILED_LOGON_RULES).unwrap();
+
+    let start = get_start();
+    let seconds = |s| start + Duration::seconds(s);
+
+    engine.process_at(&failed_logon("alice", "10.0.0.5"), seconds(0));
+    engine.process_at(&failed_logon("alice", "10.0.0.5"), seconds(1));
+
+    // Three failures are a brute force, but only two users
+    let detections = engine.process_at(&failed_logon("bob", "10.0.0.5"), seconds(2));
+    let titles: Vec<&str> = detections.correlations.iter()
+        .map(|c| c.rule.title.as_str())
+        .collect();
+    assert_eq!(titles, vec!["Logon Brute Force"]);
+
+    let detections = engine.process_at(&failed_logon("carol", "10.0.0.5"), seconds(3));
+    assert_eq!(detections.correlations.len(), 1);
+    assert_eq!(detections.correlations[0].rule.title, "Password Spray");
+    assert_eq!(detections.correlations[0].correlation.count, 3);
+    assert_eq!(detections.correlations[0].events.len(), 4);
+}
+
+
+#[test]
+fn temporal_ordered_test() {
+    let mut engine = RuleEngine::new();
+    engine.add_rules_from_path("tests/fixtures/correlations").unwrap();
+    assert_eq!(engine.get_correlation_count(), 1);
+
+    let start = get_start();
+    let seconds = |s| start + Duration::seconds(s);
+
+    let logon = |id: &str| logon_event(4624, json!({"LogonType": 3, "TargetLogonId": id}));
+    let privileges = |id: &str| logon_event(4672, json!({"SubjectLogonId": id}));
+
+    // Out of order
+    assert!(engine.process_at(&privileges("0x1"), seconds(0)).is_empty());
+    engine.process_at(&logon("0x1"), seconds(1));
+    // Another logon session
+    assert!(engine.process_at(&privileges("0x2"), seconds(2)).is_empty());
+    // Too late
+    assert!(engine.process_at(&privileges("0x1"), seconds(12)).is_empty());
+
+    engine.process_at(&logon("0x1"), seconds(20));
+    let detections = engine.process_at(&privileges("0x1"), seconds(25));
+    assert_eq!(detections.correlations.len(), 1);
+    assert!(detections.alerts.is_empty());
+
+    let alert = &detections.correlations[0];
+    assert_eq!(alert.correlation.group["LogonId"], "0x1");
+    assert_eq!(alert.correlation.first_time, seconds(20));
+    assert_eq!(alert.correlation.last_time, seconds(25));
+    assert_eq!(alert.events[0]["Event"]["System"]["EventID"], 4624);
+}
+
+
+#[test]
+fn temporal_ordered_restart_test() {
+    let rules = r"
+title: Recon
+name: recon
+detection:
+    selection:
+        EventID: 1
+    condition: selection
+---
+title: Dump
+name: dump
+detection:
+    selection:
+        EventID: 2
+    condition: selection
+---
+title: Exfil
+name: exfil
+detection:
+    selection:
+        EventID: 3
+    condition: selection
+---
+title: Recon Dump Exfil
+correlation:
+    type: temporal_ordered
+    rules:
+        - recon
+        - dump
+        - exfil
+    timespan: 10s
+";
+    let mut engine = RuleEngine::new();
+    engine.add_rule_str(rules).unwrap();
+
+    let start = get_start();
+    let seconds = |s| start + Duration::seconds(s);
+    let event = |event_id: u64| logon_event(event_id, json!({}));
+
+    engine.process_at(&event(1), seconds(0));
+    engine.process_at(&event(2), seconds(1));
+    // A new first match starts another candidate rather than being dropped
+    engine.process_at(&event(1), seconds(8));
+    // The first candidate has run out of time
+    assert!(engine.process_at(&event(3), seconds(12)).correlations.is_empty());
+
+    engine.process_at(&event(2), seconds(13));
+    let detections = engine.process_at(&event(3), seconds(15));
+    assert_eq!(detections.correlations.len(), 1);
+    assert_eq!(detections.correlations[0].correlation.first_time, seconds(8));
+    assert_eq!(detections.correlations[0].events.len(), 3);
+}
+
+
+#[test]
+fn correlation_host_test() {
+    let mut engine = RuleEngine::new();
+    engine.add_rule_str(FAILED_LOGON_RULES).unwrap();
+
+    let start = get_start();
+    let seconds = |s| start + Duration::seconds(s);
+    let from_host = |host: &str| {
+        let mut event = failed_logon("bob", "10.0.0.9");
+        event["source_host"] = json!(host);
+        event
+    };
+
+    // The same group on different hosts is not correlated
+    engine.process_at(&from_host("dc01"), seconds(0));
+    engine.process_at(&from_host("dc02"), seconds(1));
+    assert!(engine.process_at(&from_host("dc02"), seconds(2)).correlations.is_empty());
+
+    let detections = engine.process_at(&from_host("dc01"), seconds(3));
+    assert!(detections.correlations.is_empty());
+    let detections = engine.process_at(&from_host("dc01"), seconds(4));
+    assert_eq!(detections.correlations.len(), 1);
+    assert!(detections.correlations[0].events.iter().all(|e| e["source_host"] == "dc01"));
+}
+
+
+#[test]
+fn correlation_helpers_test() {
+    assert_eq!(parse_timespan("90s").unwrap(), Duration::seconds(90));
+    assert_eq!(parse_timespan("5m").unwrap(), Duration::minutes(5));
+    assert_eq!(parse_timespan("1d").unwrap(), Duration::days(1));
+    assert!(parse_timespan("5y").is_err());
+    assert!(parse_timespan("m").is_err());
+
+    let event = json!({"Event": {"System": {"TimeCreated_attributes": {"SystemTime": "2020-01-20T10:15:02.1234567Z"}}}});
+    assert_eq!(
+        get_event_timestamp(&event).unwrap(),
+        get_start() + Duration::nanoseconds(2_123_456_700)
+    );
+    assert!(get_event_timestamp(&json!({})).is_none());
+
+    let mut engine = RuleEngine::new();
+    assert!(engine.add_rule_str("title: Bad\ncorrelation:\n    type: temporal\n    rules: a\n    timespan: 1m\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ncorrelation:\n    type: event_count\n    rules: a\n    timespan: 1m\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ncorrelation:\n    type: value_count\n    rules: a\n    timespan: 1m\n    condition:\n        gte: 2\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ncorrelation:\n    type: event_count\n    rules: a\n    timespan: 1m\n    condition:\n        lt: 2\n").is_err());
+    // Upper bounds can't be decided as alerts arrive
+    for condition in &["lte: 2", "eq: 2", "range: 2..4", "gt: 2\n        gte: 3"] {
+        let yaml = format!("title: Bad\ncorrelation:\n    type: event_count\n    rules: a\n    timespan: 1m\n    condition:\n        {}\n", condition);
+        assert!(engine.add_rule_str(&yaml).is_err());
+    }
+    assert_eq!(engine.get_correlation_count(), 0);
+}
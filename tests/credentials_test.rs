@@ -0,0 +1,62 @@
+use std::io::Cursor;
+use std::fs;
+use std::env::temp_dir;
+use rswinthings::errors::ErrorType;
+use rswinthings::utils::credentials::{
+    get_credential_provider,
+    read_password_line,
+    CredentialProvider,
+    EnvCredentials,
+    FileCredentials,
+    Password
+};
+
+
+#[test]
+fn password_test() {
+    let password = Password::new("hunter2".to_owned());
+    assert_eq!(password.as_str(), "hunter2");
+    assert_eq!(format!("{:?}", password), "Password(<redacted>)");
+
+    let wide = password.to_wide();
+    assert_eq!(wide.len(), 8);
+    assert_eq!(wide[7], 0);
+
+    let mut reader = Cursor::new(b"s3cret pass\r\nsecond line\n".to_vec());
+    let password = read_password_line(&mut reader).expect("Error reading password");
+    assert_eq!(password.as_str(), "s3cret pass");
+
+    let mut reader = Cursor::new(Vec::new());
+    let error = read_password_line(&mut reader).expect_err("Expected an error");
+    assert_eq!(error.kind, ErrorType::CredentialError);
+}
+
+
+#[test]
+fn credential_provider_test() {
+    std::env::set_var("RSWINTHINGS_TEST_PASSWORD", "from env");
+    let provider = EnvCredentials::new("RSWINTHINGS_TEST_PASSWORD");
+    assert_eq!(provider.get_password().unwrap().as_str(), "from env");
+
+    let provider = EnvCredentials::new("RSWINTHINGS_TEST_MISSING_PASSWORD");
+    assert_eq!(provider.get_password().unwrap_err().kind, ErrorType::CredentialError);
+
+    let path = temp_dir().join("rswinthings_credentials_test.txt");
+    fs::write(&path, "from file\n").expect("Error writing credentials file");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let provider = FileCredentials::new(path.to_str().unwrap());
+        assert_eq!(provider.get_password().unwrap_err().kind, ErrorType::CredentialError);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let provider = get_credential_provider("File", path.to_str()).expect("Expected provider");
+    assert_eq!(provider.get_password().unwrap().as_str(), "from file");
+    fs::remove_file(&path).expect("Error removing credentials file");
+
+    assert!(get_credential_provider("File", None).is_err());
+    assert!(get_credential_provider("Keyboard", None).is_err());
+}
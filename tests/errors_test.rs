@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind};
+use rswinthings::errors::{ErrorType, WinThingError, WindowsCode};
+use rswinthings::errors::catalog::{
+    get_code_by_name,
+    get_code_info,
+    get_code_message
+};
+
+
+#[test]
+fn error_source_chain_test() {
+    let io_error = IoError::new(ErrorKind::NotFound, "journal.jsonl is missing");
+    let error: WinThingError = io_error.into();
+
+    assert_eq!(error.kind, ErrorType::IoError);
+    assert_eq!(error.kind.as_str(), "io_error");
+    assert_eq!(error.to_string(), "[io_error] journal.jsonl is missing");
+
+    let source = error.source().expect("Expected a source");
+    let io_source = source.downcast_ref::<IoError>().expect("Expected an io::Error");
+    assert_eq!(io_source.kind(), ErrorKind::NotFound);
+
+    let error = WinThingError::stream_error("Error reading stream.".to_owned())
+        .with_source(IoError::new(ErrorKind::UnexpectedEof, "short read"));
+    assert_eq!(error.display_chain(), "[stream_error] Error reading stream.: short read");
+}
+
+
+#[test]
+fn error_code_test() {
+    // HRESULT_FROM_WIN32(ERROR_EVT_CHANNEL_NOT_FOUND)
+    let error = WinThingError::from_hresult(
+        0x8007_3A9F_u32 as i32,
+        "The specified channel could not be found.".to_owned()
+    );
+
+    assert_eq!(error.code, Some(WindowsCode::HResult(0x8007_3A9F_u32 as i32)));
+    assert_eq!(error.get_win32_code(), Some(15007));
+    assert_eq!(
+        error.to_string(),
+        "[windows_error] The specified channel could not be found. (HRESULT 0x80073A9F)"
+    );
+
+    let error = WinThingError::unhandled("No code.".to_owned())
+        .with_code(WindowsCode::NtStatus(0xC000_0022));
+    assert_eq!(error.get_win32_code(), None);
+}
+
+
+#[test]
+fn code_catalog_test() {
+    let info = get_code_info(WindowsCode::Win32(15007)).expect("Expected catalog entry");
+    assert_eq!(info.name, "ERROR_EVT_CHANNEL_NOT_FOUND");
+
+    // HRESULT_FROM_WIN32 resolves through the Win32 table
+    assert_eq!(
+        WindowsCode::HResult(0x8007_0005_u32 as i32).get_name(),
+        Some("ERROR_ACCESS_DENIED")
+    );
+    assert_eq!(
+        WindowsCode::NtStatus(0xC000_0022).get_name(),
+        Some("STATUS_ACCESS_DENIED")
+    );
+
+    assert_eq!(
+        get_code_by_name("error_evt_channel_not_found"),
+        Some(WindowsCode::Win32(15007))
+    );
+    assert_eq!(
+        get_code_by_name("E_FAIL"),
+        Some(WindowsCode::HResult(0x8000_4005_u32 as i32))
+    );
+    assert_eq!(get_code_by_name("NOT_A_CODE"), None);
+
+    let error = WinThingError::from_windows_error_code(15007);
+    assert_eq!(error.message, "The specified channel could not be found.");
+    assert_eq!(error.get_win32_code(), Some(15007));
+
+    if !cfg!(windows) {
+        assert_eq!(
+            get_code_message(WindowsCode::NtStatus(0xC0FF_FFFF)),
+            "Unknown NTSTATUS 0xC0FFFFFF"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use rswinthings::events::definition::{
+    parse_template_fields,
+    EventDefinition,
+    TemplateField
+};
+
+const LOGON_TEMPLATE: &str = r#"<template xmlns="http://schemas.microsoft.com/win/2004/08/events">
+<data name="SubjectUserSid" inType="win:SID" outType="xs:string"/>
+<data name="SubjectUserName" inType="win:UnicodeString" outType="xs:string"/>
+<data name="LogonType" inType="win:UInt32" outType="xs:unsignedInt"/>
+<struct name="Target" count="2">
+<data name="Address" inType="win:UnicodeString" outType="xs:string" length="16"/>
+</struct>
+</template>"#;
+
+
+fn get_logon_definition() -> EventDefinition {
+    EventDefinition {
+        id: 4624,
+        version: 2,
+        channel: 10,
+        level: 0,
+        opcode: 0,
+        task: 12544,
+        keywords: 0x8020_0000_0000_0000,
+        message_id: Some(4624),
+        message: Some("An account was successfully logged on.%n%nSubject:%n%tSecurity ID:%t%t%1%n%tAccount Name:%t%t%2!s!%nLogon Type:%t%t%t%3%nElevated Token:%t%t%%1843%nUnknown:%t%5 100%".to_owned()),
+        template: Some(LOGON_TEMPLATE.to_owned()),
+        fields: parse_template_fields(LOGON_TEMPLATE).expect("Error parsing template")
+    }
+}
+
+
+#[test]
+fn template_fields_test() {
+    let fields = parse_template_fields(LOGON_TEMPLATE)
+        .expect("Error parsing template");
+
+    assert_eq!(fields.len(), 4);
+    assert_eq!(
+        fields[0],
+        TemplateField {
+            name: "SubjectUserSid".to_owned(),
+            in_type: Some("win:SID".to_owned()),
+            out_type: Some("xs:string".to_owned()),
+            count: None,
+            length: None
+        }
+    );
+    assert_eq!(fields[3].name, "Target.Address");
+    assert_eq!(fields[3].length, Some("16".to_owned()));
+
+    assert!(parse_template_fields("").is_err());
+}
+
+
+#[test]
+fn event_definition_test() {
+    let definition = get_logon_definition();
+    assert_eq!(definition.get_event_id(), 4624);
+
+    assert_eq!(
+        definition.get_named_message().expect("No message"),
+        "An account was successfully logged on.%n%nSubject:%n%tSecurity ID:%t%t{SubjectUserSid}%n%tAccount Name:%t%t{SubjectUserName}%nLogon Type:%t%t%t{LogonType}%nElevated Token:%t%t%%1843%nUnknown:%t%5 100%"
+    );
+
+    // Definitions can be read back from a print_publishers dump
+    let value = serde_json::to_value(&definition).expect("Error serializing definition");
+    assert_eq!(value["fields"][2]["in_type"], "win:UInt32");
+    let read: EventDefinition = serde_json::from_value(value).expect("Error reading definition");
+    assert_eq!(read, definition);
+
+    // A struct is a single insert, so later inserts keep their names
+    let struct_template = r#"<template xmlns="http://schemas.microsoft.com/win/2004/08/events">
+<data name="User" inType="win:UnicodeString" outType="xs:string"/>
+<struct name="Target" count="2">
+<data name="Address" inType="win:UnicodeString" outType="xs:string"/>
+<data name="Port" inType="win:UInt16" outType="xs:unsignedShort"/>
+</struct>
+<data name="Status" inType="win:UInt32" outType="xs:unsignedInt"/>
+</template>"#;
+    let with_struct = EventDefinition {
+        message: Some("%1 connected to %2 with %3".to_owned()),
+        template: Some(struct_template.to_owned()),
+        fields: parse_template_fields(struct_template).expect("Error parsing template"),
+        ..definition.clone()
+    };
+    assert_eq!(with_struct.fields.len(), 4);
+    assert_eq!(
+        with_struct.get_named_message().expect("No message"),
+        "{User} connected to {Target} with {Status}"
+    );
+
+    let qualified = EventDefinition {
+        id: 0x4000_1B58,
+        ..definition
+    };
+    assert_eq!(qualified.get_event_id(), 7000);
+}
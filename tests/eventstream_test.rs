@@ -0,0 +1,58 @@
+use std::thread;
+use std::time::Duration;
+use rswinthings::events::stream::{
+    split_xml_events,
+    EventStream
+};
+
+
+#[test]
+fn fixture_stream_test() {
+    let stream = EventStream::from_xml_path(
+        "tests/fixtures/events.xml",
+        1
+    ).expect("Error opening fixture");
+
+    let events: Vec<_> = stream.map(|r| r.expect("Unexpected stream error")).collect();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].source, "tests/fixtures/events.xml");
+
+    let value = events[2].to_json_value().expect("Error converting event");
+    assert_eq!(value["Event"]["System"]["EventID"], 1);
+    assert_eq!(value["Event"]["EventData"]["CommandLine"], "cmd.exe /c whoami");
+
+    let xml = "<Events><Event><EventData><Data>a</Data></EventData></Event>\n<Event xmlns='x'/></Event></Events>";
+    assert_eq!(split_xml_events(xml).len(), 2);
+}
+
+
+#[test]
+fn stream_backpressure_test() {
+    let (sender, mut stream) = EventStream::channel(2);
+
+    assert!(sender.try_send_xml("test", "<Event>1</Event>".to_owned()).unwrap());
+    assert!(sender.try_send_xml("test", "<Event>2</Event>".to_owned()).unwrap());
+    // The stream is full until it is read
+    assert!(!sender.try_send_xml("test", "<Event>3</Event>".to_owned()).unwrap());
+
+    let first = stream.recv_timeout(Duration::from_secs(1)).unwrap().unwrap();
+    assert_eq!(first.xml, "<Event>1</Event>");
+    assert!(sender.try_send_xml("test", "<Event>3</Event>".to_owned()).unwrap());
+
+    // A blocked sender continues once the stream is read
+    let blocked_sender = sender.clone();
+    let handle = thread::spawn(move || {
+        blocked_sender.send_xml("test", "<Event>4</Event>".to_owned())
+    });
+    drop(sender);
+
+    let remaining: Vec<String> = stream.by_ref()
+        .map(|r| r.unwrap().xml)
+        .collect();
+    assert_eq!(remaining, vec!["<Event>2</Event>", "<Event>3</Event>", "<Event>4</Event>"]);
+    assert!(handle.join().unwrap().is_ok());
+
+    let (sender, stream) = EventStream::channel(1);
+    drop(stream);
+    assert!(sender.send_xml("test", "<Event/>".to_owned()).is_err());
+}
@@ -0,0 +1,64 @@
+#[macro_use] extern crate serde_json;
+use std::io::Cursor;
+use chrono::Utc;
+use rswinthings::utils::json::{
+    get_json_patch,
+    DiffOptions
+};
+use rswinthings::mft::history::{
+    HistoryJournal,
+    HistoryTimeline,
+    HistoryPoint
+};
+
+
+#[test]
+fn history_state_test() {
+    let value1 = json!({
+        "header": {"record_number": 6090, "sequence": 71},
+        "attributes": {"StandardInformation": {"0": {"data": {"usn": 57216}}}}
+    });
+    let value2 = json!({
+        "header": {"record_number": 6090, "sequence": 71},
+        "attributes": {"StandardInformation": {"0": {"data": {"usn": 84272}}}}
+    });
+    let value3 = json!({
+        "header": {"record_number": 6090, "sequence": 71},
+        "attributes": {
+            "StandardInformation": {"0": {"data": {"usn": 90000}}},
+            "DATA": {"5": {"header": {"name": "Zone.Identifier"}}}
+        }
+    });
+
+    let mut buffer: Vec<u8> = Vec::new();
+    {
+        let mut journal = HistoryJournal::new(&mut buffer);
+        journal.append_snapshot(6090, 71, 50000, &value1).unwrap();
+        journal.append_diff(
+            6090, 71, 84272,
+            &get_json_patch(&value1, &value2, &DiffOptions::default())
+        ).unwrap();
+        journal.append_snapshot(42, 1, 85000, &json!({"other": true})).unwrap();
+        journal.append_snapshot(6090, 72, 86000, &json!({"reused": true})).unwrap();
+        journal.append_diff(
+            6090, 71, 90000,
+            &get_json_patch(&value2, &value3, &DiffOptions::default())
+        ).unwrap();
+    }
+
+    println!("{}", String::from_utf8_lossy(&buffer));
+
+    let timeline = HistoryTimeline::from_reader(
+        Cursor::new(buffer)
+    ).expect("Error reading journal");
+
+    assert_eq!(timeline.get_records(6090).len(), 4);
+    assert_eq!(timeline.get_references(), vec![(6090, 71), (42, 1), (6090, 72)]);
+    assert_eq!(timeline.get_reference_records(6090, 71).len(), 3);
+    assert_eq!(timeline.get_state(6090, HistoryPoint::Usn(40000)).unwrap(), None);
+    assert_eq!(timeline.get_state(6090, HistoryPoint::Usn(50000)).unwrap(), Some(value1));
+    assert_eq!(timeline.get_state(6090, HistoryPoint::Usn(85000)).unwrap(), Some(value2));
+    assert_eq!(timeline.get_state(6090, HistoryPoint::Time(Utc::now())).unwrap(), Some(value3));
+    assert_eq!(timeline.get_reference_state(6090, 72, HistoryPoint::Time(Utc::now())).unwrap(), Some(json!({"reused": true})));
+    assert_eq!(timeline.get_reference_state(6090, 72, HistoryPoint::Usn(85000)).unwrap(), None);
+}
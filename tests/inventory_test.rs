@@ -0,0 +1,79 @@
+use std::time::Duration;
+use rswinthings::utils::backoff::Backoff;
+use rswinthings::utils::inventory::{HostChannel, HostInventory};
+
+
+#[test]
+fn inventory_test() {
+    let inventory = HostInventory::from_json_str(r#"{
+        "hosts": [
+            {
+                "name": "dc01",
+                "server": "dc01.corp.local",
+                "user": "svc_events",
+                "domain": "CORP",
+                "sflag": "Kerberos",
+                "password_source": "Env",
+                "password_value": "DC01_PASSWORD",
+                "query": "*[System[Level<=3]]",
+                "channels": [
+                    "System",
+                    {"channel": "Security", "query": "*[System[EventID=4624]]"}
+                ]
+            },
+            {
+                "name": "localhost"
+            }
+        ]
+    }"#).expect("Error parsing inventory");
+
+    assert_eq!(inventory.hosts.len(), 2);
+
+    let dc01 = &inventory.hosts[0];
+    assert_eq!(dc01.channels[0], HostChannel::Name("System".to_owned()));
+    assert_eq!(dc01.channels[1].get_channel(), "Security");
+    assert_eq!(
+        dc01.get_channel_query(&dc01.channels[0]),
+        Some("*[System[Level<=3]]".to_owned())
+    );
+    assert_eq!(
+        dc01.get_channel_query(&dc01.channels[1]),
+        Some("*[System[EventID=4624]]".to_owned())
+    );
+    assert!(dc01.get_credential_provider().is_ok());
+
+    let local = &inventory.hosts[1];
+    assert_eq!(local.server, None);
+    assert!(local.channels.is_empty());
+    assert_eq!(local.get_channel_query(&HostChannel::Name("System".to_owned())), None);
+
+    // Duplicate names, unknown flags, users without a server and passwords
+    // from stdin are rejected
+    assert!(HostInventory::from_json_str(r#"{"hosts": [{"name": "a"}, {"name": "a"}]}"#).is_err());
+    assert!(HostInventory::from_json_str(r#"{"hosts": [{"name": "a", "server": "a", "sflag": "Basic"}]}"#).is_err());
+    assert!(HostInventory::from_json_str(r#"{"hosts": [{"name": "a", "user": "u"}]}"#).is_err());
+    assert!(HostInventory::from_json_str(r#"{"hosts": [{"name": "a", "server": "a", "password_source": "Stdin"}]}"#).is_err());
+}
+
+
+#[test]
+fn backoff_test() {
+    let mut backoff = Backoff::new(
+        Duration::from_secs(5),
+        Duration::from_secs(60)
+    );
+
+    assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+    assert_eq!(backoff.next_delay(), Duration::from_secs(10));
+    assert_eq!(backoff.next_delay(), Duration::from_secs(20));
+    assert_eq!(backoff.next_delay(), Duration::from_secs(40));
+    assert_eq!(backoff.next_delay(), Duration::from_secs(60));
+    for _ in 0..40 {
+        backoff.next_delay();
+    }
+    assert_eq!(backoff.next_delay(), Duration::from_secs(60));
+    assert_eq!(backoff.get_failures(), 46);
+
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_secs(5));
+}
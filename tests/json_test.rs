@@ -1,5 +1,11 @@
 #[macro_use] extern crate serde_json;
-use rswinthings::utils::json::get_difference_value;
+use rswinthings::utils::json::{
+    get_difference_value,
+    get_json_patch,
+    DiffOptions,
+    JsonPatch,
+    PatchOperation
+};
 
 
 #[test]
@@ -218,3 +224,128 @@ fn json_test() {
     println!("{}", new_value.to_string());
 }
 
+
+
+#[test]
+fn json_pointer_path_test() {
+    let before = json!({
+        "a.b": {"c/d": 1, "e~f": true},
+        "list": [1, 2, 3]
+    });
+    let after = json!({
+        "a.b": {"c/d": 2, "e~f": true},
+        "list": [1, 2]
+    });
+
+    let difference = get_difference_value(
+        &before,
+        &after
+    );
+
+    println!("{}", difference.to_string());
+    assert_eq!(difference, json!({
+        "/a.b/c~1d": {"before": 1, "after": 2},
+        "/list/2": {"removed": 3}
+    }));
+}
+
+
+#[test]
+fn json_keyed_array_test() {
+    let before = json!({
+        "attributes": [
+            {"instance": 0, "name": "$STANDARD_INFORMATION"},
+            {"instance": 2, "name": "$FILE_NAME"},
+            {"instance": 3, "name": "$DATA"}
+        ]
+    });
+    let after = json!({
+        "attributes": [
+            {"instance": 0, "name": "$STANDARD_INFORMATION"},
+            {"instance": 3, "name": "$DATA:Zone.Identifier"},
+            {"instance": 4, "name": "$OBJECT_ID"}
+        ]
+    });
+
+    let options = DiffOptions::new()
+        .with_array_key("/instance");
+
+    let patch = get_json_patch(
+        &before,
+        &after,
+        &options
+    );
+
+    println!("{}", patch.to_json_value().unwrap().to_string());
+    assert_eq!(patch.to_json_value().unwrap(), json!([
+        {"op": "test", "path": "/attributes/1", "value": {"instance": 2, "name": "$FILE_NAME"}},
+        {"op": "remove", "path": "/attributes/1"},
+        {"op": "add", "path": "/attributes/2", "value": {"instance": 4, "name": "$OBJECT_ID"}},
+        {"op": "test", "path": "/attributes/1/name", "value": "$DATA"},
+        {"op": "replace", "path": "/attributes/1/name", "value": "$DATA:Zone.Identifier"}
+    ]));
+}
+
+
+#[test]
+fn json_array_move_test() {
+    let before = json!({"list": ["a", "b", "c", "d"]});
+    let after = json!({"list": ["d", "a", "b", "c"]});
+
+    let patch = get_json_patch(
+        &before,
+        &after,
+        &DiffOptions::default()
+    );
+
+    assert_eq!(patch.0, vec![
+        PatchOperation::Move {
+            from: "/list/3".to_string(),
+            path: "/list/0".to_string()
+        }
+    ]);
+}
+
+
+#[test]
+fn json_patch_replay_test() {
+    let before = json!({
+        "header": {"sequence": 71, "flags": "ALLOCATED"},
+        "attributes": [
+            {"header": {"instance": 0}, "data": {"usn": 57216}},
+            {"header": {"instance": 2}, "data": {"name": "channels.txt"}},
+            {"header": {"instance": 3}, "data": null}
+        ]
+    });
+    let after = json!({
+        "header": {"sequence": 72, "flags": "ALLOCATED"},
+        "attributes": [
+            {"header": {"instance": 3}, "data": null},
+            {"header": {"instance": 0}, "data": {"usn": 84272}},
+            {"header": {"instance": 5}, "data": {"name": "CHANNE~1.TXT"}}
+        ],
+        "new": [1]
+    });
+
+    let options = DiffOptions::new()
+        .with_array_key("/header/instance");
+
+    let patch = get_json_patch(
+        &before,
+        &after,
+        &options
+    );
+
+    // Serialized patches can be read back and replayed
+    let patch: JsonPatch = serde_json::from_value(
+        patch.to_json_value().unwrap()
+    ).unwrap();
+
+    let mut replayed = before.clone();
+    patch.apply(&mut replayed).expect("Error applying patch");
+    assert_eq!(replayed, after);
+
+    let reverse_patch = patch.reversed().expect("Error reversing patch");
+    reverse_patch.apply(&mut replayed).expect("Error applying reversed patch");
+    assert_eq!(replayed, before);
+}
@@ -0,0 +1,240 @@
+use std::io::Cursor;
+use byteorder::{ByteOrder, LittleEndian};
+use mft::MftEntry;
+use rswinthings::errors::WinThingError;
+use rswinthings::mft::raw::{get_file_names, RawAttribute, ATTRIBUTE_LIST_TYPE, FILE_NAME_TYPE};
+use rswinthings::mft::offline::OfflineMft;
+use rswinthings::mft::enumerate::{
+    get_entry_csv_line,
+    get_entry_path,
+    EntryFilter,
+    MftEnumerator,
+    MftSource
+};
+
+mod common;
+use common::{
+    attribute_list_entry,
+    file_record_with_header,
+    non_resident_attribute,
+    resident_attribute
+};
+
+
+fn file_name_attribute(instance: u16, parent: u64, parent_sequence: u16, namespace: u8, name: &str) -> Vec<u8> {
+    let name_u16: Vec<u16> = name.encode_utf16().collect();
+    let mut value = vec![0u8; 0x42];
+    LittleEndian::write_u64(&mut value[0x00..0x08], parent | ((parent_sequence as u64) << 48));
+    value[0x40] = name_u16.len() as u8;
+    value[0x41] = namespace;
+    for c in name_u16 {
+        value.extend_from_slice(&c.to_le_bytes());
+    }
+
+    resident_attribute(FILE_NAME_TYPE, instance, "", &value)
+}
+
+
+fn attribute_list_attribute(instance: u16, segments: &[(u32, u64, u16)]) -> Vec<u8> {
+    let mut value = Vec::new();
+    for (type_code, entry, sequence) in segments {
+        value.extend(attribute_list_entry(*type_code, *entry, *sequence, 0, ""));
+    }
+
+    resident_attribute(ATTRIBUTE_LIST_TYPE, instance, "", &value)
+}
+
+
+/// Root (5) > Users (30) > alice (31) > deleted notes.txt (32), with an
+/// extension record (34) and a never used record (33). lost.txt (35) points
+/// at sequence 1 of entry 36, which is now another directory.
+fn get_mft() -> Vec<u8> {
+    let mut mft = vec![0u8; 37 * 1024];
+    let mut set_record = |entry: usize, record: Vec<u8>| {
+        mft[entry * 1024..(entry + 1) * 1024].copy_from_slice(&record);
+    };
+
+    set_record(0, file_record_with_header(0, 1, 0x01, 0, &[file_name_attribute(1, 5, 5, 3, "$MFT")]));
+    set_record(5, file_record_with_header(5, 5, 0x03, 0, &[file_name_attribute(1, 5, 5, 3, ".")]));
+    set_record(30, file_record_with_header(30, 2, 0x03, 0, &[file_name_attribute(1, 5, 5, 1, "Users")]));
+    set_record(31, file_record_with_header(31, 1, 0x03, 0, &[file_name_attribute(1, 30, 2, 1, "alice")]));
+    set_record(32, file_record_with_header(32, 3, 0x00, 0, &[
+        file_name_attribute(1, 31, 1, 2, "NOTES~1.TXT"),
+        file_name_attribute(2, 31, 1, 1, "notes.txt")
+    ]));
+    set_record(34, file_record_with_header(34, 1, 0x01, 32, &[]));
+    set_record(35, file_record_with_header(35, 1, 0x01, 0, &[file_name_attribute(1, 36, 1, 1, "lost.txt")]));
+    set_record(36, file_record_with_header(36, 4, 0x03, 0, &[file_name_attribute(1, 5, 5, 1, "Other")]));
+
+    mft
+}
+
+
+fn get_paths(filter: EntryFilter) -> Vec<(u64, String)> {
+    let mut mft = OfflineMft::from_reader(Cursor::new(get_mft())).expect("Error opening $MFT");
+    MftEnumerator::new(&mut mft)
+        .with_filter(filter)
+        .map(|e| e.expect("Error reading entry"))
+        .map(|e| (e.entry, e.path.unwrap_or_default()))
+        .collect()
+}
+
+
+#[test]
+fn raw_file_name_test() {
+    let mft = get_mft();
+    let file_names = get_file_names(&mft[32 * 1024..33 * 1024]).expect("Error getting file names");
+
+    assert_eq!(file_names.len(), 2);
+    assert_eq!(file_names[0].name, "notes.txt");
+    assert_eq!(file_names[0].parent_entry, 31);
+    assert_eq!(file_names[0].parent_sequence, 1);
+    assert!(file_names[1].is_dos());
+}
+
+
+#[test]
+fn mft_enumerate_test() {
+    let mft = OfflineMft::from_reader(Cursor::new(get_mft())).expect("Error opening $MFT");
+    assert_eq!(mft.get_record_size(), 1024);
+    assert_eq!(mft.get_entry_count(), 37);
+
+    let paths = get_paths(EntryFilter::new());
+    assert_eq!(
+        paths,
+        vec![
+            (0, "\\$MFT".to_owned()),
+            (5, "\\".to_owned()),
+            (30, "\\Users".to_owned()),
+            (31, "\\Users\\alice".to_owned()),
+            (32, "\\Users\\alice\\notes.txt".to_owned()),
+            (35, "\\$OrphanFiles\\lost.txt".to_owned()),
+            (36, "\\Other".to_owned())
+        ]
+    );
+}
+
+
+#[test]
+fn mft_enumerate_extension_test() {
+    // notes.txt (32) lists its extension 34 and the wiped record 33, and
+    // entry 37 is an extension record of the $MFT
+    let mut mft = get_mft();
+    mft.resize(38 * 1024, 0);
+    mft[32 * 1024..33 * 1024].copy_from_slice(&file_record_with_header(32, 3, 0x00, 0, &[
+        attribute_list_attribute(3, &[(FILE_NAME_TYPE, 34, 1), (FILE_NAME_TYPE, 33, 1)]),
+        file_name_attribute(2, 31, 1, 1, "notes.txt")
+    ]));
+    mft[37 * 1024..38 * 1024].copy_from_slice(&file_record_with_header(37, 1, 0x01, 1 << 48, &[]));
+
+    let mut mft = OfflineMft::from_reader(Cursor::new(mft)).expect("Error opening $MFT");
+    let entries: Vec<(u64, usize)> = MftEnumerator::new(&mut mft)
+        .map(|e| e.expect("Error reading entry"))
+        .map(|e| (e.entry, e.logical_entry.extensions.len()))
+        .collect();
+
+    // The entry with a wiped extension is resolved from its base record
+    assert!(entries.contains(&(32, 0)));
+    assert!(!entries.iter().any(|(entry, _)| *entry == 37));
+}
+
+
+/// A $MFT with clusters to read non-resident attribute values from, and an
+/// entry that can't be read.
+struct VolumeMft {
+    mft: OfflineMft<Cursor<Vec<u8>>>,
+    clusters: Vec<u8>,
+    unreadable: Option<u64>
+}
+
+impl MftSource for VolumeMft {
+    fn get_entry_count(&self) -> u64 {
+        self.mft.get_entry_count()
+    }
+
+    fn get_record(&mut self, entry: u64) -> Result<Option<MftEntry>, WinThingError> {
+        match Some(entry) == self.unreadable {
+            true => Err(WinThingError::stream_error(format!("Entry {} is unreadable.", entry))),
+            false => self.mft.get_record(entry)
+        }
+    }
+
+    fn read_attribute_value(&mut self, _attribute: &RawAttribute) -> Result<Vec<u8>, WinThingError> {
+        Ok(self.clusters.clone())
+    }
+}
+
+
+fn get_extension_count<S: MftSource>(source: &mut S, entry: u64) -> Option<usize> {
+    MftEnumerator::new(source)
+        .map(|e| e.expect("Error reading entry"))
+        .find(|e| e.entry == entry)
+        .map(|e| e.logical_entry.extensions.len())
+}
+
+
+#[test]
+fn mft_enumerate_non_resident_list_test() {
+    // notes.txt (32) has too many extensions for a resident attribute list
+    let list_value = attribute_list_entry(FILE_NAME_TYPE, 34, 1, 0, "");
+    let mut mft = get_mft();
+    mft[32 * 1024..33 * 1024].copy_from_slice(&file_record_with_header(32, 3, 0x00, 0, &[
+        non_resident_attribute(ATTRIBUTE_LIST_TYPE, 3, "", &[0x11, 0x01, 0x03], list_value.len() as u64, list_value.len() as u64),
+        file_name_attribute(2, 31, 1, 1, "notes.txt")
+    ]));
+
+    // The list is read from the volume's clusters
+    let mut volume = VolumeMft {
+        mft: OfflineMft::from_reader(Cursor::new(mft.clone())).expect("Error opening $MFT"),
+        clusters: list_value.clone(),
+        unreadable: None
+    };
+    assert_eq!(get_extension_count(&mut volume, 32), Some(1));
+
+    // An extension that can't be read leaves the base record alone
+    volume.unreadable = Some(34);
+    assert_eq!(get_extension_count(&mut volume, 32), Some(0));
+
+    // A $MFT file can't read the list, so does the same
+    let mut offline = OfflineMft::from_reader(Cursor::new(mft)).expect("Error opening $MFT");
+    assert_eq!(get_extension_count(&mut offline, 32), Some(0));
+}
+
+
+#[test]
+fn entry_path_test() {
+    let mut mft = OfflineMft::from_reader(Cursor::new(get_mft())).expect("Error opening $MFT");
+    assert_eq!(
+        get_entry_path(&mut mft, 32).expect("Error resolving path").as_deref(),
+        Some("\\Users\\alice\\notes.txt")
+    );
+    assert_eq!(get_entry_path(&mut mft, 33).expect("Error resolving path"), None);
+}
+
+
+#[test]
+fn mft_enumerate_filter_test() {
+    let deleted: Vec<u64> = get_paths(EntryFilter::new().with_deleted(true))
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect();
+    assert_eq!(deleted, vec![32]);
+
+    let directories: Vec<u64> = get_paths(EntryFilter::new().with_in_use(true).with_directories(true))
+        .into_iter()
+        .map(|(entry, _)| entry)
+        .collect();
+    assert_eq!(directories, vec![5, 30, 31, 36]);
+
+    let mut mft = OfflineMft::from_reader(Cursor::new(get_mft())).expect("Error opening $MFT");
+    let entry = MftEnumerator::new(&mut mft)
+        .with_filter(EntryFilter::new().with_deleted(true))
+        .next()
+        .expect("No deleted entry")
+        .expect("Error reading entry");
+
+    let value = entry.into_json_value().expect("Error getting entry value");
+    assert_eq!(value["in_use"], false);
+    assert_eq!(value["path"], "\\Users\\alice\\notes.txt");
+    assert!(get_entry_csv_line(&value).starts_with("32,3,false,false,\\Users\\alice\\notes.txt,"));
+}
@@ -0,0 +1,451 @@
+#[macro_use] extern crate serde_json;
+use std::fs;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use mft::MftEntry;
+use byteorder::{ByteOrder, LittleEndian};
+use rswinthings::mft::raw::{
+    apply_fixups,
+    decode_data_runs,
+    get_raw_attributes,
+    get_extension_entries,
+    get_extension_entries_with_reader,
+    parse_attribute_list,
+    DataRun,
+    ATTRIBUTE_LIST_TYPE,
+    DATA_TYPE,
+    FILE_NAME_TYPE
+};
+use rswinthings::errors::{ErrorType, WinThingError};
+use rswinthings::mft::{custom_entry_value, LogicalEntry};
+use rswinthings::mft::enumerate::MftSource;
+use rswinthings::volume::image::{NtfsImage, VolumeBootRecord};
+use rswinthings::mft::stream::{
+    get_stream_attributes,
+    ClusterReader,
+    HashWriter
+};
+
+mod common;
+use common::{
+    attribute_list_entry,
+    file_record,
+    non_resident_attribute,
+    resident_attribute
+};
+
+
+#[test]
+fn raw_attributes_test() {
+    let record = file_record(&[
+        resident_attribute(DATA_TYPE, 3, "", b"hello"),
+        resident_attribute(DATA_TYPE, 4, "Zone.Identifier", b"[ZoneTransfer]\r\nZoneId=3\r\n")
+    ]);
+
+    let attributes = get_raw_attributes(&record).expect("Error parsing attributes");
+    assert_eq!(attributes.len(), 2);
+    assert_eq!(attributes[0].name, "");
+    assert_eq!(attributes[0].resident_value, Some(b"hello".to_vec()));
+    assert_eq!(attributes[1].name, "Zone.Identifier");
+    assert_eq!(attributes[1].instance, 4);
+    assert_eq!(attributes[1].get_data_size(), 26);
+}
+
+
+#[test]
+fn attribute_list_test() {
+    let mut list_value: Vec<u8> = Vec::new();
+    list_value.extend(attribute_list_entry(0x10, 100, 2, 0, ""));
+    list_value.extend(attribute_list_entry(0x30, 100, 2, 2, ""));
+    list_value.extend(attribute_list_entry(0x30, 5120, 1, 0, ""));
+    list_value.extend(attribute_list_entry(DATA_TYPE, 5121, 1, 0, ""));
+    list_value.extend(attribute_list_entry(DATA_TYPE, 5121, 1, 1, "Zone.Identifier"));
+
+    let entries = parse_attribute_list(&list_value).expect("Error parsing attribute list");
+    assert_eq!(entries.len(), 5);
+    assert_eq!(entries[2].segment_entry, 5120);
+    assert_eq!(entries[2].segment_sequence, 1);
+    assert_eq!(entries[4].name, "Zone.Identifier");
+
+    let record = file_record(&[
+        resident_attribute(ATTRIBUTE_LIST_TYPE, 5, "", &list_value)
+    ]);
+
+    let extensions = get_extension_entries(&record, 100).expect("Error getting extensions");
+    assert_eq!(extensions, vec![5120, 5121]);
+}
+
+
+#[test]
+fn data_runs_test() {
+    // 0x18 clusters at lcn 0x5634, 0x10 sparse clusters, 0x20 clusters 0x100 back
+    let runs = decode_data_runs(&[
+        0x21, 0x18, 0x34, 0x56,
+        0x01, 0x10,
+        0x21, 0x20, 0x00, 0xFF,
+        0x00
+    ]).expect("Error decoding data runs");
+
+    assert_eq!(runs, vec![
+        DataRun { lcn: Some(0x5634), length: 0x18 },
+        DataRun { lcn: None, length: 0x10 },
+        DataRun { lcn: Some(0x5534), length: 0x20 }
+    ]);
+}
+
+
+#[test]
+fn invalid_data_runs_test() {
+    // The second offset overflows the lcn past i64::MAX
+    let overflow = decode_data_runs(&[
+        0x81, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F,
+        0x81, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00
+    ]);
+    assert_eq!(overflow.unwrap_err().kind, ErrorType::InvalidMftRecord);
+
+    // 0x10 clusters 0x100 before the start of the volume
+    let negative = decode_data_runs(&[
+        0x21, 0x10, 0x00, 0xFF,
+        0x00
+    ]);
+    assert_eq!(negative.unwrap_err().kind, ErrorType::InvalidMftRecord);
+}
+
+
+#[test]
+fn fixup_test() {
+    let mut record = vec![0u8; 1024];
+    record[0..4].copy_from_slice(b"FILE");
+    LittleEndian::write_u16(&mut record[0x04..0x06], 0x30);
+    LittleEndian::write_u16(&mut record[0x06..0x08], 3);
+    record[0x30..0x36].copy_from_slice(&[0x07, 0x00, 0xAA, 0xBB, 0xCC, 0xDD]);
+    record[510..512].copy_from_slice(&[0x07, 0x00]);
+    record[1022..1024].copy_from_slice(&[0x07, 0x00]);
+
+    apply_fixups(&mut record, 512).expect("Error applying fixups");
+    assert_eq!(&record[510..512], &[0xAA, 0xBB]);
+    assert_eq!(&record[1022..1024], &[0xCC, 0xDD]);
+
+    record[510..512].copy_from_slice(&[0x01, 0x00]);
+    LittleEndian::write_u16(&mut record[0x30..0x32], 0x08);
+    assert!(apply_fixups(&mut record, 512).is_err());
+}
+
+
+#[test]
+fn stream_extraction_test() {
+    // Volume with 512 byte clusters. The stream is 1700 bytes over clusters
+    // 10-11, one sparse cluster and cluster 4, initialized up to 1600 bytes.
+    let mut volume = vec![0u8; 512 * 16];
+    for (i, b) in volume.iter_mut().enumerate() {
+        *b = (i / 512) as u8;
+    }
+
+    let record = file_record(&[
+        non_resident_attribute(DATA_TYPE, 1, "", &[0x11, 0x02, 0x0A, 0x01, 0x01, 0x11, 0x01, 0xFA], 1700, 1600),
+        resident_attribute(DATA_TYPE, 2, "Zone.Identifier", b"[ZoneTransfer]\r\nZoneId=3\r\n")
+    ]);
+    let records = vec![record];
+
+    let mut cluster_reader = ClusterReader::new(
+        Cursor::new(volume),
+        512
+    );
+
+    let attributes = get_stream_attributes(&records, "").expect("Error getting stream");
+    let data = cluster_reader.read_stream(&attributes).expect("Error reading stream");
+    assert_eq!(data.len(), 1700);
+    assert!(data[0..512].iter().all(|b| *b == 10));
+    assert!(data[512..1024].iter().all(|b| *b == 11));
+    assert!(data[1024..1536].iter().all(|b| *b == 0));
+    assert!(data[1536..1600].iter().all(|b| *b == 4));
+    assert!(data[1600..].iter().all(|b| *b == 0));
+
+    let attributes = get_stream_attributes(&records, "Zone.Identifier").expect("Error getting stream");
+    let mut writer = HashWriter::new(Vec::new());
+    cluster_reader.write_stream(&attributes, &mut writer).expect("Error writing stream");
+    let (content, _) = writer.finalize();
+    assert_eq!(content, b"[ZoneTransfer]\r\nZoneId=3\r\n".to_vec());
+
+    assert!(get_stream_attributes(&records, "missing").is_err());
+}
+
+
+#[test]
+fn corrupt_stream_test() {
+    let mut cluster_reader = ClusterReader::new(
+        Cursor::new(vec![0u8; 512 * 4]),
+        512
+    );
+    assert_eq!(cluster_reader.read_clusters(u64::MAX, 1).unwrap_err().kind, ErrorType::StreamError);
+    assert_eq!(cluster_reader.read_clusters(0, u64::MAX).unwrap_err().kind, ErrorType::StreamError);
+
+    // One sparse cluster claiming a terabyte of data
+    let records = vec![file_record(&[
+        non_resident_attribute(DATA_TYPE, 1, "", &[0x01, 0x01], 1 << 40, 0)
+    ])];
+    let attributes = get_stream_attributes(&records, "").expect("Error getting stream");
+    assert_eq!(cluster_reader.read_stream(&attributes).unwrap_err().kind, ErrorType::StreamError);
+
+    // Run lengths that overflow when added up
+    let runs = vec![
+        DataRun { lcn: None, length: u64::MAX - 1 },
+        DataRun { lcn: Some(0), length: 2 }
+    ];
+    let mut cluster_reader = ClusterReader::new(
+        Cursor::new(vec![0u8; 4]),
+        1
+    );
+    assert_eq!(cluster_reader.read_run_range(&runs, u64::MAX - 1, 1).unwrap_err().kind, ErrorType::StreamError);
+}
+
+
+#[test]
+fn stream_hash_test() {
+    let mut writer = HashWriter::new(Vec::new());
+    writer.write_all(b"abc").unwrap();
+    let (_, hashes) = writer.finalize();
+
+    assert_eq!(hashes.md5, "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(hashes.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+    assert_eq!(hashes.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+}
+
+
+fn boot_sector(sectors_per_cluster: u8, clusters_per_record: u8) -> Vec<u8> {
+    let mut boot_sector = vec![0u8; 512];
+    boot_sector[0x03..0x0B].copy_from_slice(b"NTFS    ");
+    LittleEndian::write_u16(&mut boot_sector[0x0B..0x0D], 512);
+    boot_sector[0x0D] = sectors_per_cluster;
+    LittleEndian::write_u64(&mut boot_sector[0x28..0x30], 64);
+    LittleEndian::write_u64(&mut boot_sector[0x30..0x38], 2);
+    boot_sector[0x40] = clusters_per_record;
+    boot_sector
+}
+
+
+#[test]
+fn boot_record_test() {
+    let boot_record = VolumeBootRecord::from_buffer(&boot_sector(0xF8, 0xF6)).expect("Error parsing boot sector");
+    assert_eq!(boot_record.bytes_per_cluster, 512 * 256);
+    assert_eq!(boot_record.bytes_per_file_record, 1024);
+
+    // Exponents a corrupt boot sector could hold
+    assert!(VolumeBootRecord::from_buffer(&boot_sector(0x81, 0xF6)).is_err());
+    assert!(VolumeBootRecord::from_buffer(&boot_sector(1, 0x81)).is_err());
+    assert!(VolumeBootRecord::from_buffer(&boot_sector(0xE0, 0xF6)).is_err());
+    assert!(VolumeBootRecord::from_buffer(&boot_sector(0xE8, 0x7F)).is_err());
+}
+
+
+#[test]
+fn image_stream_test() {
+    // Volume at offset 1024 with 512 byte clusters and 1024 byte records.
+    // The MFT is 16 clusters at lcn 2, entry 5 has its data at lcn 20.
+    let volume_offset = 1024;
+    let mut image = vec![0u8; volume_offset + 512 * 24];
+
+    let boot_sector = &mut image[volume_offset..volume_offset + 512];
+    boot_sector[0x03..0x0B].copy_from_slice(b"NTFS    ");
+    LittleEndian::write_u16(&mut boot_sector[0x0B..0x0D], 512);
+    boot_sector[0x0D] = 1;
+    LittleEndian::write_u64(&mut boot_sector[0x28..0x30], 24);
+    LittleEndian::write_u64(&mut boot_sector[0x30..0x38], 2);
+    boot_sector[0x40] = 0xF6;
+
+    let mft_record = file_record(&[
+        non_resident_attribute(DATA_TYPE, 1, "", &[0x11, 0x10, 0x02], 8 * 1024, 8 * 1024)
+    ]);
+    let mft_offset = volume_offset + 2 * 512;
+    image[mft_offset..mft_offset + 1024].copy_from_slice(&mft_record);
+
+    let entry_record = file_record(&[
+        non_resident_attribute(DATA_TYPE, 1, "", &[0x11, 0x01, 0x14], 100, 100),
+        resident_attribute(DATA_TYPE, 2, "Zone.Identifier", b"ZoneId=3")
+    ]);
+    let entry_offset = mft_offset + 5 * 1024;
+    image[entry_offset..entry_offset + 1024].copy_from_slice(&entry_record);
+
+    let data_offset = volume_offset + 20 * 512;
+    for b in image[data_offset..data_offset + 512].iter_mut() {
+        *b = 0x41;
+    }
+
+    let mut ntfs_image = NtfsImage::from_reader(
+        Cursor::new(image),
+        volume_offset as u64
+    ).expect("Error opening image");
+
+    assert_eq!(ntfs_image.get_boot_record().bytes_per_cluster, 512);
+    assert_eq!(ntfs_image.get_boot_record().bytes_per_file_record, 1024);
+    assert_eq!(ntfs_image.get_entry_count(), 8);
+    assert_eq!(ntfs_image.read_stream(5, "").unwrap(), vec![0x41; 100]);
+    assert_eq!(ntfs_image.read_stream(5, "Zone.Identifier").unwrap(), b"ZoneId=3".to_vec());
+}
+
+
+#[test]
+fn fragmented_mft_image_test() {
+    // Volume with 512 byte clusters and 1024 byte records. The $MFT's base
+    // record maps entries 0-1 at lcn 2 and its extension record (entry 1)
+    // maps entries 2-3 at lcn 30.
+    let mut image = vec![0u8; 512 * 40];
+    image[0..512].copy_from_slice(&boot_sector(1, 0xF6));
+
+    let mut list_value: Vec<u8> = Vec::new();
+    list_value.extend(attribute_list_entry(DATA_TYPE, 0, 1, 1, ""));
+    list_value.extend(attribute_list_entry(DATA_TYPE, 1, 1, 0, ""));
+
+    let mft_record = file_record(&[
+        resident_attribute(ATTRIBUTE_LIST_TYPE, 2, "", &list_value),
+        non_resident_attribute(DATA_TYPE, 1, "", &[0x11, 0x04, 0x02], 4 * 1024, 4 * 1024)
+    ]);
+    image[2 * 512..4 * 512].copy_from_slice(&mft_record);
+
+    let mut second_piece = non_resident_attribute(DATA_TYPE, 0, "", &[0x11, 0x04, 0x1E], 0, 0);
+    LittleEndian::write_u64(&mut second_piece[0x10..0x18], 4);
+    LittleEndian::write_u64(&mut second_piece[0x18..0x20], 7);
+    let mut extension_record = file_record(&[second_piece]);
+    LittleEndian::write_u64(&mut extension_record[0x20..0x28], 1 << 48);
+    image[4 * 512..6 * 512].copy_from_slice(&extension_record);
+
+    let entry_record = file_record(&[
+        resident_attribute(DATA_TYPE, 1, "", b"past the first piece")
+    ]);
+    image[32 * 512..34 * 512].copy_from_slice(&entry_record);
+
+    let mut ntfs_image = NtfsImage::from_reader(
+        Cursor::new(image),
+        0
+    ).expect("Error opening image");
+
+    assert_eq!(ntfs_image.get_entry_count(), 4);
+    assert_eq!(ntfs_image.read_stream(3, "").unwrap(), b"past the first piece".to_vec());
+}
+
+
+/// Base entry 100 whose $ATTRIBUTE_LIST points at extension records 5120
+/// ($FILE_NAME), 5121 (the unnamed and Zone.Identifier $DATA) and 5122,
+/// which has since been reused by another file.
+fn get_fixture_records() -> HashMap<u64, Vec<u8>> {
+    let fixture = fs::read("tests/fixtures/attribute_list_records.bin")
+        .expect("Error reading fixture");
+
+    let mut records: HashMap<u64, Vec<u8>> = HashMap::new();
+    for record in fixture.chunks(1024) {
+        let number = LittleEndian::read_u32(&record[0x2C..0x30]) as u64;
+        records.insert(number, record.to_vec());
+    }
+
+    records
+}
+
+
+#[test]
+fn logical_entry_test() {
+    let records = get_fixture_records();
+    let get_record = |entry: u64| -> Result<MftEntry, WinThingError> {
+        let buffer = records.get(&entry).expect("Entry not in fixture").to_owned();
+        Ok(MftEntry::from_buffer_skip_fixup(buffer, entry)?)
+    };
+
+    let base = get_record(100).unwrap();
+    let unresolved = LogicalEntry::from_base(get_record(100).unwrap());
+    assert!(unresolved.extensions.is_empty());
+
+    let logical_entry = LogicalEntry::resolve(base, get_record).expect("Error resolving extensions");
+    let extension_numbers: Vec<u64> = logical_entry.extensions.iter()
+        .map(|e| e.header.record_number)
+        .collect();
+    assert_eq!(extension_numbers, vec![5120, 5121]);
+    assert_eq!(logical_entry.iter_attributes().count(), 5);
+    assert_eq!(logical_entry.get_record_buffers().len(), 3);
+
+    let value = logical_entry.into_json_value().expect("Error getting entry value");
+    assert_eq!(value["extensions"], json!([5120, 5121]));
+    assert_eq!(value["streams"]["$DATA"]["instances"], json!(["5121:0"]));
+    assert_eq!(value["streams"]["$DATA"]["size"], json!(11));
+    assert_eq!(value["streams"]["Zone.Identifier"]["instances"], json!(["5121:1"]));
+    assert!(value["streams"]["stale"].is_null());
+    assert!(value["attributes"]["DATA"]["5121:1"].is_object());
+}
+
+
+#[test]
+fn non_resident_attribute_list_test() {
+    // The list is in cluster 3 of a volume with 512 byte clusters
+    let mut list_value: Vec<u8> = Vec::new();
+    list_value.extend(attribute_list_entry(0x10, 100, 2, 0, ""));
+    list_value.extend(attribute_list_entry(0x30, 5120, 1, 0, ""));
+    list_value.extend(attribute_list_entry(DATA_TYPE, 5121, 1, 0, ""));
+
+    let mut volume = vec![0u8; 512 * 4];
+    volume[3 * 512..3 * 512 + list_value.len()].copy_from_slice(&list_value);
+
+    let record = file_record(&[
+        non_resident_attribute(ATTRIBUTE_LIST_TYPE, 5, "", &[0x11, 0x01, 0x03], list_value.len() as u64, list_value.len() as u64)
+    ]);
+
+    let extensions = get_extension_entries(&record, 100).expect("Error getting extensions");
+    assert!(extensions.is_empty());
+
+    let mut cluster_reader = ClusterReader::new(
+        Cursor::new(volume),
+        512
+    );
+    let extensions = get_extension_entries_with_reader(
+        &record,
+        100,
+        |attribute| cluster_reader.read_stream(std::slice::from_ref(attribute))
+    ).expect("Error getting extensions");
+    assert_eq!(extensions, vec![5120, 5121]);
+}
+
+
+fn file_name_value(parent: u64, namespace: u8, name: &str) -> Vec<u8> {
+    let name_u16: Vec<u16> = name.encode_utf16().collect();
+    let mut value = vec![0u8; 0x42];
+    LittleEndian::write_u64(&mut value[0x00..0x08], parent | (5 << 48));
+    for offset in &[0x08, 0x10, 0x18, 0x20] {
+        LittleEndian::write_u64(&mut value[*offset..*offset + 8], 132_223_104_000_000_000);
+    }
+    value[0x40] = name_u16.len() as u8;
+    value[0x41] = namespace;
+    for c in name_u16 {
+        value.extend_from_slice(&c.to_le_bytes());
+    }
+    value
+}
+
+
+#[test]
+fn custom_entry_value_instances_test() {
+    let mut record = file_record(&[
+        resident_attribute(FILE_NAME_TYPE, 1, "", &file_name_value(5, 2, "REPORT~1.DOC")),
+        resident_attribute(FILE_NAME_TYPE, 2, "", &file_name_value(5, 1, "report.docx")),
+        resident_attribute(DATA_TYPE, 3, "", b"hello"),
+        resident_attribute(DATA_TYPE, 4, "Zone.Identifier", b"ZoneId=3")
+    ]);
+    LittleEndian::write_u16(&mut record[0x10..0x12], 1);
+    LittleEndian::write_u16(&mut record[0x16..0x18], 0x01);
+    LittleEndian::write_u32(&mut record[0x1C..0x20], 1024);
+    LittleEndian::write_u32(&mut record[0x2C..0x30], 64);
+
+    let entry = MftEntry::from_buffer_skip_fixup(record, 64).expect("Error parsing entry");
+    let value = custom_entry_value(entry).expect("Error getting entry value");
+
+    let file_names = value["attributes"]["FileName"].as_object().expect("No FileName attributes");
+    let mut keys: Vec<&String> = file_names.keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["1", "2"]);
+
+    let data = value["attributes"]["DATA"].as_object().expect("No DATA attributes");
+    let mut keys: Vec<&String> = data.keys().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["3", "4"]);
+
+    assert_eq!(value["streams"]["$DATA"]["size"], json!(5));
+    assert_eq!(value["streams"]["$DATA"]["instances"], json!(["3"]));
+    assert_eq!(value["streams"]["Zone.Identifier"]["instances"], json!(["4"]));
+}
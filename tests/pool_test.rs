@@ -0,0 +1,180 @@
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+use std::collections::BTreeMap;
+use rswinthings::errors::WinThingError;
+use rswinthings::utils::pool::{
+    run_cancellable_tasks,
+    run_shared_tasks,
+    run_tasks,
+    PoolOptions
+};
+
+
+fn get_names(names: &[&str]) -> Vec<String> {
+    names.iter()
+        .map(|n| n.to_string())
+        .collect()
+}
+
+
+#[test]
+fn pool_results_test() {
+    let options = PoolOptions::new()
+        .with_workers(3)
+        .with_timeout(Some(Duration::from_secs(5)));
+
+    let outcomes: BTreeMap<String, _> = run_tasks(
+        get_names(&["a", "bb", "ccc", "dddd", "missing"]),
+        options,
+        |name| match name {
+            "missing" => Err(WinThingError::from_windows_error_code(15002)),
+            _ => Ok(name.len())
+        }
+    ).iter().map(|o| (o.name.clone(), o)).collect();
+
+    assert_eq!(outcomes.len(), 5);
+    assert_eq!(outcomes["ccc"].result.as_ref().ok(), Some(&3));
+    assert!(outcomes["a"].get_error_record("publisher_error").is_none());
+
+    let record = outcomes["missing"].get_error_record("publisher_error")
+        .expect("Expected an error record");
+    assert_eq!(record.status, "publisher_error");
+    assert_eq!(record.name, "missing");
+    assert_eq!(record.code, Some(15002));
+    assert!(!record.is_timeout());
+}
+
+
+#[test]
+fn pool_timeout_test() {
+    let options = PoolOptions::new()
+        .with_workers(0)
+        .with_timeout(Some(Duration::from_millis(100)));
+    assert_eq!(options.workers, 1);
+
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let task_cancelled = Arc::clone(&cancelled);
+    let outcomes: Vec<_> = run_cancellable_tasks(
+        get_names(&["slow", "fast", "panics"]),
+        options,
+        move |name| match name {
+            "slow" => {
+                for _ in 0..200 {
+                    if task_cancelled.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Ok(())
+            },
+            "panics" => panic!("task panicked"),
+            _ => Ok(())
+        },
+        move |name| {
+            assert_eq!(name, "slow");
+            cancelled.store(true, Ordering::SeqCst);
+        }
+    ).iter().collect();
+
+    // The cancelled task frees the single worker for the others
+    assert_eq!(outcomes.len(), 3);
+    assert!(outcomes[1].result.is_ok());
+
+    let slow = outcomes[0].get_error_record("publisher_error")
+        .expect("Expected a timeout record");
+    assert!(slow.is_timeout());
+    assert_eq!(slow.error_type, "timeout_error");
+    assert!(slow.elapsed_ms >= 100 && slow.elapsed_ms < 2000);
+
+    let panicked = outcomes[2].get_error_record("publisher_error")
+        .expect("Expected an error record");
+    assert_eq!(panicked.error_type, "unhandled_logic");
+}
+
+
+#[test]
+fn pool_stuck_test() {
+    let options = PoolOptions::new()
+        .with_workers(1)
+        .with_timeout(Some(Duration::from_millis(100)));
+
+    // A task that ignores cancelling keeps its worker, so the tasks that
+    // were not started time out instead of waiting on it
+    let outcomes: Vec<_> = run_tasks(
+        get_names(&["hung", "queued"]),
+        options,
+        |name| {
+            if name == "hung" {
+                thread::sleep(Duration::from_secs(2));
+            }
+            Ok(())
+        }
+    ).iter().collect();
+
+    assert_eq!(outcomes.len(), 2);
+    assert_eq!(outcomes[0].name, "hung");
+    assert_eq!(outcomes[1].name, "queued");
+
+    let queued = outcomes[1].get_error_record("publisher_error")
+        .expect("Expected a timeout record");
+    assert!(queued.is_timeout());
+    assert!(outcomes[1].elapsed < Duration::from_secs(1));
+}
+
+
+#[test]
+fn pool_shared_cancel_test() {
+    let options = PoolOptions::new()
+        .with_workers(2)
+        .with_timeout(Some(Duration::from_millis(100)));
+
+    // Cancelling the slow task also cancels the sibling started beside it
+    // after the quick task
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let task_cancelled = Arc::clone(&cancelled);
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let task_attempts = Arc::clone(&attempts);
+    let outcomes: BTreeMap<String, _> = run_shared_tasks(
+        get_names(&["slow", "quick", "sibling"]),
+        options,
+        move |name| {
+            if name == "quick" {
+                thread::sleep(Duration::from_millis(40));
+                return Ok(());
+            }
+            if name == "sibling" && task_attempts.fetch_add(1, Ordering::SeqCst) > 0 {
+                return Ok(());
+            }
+
+            for _ in 0..200 {
+                if task_cancelled.load(Ordering::SeqCst) {
+                    return Err(WinThingError::from_windows_error_code(1223));
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+            Ok(())
+        },
+        move |_| cancelled.store(true, Ordering::SeqCst)
+    ).iter().map(|o| (o.name.clone(), o)).collect();
+
+    assert_eq!(outcomes.len(), 3);
+    assert!(outcomes["slow"].get_error_record("publisher_error")
+        .expect("Expected a timeout record")
+        .is_timeout());
+
+    // The sibling is run again rather than reported as cancelled
+    assert!(outcomes["sibling"].result.is_ok());
+    assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+    // A cancel without a timeout is reported
+    let outcomes: Vec<_> = run_shared_tasks(
+        get_names(&["cancelled"]),
+        options,
+        |_| -> Result<(), _> { Err(WinThingError::from_windows_error_code(1223)) },
+        |_| {}
+    ).iter().collect();
+    assert_eq!(outcomes.len(), 1);
+    assert!(outcomes[0].is_cancelled());
+}
@@ -0,0 +1,141 @@
+use serde_json::json;
+use rswinthings::errors::ErrorType;
+use rswinthings::publishers::{read_publisher_dump, read_publisher_dump_path};
+use rswinthings::publishers::diff::PublisherDiff;
+
+
+#[test]
+fn publisher_diff_test() {
+    let before = read_publisher_dump_path("tests/fixtures/publishers.jsonl")
+        .expect("Error reading publisher dump")
+        .publishers;
+    let after = read_publisher_dump_path("tests/fixtures/publishers_after.jsonl")
+        .expect("Error reading publisher dump")
+        .publishers;
+
+    let diff = PublisherDiff::from_values(&before, &after, &[])
+        .expect("Error diffing publishers");
+
+    assert!(!diff.is_empty());
+    assert_eq!(diff.added, vec!["Microsoft-Windows-Sysmon"]);
+    assert_eq!(diff.removed, vec!["Microsoft-Windows-WinRM"]);
+    assert_eq!(diff.changed.len(), 2);
+
+    let eventlog = &diff.changed[0];
+    assert_eq!(eventlog.name, "Microsoft-Windows-Eventlog");
+    assert_eq!(
+        eventlog.get_changed_properties(),
+        vec!["EvtPublisherMetadataChannelReferences"]
+    );
+
+    let auditing = &diff.changed[1];
+    assert_eq!(
+        auditing.differences["/EvtPublisherMetadataResourceFilePath"],
+        json!({
+            "before": "C:\\Windows\\system32\\adtschema.dll",
+            "after": "C:\\Users\\Public\\adtschema.dll"
+        })
+    );
+
+    let records = diff.to_records();
+    assert_eq!(records.len(), 4);
+    assert_eq!(records[0], json!({"change": "added", "name": "Microsoft-Windows-Sysmon"}));
+    assert_eq!(records[3]["properties"], json!(["EvtPublisherMetadataMessageFilePath", "EvtPublisherMetadataResourceFilePath"]));
+}
+
+
+#[test]
+fn publisher_diff_properties_test() {
+    let before = read_publisher_dump_path("tests/fixtures/publishers.jsonl")
+        .expect("Error reading publisher dump")
+        .publishers;
+    let after = read_publisher_dump_path("tests/fixtures/publishers_after.jsonl")
+        .expect("Error reading publisher dump")
+        .publishers;
+
+    // Only compare the channels
+    let diff = PublisherDiff::from_values(
+        &before,
+        &after,
+        &["EvtPublisherMetadataChannelReferences".to_owned()]
+    ).expect("Error diffing publishers");
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].name, "Microsoft-Windows-Eventlog");
+
+    let same = PublisherDiff::from_values(&before, &before, &[])
+        .expect("Error diffing publishers");
+    assert!(same.is_empty());
+
+    // Publishers that couldn't be read are not removed
+    let unavailable = PublisherDiff::from_values(&before, &after, &[])
+        .expect("Error diffing publishers")
+        .with_unavailable(&["Microsoft-Windows-WinRM".to_owned()]);
+    assert!(unavailable.removed.is_empty());
+    assert_eq!(unavailable.unavailable, vec!["Microsoft-Windows-WinRM"]);
+    assert_eq!(
+        unavailable.to_records().last(),
+        Some(&json!({"change": "unavailable", "name": "Microsoft-Windows-WinRM"}))
+    );
+
+    // Event definitions are ignored unless both sides have them
+    let with_events: Vec<_> = before.iter()
+        .map(|publisher| {
+            let mut publisher = publisher.clone();
+            publisher["Events"] = json!([{"id": 1}]);
+            publisher
+        })
+        .collect();
+    assert!(PublisherDiff::from_values(&with_events, &before, &[]).unwrap().is_empty());
+    assert!(PublisherDiff::from_values(&before, &with_events, &[]).unwrap().is_empty());
+    assert!(PublisherDiff::from_values(&with_events, &with_events, &[]).unwrap().is_empty());
+
+    assert_eq!(
+        PublisherDiff::from_values(&[json!({"Guid": "x"})], &[], &[]).unwrap_err().kind,
+        ErrorType::InvalidInput
+    );
+}
+
+
+#[test]
+fn publisher_dump_error_record_test() {
+    // A print_publishers --jobs dump with the record of a publisher that failed
+    let mut lines: Vec<String> = std::fs::read_to_string("tests/fixtures/publishers_after.jsonl")
+        .expect("Error reading publisher dump")
+        .lines()
+        .map(|line| line.to_owned())
+        .collect();
+    lines.push(
+        json!({
+            "status": "publisher_error",
+            "name": "Microsoft-Windows-WinRM",
+            "error_type": "timeout_error",
+            "message": "Microsoft-Windows-WinRM did not finish within 30s.",
+            "elapsed_ms": 30001
+        }).to_string()
+    );
+
+    let after = read_publisher_dump(lines.join("\n").as_bytes())
+        .expect("Error reading publisher dump");
+    assert_eq!(after.publishers.len(), 4);
+    assert_eq!(after.unavailable, vec!["Microsoft-Windows-WinRM"]);
+
+    let before = read_publisher_dump_path("tests/fixtures/publishers.jsonl")
+        .expect("Error reading publisher dump");
+    let diff = PublisherDiff::from_values(&before.publishers, &after.publishers, &[])
+        .expect("Error diffing publishers")
+        .with_unavailable(&after.unavailable);
+    assert!(diff.removed.is_empty());
+    assert_eq!(diff.unavailable, vec!["Microsoft-Windows-WinRM"]);
+
+    // Publishers that failed in the first set are not added either
+    let diff = PublisherDiff::from_values(&after.publishers, &before.publishers, &[])
+        .expect("Error diffing publishers")
+        .with_unavailable(&after.unavailable);
+    assert!(diff.added.is_empty());
+    assert_eq!(diff.removed, vec!["Microsoft-Windows-Sysmon"]);
+
+    assert_eq!(
+        read_publisher_dump(&b"{\"status\": \"publisher_error\"}"[..]).unwrap_err().kind,
+        ErrorType::InvalidInput
+    );
+}
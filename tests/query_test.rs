@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use rswinthings::events::query::{
+    parse_query_time,
+    QueryFilter,
+    QueryOptions
+};
+
+
+fn utc(time_str: &str) -> DateTime<Utc> {
+    time_str.parse().expect("Error parsing time")
+}
+
+
+#[test]
+fn query_filter_test() {
+    assert_eq!(QueryFilter::new().to_xpath(), "*");
+
+    let filter = QueryFilter::new()
+        .with_provider("Microsoft-Windows-Security-Auditing")
+        .with_event_id(4624)
+        .with_event_id(4625)
+        .with_start_time(utc("2020-01-20T10:00:00Z"))
+        .with_end_time(utc("2020-01-21T00:00:00.500Z"));
+    assert_eq!(
+        filter.to_xpath(),
+        "*[System[Provider[@Name='Microsoft-Windows-Security-Auditing'] and (EventID=4624 or EventID=4625) and \
+        TimeCreated[@SystemTime>='2020-01-20T10:00:00.000Z' and @SystemTime<='2020-01-21T00:00:00.500Z']]]"
+    );
+
+    let filter = QueryFilter::new()
+        .with_event_id(4624)
+        .with_xpath("*[EventData[Data[@Name='LogonType']='3']]");
+    assert_eq!(
+        filter.to_xpath(),
+        "*[System[(EventID=4624)] and (EventData[Data[@Name='LogonType']='3'])]"
+    );
+
+    // An `or` in the XPath only applies to the XPath
+    let filter = QueryFilter::new()
+        .with_start_time(utc("2020-01-20T10:00:00Z"))
+        .with_xpath("System[EventID=1] or System[EventID=2]");
+    assert_eq!(
+        filter.to_xpath(),
+        "*[System[TimeCreated[@SystemTime>='2020-01-20T10:00:00.000Z']] and (System[EventID=1] or System[EventID=2])]"
+    );
+
+    // Only a bracket that encloses the whole query is removed
+    let filter = QueryFilter::new()
+        .with_xpath("*[System[EventID=1]] or *[System[EventID=2]]");
+    assert_eq!(filter.to_xpath(), "*[(*[System[EventID=1]] or *[System[EventID=2]])]");
+
+    let filter = QueryFilter::new()
+        .with_xpath("*[EventData[Data='a]b']]");
+    assert_eq!(filter.to_xpath(), "*[(EventData[Data='a]b'])]");
+
+    let filter = QueryFilter::new()
+        .with_provider("O'Brien");
+    assert_eq!(filter.to_xpath(), "*[System[Provider[@Name=\"O'Brien\"]]]");
+
+    let filter = QueryFilter::new()
+        .with_provider("O'Brien \"Labs\"");
+    assert_eq!(filter.to_xpath(), "*[System[Provider[@Name=concat('O', \"'\", 'Brien \"Labs\"')]]]");
+
+    let options = QueryOptions::new()
+        .with_reverse(true)
+        .with_max_count(10)
+        .with_batch_size(0);
+    assert!(options.reverse);
+    assert_eq!(options.max_count, Some(10));
+    assert_eq!(options.batch_size, 1);
+}
+
+
+#[test]
+fn parse_query_time_test() {
+    assert_eq!(
+        parse_query_time("2020-01-20T12:15:00+02:00").unwrap(),
+        utc("2020-01-20T10:15:00Z")
+    );
+    assert_eq!(
+        parse_query_time("2020-01-20").unwrap(),
+        utc("2020-01-20T00:00:00Z")
+    );
+    assert!(parse_query_time("yesterday").is_err());
+}
@@ -0,0 +1,137 @@
+use serde_json::{json, Value};
+use rswinthings::detection::engine::RuleEngine;
+use rswinthings::detection::sigma::{FieldMapping, SigmaRule};
+use rswinthings::events::stream::EventStream;
+
+
+fn get_fixture_events() -> Vec<Value> {
+    EventStream::from_xml_path("tests/fixtures/events.xml", 3)
+        .expect("Error opening fixture")
+        .map(|r| r.expect("Unexpected stream error"))
+        .map(|e| e.to_json_value().expect("Error converting event"))
+        .collect()
+}
+
+
+fn get_titles(engine: &RuleEngine, event: &Value) -> Vec<String> {
+    engine.evaluate(event)
+        .into_iter()
+        .map(|a| a.rule.title)
+        .collect()
+}
+
+
+#[test]
+fn fixture_rules_test() {
+    let events = get_fixture_events();
+
+    let mut engine = RuleEngine::new();
+    // The linux rule and the rule with an unknown category are skipped
+    let count = engine.add_rules_from_path("tests/fixtures/rules")
+        .expect("Error loading rules");
+    assert_eq!(count, 2);
+    assert_eq!(engine.get_rule_count(), 2);
+
+    assert!(get_titles(&engine, &events[0]).is_empty());
+    assert_eq!(get_titles(&engine, &events[1]), vec!["Failed Remote Interactive Logon"]);
+    assert_eq!(get_titles(&engine, &events[2]), vec!["Whoami Execution"]);
+
+    let alert = engine.evaluate(&events[2])
+        .remove(0)
+        .to_json_value()
+        .expect("Error serializing alert");
+    assert_eq!(alert["alert"]["level"], "medium");
+    assert_eq!(alert["alert"]["tags"], json!(["attack.discovery", "attack.t1033"]));
+    assert_eq!(alert["alert"]["falsepositives"], json!(["Admin activity"]));
+    assert_eq!(alert["alert"]["logsource"]["category"], "process_creation");
+    assert_eq!(alert["event"], events[2]);
+}
+
+
+#[test]
+fn sigma_modifiers_test() {
+    let events = get_fixture_events();
+
+    let mut engine = RuleEngine::new();
+    engine.add_rule_str(r"
+title: Modifiers
+detection:
+    selection:
+        TargetUserName|startswith: 'ALI'
+        IpAddress|re: '^10\.0\.0\.\d+$'
+        EventRecordID|gte: 1001
+        Provider_Name: 'Microsoft-Windows-Security-*'
+    missing:
+        SubjectUserName: null
+    cased:
+        TargetUserName|cased: 'ALICE'
+    condition: selection and missing and not cased
+").expect("Error adding rule");
+    engine.add_rule_str(r"
+title: Keywords
+detection:
+    keywords:
+        - 'WHOAMI'
+    condition: keywords
+").expect("Error adding rule");
+
+    assert_eq!(get_titles(&engine, &events[0]), vec!["Modifiers"]);
+    assert!(get_titles(&engine, &events[1]).is_empty());
+    assert_eq!(get_titles(&engine, &events[2]), vec!["Keywords"]);
+}
+
+
+#[test]
+fn sigma_field_mapping_test() {
+    let events = get_fixture_events();
+
+    let mapping = FieldMapping::from_yaml_str(r"
+fieldmappings:
+    ParentProcessName: /Event/EventData/ParentImage
+    User: [/Event/EventData/SubjectUserName, /Event/EventData/User]
+").expect("Error reading field mapping");
+
+    let mut engine = RuleEngine::new()
+        .with_field_mapping(mapping);
+    engine.add_rule_str(r"
+title: Explorer Child
+logsource:
+    product: windows
+    category: process_creation
+detection:
+    selection:
+        ParentProcessName|endswith: '\explorer.exe'
+        User: 'WS01\alice'
+    condition: 1 of them
+").expect("Error adding rule");
+
+    assert!(get_titles(&engine, &events[0]).is_empty());
+    assert_eq!(get_titles(&engine, &events[2]), vec!["Explorer Child"]);
+
+    assert!(FieldMapping::from_yaml_str("fieldmappings:\n    User: EventData/User\n").is_err());
+}
+
+
+#[test]
+fn sigma_rule_errors_test() {
+    let rule = SigmaRule::from_yaml_str(r"
+title: Count
+detection:
+    selection:
+        EventID: 4625
+    condition: selection | count() by IpAddress > 5
+").expect("Error reading rule");
+    assert_eq!(rule.title, "Count");
+
+    let mut engine = RuleEngine::new();
+    let error = engine.add_rule(&rule).unwrap_err();
+    assert_eq!(error.kind.as_str(), "rule_error");
+
+    assert!(engine.add_rule_str("title: Bad\ndetection:\n    sel:\n        Image|base64offset: x\n    condition: sel\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ndetection:\n    sel:\n        Image: x\n    condition: sel and other\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ndetection:\n    sel:\n        Image: x\n    condition: (sel\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\ndetection:\n    sel:\n        Image: x\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\nlogsource:\n    service: unknown\ndetection:\n    sel:\n        Image: x\n    condition: sel\n").is_err());
+    assert!(engine.add_rule_str("title: Bad\nlogsource:\n    category: unknown\ndetection:\n    sel:\n        Image: x\n    condition: sel\n").is_err());
+    assert_eq!(engine.get_rule_count(), 0);
+}
@@ -0,0 +1,183 @@
+#[macro_use] extern crate serde_json;
+use chrono::{TimeZone, Utc};
+use rswinthings::mft::history::{HistoryJournal, HistoryTimeline};
+use rswinthings::timeline::record::{
+    read_event_timeline_path,
+    read_usn_timeline_path
+};
+use rswinthings::timeline::export::{
+    export_items,
+    get_bodyfile_line,
+    get_entry_export_items,
+    get_history_export_items,
+    ExportFormat,
+    ExportItem,
+    ExportKind,
+    TimeRange
+};
+
+
+fn get_entry_value() -> serde_json::Value {
+    json!({
+        "header": {"record_number": 91234, "sequence": 4, "flags": "ALLOCATED"},
+        "attributes": {
+            "StandardInformation": {"0": {"data": {
+                "created": "2020-01-20T10:16:31.250Z",
+                "modified": "2020-01-20T10:16:31.900Z",
+                "mft_modified": "2020-01-20T10:16:31.900Z",
+                "accessed": "2020-01-20T10:16:31.900Z"
+            }}},
+            "FileName": {
+                "2": {"data": {
+                    "name": "STAGE~1.PS1",
+                    "namespace": "DOS",
+                    "created": "2020-01-20T10:16:31.250Z",
+                    "modified": "2020-01-20T10:16:31.250Z",
+                    "mft_modified": "2020-01-20T10:16:31.250Z",
+                    "accessed": "2020-01-20T10:16:31.250Z"
+                }},
+                "3": {"data": {
+                    "name": "stage.ps1",
+                    "namespace": "Win32",
+                    "created": "2020-01-20T10:16:31.250Z",
+                    "modified": "2020-01-20T10:16:31.250Z",
+                    "mft_modified": "2020-01-20T10:16:31.250Z",
+                    "accessed": "2020-01-20T10:16:31.250Z"
+                }}
+            }
+        },
+        "streams": {"$DATA": {"resident": false, "size": 512}}
+    })
+}
+
+
+#[test]
+fn entry_export_items_test() {
+    let items = get_entry_export_items(
+        &get_entry_value(),
+        Some("ws01"),
+        Some("C:\\Users\\alice\\stage.ps1")
+    );
+    assert_eq!(items.len(), 3);
+
+    let standard_info = &items[0];
+    assert_eq!(standard_info.kind, ExportKind::StandardInformation);
+    assert_eq!(standard_info.inode.as_deref(), Some("91234-4"));
+    assert_eq!(standard_info.size, Some(512));
+    assert_eq!(
+        standard_info.times.get_groups().iter().map(|(_, f)| f.as_str()).collect::<Vec<&str>>(),
+        vec!["...B", "MAC."]
+    );
+    assert_eq!(
+        get_bodyfile_line(standard_info),
+        "0|C:\\Users\\alice\\stage.ps1|91234-4|r/rrwxrwxrwx|0|0|512|1579515391|1579515391|1579515391|1579515391"
+    );
+
+    // $FILE_NAME items use their own name
+    assert_eq!(items[1].kind, ExportKind::FileName);
+    assert_eq!(items[1].path.as_deref(), Some("C:\\Users\\alice\\STAGE~1.PS1"));
+    assert!(get_bodyfile_line(&items[2]).starts_with("0|C:\\Users\\alice\\stage.ps1 ($FILE_NAME)|"));
+    assert_eq!(items[2].times.get_groups()[0].1, "MACB");
+
+    // Without a path the names are marked as not resolved
+    let items = get_entry_export_items(&get_entry_value(), Some("ws01"), None);
+    assert_eq!(items[0].path, None);
+    assert_eq!(items[0].short, "stage.ps1");
+    assert!(get_bodyfile_line(&items[0]).starts_with("0|stage.ps1 (path not resolved)|91234-4|"));
+    assert!(get_bodyfile_line(&items[1]).starts_with("0|STAGE~1.PS1 (path not resolved) ($FILE_NAME)|"));
+}
+
+
+#[test]
+fn history_export_test() {
+    let before = get_entry_value();
+    let mut reused = get_entry_value();
+    reused["header"]["sequence"] = json!(5);
+    reused["attributes"]["FileName"] = json!({"3": {"data": {"name": "other.txt", "namespace": "Win32"}}});
+
+    let mut buffer: Vec<u8> = Vec::new();
+    {
+        let mut journal = HistoryJournal::new(&mut buffer);
+        journal.append_snapshot(91234, 4, 1203904, &before).unwrap();
+        journal.append_snapshot(91234, 5, 1204200, &reused).unwrap();
+    }
+
+    let history = HistoryTimeline::from_reader(buffer.as_slice()).unwrap();
+    let items = get_history_export_items(&history, Some("ws01"), Some("C:")).unwrap();
+    let descriptions: Vec<(&str, &str)> = items.iter()
+        .map(|i| (i.inode.as_deref().unwrap_or(""), i.description.as_str()))
+        .collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            ("91234-4", "stage.ps1 (path not resolved)"),
+            ("91234-4", "STAGE~1.PS1 (path not resolved)"),
+            ("91234-4", "stage.ps1 (path not resolved)"),
+            ("91234-5", "other.txt (path not resolved)"),
+            ("91234-5", "other.txt (path not resolved)")
+        ]
+    );
+    assert!(items.iter().all(|i| i.path.is_none()));
+}
+
+
+#[test]
+fn record_export_test() {
+    let mut items: Vec<ExportItem> = read_usn_timeline_path("tests/fixtures/usn.jsonl", Some("ws01"), Some("C:"))
+        .unwrap()
+        .iter()
+        .map(ExportItem::from_timeline_record)
+        .collect();
+    items.extend(
+        read_event_timeline_path("tests/fixtures/timeline_events.jsonl")
+            .unwrap()
+            .iter()
+            .map(ExportItem::from_timeline_record)
+    );
+
+    let event = &items[3];
+    assert_eq!(event.kind, ExportKind::EventRecord);
+    assert!(event.description.starts_with(
+        "Microsoft-Windows-PowerShell/Operational 4104 Microsoft-Windows-PowerShell ["
+    ));
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let count = export_items(&mut buffer, &items, ExportFormat::Tln, &TimeRange::default()).unwrap();
+    assert_eq!(count, 4);
+
+    let output = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines[1], "1579515391|FILE|ws01||[USN] C:\\Users\\alice\\stage.ps1 USN_REASON_FILE_CREATE");
+    // The | of the script block text can't split the line
+    assert!(lines[3].starts_with("1579515392|EVT|ws01||[EVT] "));
+    assert_eq!(lines[3].matches('|').count(), 4);
+}
+
+
+#[test]
+fn time_range_export_test() {
+    let items = get_entry_export_items(&get_entry_value(), Some("ws01"), Some("C:\\stage.ps1"));
+    let range = TimeRange::new(
+        Some(Utc.with_ymd_and_hms(2020, 1, 20, 10, 16, 31).unwrap() + chrono::Duration::milliseconds(500)),
+        None
+    );
+
+    let mut buffer: Vec<u8> = Vec::new();
+    let count = export_items(&mut buffer, &items, ExportFormat::L2tCsv, &range).unwrap();
+    assert_eq!(count, 1);
+
+    let output = String::from_utf8(buffer).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[0].starts_with("date,time,timezone,MACB,"));
+    assert_eq!(
+        lines[1],
+        "01/20/2020,10:16:31,UTC,MAC.,FILE,NTFS $STANDARD_INFORMATION,\
+        Content Modification Time; Last Access Time; Metadata Modification Time,\
+        -,ws01,$SI stage.ps1,C:\\stage.ps1,2,C:\\stage.ps1,91234-4,-,rswinthings,-"
+    );
+
+    // A bodyfile line has all four times, so only the range of the item is checked
+    let mut buffer: Vec<u8> = Vec::new();
+    let count = export_items(&mut buffer, &items, ExportFormat::Bodyfile, &range).unwrap();
+    assert_eq!(count, 1);
+}
@@ -0,0 +1,135 @@
+#[macro_use] extern crate serde_json;
+use chrono::{DateTime, Utc};
+use rswinthings::mft::history::{HistoryJournal, HistoryTimeline};
+use rswinthings::utils::json::{get_json_patch, DiffOptions};
+use rswinthings::timeline::merge::{merge_timelines, TimelineMerger};
+use rswinthings::timeline::record::{
+    get_history_timeline,
+    normalize_path,
+    read_event_timeline_path,
+    read_usn_timeline_path,
+    TimelineRecord,
+    TimelineSource
+};
+
+
+fn get_time(time: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(time)
+        .unwrap()
+        .with_timezone(&Utc)
+}
+
+
+fn get_record(time: &str, description: &str) -> TimelineRecord {
+    TimelineRecord {
+        timestamp: get_time(time),
+        host: None,
+        source: TimelineSource::EventLog,
+        path: None,
+        name: None,
+        description: description.to_owned(),
+        data: json!(null)
+    }
+}
+
+
+#[test]
+fn timeline_record_test() {
+    assert_eq!(normalize_path("\\\\?\\C:\\Windows/Temp", None), "C:\\Windows\\Temp");
+    assert_eq!(normalize_path("Users\\alice", Some("\\\\.\\C:")), "C:\\Users\\alice");
+    assert_eq!(normalize_path("\\Users\\alice", Some("C:\\")), "C:\\Users\\alice");
+    assert_eq!(normalize_path("D:\\data", Some("C:")), "D:\\data");
+
+    let events = read_event_timeline_path("tests/fixtures/events.xml").unwrap();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].host.as_deref(), Some("WS01"));
+    assert_eq!(events[0].description, "Security 4624");
+    assert_eq!(events[2].path.as_deref(), Some("C:\\Windows\\System32\\cmd.exe"));
+
+    // The status record is skipped
+    let events = read_event_timeline_path("tests/fixtures/timeline_events.jsonl").unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].host.as_deref(), Some("ws01"));
+    assert_eq!(events[0].path.as_deref(), Some("C:\\Users\\alice\\stage.ps1"));
+
+    let usn = read_usn_timeline_path("tests/fixtures/usn.jsonl", Some("ws01"), Some("\\\\.\\C:")).unwrap();
+    assert_eq!(usn.len(), 3);
+    assert_eq!(usn[0].source, TimelineSource::Usn);
+    assert_eq!(usn[0].timestamp, get_time("2020-01-20T10:16:31.25Z"));
+    assert_eq!(usn[0].path, events[0].path);
+    assert_eq!(usn[0].name.as_deref(), Some("stage.ps1"));
+    // Without a resolved path only the name is known
+    assert_eq!(usn[2].path, None);
+    assert_eq!(usn[2].name.as_deref(), Some("old_notes.txt"));
+    assert_eq!(usn[2].description, "USN_REASON_FILE_DELETE | USN_REASON_CLOSE");
+
+    let value = serde_json::to_value(&usn[0]).unwrap();
+    assert_eq!(value["source"], "usn");
+    assert_eq!(value["timestamp"], "2020-01-20T10:16:31.250Z");
+}
+
+
+#[test]
+fn history_timeline_test() {
+    let before = json!({
+        "header": {"record_number": 91234, "sequence": 4},
+        "attributes": {"FileName": {
+            "2": {"data": {"name": "STAGE~1.PS1", "namespace": "DOS"}},
+            "3": {"data": {"name": "stage.ps1", "namespace": "Win32"}}
+        }}
+    });
+    let mut after = before.clone();
+    after["streams"] = json!({"$DATA": {"size": 512}});
+
+    let mut buffer: Vec<u8> = Vec::new();
+    {
+        let mut journal = HistoryJournal::new(&mut buffer);
+        journal.append_snapshot(91234, 4, 1203904, &before).unwrap();
+        journal.append_diff(
+            91234, 4, 1203984,
+            &get_json_patch(&before, &after, &DiffOptions::default())
+        ).unwrap();
+        // The entry is reused by another file
+        journal.append_diff(
+            91234, 5, 1204200,
+            &get_json_patch(&after, &before, &DiffOptions::default())
+        ).unwrap();
+    }
+
+    let history = HistoryTimeline::from_reader(buffer.as_slice()).unwrap();
+    let timeline = get_history_timeline(history.get_all_records(), Some("ws01")).unwrap();
+    assert_eq!(timeline.len(), 3);
+    assert_eq!(timeline[1].source, TimelineSource::Mft);
+    assert_eq!(timeline[1].description, "Entry 91234 changed");
+    assert_eq!(timeline[1].path, None);
+    assert_eq!(timeline[1].name.as_deref(), Some("stage.ps1"));
+    assert_eq!(timeline[1].data[0]["path"], "/streams");
+    assert_eq!(timeline[2].name, None);
+}
+
+
+#[test]
+fn timeline_merge_test() {
+    let events = read_event_timeline_path("tests/fixtures/timeline_events.jsonl").unwrap();
+    let usn = read_usn_timeline_path("tests/fixtures/usn.jsonl", None, Some("C:")).unwrap();
+
+    let merged = merge_timelines(vec![events, usn]);
+    let descriptions: Vec<&str> = merged.iter().map(|r| r.description.as_str()).collect();
+    assert_eq!(
+        descriptions,
+        vec![
+            "USN_REASON_FILE_DELETE | USN_REASON_CLOSE",
+            "USN_REASON_FILE_CREATE",
+            "USN_REASON_DATA_EXTEND | USN_REASON_FILE_CREATE",
+            "Microsoft-Windows-PowerShell/Operational 4104"
+        ]
+    );
+
+    // Ties keep the order of the streams
+    let merged: Vec<String> = TimelineMerger::new(vec![
+        vec![get_record("2020-01-20T10:00:01Z", "a1"), get_record("2020-01-20T10:00:02Z", "a2")].into_iter(),
+        vec![get_record("2020-01-20T10:00:01Z", "b1")].into_iter()
+    ]).map(|r| r.description).collect();
+    assert_eq!(merged, vec!["a1", "b1", "a2"]);
+}
+
@@ -0,0 +1,165 @@
+use std::mem::size_of;
+use serde_json::json;
+use rswinthings::winevt::variant::*;
+
+const BASE: u64 = 0x7ff0_0000_1000;
+
+
+/// Build an EVT_VARIANT.
+fn variant(value: u64, count: u32, variant_type: u32) -> Vec<u8> {
+    let mut bytes = value.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&count.to_le_bytes());
+    bytes.extend_from_slice(&variant_type.to_le_bytes());
+    bytes
+}
+
+fn pointer(value: u64) -> Vec<u8> {
+    value.to_le_bytes()[..size_of::<usize>()].to_vec()
+}
+
+fn wide(s: &str) -> Vec<u8> {
+    s.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|c| c.to_le_bytes().to_vec())
+        .collect()
+}
+
+fn decode(buffer: &[u8]) -> VariantValue {
+    VariantValue::from_buffer(buffer, BASE).expect("Error decoding variant")
+}
+
+
+#[test]
+fn variant_scalar_test() {
+    assert_eq!(decode(&variant(0, 0, EVT_VAR_TYPE_NULL)), VariantValue::Null);
+    assert_eq!(decode(&variant(0xff, 0, EVT_VAR_TYPE_SBYTE)), VariantValue::Int(-1));
+    assert_eq!(decode(&variant(0xfffe, 0, EVT_VAR_TYPE_UINT16)), VariantValue::UInt(65534));
+    assert_eq!(decode(&variant(0xffff_ffff, 0, EVT_VAR_TYPE_INT32)), VariantValue::Int(-1));
+    assert_eq!(decode(&variant(1, 0, EVT_VAR_TYPE_BOOLEAN)), VariantValue::Boolean(true));
+    assert_eq!(decode(&variant(2.5f64.to_bits(), 0, EVT_VAR_TYPE_DOUBLE)), VariantValue::Double(2.5));
+    assert_eq!(decode(&variant(0x8000_0000_0000_0000, 0, EVT_VAR_TYPE_HEX_INT64)), VariantValue::UInt(1 << 63));
+    assert_eq!(decode(&variant(0x1f4, 0, EVT_VAR_TYPE_EVT_HANDLE)), VariantValue::Handle(0x1f4));
+
+    let filetime = decode(&variant(132239880000000000, 0, EVT_VAR_TYPE_FILE_TIME));
+    assert_eq!(filetime.to_string(), "2020-01-20T10:00:00Z");
+    assert_eq!(filetime.to_json_value(), json!("2020-01-20T10:00:00Z"));
+    assert_eq!(
+        filetime_to_datetime(132239880001234567).unwrap().to_rfc3339(),
+        "2020-01-20T10:00:00.123456700+00:00"
+    );
+
+    // Unknown types are an error
+    assert!(VariantValue::from_buffer(&variant(0, 0, 99), BASE).is_err());
+}
+
+
+#[test]
+fn variant_pointer_test() {
+    // String
+    let mut buffer = variant(BASE + 16, 0, EVT_VAR_TYPE_STRING);
+    buffer.extend(wide("Security"));
+    assert_eq!(decode(&buffer), VariantValue::String("Security".to_owned()));
+
+    // A null pointer is Null and a pointer outside the buffer an error
+    assert_eq!(decode(&variant(0, 0, EVT_VAR_TYPE_STRING)), VariantValue::Null);
+    assert!(VariantValue::from_buffer(&variant(BASE + 64, 0, EVT_VAR_TYPE_STRING), BASE).is_err());
+
+    // SID
+    let mut buffer = variant(BASE + 16, 0, EVT_VAR_TYPE_SID);
+    buffer.extend(&[1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0]);
+    assert_eq!(decode(&buffer), VariantValue::Sid("S-1-5-18".to_owned()));
+
+    let mut buffer = variant(BASE + 16, 0, EVT_VAR_TYPE_SID);
+    buffer.extend(&[1, 2, 0, 0, 0, 0, 0, 5, 32, 0, 0, 0, 0x3d, 0x02, 0, 0]);
+    assert_eq!(decode(&buffer).to_string(), "S-1-5-32-573");
+
+    // GUID
+    let mut buffer = variant(BASE + 16, 0, EVT_VAR_TYPE_GUID);
+    buffer.extend(&[
+        0x25, 0x96, 0x84, 0x54, 0x78, 0x54, 0x94, 0x49,
+        0xA5, 0xBA, 0x3E, 0x3B, 0x03, 0x28, 0xC3, 0x0D
+    ]);
+    assert_eq!(
+        decode(&buffer),
+        VariantValue::Guid("{54849625-5478-4994-A5BA-3E3B0328C30D}".to_owned())
+    );
+
+    // SYSTEMTIME
+    let mut buffer = variant(BASE + 16, 0, EVT_VAR_TYPE_SYS_TIME);
+    for field in &[2020u16, 1, 1, 20, 10, 15, 30, 250] {
+        buffer.extend(&field.to_le_bytes());
+    }
+    assert_eq!(decode(&buffer).to_string(), "2020-01-20T10:15:30.250Z");
+
+    // Binary values are count bytes
+    let mut buffer = variant(BASE + 16, 4, EVT_VAR_TYPE_BINARY);
+    buffer.extend(&[0xde, 0xad, 0xbe, 0xef, 0xff]);
+    assert_eq!(decode(&buffer), VariantValue::Binary(vec![0xde, 0xad, 0xbe, 0xef]));
+    assert_eq!(decode(&buffer).to_json_value(), json!("deadbeef"));
+}
+
+
+#[test]
+fn variant_array_test() {
+    // An array of strings is an array of pointers
+    let pointer_size = size_of::<usize>() as u64;
+    let strings_offset = 16 + pointer_size * 2;
+    let mut buffer = variant(BASE + 16, 2, EVT_VAR_TYPE_STRING | EVT_VARIANT_TYPE_ARRAY);
+    buffer.extend(pointer(BASE + strings_offset));
+    buffer.extend(pointer(BASE + strings_offset + 8));
+    buffer.extend(wide("App"));
+    buffer.extend(wide("Sys"));
+    assert_eq!(
+        decode(&buffer).to_json_value(),
+        json!(["App", "Sys"])
+    );
+
+    // A null element is Null
+    let mut buffer = variant(BASE + 16, 2, EVT_VAR_TYPE_STRING | EVT_VARIANT_TYPE_ARRAY);
+    buffer.extend(pointer(0));
+    buffer.extend(pointer(BASE + 16 + pointer_size * 2));
+    buffer.extend(wide("App"));
+    assert_eq!(
+        decode(&buffer).to_json_value(),
+        json!([null, "App"])
+    );
+
+    let mut buffer = variant(BASE + 16, 1, EVT_VAR_TYPE_SID | EVT_VARIANT_TYPE_ARRAY);
+    buffer.extend(pointer(0));
+    assert_eq!(decode(&buffer), VariantValue::Array(vec![VariantValue::Null]));
+
+    let mut buffer = variant(BASE + 16, 3, EVT_VAR_TYPE_UINT32 | EVT_VARIANT_TYPE_ARRAY);
+    for value in &[1u32, 2, 3] {
+        buffer.extend(&value.to_le_bytes());
+    }
+    assert_eq!(
+        decode(&buffer),
+        VariantValue::Array(vec![VariantValue::UInt(1), VariantValue::UInt(2), VariantValue::UInt(3)])
+    );
+
+    // Handles are pointer sized
+    let mut buffer = variant(BASE + 16, 2, EVT_VAR_TYPE_EVT_HANDLE | EVT_VARIANT_TYPE_ARRAY);
+    buffer.extend(pointer(0x1f4));
+    buffer.extend(pointer(0x1f8));
+    assert_eq!(
+        decode(&buffer),
+        VariantValue::Array(vec![VariantValue::Handle(0x1f4), VariantValue::Handle(0x1f8)])
+    );
+
+    // GUIDs are held in the array
+    let mut buffer = variant(BASE + 16, 1, EVT_VAR_TYPE_GUID | EVT_VARIANT_TYPE_ARRAY);
+    buffer.extend(&[0u8; 16]);
+    assert_eq!(
+        decode(&buffer).to_json_value(),
+        json!(["{00000000-0000-0000-0000-000000000000}"])
+    );
+
+    // Consecutive variants, as rendered event values are
+    let mut buffer = variant(4624, 0, EVT_VAR_TYPE_UINT16);
+    buffer.extend(variant(BASE + 32, 0, EVT_VAR_TYPE_STRING));
+    buffer.extend(wide("alice"));
+    assert_eq!(
+        decode_variants(&buffer, 2, BASE).unwrap(),
+        vec![VariantValue::UInt(4624), VariantValue::String("alice".to_owned())]
+    );
+}